@@ -0,0 +1,46 @@
+/*
+ * C interface to memexfs (Cargo feature `ffi`).
+ *
+ * Strings are NUL-terminated UTF-8. Every string the library returns,
+ * results and errors alike, must be freed with memexfs_string_free.
+ *
+ * On failure a function returns NULL and, if `error` is not NULL, stores
+ * a JSON object there: {"code": ..., "message": ..., ...} with the codes
+ * documented under "Errors" in the README, or "panic" for an internal
+ * failure. `*error` is set to NULL on success.
+ *
+ * An instance is not thread-safe: use it from one thread at a time.
+ */
+
+#ifndef MEMEXFS_H
+#define MEMEXFS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MemexFsCore MemexFsCore;
+
+/* Index documents: a JSON array of [path, content] pairs or
+ * {path, content, metadata} objects. `options_json` is MemexOptions JSON,
+ * or NULL for the defaults. Free the instance with memexfs_free. */
+MemexFsCore *memexfs_new(const char *docs_json, const char *options_json, char **error);
+
+/* Run an LLM tool ("grep", "read", "ls", ...) with its params JSON, or
+ * NULL for none, and return the tool output. */
+char *memexfs_call(MemexFsCore *fs, const char *name, const char *params_json, char **error);
+
+/* The tool definitions as a JSON array. */
+char *memexfs_tool_definitions(const MemexFsCore *fs, char **error);
+
+/* Free a string returned by the library. NULL is ignored. */
+void memexfs_string_free(char *s);
+
+/* Free an instance. NULL is ignored. */
+void memexfs_free(MemexFsCore *fs);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MEMEXFS_H */
@@ -30,5 +30,5 @@ export function loadFromDirectory(dir) {
     a[0].localeCompare(b[0])
   );
 
-  return new MemexFS(JSON.stringify(docs));
+  return new MemexFS(docs);
 }
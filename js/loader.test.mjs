@@ -22,7 +22,7 @@ describe("MemexFS via WASM", () => {
   });
 
   it("grep finds 'archive' in tar.md", () => {
-    const results = JSON.parse(fs.grep("archive"));
+    const results = fs.grep("archive");
     assert.ok(results.length > 0, "should find matches");
     assert.ok(
       results.some((r) => r.path === "tar.md"),
@@ -31,7 +31,7 @@ describe("MemexFS via WASM", () => {
   });
 
   it("grep with glob restricts results", () => {
-    const results = JSON.parse(fs.grep("file", "tar.md"));
+    const results = fs.grep("file", "tar.md");
     assert.ok(results.length > 0, "should find matches");
     assert.ok(
       results.every((r) => r.path === "tar.md"),
@@ -57,30 +57,31 @@ describe("MemexFS via WASM", () => {
   });
 
   it("grep supports regex patterns", () => {
-    const results = JSON.parse(fs.grep("https?://"));
+    const results = fs.grep("https?://");
     assert.ok(results.length > 0, "should find URLs via regex");
   });
 
   it("call dispatches grep and read", () => {
     const grepResult = fs.call("grep", JSON.stringify({ pattern: "server" }));
     const parsed = JSON.parse(grepResult);
-    assert.ok(Array.isArray(parsed));
-    assert.ok(parsed.length > 0);
+    assert.ok(Array.isArray(parsed.results));
+    assert.ok(parsed.results.length > 0);
+    assert.ok(parsed.total_matches >= parsed.results.length);
 
     const readResult = fs.call("read", JSON.stringify({ path: "git.md" }));
     assert.ok(readResult.includes("git"));
   });
 
-  it("tool_definitions returns three tools", () => {
-    const defs = JSON.parse(fs.tool_definitions());
+  it("tool_definitions returns every tool", () => {
+    const defs = fs.tool_definitions();
     assert.ok(Array.isArray(defs));
-    assert.equal(defs.length, 3);
+    assert.equal(defs.length, 13);
     const names = defs.map((d) => d.name).sort();
-    assert.deepEqual(names, ["grep", "ls", "read"]);
+    assert.deepEqual(names, ["extensions", "glob", "grep", "ls", "missing_in_glob", "outline", "query_table", "read", "read_json", "read_lines", "search", "tree", "write"]);
   });
 
   it("ls lists files and directories at root", () => {
-    const entries = JSON.parse(fs.ls(""));
+    const entries = fs.ls("");
     assert.ok(entries.length > 0, "should list entries");
     assert.ok(
       entries.includes("nested/"),
@@ -115,7 +116,7 @@ describe("loadFromDirectory recursive", () => {
 
   it("ls shows subdirectories for recursively loaded docs", () => {
     const nested = loadFromDirectory(nestedDir);
-    const entries = JSON.parse(nested.ls(""));
+    const entries = nested.ls("");
     assert.deepEqual(entries, ["subdir/", "top.md"]);
   });
 });
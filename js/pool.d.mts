@@ -1,15 +1,6 @@
-export interface GrepResult {
-  path: string;
-  line: number;
-  content: string;
-}
+import type { GrepResult, ToolDefinition } from "../pkg/memexfs.js";
 
-export interface ToolDefinition {
-  name: string;
-  description: string;
-  parameters: Record<string, { type: string; description: string }>;
-  required: string[];
-}
+export type { GrepResult, ToolDefinition };
 
 export interface PoolOptions {
   /** Number of worker threads. Defaults to `os.availableParallelism()`. */
@@ -57,12 +57,12 @@ describe("pool", () => {
     assert.ok(entries.some((e) => e.endsWith(".md")));
   });
 
-  it("toolDefinitions returns three tools", async () => {
+  it("toolDefinitions returns every tool", async () => {
     const defs = await pool.toolDefinitions();
     assert.ok(Array.isArray(defs));
-    assert.equal(defs.length, 3);
+    assert.equal(defs.length, 13);
     const names = defs.map((d) => d.name).sort();
-    assert.deepEqual(names, ["grep", "ls", "read"]);
+    assert.deepEqual(names, ["extensions", "glob", "grep", "ls", "missing_in_glob", "outline", "query_table", "read", "read_json", "read_lines", "search", "tree", "write"]);
   });
 
   it("dispatches concurrent requests across workers", async () => {
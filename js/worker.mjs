@@ -15,7 +15,7 @@ const absDir = resolve(workerData.dir);
 const docs = collectMdFiles(absDir, absDir).sort((a, b) =>
   a[0].localeCompare(b[0])
 );
-const fs = new MemexFS(JSON.stringify(docs));
+const fs = new MemexFS(docs);
 
 parentPort.postMessage({ type: "ready" });
 
@@ -25,16 +25,16 @@ parentPort.on("message", (msg) => {
     let result;
     switch (method) {
       case "grep":
-        result = JSON.parse(fs.grep(...args));
+        result = fs.grep(...args);
         break;
       case "read":
         result = fs.read(...args);
         break;
       case "ls":
-        result = JSON.parse(fs.ls(...args));
+        result = fs.ls(...args);
         break;
       case "toolDefinitions":
-        result = JSON.parse(fs.tool_definitions());
+        result = fs.tool_definitions();
         break;
       case "documentCount":
         result = fs.document_count();
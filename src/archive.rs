@@ -0,0 +1,146 @@
+use std::io::Read;
+
+use crate::document::DocInput;
+use crate::error::MemexError;
+use crate::sniff::{self, BinaryPolicy};
+
+#[cfg(feature = "tar")]
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The text files in a tar archive, gzipped or not, with entry paths as
+/// document paths (a leading `./` dropped). Directories, links, hidden
+/// entries (a component starting with `.`, such as macOS `._` files) are
+/// skipped, and files that aren't text are handled by `policy`.
+#[cfg(feature = "tar")]
+pub fn tar_documents(bytes: &[u8], policy: BinaryPolicy) -> Result<Vec<DocInput>, MemexError> {
+    let unzipped;
+    let bytes = if bytes.starts_with(&GZIP_MAGIC) {
+        unzipped = crate::compress::decompress(bytes)?;
+        &unzipped[..]
+    } else {
+        bytes
+    };
+    let invalid = |e: std::io::Error| MemexError::InvalidDocuments { reason: format!("tar: {}", e) };
+    let mut archive = tar::Archive::new(bytes);
+    let mut docs = Vec::new();
+    for entry in archive.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(invalid)?.to_string_lossy().into_owned();
+        let Some(path) = document_path(&path) else { continue };
+        // Not sized from the header, which a crafted archive can inflate
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).map_err(invalid)?;
+        docs.extend(sniff::from_bytes(path, content, policy)?);
+    }
+    Ok(docs)
+}
+
+/// The text files in a zip archive, skipped and named as in
+/// `tar_documents`. Entries must be stored or deflated.
+#[cfg(feature = "zip")]
+pub fn zip_documents(bytes: &[u8], policy: BinaryPolicy) -> Result<Vec<DocInput>, MemexError> {
+    let invalid =
+        |e: zip::result::ZipError| MemexError::InvalidDocuments { reason: format!("zip: {}", e) };
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).map_err(invalid)?;
+    let mut docs = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(invalid)?;
+        if !entry.is_file() {
+            continue;
+        }
+        let Some(path) = document_path(entry.name()) else { continue };
+        // Not sized from the central directory, as for tar headers
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).map_err(|e| MemexError::InvalidDocuments {
+            reason: format!("zip: {}: {}", path, e),
+        })?;
+        docs.extend(sniff::from_bytes(path, content, policy)?);
+    }
+    Ok(docs)
+}
+
+/// An archive entry's path as a document path: `/`-separated, without a
+/// leading `./` or `/`, or `None` for hidden entries.
+fn document_path(entry: &str) -> Option<String> {
+    let path = entry.replace('\\', "/");
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+    let hidden = path.split('/').any(|part| part.starts_with('.'));
+    (!hidden && !path.is_empty()).then(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[cfg(feature = "tar")]
+    fn tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *content).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_tar_documents() {
+        let long = format!("{}/deep.md", "nested".repeat(20));
+        let tar = tarball(&[
+            ("./docs/guide.md", b"# Guide"),
+            ("docs/._guide.md", b"\0\x05\x16\x07"),
+            (&long, b"# Deep"),
+            ("docs/logo.png", b"\x89PNG\r\n\x1a\n\0\0"),
+        ]);
+        let paths = |bytes: &[u8]| -> Vec<String> {
+            let docs = tar_documents(bytes, BinaryPolicy::Skip).unwrap();
+            docs.iter().map(|doc| doc.path().to_string()).collect()
+        };
+        assert_eq!(paths(&tar), ["docs/guide.md".to_string(), long]);
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gz.write_all(&tar).unwrap();
+        assert_eq!(paths(&gz.finish().unwrap()).len(), 2);
+
+        let stubs = tar_documents(&tar, BinaryPolicy::Stub).unwrap();
+        assert_eq!(stubs.last().unwrap().path(), "docs/logo.png");
+        let garbage = tar_documents(&[1u8; 1024], BinaryPolicy::Skip).unwrap_err();
+        assert_eq!(garbage.code(), "invalid_documents");
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_zip_documents() {
+        use zip::write::SimpleFileOptions;
+        use zip::CompressionMethod;
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let files: [(&str, &[u8], CompressionMethod); 4] = [
+            ("docs/guide.md", b"# Guide", CompressionMethod::Deflated),
+            ("docs/notes.txt", b"plain text", CompressionMethod::Stored),
+            ("__MACOSX/docs/._guide.md", b"\0\x05\x16\x07", CompressionMethod::Stored),
+            ("docs/logo.png", b"\x89PNG\r\n\x1a\n\0\0", CompressionMethod::Stored),
+        ];
+        zip.add_directory("docs/", SimpleFileOptions::default()).unwrap();
+        for (path, content, method) in files {
+            zip.start_file(path, SimpleFileOptions::default().compression_method(method)).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let docs = zip_documents(&bytes, BinaryPolicy::Skip).unwrap();
+        let paths: Vec<&str> = docs.iter().map(DocInput::path).collect();
+        assert_eq!(paths, ["docs/guide.md", "docs/notes.txt"]);
+        assert_eq!(docs[0].clone().into_parts().1, "# Guide");
+        let rejected = zip_documents(&bytes, BinaryPolicy::Reject).unwrap_err();
+        assert_eq!(rejected.to_string(), "MemexError: binary document: docs/logo.png (not valid UTF-8)");
+        assert_eq!(zip_documents(b"PK\x03\x04", BinaryPolicy::Skip).unwrap_err().code(), "invalid_documents");
+    }
+}
@@ -0,0 +1,46 @@
+// MCP server over stdio: loads every .md, .csv, .tsv, and .json file
+// under a directory and answers newline-delimited JSON-RPC on
+// stdin/stdout with the memexfs tools. Logs go to stderr, since stdout
+// carries the protocol.
+//
+//     memexfs-mcp ~/notes
+
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+use memexfs::{DirOptions, MemexFsCore, TOOL_FILE_GLOBS};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [dir] = args.as_slice() else {
+        eprintln!("usage: memexfs-mcp <directory>");
+        return ExitCode::from(2);
+    };
+
+    let options = DirOptions {
+        include: TOOL_FILE_GLOBS.map(String::from).to_vec(),
+        ..Default::default()
+    };
+    let mut core = match MemexFsCore::from_dir(dir, &options) {
+        Ok(core) => core,
+        Err(e) => {
+            eprintln!("memexfs-mcp: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("memexfs-mcp: serving {} documents from {}", core.document_count(), dir);
+
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = core.handle_mcp_request(&line) {
+            if writeln!(stdout, "{}", response).and_then(|_| stdout.flush()).is_err() {
+                break;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,178 @@
+// Command-line access to an index, for trying out queries and index
+// options without going through the wasm build. The query commands run
+// the LLM tools through `call`, so they print exactly what a model sees.
+//
+//     memexfs index ~/notes notes.snap --options options.json
+//     memexfs grep notes.snap "refund" --glob "billing/**" --context-after 2
+//     memexfs read ~/notes billing/refund.md --section Exceptions
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use memexfs::{DirOptions, MemexFsCore, MemexOptions, TOOL_FILE_GLOBS};
+use serde_json::{Map, Value};
+
+const USAGE: &str = "\
+usage: memexfs <command> <source> [args] [--name value ...]
+
+commands:
+  grep <source> <pattern>    search, as the grep tool
+  read <source> <path>       read a document, as the read tool
+  ls <source> [dir]          list a directory, as the ls tool
+  index <dir> <snapshot>     index a directory and write a snapshot
+
+<source> is a directory of .md, .csv, .tsv, and .json files or a snapshot
+written by `index`.
+`--options FILE` reads MemexOptions (JSON) for indexing a directory.
+Other `--name value` pairs are tool parameters, with `-` for `_` in the
+name (`--context-before 2`); names the tool does not take are rejected.
+Values that parse as JSON (numbers, booleans, arrays, objects) are passed
+as JSON and anything else as a string; a `--name` with no value is `true`.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Error::Usage) => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+        Err(Error::Failed(message)) => {
+            eprintln!("memexfs: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+enum Error {
+    Usage,
+    Failed(String),
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Failed(message)
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Error> {
+    let (positional, mut params) = split_args(args)?;
+    let options: Option<MemexOptions> = match params.remove("options") {
+        Some(Value::String(file)) => {
+            let json = std::fs::read_to_string(&file).map_err(|e| format!("{}: {}", file, e))?;
+            Some(serde_json::from_str(&json).map_err(|e| format!("{}: {}", file, e))?)
+        }
+        Some(_) => return Err(Error::Usage),
+        None => None,
+    };
+    let [command, source, rest @ ..] = positional.as_slice() else {
+        return Err(Error::Usage);
+    };
+
+    // Positional arguments fill the tool's leading parameters
+    let (tool, names): (&str, &[&str]) = match command.as_str() {
+        "grep" => ("grep", &["pattern"]),
+        "read" => ("read", &["path"]),
+        "ls" => ("ls", &["path"]),
+        "index" => return index(source, rest, &params, &options.unwrap_or_default()),
+        _ => return Err(Error::Usage),
+    };
+    if rest.len() > names.len() {
+        return Err(Error::Usage);
+    }
+    for (name, value) in names.iter().zip(rest) {
+        params.insert(name.to_string(), Value::String(value.clone()));
+    }
+    if tool == "ls" && !params.contains_key("path") {
+        params.insert("path".to_string(), Value::String(String::new()));
+    }
+
+    let core = open(source, &options)?;
+    // The tools ignore parameters they don't know, so a misspelled flag
+    // would otherwise quietly do nothing
+    let definitions = core.tool_definitions_value();
+    let schema = definitions
+        .as_array()
+        .and_then(|all| all.iter().find(|def| def["name"] == tool))
+        .and_then(|def| def["parameters"].as_object());
+    if let Some(name) = params.keys().find(|name| !schema.is_some_and(|schema| schema.contains_key(*name))) {
+        return Err(format!("{} takes no parameter --{}", tool, name.replace('_', "-")).into());
+    }
+    let output = core.call(tool, &Value::Object(params).to_string()).map_err(|e| e.to_string())?;
+    println!("{}", output);
+    Ok(())
+}
+
+/// `index <dir> <snapshot>`: build the index and report its size.
+fn index(
+    dir: &str,
+    rest: &[String],
+    params: &Map<String, Value>,
+    options: &MemexOptions,
+) -> Result<(), Error> {
+    let ([out], true) = (rest, params.is_empty()) else {
+        return Err(Error::Usage);
+    };
+    let core = load_dir(dir, options)?;
+    let snapshot = core.to_snapshot();
+    std::fs::write(out, &snapshot).map_err(|e| format!("{}: {}", out, e))?;
+
+    let memory = core.memory_stats();
+    println!("documents  {}", core.document_count());
+    println!("terms      {}", core.token_count());
+    println!(
+        "memory     {} bytes (documents {}, folded {}, index {}, filters {})",
+        memory.total, memory.documents, memory.folded, memory.index, memory.filters
+    );
+    println!("snapshot   {} bytes -> {}", snapshot.len(), out);
+    Ok(())
+}
+
+/// Load `source`: index a directory, or restore a snapshot file, which
+/// keeps the options it was indexed with.
+fn open(source: &str, options: &Option<MemexOptions>) -> Result<MemexFsCore, Error> {
+    let path = Path::new(source);
+    if path.is_dir() {
+        return Ok(load_dir(source, &options.clone().unwrap_or_default())?);
+    }
+    if options.is_some() {
+        return Err(format!("{}: --options only applies to a directory", source).into());
+    }
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", source, e))?;
+    Ok(MemexFsCore::from_snapshot(&bytes).map_err(|e| format!("{}: {}", source, e))?)
+}
+
+/// Index the .md, .csv, .tsv, and .json files under `dir`.
+fn load_dir(dir: &str, options: &MemexOptions) -> Result<MemexFsCore, String> {
+    let options = DirOptions {
+        include: TOOL_FILE_GLOBS.map(String::from).to_vec(),
+        exclude: Vec::new(),
+        options: options.clone(),
+    };
+    MemexFsCore::from_dir(dir, &options).map_err(|e| e.to_string())
+}
+
+/// Split `args` into positional arguments and `--name value` parameters.
+fn split_args(args: &[String]) -> Result<(Vec<String>, Map<String, Value>), Error> {
+    let mut positional = Vec::new();
+    let mut params = Map::new();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let Some(name) = arg.strip_prefix("--") else {
+            positional.push(arg.clone());
+            continue;
+        };
+        if name.is_empty() || name == "help" {
+            return Err(Error::Usage);
+        }
+        let value = match args.next_if(|next| !next.starts_with("--")) {
+            Some(value) => match serde_json::from_str::<Value>(value) {
+                Ok(json) if !json.is_string() => json,
+                _ => Value::String(value.clone()),
+            },
+            None => Value::Bool(true),
+        };
+        params.insert(name.replace('-', "_"), value);
+    }
+    Ok((positional, params))
+}
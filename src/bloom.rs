@@ -0,0 +1,212 @@
+use regex_syntax::hir::{Hir, HirKind};
+
+use crate::fold::fold_case;
+use crate::tokenizer::{is_cjk, Tokenizer};
+
+/// Bits per inserted item; with `HASHES` probes this keeps false positives
+/// around 1-2%.
+const BITS_PER_ITEM: usize = 10;
+const HASHES: u64 = 4;
+
+/// Per-document bloom filter over its tokens and the character trigrams of
+/// those tokens, so line scans can skip a document that cannot contain a
+/// query. Answers "maybe" or "definitely not"; never a false negative.
+#[derive(Debug, Clone)]
+pub struct TokenFilter {
+    bits: Vec<u64>,
+}
+
+impl TokenFilter {
+    /// Filter over the tokens and sub-tokens `tokenizer` splits `lines`
+    /// into, plus the plain spellings its folding indexes them under.
+    pub fn new<'a>(lines: impl IntoIterator<Item = &'a str>, tokenizer: &Tokenizer) -> Self {
+        let mut tokens: Vec<String> = lines
+            .into_iter()
+            .flat_map(|line| tokenizer.tokenize_parts(line))
+            .flat_map(|(token, sub)| std::iter::once(token).chain(sub))
+            .collect();
+        let variants: Vec<String> = tokens.iter().filter_map(|t| tokenizer.folding.variant(t)).collect();
+        tokens.extend(variants);
+        tokens.sort_unstable();
+        tokens.dedup();
+        let mut grams: Vec<[char; 3]> = tokens.iter().flat_map(|t| trigrams(t)).collect();
+        grams.sort_unstable();
+        grams.dedup();
+
+        let items = tokens.len() + grams.len();
+        let words = (items * BITS_PER_ITEM).div_ceil(64).next_power_of_two();
+        let mut filter = Self {
+            bits: vec![0; words],
+        };
+        for token in &tokens {
+            filter.insert(token_hash(token));
+        }
+        for gram in &grams {
+            filter.insert(gram_hash(gram));
+        }
+        filter
+    }
+
+    pub fn heap_size(&self) -> usize {
+        self.bits.capacity() * size_of::<u64>()
+    }
+
+    /// Whether the document may satisfy `query`.
+    pub fn may_match(&self, query: &FilterQuery) -> bool {
+        query.tokens.iter().all(|t| self.contains(token_hash(t)))
+            && query
+                .runs
+                .iter()
+                .flat_map(|r| trigrams(r))
+                .all(|g| self.contains(gram_hash(&g)))
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for bit in self.probes(hash) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.probes(hash).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Bit positions for `hash`, by double hashing.
+    fn probes(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let len = (self.bits.len() * 64) as u64;
+        let step = (hash >> 32) | 1;
+        (0..HASHES).map(move |i| (hash.wrapping_add(i.wrapping_mul(step)) % len) as usize)
+    }
+}
+
+/// What a matching document must contain, as far as a `TokenFilter` can
+/// tell: whole `tokens`, and alphanumeric `runs` lying inside some token.
+/// Both are case-folded. An empty query rules nothing out.
+#[derive(Debug, Default)]
+pub struct FilterQuery {
+    tokens: Vec<String>,
+    runs: Vec<String>,
+}
+
+impl FilterQuery {
+    /// Requirements of a line containing the folded literal `pattern_lower`.
+    pub fn literal(pattern_lower: &str) -> Self {
+        let mut query = Self::default();
+        query.add_literal(pattern_lower);
+        query
+    }
+
+    /// Requirements of any match of regex `source`, from the literal text
+    /// it cannot match without. Anything under an alternation or optional
+    /// repetition is ignored, and so is a pattern `regex_syntax` rejects
+    /// (e.g. one only `fancy-regex` compiles).
+    pub fn regex(source: &str) -> Self {
+        let mut query = Self::default();
+        if let Ok(hir) = regex_syntax::parse(source) {
+            for literal in required_literals(&hir) {
+                query.add_literal(&fold_case(&literal));
+            }
+        }
+        query
+    }
+
+    /// Words of `literal` with a non-alphanumeric character on both sides
+    /// must be whole tokens; the first and last word may be cut off, so
+    /// only their trigrams are required. Words with CJK text are skipped,
+    /// since with `cjk_ngram` they are not tokens at all.
+    fn add_literal(&mut self, literal: &str) {
+        let pieces: Vec<&str> = literal.split(|c: char| !c.is_alphanumeric()).collect();
+        let last = pieces.len() - 1;
+        let usable = |p: &&str| !p.is_empty() && !p.chars().any(is_cjk);
+        for (i, piece) in pieces.iter().enumerate().filter(|(_, p)| usable(p)) {
+            if i > 0 && i < last {
+                self.tokens.push(piece.to_string());
+            } else if piece.chars().count() >= 3 {
+                self.runs.push(piece.to_string());
+            }
+        }
+    }
+}
+
+/// Literal strings every match of `hir` contains. Adjacent literals in a
+/// concatenation are joined, so `foo bar` stays one string.
+fn required_literals(hir: &Hir) -> Vec<String> {
+    match hir.kind() {
+        HirKind::Literal(literal) => String::from_utf8(literal.0.to_vec()).into_iter().collect(),
+        HirKind::Capture(capture) => required_literals(&capture.sub),
+        HirKind::Repetition(repetition) if repetition.min >= 1 => required_literals(&repetition.sub),
+        HirKind::Concat(children) => {
+            let mut literals = Vec::new();
+            let mut run = Vec::new();
+            for child in children {
+                if let HirKind::Literal(literal) = child.kind() {
+                    run.extend_from_slice(&literal.0);
+                    continue;
+                }
+                literals.extend(String::from_utf8(std::mem::take(&mut run)));
+                literals.extend(required_literals(child));
+            }
+            literals.extend(String::from_utf8(run));
+            literals
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn trigrams(s: &str) -> Vec<[char; 3]> {
+    let chars: Vec<char> = s.chars().collect();
+    chars.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+/// FNV-1a, tagged so a token and a trigram never share a hash.
+fn fnv(tag: u8, bytes: impl Iterator<Item = u8>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in std::iter::once(tag).chain(bytes) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn token_hash(token: &str) -> u64 {
+    fnv(b't', token.bytes())
+}
+
+fn gram_hash(gram: &[char; 3]) -> u64 {
+    let mut buf = [0; 12];
+    let mut len = 0;
+    for c in gram {
+        len += c.encode_utf8(&mut buf[len..]).len();
+    }
+    fnv(b'g', buf[..len].iter().copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(content: &str) -> TokenFilter {
+        TokenFilter::new(content.lines(), &Tokenizer::default())
+    }
+
+    #[test]
+    fn test_literal_queries() {
+        let doc = filter("Reset your Password\nthen log-in again");
+        assert!(doc.may_match(&FilterQuery::literal("reset your pass")));
+        assert!(doc.may_match(&FilterQuery::literal("g-in ag")));
+        assert!(doc.may_match(&FilterQuery::literal("-")));
+        assert!(!doc.may_match(&FilterQuery::literal("reset my password")));
+        assert!(!doc.may_match(&FilterQuery::literal("passport")));
+    }
+
+    #[test]
+    fn test_regex_queries() {
+        let doc = filter("Reset your Password\nthen log-in again");
+        assert!(doc.may_match(&FilterQuery::regex(r"(?m)reset\s+\w+ pass")));
+        assert!(doc.may_match(&FilterQuery::regex(r"invoice|password")));
+        assert!(doc.may_match(&FilterQuery::regex(r"(?:invoice)?\s*password")));
+        assert!(doc.may_match(&FilterQuery::regex(r"pass(?!word)")));
+        assert!(!doc.may_match(&FilterQuery::regex(r"(Password)+ invoice")));
+        assert!(!doc.may_match(&FilterQuery::regex(r"\binvoices?\b")));
+    }
+}
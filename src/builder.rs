@@ -0,0 +1,400 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::error::MemexError;
+use crate::snapshot;
+use crate::tokenizer::TokenizerConfig;
+use crate::tools::ToolText;
+use crate::{tool_definitions_json, DuplicatePolicy, LoadReport, MemexFsCore, MemexOptions, StopWordList, SynonymMode};
+
+/// How much memory an instance spends on query speed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemoryMode {
+    /// Only the documents and the inverted index (the default).
+    #[default]
+    Compact,
+    /// Also `fold_cache` and `file_index`: case-insensitive scans never
+    /// fold lines, and file-level queries skip line postings. Roughly
+    /// doubles content memory.
+    Fast,
+}
+
+/// Host defaults set through `MemexFsBuilder`. They describe how this
+/// host wants queries answered rather than the corpus, so snapshots leave
+/// them out.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// `max_results` for greps that don't give one.
+    pub max_results: Option<usize>,
+    /// Glob scoping greps and `grep_files` calls that don't give one.
+    pub glob: Option<String>,
+    /// Tools `call` accepts and `tool_definitions` lists; all when `None`.
+    pub tools: Option<BTreeSet<String>>,
+    /// `MemexOptions::call_envelope`.
+    pub call_envelope: bool,
+    /// `MemexOptions::tool_text`.
+    pub tool_text: ToolText,
+    /// `MemexOptions::max_document_bytes`.
+    pub max_document_bytes: Option<usize>,
+}
+
+impl Settings {
+    /// These settings plus the host settings `options` carries.
+    pub fn with_options(self, options: &MemexOptions) -> Self {
+        Self {
+            call_envelope: options.call_envelope,
+            tool_text: options.tool_text.clone(),
+            max_document_bytes: options.max_document_bytes,
+            ..self
+        }
+    }
+}
+
+/// Step-by-step construction of a `MemexFsCore`, for settings that
+/// `MemexOptions` can't carry as JSON. `MemexFsCore::from_json` and
+/// friends are shorthands for a builder with only `options`.
+#[derive(Debug, Clone, Default)]
+pub struct MemexFsBuilder {
+    options: MemexOptions,
+    tokenizer: TokenizerConfig,
+    settings: Settings,
+}
+
+impl MemexFsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from `options`; later calls override their fields.
+    pub fn options(mut self, options: MemexOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// How documents and queries are split into index tokens.
+    pub fn tokenizer(mut self, tokenizer: TokenizerConfig) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Results per grep page when a call doesn't set `max_results`
+    /// (otherwise 100).
+    pub fn max_results(mut self, max_results: usize) -> Self {
+        self.settings.max_results = Some(max_results);
+        self
+    }
+
+    /// Scope greps and `grep_files` to paths matching `glob` unless a call
+    /// gives its own, e.g. `"docs/**"` to keep drafts out of answers.
+    pub fn default_glob(mut self, glob: &str) -> Self {
+        self.settings.glob = Some(glob.to_string());
+        self
+    }
+
+    /// Only offer these tools: `tool_definitions` lists just them and
+    /// `call` rejects the rest, e.g. `["grep", "read"]` for a read-only
+    /// agent without `write`.
+    pub fn tools<S: AsRef<str>>(mut self, tools: impl IntoIterator<Item = S>) -> Self {
+        self.settings.tools = Some(tools.into_iter().map(|t| t.as_ref().to_string()).collect());
+        self
+    }
+
+    /// Wrap every `call` result in a `CallEnvelope`
+    /// (`MemexOptions::call_envelope`).
+    pub fn call_envelope(mut self, enabled: bool) -> Self {
+        self.options.call_envelope = enabled;
+        self
+    }
+
+    /// Tailor the tool definitions' wording (`MemexOptions::tool_text`).
+    pub fn tool_text(mut self, text: ToolText) -> Self {
+        self.options.tool_text = text;
+        self
+    }
+
+    /// What loads do with a path that is already taken
+    /// (`MemexOptions::duplicate_paths`).
+    pub fn duplicate_paths(mut self, policy: DuplicatePolicy) -> Self {
+        self.options.duplicate_paths = policy;
+        self
+    }
+
+    /// See `MemoryMode`.
+    pub fn memory_mode(mut self, mode: MemoryMode) -> Self {
+        let fast = mode == MemoryMode::Fast;
+        self.options.fold_cache = fast;
+        self.options.file_index = fast;
+        self
+    }
+
+    /// Synonyms and how they are applied (`MemexOptions::synonyms`).
+    pub fn synonyms(mut self, synonyms: HashMap<String, Vec<String>>, mode: SynonymMode) -> Self {
+        self.options.synonyms = synonyms;
+        self.options.synonym_mode = mode;
+        self
+    }
+
+    /// Words ranked queries leave out (`MemexOptions::stop_words`).
+    pub fn stop_words(mut self, stop_words: StopWordList) -> Self {
+        self.options.stop_words = Some(stop_words);
+        self
+    }
+
+    /// Match other inflections of words (`MemexOptions::stemmer`).
+    #[cfg(feature = "stemming")]
+    pub fn stemmer(mut self, language: crate::StemLanguage) -> Self {
+        self.options.stemmer = Some(language);
+        self
+    }
+
+    /// An instance with no documents yet (see `MemexFsCore::empty`).
+    /// Fails if `tools` or `tool_text` names a tool (or parameter) that
+    /// doesn't exist.
+    pub fn empty(self) -> Result<MemexFsCore, MemexError> {
+        self.check_tools()?;
+        let mut core = MemexFsCore::build(&self.options, self.tokenizer);
+        core.settings = self.settings.with_options(&self.options);
+        Ok(core)
+    }
+
+    /// An instance over a JSON array of documents (see
+    /// `MemexFsCore::from_json`).
+    pub fn from_json(self, docs_json: &str) -> Result<MemexFsCore, MemexError> {
+        let mut core = self.empty()?;
+        core.add_documents(docs_json)?;
+        core.finalize()?;
+        Ok(core)
+    }
+
+    /// An instance over as many documents of a JSON array as can be
+    /// loaded, with a report of the rest (see
+    /// `MemexFsCore::add_documents_lenient`). Unlike `from_json`, an
+    /// instance is returned even if nothing could be loaded.
+    pub fn from_json_lenient(self, docs_json: &str) -> Result<(MemexFsCore, LoadReport), MemexError> {
+        let mut core = self.empty()?;
+        let report = core.add_documents_lenient(docs_json)?;
+        core.store.shrink_to_fit();
+        Ok((core, report))
+    }
+
+    /// An instance over compressed documents JSON (see
+    /// `MemexFsCore::add_documents_compressed`).
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    pub fn from_compressed(self, bytes: &[u8]) -> Result<MemexFsCore, MemexError> {
+        let mut core = self.empty()?;
+        core.add_documents_compressed(bytes)?;
+        core.finalize()?;
+        Ok(core)
+    }
+
+    /// An instance over the text files in a tar archive, gzipped or not.
+    /// Entry paths become document paths, without a leading `./`; hidden
+    /// entries (such as macOS `._` files) are skipped, binary files
+    /// handled by `MemexOptions::binary_policy`, and an entry repeated by
+    /// an appended update by `MemexOptions::duplicate_paths`.
+    #[cfg(feature = "tar")]
+    pub fn from_tar(self, bytes: &[u8]) -> Result<MemexFsCore, MemexError> {
+        let docs = crate::archive::tar_documents(bytes, self.options.binary_policy)?;
+        let mut core = self.empty()?;
+        core.load_documents(docs)?;
+        core.finalize()?;
+        Ok(core)
+    }
+
+    /// An instance over the text files in a zip archive, such as a CMS
+    /// export. Paths and skipped entries are as for `from_tar`; entries
+    /// must be stored or deflated.
+    #[cfg(feature = "zip")]
+    pub fn from_zip(self, bytes: &[u8]) -> Result<MemexFsCore, MemexError> {
+        let docs = crate::archive::zip_documents(bytes, self.options.binary_policy)?;
+        let mut core = self.empty()?;
+        core.load_documents(docs)?;
+        core.finalize()?;
+        Ok(core)
+    }
+
+    /// Restore a snapshot. Its corpus settings, tokenizer config
+    /// included, come from the snapshot; from this builder come the host
+    /// defaults (`max_results`, `default_glob`, `tools`, `call_envelope`,
+    /// `tool_text`) and a split function (`TokenizerConfig::split`), which
+    /// snapshots can't store.
+    pub fn from_snapshot(self, bytes: &[u8]) -> Result<MemexFsCore, MemexError> {
+        self.check_tools()?;
+        let mut snapshot = snapshot::decode(bytes)?;
+        let index = snapshot.store.index_mut();
+        index.restore_split(&self.tokenizer);
+        if index.tokenizer().config.missing_split() {
+            return Err(MemexError::InvalidSnapshot {
+                reason: "snapshot was built with a custom split function; set it with TokenizerConfig::split".to_string(),
+            });
+        }
+        let mut core = MemexFsCore::restored(snapshot);
+        core.settings = self.settings.with_options(&self.options);
+        Ok(core)
+    }
+
+    fn check_tools(&self) -> Result<(), MemexError> {
+        let definitions = tool_definitions_json();
+        self.options.tool_text.check(&definitions)?;
+        let known: BTreeSet<&str> = definitions.as_array().into_iter().flatten().filter_map(|def| def["name"].as_str()).collect();
+        match self.settings.tools.iter().flatten().find(|t| !known.contains(t.as_str())) {
+            Some(tool) => Err(MemexError::UnknownTool { name: tool.clone() }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::SplitFn;
+    use crate::tools::ToolOverride;
+    use crate::GrepOptions;
+
+    fn docs() -> String {
+        serde_json::to_string(&vec![
+            ("api.md", "Call user_id.get() or user.get() first"),
+            ("log.txt", "2024-01-05|ERROR|disk full\n2024-01-06|INFO|ok"),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tokenizer_config() {
+        let code = MemexFsBuilder::new()
+            .tokenizer(TokenizerConfig {
+                keep_chars: "_.".to_string(),
+                min_token_len: 3,
+                ..Default::default()
+            })
+            .from_json(&docs())
+            .unwrap();
+        let index = code.store.index();
+        assert!(index.lookup("user_id.get").is_some());
+        assert!(index.lookup("or").is_none());
+        assert_eq!(code.search("user_id.get", None)[0].path, "api.md");
+        // Grep is unaffected: tokens no longer match words, so it scans
+        assert_eq!(code.grep("user", None).unwrap().len(), 1);
+        assert_eq!(code.grep_files("or", None).unwrap(), vec!["api.md", "log.txt"]);
+
+        let cased = MemexFsBuilder::new()
+            .tokenizer(TokenizerConfig {
+                lowercase: false,
+                ..Default::default()
+            })
+            .from_json(&docs())
+            .unwrap();
+        assert!(cased.store.index().lookup("ERROR").is_some());
+        assert!(cased.store.index().lookup("error").is_none());
+        assert_eq!(cased.search("ERROR", None)[0].path, "log.txt");
+        assert!(cased.search("error", None).is_empty());
+        assert_eq!(cased.grep("error", None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_host_defaults() {
+        let docs = serde_json::to_string(&vec![
+            ("docs/a.md", "deploy\ndeploy\ndeploy"),
+            ("drafts/b.md", "deploy"),
+        ])
+        .unwrap();
+        let fs = MemexFsBuilder::new()
+            .max_results(2)
+            .default_glob("docs/**")
+            .tools(["grep", "read"])
+            .memory_mode(MemoryMode::Fast)
+            .from_json(&docs)
+            .unwrap();
+
+        let page = fs.grep_page("deploy", &GrepOptions::default()).unwrap();
+        assert_eq!((page.results.len(), page.total_matches), (2, 3));
+        let all = GrepOptions {
+            glob: Some("**".to_string()),
+            max_results: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(fs.grep_page("deploy", &all).unwrap().total_matches, 4);
+        assert_eq!(fs.grep_files("deploy", None).unwrap(), vec!["docs/a.md"]);
+        assert!(fs.memory_stats().folded > 0);
+
+        let defs: serde_json::Value = serde_json::from_str(&fs.tool_definitions()).unwrap();
+        let names: Vec<&str> = defs.as_array().unwrap().iter().map(|d| d["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["grep", "read"]);
+        assert!(fs.call("read", r#"{"path": "docs/a.md"}"#).is_ok());
+        let err = fs.call("write", r#"{"path": "x.md", "content": "x"}"#).unwrap_err();
+        assert!(err.to_string().contains("tool not enabled: write"));
+
+        // Host defaults aren't part of a snapshot, but a builder restores them
+        let bytes = fs.to_snapshot();
+        assert_eq!(MemexFsCore::from_snapshot(&bytes).unwrap().grep("deploy", None).unwrap().len(), 4);
+        let restored = MemexFsBuilder::new().default_glob("drafts/**").from_snapshot(&bytes).unwrap();
+        assert_eq!(restored.grep_files("deploy", None).unwrap(), vec!["drafts/b.md"]);
+
+        let err = MemexFsBuilder::new().tools(["grpe"]).from_json(&docs).err().unwrap();
+        assert!(err.to_string().contains("unknown tool: grpe"));
+    }
+
+    #[test]
+    fn test_tool_text() {
+        let docs = serde_json::to_string(&vec![("docs/a.md", "Refund policy")]).unwrap();
+        let text: ToolText = serde_json::from_value(serde_json::json!({
+            "corpus": "Acme help center",
+            "tools": {
+                "grep": {
+                    "name": "buscar",
+                    "description": "Busca un patrón en los documentos.",
+                    "parameters": { "pattern": "Patrón de búsqueda" }
+                }
+            }
+        }))
+        .unwrap();
+        let fs = MemexFsBuilder::new()
+            .tool_text(text.clone())
+            .tools(["grep", "read"])
+            .from_json(&docs)
+            .unwrap();
+
+        let defs = fs.tool_definitions_value();
+        assert_eq!(defs[0]["name"], "buscar");
+        assert_eq!(defs[0]["description"], "Busca un patrón en los documentos.");
+        assert_eq!(defs[0]["parameters"]["pattern"]["description"], "Patrón de búsqueda");
+        assert_eq!(defs[1]["name"], "read");
+        assert_eq!(
+            defs[1]["parameters"]["path"]["description"],
+            "Document path relative to the Acme help center root"
+        );
+
+        // Called by either name; `tools` still takes the built-in names
+        let by_new = fs.call("buscar", r#"{"pattern": "refund"}"#).unwrap();
+        assert_eq!(by_new, fs.call("grep", r#"{"pattern": "refund"}"#).unwrap());
+        assert!(fs.call("ls", r#"{"path": ""}"#).is_err());
+
+        let mut bad = text.clone();
+        bad.tools.get_mut("grep").unwrap().parameters.insert("patern".to_string(), "x".to_string());
+        let err = MemexFsBuilder::new().tool_text(bad).from_json(&docs).err().unwrap();
+        assert_eq!(err.code(), "invalid_params");
+        let mut bad = text;
+        bad.tools.insert("serach".to_string(), ToolOverride::default());
+        let err = MemexFsBuilder::new().tool_text(bad).from_json(&docs).err().unwrap();
+        assert_eq!(err, MemexError::UnknownTool { name: "serach".to_string() });
+    }
+
+    #[test]
+    fn test_split_function_survives_snapshots() {
+        let builder = MemexFsBuilder::new().tokenizer(TokenizerConfig {
+            split: Some(SplitFn::new(|c| c == '|' || c == '\n')),
+            ..Default::default()
+        });
+        let fs = builder.clone().from_json(&docs()).unwrap();
+        assert!(fs.store.index().lookup("disk full").is_some());
+
+        let bytes = fs.to_snapshot();
+        let err = MemexFsCore::from_snapshot(&bytes).err().unwrap();
+        assert!(err.to_string().contains("custom split function"));
+        assert!(MemexFsBuilder::new().from_snapshot(&bytes).is_err());
+
+        let mut restored = builder.from_snapshot(&bytes).unwrap();
+        restored.write_document("new.txt", "2024-01-07|WARN|slow disk").unwrap();
+        assert!(restored.store.index().lookup("slow disk").is_some());
+        assert_eq!(restored.search("disk full", None)[0].path, "log.txt");
+    }
+}
@@ -0,0 +1,80 @@
+use crate::error::MemexError;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Decompress a documents payload: gzip, recognized by its magic bytes,
+/// or else brotli, which has no magic of its own.
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, MemexError> {
+    let mut out = Vec::with_capacity(bytes.len().saturating_mul(6));
+    if bytes.starts_with(&GZIP_MAGIC) {
+        gunzip(bytes, &mut out)?;
+    } else {
+        unbrotli(bytes, &mut out)?;
+    }
+    Ok(out)
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip(bytes: &[u8], out: &mut Vec<u8>) -> Result<(), MemexError> {
+    use std::io::Read;
+    match flate2::read::MultiGzDecoder::new(bytes).read_to_end(out) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(invalid(format!("gzip: {}", e))),
+    }
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_: &[u8], _: &mut Vec<u8>) -> Result<(), MemexError> {
+    Err(invalid("gzip input needs the `gzip` feature".to_string()))
+}
+
+#[cfg(feature = "brotli")]
+fn unbrotli(bytes: &[u8], out: &mut Vec<u8>) -> Result<(), MemexError> {
+    use std::io::Read;
+    match brotli_decompressor::Decompressor::new(bytes, 4096).read_to_end(out) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(invalid(format!("brotli: {}", e))),
+    }
+}
+
+#[cfg(not(feature = "brotli"))]
+fn unbrotli(_: &[u8], _: &mut Vec<u8>) -> Result<(), MemexError> {
+    Err(invalid("not gzip data".to_string()))
+}
+
+fn invalid(reason: String) -> MemexError {
+    MemexError::InvalidDocuments { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_decompress_gzip() {
+        use std::io::Write;
+        let json = br#"[["a.md", "hello"]]"#;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(json).unwrap();
+        let gz = encoder.finish().unwrap();
+        assert_eq!(decompress(&gz).unwrap(), json);
+
+        let truncated = decompress(&gz[..gz.len() / 2]).unwrap_err();
+        assert_eq!(truncated.code(), "invalid_documents");
+        #[cfg(not(feature = "brotli"))]
+        assert_eq!(
+            decompress(json).unwrap_err().to_string(),
+            "MemexError: invalid documents: not gzip data"
+        );
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn test_decompress_brotli() {
+        // `{"a":1}` compressed with `brotli -q 11`
+        let br = [0x0b, 0x03, 0x80, 0x7b, 0x22, 0x61, 0x22, 0x3a, 0x31, 0x7d, 0x03];
+        assert_eq!(decompress(&br).unwrap(), br#"{"a":1}"#);
+        assert_eq!(decompress(b"not brotli").unwrap_err().code(), "invalid_documents");
+    }
+}
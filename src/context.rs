@@ -0,0 +1,76 @@
+/// Rough token estimate for budgeting LLM context: about four characters
+/// per token, rounded up.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// A retrieved snippet with the location it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Passage {
+    pub path: String,
+    pub line: u32,
+    pub text: String,
+}
+
+impl Passage {
+    /// The passage as one `[path:line] text` context line.
+    fn render(&self) -> String {
+        format!("[{}:{}] {}", self.path, self.line, self.text)
+    }
+}
+
+/// Join passages (most relevant first) into newline-separated context,
+/// skipping any that would push the total past `budget_tokens`.
+pub fn pack(passages: &[Passage], budget_tokens: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+
+    for passage in passages {
+        let entry = passage.render();
+        // Every entry but the first also costs its separating newline
+        let cost = estimate_tokens(&entry) + usize::from(!out.is_empty());
+        if used + cost > budget_tokens {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&entry);
+        used += cost;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passage(path: &str, line: u32, text: &str) -> Passage {
+        Passage {
+            path: path.to_string(),
+            line,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn test_pack_skips_what_does_not_fit() {
+        let passages = [
+            passage("a.md", 1, "short"),
+            passage("b.md", 2, &"long ".repeat(40)),
+            passage("c.md", 3, "also short"),
+        ];
+        let packed = pack(&passages, 12);
+        assert_eq!(packed, "[a.md:1] short\n[c.md:3] also short");
+        assert!(estimate_tokens(&packed) <= 12);
+        assert_eq!(pack(&passages, 0), "");
+    }
+}
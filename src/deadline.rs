@@ -0,0 +1,51 @@
+/// A point in time after which a query stops scanning and returns what it
+/// has found so far.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    at_ms: f64,
+}
+
+impl Deadline {
+    pub fn after_ms(ms: u64) -> Self {
+        Self {
+            at_ms: now_ms() + ms as f64,
+        }
+    }
+
+    pub fn expired(&self) -> bool {
+        now_ms() >= self.at_ms
+    }
+}
+
+/// Milliseconds on a monotonic-enough clock. `std::time::Instant` panics
+/// on `wasm32-unknown-unknown`, so the browser build asks JS instead.
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+/// No clock without JS on wasm32, so deadlines never expire there.
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+fn now_ms() -> f64 {
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_expires() {
+        assert!(Deadline::after_ms(0).expired());
+        assert!(!Deadline::after_ms(60_000).expired());
+    }
+}
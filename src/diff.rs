@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::document::Document;
+
+/// Unchanged lines kept on each side of a change, as in `diff -u`.
+const CONTEXT: usize = 3;
+
+/// Differing lines past which a document is taken as rewritten: its
+/// changed middle is removed and added whole instead of matched up, which
+/// bounds the time and memory a diff takes.
+const MAX_EDITS: usize = 1000;
+
+/// How two corpora differ, from `diff_stores` or
+/// `MemexFsCore::diff_mounts`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoreDiff {
+    /// Paths only the newer corpus has, sorted.
+    pub added: Vec<String>,
+    /// Paths only the older corpus has, sorted.
+    pub removed: Vec<String>,
+    /// Documents at paths both have whose lines or metadata differ,
+    /// sorted by path.
+    pub changed: Vec<DocumentDiff>,
+}
+
+/// What changed in one document of `StoreDiff::changed`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentDiff {
+    pub path: String,
+    /// Lines only the newer version has.
+    pub lines_added: usize,
+    /// Lines only the older version has.
+    pub lines_removed: usize,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub metadata_changed: bool,
+    /// The changed lines with their context, in document order. Empty if
+    /// only the metadata changed.
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// A run of changes with up to three unchanged lines around it, as in a
+/// unified diff.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffHunk {
+    /// 1-indexed first line of the hunk in the older version, or the line
+    /// it follows when it has no lines there.
+    pub old_start: u32,
+    pub old_lines: u32,
+    /// Same as `old_start`, in the newer version.
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// The lines, each starting with ` ` (unchanged), `-` (removed), or
+    /// `+` (added).
+    pub lines: Vec<String>,
+}
+
+impl StoreDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Number of paths added, removed, or changed.
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+}
+
+/// A summary line per path, then a unified diff of each changed document.
+impl fmt::Display for StoreDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No differences");
+        }
+        for path in &self.added {
+            writeln!(f, "added: {}", path)?;
+        }
+        for path in &self.removed {
+            writeln!(f, "removed: {}", path)?;
+        }
+        for doc in &self.changed {
+            write!(f, "changed: {} (+{} -{}", doc.path, doc.lines_added, doc.lines_removed)?;
+            if doc.metadata_changed {
+                write!(f, ", metadata")?;
+            }
+            writeln!(f, ")")?;
+        }
+        for doc in self.changed.iter().filter(|doc| !doc.hunks.is_empty()) {
+            write!(f, "\n--- {0}\n+++ {0}\n", doc.path)?;
+            for hunk in &doc.hunks {
+                writeln!(f, "@@ -{},{} +{},{} @@", hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines)?;
+                for line in &hunk.lines {
+                    writeln!(f, "{}", line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compare two corpora given as `(path, document)` pairs, the older one
+/// first. Documents are matched by path.
+pub fn diff<'a>(
+    old: impl IntoIterator<Item = (&'a str, &'a Document)>,
+    new: impl IntoIterator<Item = (&'a str, &'a Document)>,
+) -> StoreDiff {
+    let mut old: BTreeMap<&str, &Document> = old.into_iter().collect();
+    let new: BTreeMap<&str, &Document> = new.into_iter().collect();
+    let mut diff = StoreDiff::default();
+    for (path, doc) in new {
+        match old.remove(path) {
+            Some(before) => diff.changed.extend(document(path, before, doc)),
+            None => diff.added.push(path.to_string()),
+        }
+    }
+    diff.removed = old.into_keys().map(String::from).collect();
+    diff
+}
+
+/// The changes from `old` to `new`, or `None` if their lines and
+/// metadata are the same.
+fn document(path: &str, old: &Document, new: &Document) -> Option<DocumentDiff> {
+    let metadata_changed = old.metadata != new.metadata;
+    if !metadata_changed && old.lines().eq(new.lines()) {
+        return None;
+    }
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let edits = edits(&old, &new);
+    let count = |op: Op| edits.iter().filter(|edit| edit.op == op).count();
+    Some(DocumentDiff {
+        path: path.to_string(),
+        lines_added: count(Op::Add),
+        lines_removed: count(Op::Remove),
+        metadata_changed,
+        hunks: hunks(&edits, &old, &new),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Keep,
+    Remove,
+    Add,
+}
+
+/// One step of an edit script, at 0-indexed line `old` of the older
+/// version and `new` of the newer one. An added line sits before line
+/// `old`, a removed one before line `new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Edit {
+    op: Op,
+    old: usize,
+    new: usize,
+}
+
+/// A shortest edit script turning `old` into `new`, in order. Common
+/// leading and trailing lines are set aside first, so a small change to a
+/// long document stays cheap.
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit { op: Op::Keep, old: i, new: i }).collect();
+    match myers(a, b) {
+        Some(middle) => {
+            edits.extend(middle.into_iter().map(|e| Edit { old: e.old + prefix, new: e.new + prefix, ..e }));
+        }
+        None => {
+            edits.extend((0..a.len()).map(|i| Edit { op: Op::Remove, old: prefix + i, new: prefix }));
+            let old = prefix + a.len();
+            edits.extend((0..b.len()).map(|j| Edit { op: Op::Add, old, new: prefix + j }));
+        }
+    }
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    edits.extend((0..suffix).map(|i| Edit { op: Op::Keep, old: old_end + i, new: new_end + i }));
+    edits
+}
+
+/// Myers' O(ND) diff of `a` and `b`, or `None` if they differ in more
+/// than `MAX_EDITS` lines.
+fn myers(a: &[&str], b: &[&str]) -> Option<Vec<Edit>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_EDITS) as isize;
+    // Furthest x reached on each diagonal k = x - y, offset so k - 1 and
+    // k + 1 stay in bounds
+    let at = |k: isize| (k + max + 1) as usize;
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    let mut trace = Vec::new();
+    for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m, at));
+            }
+        }
+    }
+    None
+}
+
+/// Walk `trace`, the furthest points before each round of `myers`, back
+/// from the end to recover the edits.
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize, at: impl Fn(isize) -> usize) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) { k + 1 } else { k - 1 };
+        let prev_x = v[at(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit { op: Op::Keep, old: x as usize, new: y as usize });
+        }
+        if d > 0 {
+            let op = if x == prev_x { Op::Add } else { Op::Remove };
+            edits.push(Edit { op, old: prev_x as usize, new: prev_y as usize });
+        }
+        (x, y) = (prev_x, prev_y);
+    }
+    edits.reverse();
+    edits
+}
+
+/// Group `edits` into hunks, merging changes whose context would overlap.
+fn hunks(edits: &[Edit], old: &[&str], new: &[&str]) -> Vec<DiffHunk> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (i, _) in edits.iter().enumerate().filter(|(_, edit)| edit.op != Op::Keep) {
+        let range = i.saturating_sub(CONTEXT)..(i + CONTEXT + 1).min(edits.len());
+        match ranges.last_mut() {
+            Some(last) if range.start <= last.end => last.end = range.end,
+            _ => ranges.push(range),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|range| {
+            let edits = &edits[range];
+            let lines: Vec<String> = edits
+                .iter()
+                .map(|edit| match edit.op {
+                    Op::Keep => format!(" {}", old[edit.old]),
+                    Op::Remove => format!("-{}", old[edit.old]),
+                    Op::Add => format!("+{}", new[edit.new]),
+                })
+                .collect();
+            let old_lines = edits.iter().filter(|edit| edit.op != Op::Add).count();
+            let new_lines = edits.iter().filter(|edit| edit.op != Op::Remove).count();
+            let start = |at: usize, lines: usize| (at + usize::from(lines > 0)) as u32;
+            DiffHunk {
+                old_start: start(edits[0].old, old_lines),
+                old_lines: old_lines as u32,
+                new_start: start(edits[0].new, new_lines),
+                new_lines: new_lines as u32,
+                lines,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(path: &str, content: &str) -> Document {
+        Document::new(path.into(), content)
+    }
+
+    fn pairs(docs: &[Document]) -> impl Iterator<Item = (&str, &Document)> {
+        docs.iter().map(|doc| (doc.path.as_str(), doc))
+    }
+
+    /// `edits` applied to `old`, which should give `new`.
+    fn apply<'a>(edits: &[Edit], old: &[&'a str], new: &[&'a str]) -> Vec<&'a str> {
+        edits
+            .iter()
+            .filter_map(|edit| match edit.op {
+                Op::Keep => Some(old[edit.old]),
+                Op::Remove => None,
+                Op::Add => Some(new[edit.new]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_edits() {
+        let cases: [(&[&str], &[&str], usize); 5] = [
+            (&["a", "b", "c"], &["a", "b", "c"], 0),
+            (&[], &["a", "b"], 2),
+            (&["a", "b", "c", "a", "b", "b", "a"], &["c", "b", "a", "b", "a", "c"], 5),
+            (&["x", "a", "y"], &["a", "z"], 3),
+            (&["a"], &[], 1),
+        ];
+        for (old, new, changes) in cases {
+            let edits = edits(old, new);
+            assert_eq!(apply(&edits, old, new), new, "{:?} -> {:?}", old, new);
+            assert_eq!(edits.iter().filter(|e| e.op != Op::Keep).count(), changes, "{:?} -> {:?}", old, new);
+        }
+
+        // Past MAX_EDITS the middle is replaced whole, still correctly
+        let old: Vec<String> = (0..MAX_EDITS).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..MAX_EDITS).map(|i| format!("new {}", i)).collect();
+        let old: Vec<&str> = ["same"].into_iter().chain(old.iter().map(String::as_str)).collect();
+        let new: Vec<&str> = ["same"].into_iter().chain(new.iter().map(String::as_str)).collect();
+        let edits = edits(&old, &new);
+        assert_eq!(apply(&edits, &old, &new), new);
+        assert_eq!(edits.len(), 1 + 2 * MAX_EDITS);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old_lines: Vec<String> = (1..=12).map(|i| format!("line {}", i)).collect();
+        let mut new_lines = old_lines.clone();
+        new_lines[1] = "line two".into();
+        new_lines.remove(10);
+        let v1 = [
+            doc("a.md", &old_lines.join("\n")),
+            doc("gone.md", "bye"),
+            doc("same.md", "same\r\n"),
+            doc("tagged.md", "body"),
+        ];
+        let mut tagged = doc("tagged.md", "body");
+        tagged.metadata = Some(serde_json::json!({"tags": ["new"]}));
+        let v2 = [doc("a.md", &new_lines.join("\n")), doc("new.md", "hi"), doc("same.md", "same"), tagged];
+
+        let diff = diff(pairs(&v1), pairs(&v2));
+        assert_eq!(diff.added, ["new.md"]);
+        assert_eq!(diff.removed, ["gone.md"]);
+        assert_eq!(diff.changed.len(), 2);
+        assert_eq!(diff.len(), 4);
+
+        let a = &diff.changed[0];
+        assert_eq!((a.path.as_str(), a.lines_added, a.lines_removed, a.metadata_changed), ("a.md", 1, 2, false));
+        let starts: Vec<_> = a.hunks.iter().map(|h| (h.old_start, h.old_lines, h.new_start, h.new_lines)).collect();
+        assert_eq!(starts, [(1, 5, 1, 5), (8, 5, 8, 4)]);
+        assert_eq!(a.hunks[0].lines, [" line 1", "-line 2", "+line two", " line 3", " line 4", " line 5"]);
+        assert_eq!(a.hunks[1].lines[3], "-line 11");
+        assert!(diff.changed[1].metadata_changed && diff.changed[1].hunks.is_empty());
+
+        let text = diff.to_string();
+        assert!(text.starts_with("added: new.md\nremoved: gone.md\nchanged: a.md (+1 -2)\n"), "{}", text);
+        assert!(text.contains("changed: tagged.md (+0 -0, metadata)\n"));
+        assert!(text.contains("\n--- a.md\n+++ a.md\n@@ -1,5 +1,5 @@\n line 1\n-line 2\n"));
+        assert!(!text.contains("+++ tagged.md"));
+        assert_eq!(StoreDiff::default().to_string(), "No differences");
+    }
+}
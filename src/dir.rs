@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::document::DocInput;
+use crate::error::MemexError;
+use crate::sniff;
+use crate::MemexOptions;
+
+/// Include globs for the files the tools work with: markdown, CSV and TSV
+/// tables for `query_table`, and JSON for `read_json`. The command-line
+/// binaries load these.
+pub const TOOL_FILE_GLOBS: [&str; 4] = ["**/*.md", "**/*.csv", "**/*.tsv", "**/*.json"];
+
+/// What `MemexFsCore::from_dir` loads and how it indexes it.
+#[derive(Debug, Default, Clone)]
+pub struct DirOptions {
+    /// Globs over paths relative to the directory, e.g. `**/*.md`. A file
+    /// is loaded if it matches any of them; empty loads every text file.
+    pub include: Vec<String>,
+    /// Globs for files and directories to leave out, e.g. `drafts/**` or
+    /// `**/node_modules`. Checked before `include`.
+    pub exclude: Vec<String>,
+    /// Options for the instance.
+    pub options: MemexOptions,
+}
+
+impl DirOptions {
+    fn excluded(&self, path: &str) -> bool {
+        self.exclude.iter().any(|glob| glob_match::glob_match(glob, path))
+    }
+
+    fn included(&self, path: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|glob| glob_match::glob_match(glob, path))
+    }
+}
+
+/// The documents under `root`, sorted by path. Paths are relative to
+/// `root` and `/`-separated. Hidden files and directories (`.git`) are
+/// skipped; files that aren't text are handled by the options'
+/// `binary_policy`. Symbolic links are followed, except dangling ones and
+/// ones leading back into a directory being walked.
+pub fn collect(root: &Path, options: &DirOptions) -> Result<Vec<DocInput>, MemexError> {
+    let mut docs = Vec::new();
+    walk(root, "", options, &mut HashSet::new(), &mut docs)?;
+    docs.sort_by(|a, b| a.path().cmp(b.path()));
+    Ok(docs)
+}
+
+/// `ancestors` holds the canonical paths of the directories being walked,
+/// so a symlink loop is not followed around.
+fn walk(
+    dir: &Path,
+    prefix: &str,
+    options: &DirOptions,
+    ancestors: &mut HashSet<PathBuf>,
+    docs: &mut Vec<DocInput>,
+) -> Result<(), MemexError> {
+    let io_error = |path: &Path, e: std::io::Error| MemexError::Io {
+        path: path.display().to_string(),
+        reason: e.to_string(),
+    };
+    let real = dir.canonicalize().map_err(|e| io_error(dir, e))?;
+    if !ancestors.insert(real.clone()) {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir).map_err(|e| io_error(dir, e))? {
+        let entry = entry.map_err(|e| io_error(dir, e))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{}{}", prefix, name);
+        if name.starts_with('.') || options.excluded(&path) {
+            continue;
+        }
+        // `metadata` follows symlinks, where `entry.file_type` doesn't
+        let metadata = match std::fs::metadata(entry.path()) {
+            Ok(metadata) => metadata,
+            Err(_) if entry.file_type().is_ok_and(|t| t.is_symlink()) => continue,
+            Err(e) => return Err(io_error(&entry.path(), e)),
+        };
+        if metadata.is_dir() {
+            walk(&entry.path(), &format!("{}/", path), options, ancestors, docs)?;
+        } else if options.included(&path) {
+            let bytes = std::fs::read(entry.path()).map_err(|e| io_error(&entry.path(), e))?;
+            docs.extend(sniff::from_bytes(path, bytes, options.options.binary_policy)?);
+        }
+    }
+    ancestors.remove(&real);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_globs() {
+        let root = std::env::temp_dir().join(format!("memexfs-dir-{}", std::process::id()));
+        let files: [(&str, &[u8]); 6] = [
+            ("guide.md", b"# Guide"),
+            ("billing/refund.md", b"# Refunds"),
+            ("billing/drafts/new.md", b"# Draft"),
+            ("billing/notes.txt", b"plain text"),
+            ("logo.png", b"\x89PNG\r\n\x1a\n\0\0"),
+            (".git/HEAD.md", b"ref"),
+        ];
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let paths = |include: &[&str], exclude: &[&str]| {
+            let options = DirOptions {
+                include: include.iter().map(|s| s.to_string()).collect(),
+                exclude: exclude.iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            };
+            let docs = collect(&root, &options).unwrap();
+            docs.into_iter().map(|doc| doc.into_parts().0).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            paths(&[], &[]),
+            ["billing/drafts/new.md", "billing/notes.txt", "billing/refund.md", "guide.md"]
+        );
+        assert_eq!(paths(&["**/*.md"], &["billing/drafts"]), ["billing/refund.md", "guide.md"]);
+        assert_eq!(paths(&["*.md"], &[]), ["guide.md"]);
+
+        let missing = collect(&root.join("missing"), &DirOptions::default()).unwrap_err();
+        assert_eq!(missing.code(), "io");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let root = std::env::temp_dir().join(format!("memexfs-links-{}", std::process::id()));
+        let shared = std::env::temp_dir().join(format!("memexfs-shared-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::write(root.join("docs/guide.md"), "# Guide").unwrap();
+        std::fs::write(shared.join("faq.md"), "# FAQ").unwrap();
+        symlink(&shared, root.join("shared")).unwrap();
+        symlink(root.join("docs/guide.md"), root.join("start.md")).unwrap();
+        symlink(&root, root.join("docs/loop")).unwrap();
+        symlink(root.join("gone.md"), root.join("dangling.md")).unwrap();
+
+        let docs = collect(&root, &DirOptions::default()).unwrap();
+        let paths: Vec<String> = docs.into_iter().map(|doc| doc.into_parts().0).collect();
+        assert_eq!(paths, ["docs/guide.md", "shared/faq.md", "start.md"]);
+        std::fs::remove_dir_all(&root).unwrap();
+        std::fs::remove_dir_all(&shared).unwrap();
+    }
+}
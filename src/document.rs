@@ -1,47 +1,549 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::fold::{fold_case, Folding};
+use crate::frontmatter;
+use crate::sniff::{self, BinaryPolicy};
+
+/// A document as accepted by `from_json` and `add_documents`: a
+/// `[path, content]` pair or a `{ path, content, metadata? }` object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DocInput {
+    Object {
+        path: String,
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<Value>,
+    },
+    Pair(String, String),
+}
+
+impl DocInput {
+    /// Split into path, content, and metadata.
+    pub fn into_parts(self) -> (String, String, Option<Value>) {
+        match self {
+            DocInput::Object {
+                path,
+                content,
+                metadata,
+            } => (path, content, metadata),
+            DocInput::Pair(path, content) => (path, content, None),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            DocInput::Object { path, .. } | DocInput::Pair(path, _) => path,
+        }
+    }
+
+    pub fn content(&self) -> &str {
+        match self {
+            DocInput::Object { content, .. } | DocInput::Pair(_, content) => content,
+        }
+    }
+
+    /// A document from a file's bytes, or `None` if they aren't text: not
+    /// UTF-8, containing NUL, or mostly control characters.
+    pub fn from_bytes(path: String, bytes: Vec<u8>) -> Option<Self> {
+        sniff::from_bytes(path, bytes, BinaryPolicy::Skip).ok().flatten()
+    }
+}
+
+impl From<(String, String)> for DocInput {
+    fn from((path, content): (String, String)) -> Self {
+        DocInput::Pair(path, content)
+    }
+}
+
+impl From<&Document> for DocInput {
+    fn from(doc: &Document) -> Self {
+        DocInput::Object {
+            path: doc.path.clone(),
+            content: doc.content().to_string(),
+            metadata: doc.metadata.clone(),
+        }
+    }
+}
 
-/// A single document stored as a path and its lines.
+/// Options for a line-numbered read of a document.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct ReadOptions {
+    /// Line number to start from (1-indexed). Defaults to 1.
+    pub offset: Option<usize>,
+    /// Maximum number of lines to return. Defaults to all lines.
+    pub limit: Option<usize>,
+    /// Expand tabs to spaces using tab stops of this width. Stored content
+    /// is never modified.
+    pub tab_width: Option<usize>,
+    /// Only read the section under this markdown heading (matched like
+    /// `Document::section_range`). `offset` and `limit` still apply, clamped
+    /// to the section, and line numbers stay those of the whole document.
+    pub section: Option<String>,
+}
+
+/// A single line as returned by `Document::read_lines`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NumberedLine {
+    /// 1-indexed line number.
+    pub number: u32,
+    pub text: String,
+}
+
+/// Folded lines of a document, laid out like `Document::content` and
+/// `spans`. Folding can change byte lengths, so the spans are separate.
+#[derive(Debug, Clone, Default)]
+struct FoldedLines {
+    content: String,
+    spans: Vec<Range<u32>>,
+}
+
+impl FoldedLines {
+    fn line(&self, idx: usize) -> &str {
+        let span = &self.spans[idx];
+        &self.content[span.start as usize..span.end as usize]
+    }
+}
+
+/// A markdown ATX heading (`#` through `######`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    /// 1-indexed line number of the heading.
+    pub line: u32,
+}
+
+/// A single document stored as a path and its content. Lines are byte
+/// ranges into the one content buffer rather than separate strings.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document {
     pub path: String,
-    pub lines: Vec<String>,
-    /// Pre-lowercased lines for fast case-insensitive search.
-    pub lines_lower: Vec<String>,
+    content: String,
+    /// Byte range of each line in `content`, without its line ending.
+    /// Derived from `content`, so left out of snapshots and rebuilt with
+    /// `split_lines`.
+    #[serde(skip)]
+    spans: Vec<Range<u32>>,
+    /// 0-indexed line ranges of fenced code blocks, fences included, in
+    /// document order. Derived from `content` along with `spans`.
+    #[serde(skip)]
+    code_blocks: Vec<Range<u32>>,
+    /// Case-folded copy of the lines, kept only when the store caches
+    /// folded lines (see `cache_folded`); otherwise lines are folded as
+    /// they are scanned.
+    #[serde(skip)]
+    folded: Option<FoldedLines>,
+    /// Number of leading lines taken up by a YAML frontmatter block
+    /// (including both `---` fences), or 0 if there is none.
+    pub frontmatter_len: usize,
+    /// Arbitrary JSON supplied with the document (tags, author, ...), merged
+    /// over the keys parsed from its frontmatter.
+    #[serde(with = "json_string")]
+    pub metadata: Option<Value>,
 }
 
 impl Document {
     pub fn new(path: String, content: &str) -> Self {
-        let lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
-        let lines_lower: Vec<String> = lines.iter().map(|l| l.to_lowercase()).collect();
-        Self { path, lines, lines_lower }
+        let lines: Vec<&str> = content.lines().collect();
+        let frontmatter_len = frontmatter_len(&lines);
+        let metadata = (frontmatter_len > 0)
+            .then(|| frontmatter::parse(&lines[1..frontmatter_len - 1]))
+            .filter(|keys| !keys.is_empty())
+            .map(Value::Object);
+        let mut doc = Self {
+            path,
+            content: content.to_string(),
+            spans: Vec::new(),
+            code_blocks: Vec::new(),
+            folded: None,
+            frontmatter_len,
+            metadata,
+        };
+        doc.split_lines();
+        doc
     }
 
-    /// Read lines with optional offset (1-indexed) and limit.
-    /// Returns formatted text with line numbers.
+    /// Line `idx` (0-indexed), without its line ending.
+    pub fn line(&self, idx: usize) -> &str {
+        let span = &self.spans[idx];
+        &self.content[span.start as usize..span.end as usize]
+    }
+
+    /// Every line in order, as `str::lines` would split the content.
+    pub fn lines(&self) -> impl ExactSizeIterator<Item = &str> + DoubleEndedIterator + Clone {
+        self.lines_in(0..self.spans.len())
+    }
+
+    /// Lines `range` (0-indexed).
+    fn lines_in(&self, range: Range<usize>) -> impl ExactSizeIterator<Item = &str> + DoubleEndedIterator + Clone {
+        self.spans[range]
+            .iter()
+            .map(|span| &self.content[span.start as usize..span.end as usize])
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Recompute the line spans and code blocks from `content`. Lines end
+    /// at `\n` or `\r\n`, and a final line ending does not start another
+    /// line.
+    pub fn split_lines(&mut self) {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        for piece in self.content.split_inclusive('\n') {
+            let line = piece.strip_suffix('\n').map_or(piece, |l| l.strip_suffix('\r').unwrap_or(l));
+            spans.push(start as u32..(start + line.len()) as u32);
+            start += piece.len();
+        }
+        self.spans = spans;
+        self.code_blocks = self.find_code_blocks();
+    }
+
+    /// Line ranges of the ``` and ~~~ fenced blocks after any frontmatter.
+    /// A fence left open runs to the end of the document.
+    fn find_code_blocks(&self) -> Vec<Range<u32>> {
+        let mut blocks = Vec::new();
+        let mut open = None;
+        for (i, line) in self.lines().enumerate().skip(self.frontmatter_len) {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                match open.take() {
+                    Some(start) => blocks.push(start..i as u32 + 1),
+                    None => open = Some(i as u32),
+                }
+            }
+        }
+        if let Some(start) = open {
+            blocks.push(start..self.line_count() as u32);
+        }
+        blocks
+    }
+
+    /// Whether the 0-indexed line `idx` belongs to a fenced code block,
+    /// fences included.
+    pub fn is_code(&self, idx: usize) -> bool {
+        let idx = idx as u32;
+        let pos = self.code_blocks.partition_point(|block| block.end <= idx);
+        self.code_blocks.get(pos).is_some_and(|block| block.start <= idx)
+    }
+
+    /// Estimated heap bytes of the path, content, line spans, and metadata,
+    /// leaving out the folded cache.
+    pub fn heap_size(&self) -> usize {
+        self.path.capacity()
+            + self.content.capacity()
+            + (self.spans.capacity() + self.code_blocks.capacity()) * size_of::<Range<u32>>()
+            + self.metadata.as_ref().map_or(0, |m| m.to_string().len())
+    }
+
+    /// Estimated heap bytes of the folded cache; 0 without one.
+    pub fn folded_size(&self) -> usize {
+        self.folded
+            .as_ref()
+            .map_or(0, |f| f.content.capacity() + f.spans.capacity() * size_of::<Range<u32>>())
+    }
+
+    /// Keep a case-folded copy of every line, trading memory for
+    /// case-insensitive scans that never fold at query time.
+    pub fn cache_folded(&mut self, folding: Folding) {
+        let mut folded = FoldedLines::default();
+        for line in self.lines() {
+            let start = folded.content.len() as u32;
+            folded.content.push_str(&folding.fold(line));
+            folded.spans.push(start..folded.content.len() as u32);
+        }
+        self.folded = Some(folded);
+    }
+
+    /// Line `idx` folded by `folding`, from the cache when there is one
+    /// (which must have been built with the same `folding`).
+    pub fn folded_line(&self, idx: usize, folding: Folding) -> Cow<'_, str> {
+        match &self.folded {
+            Some(folded) => Cow::Borrowed(folded.line(idx)),
+            None => folding.fold_line(self.line(idx)),
+        }
+    }
+
+    /// Whether line `idx`, folded, contains `needle_folded`.
+    pub fn line_contains_folded(&self, idx: usize, needle_folded: &str, folding: Folding) -> bool {
+        match &self.folded {
+            Some(folded) => folded.line(idx).contains(needle_folded),
+            None => folding.contains(self.line(idx), needle_folded),
+        }
+    }
+
+    /// Whether any line, folded, contains `needle_folded`.
+    pub fn contains_folded(&self, needle_folded: &str, folding: Folding) -> bool {
+        (0..self.line_count()).any(|idx| self.line_contains_folded(idx, needle_folded, folding))
+    }
+
+    /// Set caller-supplied metadata. Object keys override same-named
+    /// frontmatter keys; any other value replaces the frontmatter metadata.
+    pub fn with_metadata(mut self, metadata: Option<Value>) -> Self {
+        match (&mut self.metadata, metadata) {
+            (Some(Value::Object(parsed)), Some(Value::Object(given))) => parsed.extend(given),
+            (_, Some(given)) => self.metadata = Some(given),
+            (_, None) => {}
+        }
+        self
+    }
+
+    /// Whether the metadata has every key in `filter` with a matching value.
+    /// A filter array requires all of its elements; a metadata array matches
+    /// any scalar it contains, so `{"tags": ["billing"]}` selects documents
+    /// tagged `billing`.
+    pub fn matches_metadata(&self, filter: &Map<String, Value>) -> bool {
+        let Some(Value::Object(metadata)) = &self.metadata else {
+            return filter.is_empty();
+        };
+        filter.iter().all(|(key, wanted)| {
+            metadata
+                .get(key)
+                .is_some_and(|have| metadata_value_matches(have, wanted))
+        })
+    }
+
+    /// Markdown headings in document order, skipping frontmatter and fenced
+    /// code blocks.
+    pub fn headings(&self) -> Vec<Heading> {
+        let mut headings = Vec::new();
+
+        for (i, line) in self.lines().enumerate().skip(self.frontmatter_len) {
+            if self.is_code(i) {
+                continue;
+            }
+            if let Some((level, text)) = parse_heading(line) {
+                headings.push(Heading {
+                    level,
+                    text,
+                    line: (i + 1) as u32,
+                });
+            }
+        }
+
+        headings
+    }
+
+    /// 0-indexed line range of the section under the first heading whose
+    /// text matches `heading` (case-insensitive). The section runs until the
+    /// next heading of equal or higher level.
+    pub fn section_range(&self, heading: &str) -> Option<std::ops::Range<usize>> {
+        let wanted = fold_case(heading.trim().trim_start_matches('#').trim());
+        let headings = self.headings();
+        let pos = headings
+            .iter()
+            .position(|h| fold_case(&h.text) == wanted)?;
+        let target = &headings[pos];
+
+        let end = headings[pos + 1..]
+            .iter()
+            .find(|h| h.level <= target.level)
+            .map_or(self.line_count(), |h| (h.line - 1) as usize);
+        Some((target.line - 1) as usize..end)
+    }
+
+    /// Inferred title: the first heading, or failing that the first
+    /// non-empty line after any frontmatter.
+    pub fn title(&self) -> Option<&str> {
+        let body = || self.lines().enumerate().skip(self.frontmatter_len);
+        let heading = body()
+            .find(|&(i, line)| !self.is_code(i) && parse_heading(line).is_some())
+            .map(|(_, line)| heading_text(line));
+
+        heading
+            .or_else(|| body().map(|(_, l)| l.trim()).find(|l| !l.is_empty()))
+            .filter(|t| !t.is_empty())
+    }
+
+    /// The whole content as loaded, line endings included.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Size of the content in bytes, with lines joined by `\n`.
+    pub fn byte_len(&self) -> usize {
+        self.lines().map(|l| l.len()).sum::<usize>() + self.line_count().saturating_sub(1)
+    }
+
+    /// Whether the 0-indexed line `idx` lies inside the frontmatter block.
+    pub fn is_frontmatter(&self, idx: usize) -> bool {
+        idx < self.frontmatter_len
+    }
+
+    #[cfg(test)]
     pub fn read(&self, offset: Option<usize>, limit: Option<usize>) -> String {
-        let start = offset.unwrap_or(1).saturating_sub(1); // convert 1-indexed to 0-indexed
-        if start >= self.lines.len() {
-            return String::new();
+        self.read_with_options(&ReadOptions {
+            offset,
+            limit,
+            ..Default::default()
+        })
+    }
+
+    /// Lines selected by `options` as structured `{number, text}` entries,
+    /// for clients that don't want to parse the numbered gutter.
+    pub fn read_lines(&self, options: &ReadOptions) -> Vec<NumberedLine> {
+        let range = self.window(options);
+        self.lines_in(range.clone())
+            .zip(range)
+            .map(|(line, i)| NumberedLine {
+                number: (i + 1) as u32,
+                text: match options.tab_width {
+                    Some(tw) => expand_tabs(line, tw),
+                    None => line.to_string(),
+                },
+            })
+            .collect()
+    }
+
+    /// Number of lines `options` selects, and of lines in the section or
+    /// document they are taken from.
+    pub fn read_extent(&self, options: &ReadOptions) -> (usize, usize) {
+        let bounds = options
+            .section
+            .as_deref()
+            .and_then(|heading| self.section_range(heading))
+            .unwrap_or(0..self.line_count());
+        (self.window(options).len(), bounds.len())
+    }
+
+    /// 0-indexed line range for the options' 1-indexed offset and limit,
+    /// clamped to the requested section or the whole document (empty if the
+    /// offset is past the end).
+    fn window(&self, options: &ReadOptions) -> std::ops::Range<usize> {
+        let bounds = options
+            .section
+            .as_deref()
+            .and_then(|heading| self.section_range(heading))
+            .unwrap_or(0..self.line_count());
+        let start = options
+            .offset
+            .map_or(bounds.start, |o| o.saturating_sub(1).max(bounds.start)); // convert 1-indexed to 0-indexed
+        if start >= bounds.end {
+            return bounds.end..bounds.end;
         }
 
-        let end = match limit {
-            Some(lim) => (start + lim).min(self.lines.len()),
-            None => self.lines.len(),
+        let end = match options.limit {
+            Some(lim) => (start + lim).min(bounds.end),
+            None => bounds.end,
         };
+        start..end
+    }
+
+    /// Read lines with optional offset (1-indexed) and limit.
+    /// Returns formatted text with line numbers.
+    pub fn read_with_options(&self, options: &ReadOptions) -> String {
+        let range = self.window(options);
+        let (start, end) = (range.start, range.end);
+        if start >= end {
+            return String::new();
+        }
 
         let width = end.to_string().len().max(3);
-        self.lines[start..end]
-            .iter()
+        self.lines_in(start..end)
             .enumerate()
             .map(|(i, line)| {
                 let line_num = start + i + 1; // back to 1-indexed for display
-                format!("{:>width$}  {}", line_num, line, width = width)
+                match options.tab_width {
+                    Some(tw) if line.contains('\t') => {
+                        format!("{:>width$}  {}", line_num, expand_tabs(line, tw), width = width)
+                    }
+                    _ => format!("{:>width$}  {}", line_num, line, width = width),
+                }
             })
             .collect::<Vec<_>>()
             .join("\n")
     }
 }
 
+fn metadata_value_matches(have: &Value, wanted: &Value) -> bool {
+    match (have, wanted) {
+        (_, Value::Array(all)) => all.iter().all(|w| metadata_value_matches(have, w)),
+        (Value::Array(items), _) => items.contains(wanted),
+        _ => have == wanted,
+    }
+}
+
+/// Serde adapter storing metadata as a JSON string, since snapshot formats
+/// like bincode cannot encode a self-describing `Value`.
+mod json_string {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+
+    pub fn serialize<S: Serializer>(value: &Option<Value>, s: S) -> Result<S::Ok, S::Error> {
+        value.as_ref().map(|v| v.to_string()).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Value>, D::Error> {
+        Option::<String>::deserialize(d)?
+            .map(|json| serde_json::from_str(&json).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// Parse an ATX heading line into (level, text).
+fn parse_heading(line: &str) -> Option<(u8, String)> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None; // indented code block
+    }
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some((level as u8, heading_text(line).to_string()))
+}
+
+/// Text of a heading line with the `#` markers stripped.
+fn heading_text(line: &str) -> &str {
+    line.trim()
+        .trim_start_matches('#')
+        .trim()
+        .trim_end_matches('#')
+        .trim_end()
+}
+
+/// Replace each tab with spaces up to the next multiple of `tab_width`.
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let tab_width = tab_width.max(1);
+    let mut out = String::with_capacity(line.len());
+    let mut col = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width - col % tab_width;
+            out.extend(std::iter::repeat_n(' ', spaces));
+            col += spaces;
+        } else {
+            out.push(c);
+            col += 1;
+        }
+    }
+    out
+}
+
+/// Detect a leading `---` fenced YAML block. Returns the number of lines it
+/// spans including both fences, or 0 if the document has no frontmatter.
+fn frontmatter_len(lines: &[&str]) -> usize {
+    if lines.first().map(|l| l.trim_end()) != Some("---") {
+        return 0;
+    }
+    lines
+        .iter()
+        .skip(1)
+        .position(|l| matches!(l.trim_end(), "---" | "..."))
+        .map_or(0, |pos| pos + 2)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,8 +551,130 @@ mod tests {
     #[test]
     fn test_document_new() {
         let doc = Document::new("test.md".into(), "line one\nline two\nline three");
-        assert_eq!(doc.lines.len(), 3);
-        assert_eq!(doc.lines[0], "line one");
+        assert_eq!(doc.line_count(), 3);
+        assert_eq!(doc.line(0), "line one");
+    }
+
+    #[test]
+    fn test_lines_split_like_str_lines() {
+        for content in ["a\r\nb\n\nc\n", "a\rb\r\n", "", "\n", "tail\r"] {
+            let doc = Document::new("test.md".into(), content);
+            assert_eq!(doc.lines().collect::<Vec<_>>(), content.lines().collect::<Vec<_>>(), "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn test_cached_folding_matches_lazy() {
+        let lazy = Document::new("a.md".into(), "Reset PASSWORD\nİstanbul Straße\n\nplain");
+        let mut cached = lazy.clone();
+        let folding = Folding::default();
+        cached.cache_folded(folding);
+        for idx in 0..lazy.line_count() {
+            assert_eq!(cached.folded_line(idx, folding), lazy.folded_line(idx, folding));
+            for needle in ["password", "i̇stanbul", "straße", "plain", ""] {
+                assert_eq!(
+                    cached.line_contains_folded(idx, needle, folding),
+                    lazy.line_contains_folded(idx, needle, folding)
+                );
+            }
+        }
+        assert!(cached.contains_folded("reset password", folding));
+        assert!(!lazy.contains_folded("reset  password", folding));
+    }
+
+    #[test]
+    fn test_frontmatter_detection() {
+        let doc = Document::new("a.md".into(), "---\ntitle: A\ntags: [x]\n---\n# A\nbody");
+        assert_eq!(doc.frontmatter_len, 4);
+        assert!(doc.is_frontmatter(3));
+        assert!(!doc.is_frontmatter(4));
+
+        let unterminated = Document::new("b.md".into(), "---\ntitle: B\n# B");
+        assert_eq!(unterminated.frontmatter_len, 0);
+
+        let plain = Document::new("c.md".into(), "# C\n---\nbody");
+        assert_eq!(plain.frontmatter_len, 0);
+        assert_eq!(plain.metadata, None);
+    }
+
+    #[test]
+    fn test_code_blocks() {
+        let content = "---\nfence: ```\n---\nprose\n```sh\nls\n```\nmore\n  ~~~\nopen";
+        let doc = Document::new("a.md".into(), content);
+        let code: Vec<usize> = (0..doc.line_count()).filter(|&i| doc.is_code(i)).collect();
+        assert_eq!(code, [4, 5, 6, 8, 9]);
+
+        let mut restored: Document = serde_json::from_value(serde_json::to_value(&doc).unwrap()).unwrap();
+        restored.split_lines();
+        assert!(restored.is_code(5) && !restored.is_code(7));
+    }
+
+    #[test]
+    fn test_frontmatter_metadata() {
+        let doc = Document::new("a.md".into(), "---\ntitle: A\ntags: [x, y]\n---\n# A\nbody");
+        assert_eq!(doc.metadata, Some(serde_json::json!({ "title": "A", "tags": ["x", "y"] })));
+
+        // Supplied metadata wins key by key
+        let doc = doc.with_metadata(Some(serde_json::json!({ "tags": ["z"], "author": "kim" })));
+        assert_eq!(
+            doc.metadata,
+            Some(serde_json::json!({ "title": "A", "tags": ["z"], "author": "kim" }))
+        );
+    }
+
+    #[test]
+    fn test_matches_metadata() {
+        let doc = Document::new("a.md".into(), "body").with_metadata(Some(serde_json::json!({
+            "tags": ["billing", "faq"],
+            "author": "kim",
+        })));
+        let filter = |v: Value| v.as_object().unwrap().clone();
+        assert!(doc.matches_metadata(&filter(serde_json::json!({ "tags": ["billing"] }))));
+        assert!(doc.matches_metadata(&filter(serde_json::json!({ "tags": "faq", "author": "kim" }))));
+        assert!(!doc.matches_metadata(&filter(serde_json::json!({ "tags": ["billing", "legal"] }))));
+        assert!(!doc.matches_metadata(&filter(serde_json::json!({ "owner": "kim" }))));
+
+        let bare = Document::new("b.md".into(), "body");
+        assert!(bare.matches_metadata(&Map::new()));
+        assert!(!bare.matches_metadata(&filter(serde_json::json!({ "tags": ["billing"] }))));
+    }
+
+    #[test]
+    fn test_headings() {
+        let doc = Document::new(
+            "a.md".into(),
+            "---\ntitle: x\n---\n# Title\n\n## Setup ##\n```sh\n# not a heading\n```\n#hashtag\n### Details\n## Usage",
+        );
+        let headings = doc.headings();
+        let summary: Vec<_> = headings.iter().map(|h| (h.level, h.text.as_str(), h.line)).collect();
+        assert_eq!(
+            summary,
+            vec![(1, "Title", 4), (2, "Setup", 6), (3, "Details", 11), (2, "Usage", 12)]
+        );
+    }
+
+    #[test]
+    fn test_title() {
+        let doc = Document::new("a.md".into(), "---\ntitle: x\n---\n\nIntro\n```\n# code\n```\n## Real Title ##");
+        assert_eq!(doc.title(), Some("Real Title"));
+
+        let doc = Document::new("b.md".into(), "\n  plain first line  \nsecond");
+        assert_eq!(doc.title(), Some("plain first line"));
+
+        let doc = Document::new("c.md".into(), "\n\n");
+        assert_eq!(doc.title(), None);
+    }
+
+    #[test]
+    fn test_section_range() {
+        let doc = Document::new(
+            "a.md".into(),
+            "# Title\nintro\n## Setup\nstep\n### Details\nmore\n## Usage\nuse it",
+        );
+        assert_eq!(doc.section_range("setup"), Some(2..6));
+        assert_eq!(doc.section_range("## Usage"), Some(6..8));
+        assert_eq!(doc.section_range("Title"), Some(0..8));
+        assert_eq!(doc.section_range("missing"), None);
     }
 
     #[test]
@@ -71,6 +695,70 @@ mod tests {
         assert!(!result.contains("line 4"));
     }
 
+    #[test]
+    fn test_read_expands_tabs() {
+        let doc = Document::new("conf.md".into(), "server {\n\tlisten 80;\n\t\troot /srv;\nab\tc");
+        let options = ReadOptions {
+            tab_width: Some(4),
+            ..Default::default()
+        };
+        let result = doc.read_with_options(&options);
+        assert!(!result.contains('\t'));
+        assert!(result.contains("  2      listen 80;"));
+        assert!(result.contains("  3          root /srv;"));
+        assert!(result.contains("  4  ab  c"), "tab should advance to the next tab stop");
+        assert_eq!(doc.line(1), "\tlisten 80;", "stored content is unchanged");
+
+        // Tabs are left alone without the option
+        assert!(doc.read(None, None).contains('\t'));
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let doc = Document::new("test.md".into(), "line 1\nline 2\nline 3\nline 4");
+        let options = ReadOptions {
+            offset: Some(2),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let lines = doc.read_lines(&options);
+        assert_eq!(
+            lines,
+            vec![
+                NumberedLine { number: 2, text: "line 2".into() },
+                NumberedLine { number: 3, text: "line 3".into() },
+            ]
+        );
+
+        let past_end = ReadOptions {
+            offset: Some(10),
+            ..Default::default()
+        };
+        assert!(doc.read_lines(&past_end).is_empty());
+    }
+
+    #[test]
+    fn test_read_section() {
+        let doc = Document::new(
+            "a.md".into(),
+            "# Title\nintro\n## Setup\nstep one\nstep two\n## Usage\nuse it",
+        );
+        let section = |offset, limit| ReadOptions {
+            offset,
+            limit,
+            section: Some("setup".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.read_with_options(&section(None, None)),
+            "  3  ## Setup\n  4  step one\n  5  step two"
+        );
+        // Offset and limit stay within the section
+        let lines = doc.read_lines(&section(Some(1), Some(2)));
+        assert_eq!(lines.iter().map(|l| l.number).collect::<Vec<_>>(), vec![3, 4]);
+        assert!(doc.read_lines(&section(Some(6), None)).is_empty());
+    }
+
     #[test]
     fn test_read_offset_beyond_end() {
         let doc = Document::new("test.md".into(), "only line");
@@ -1,22 +1,192 @@
 use std::fmt;
 
-#[derive(Debug)]
-pub struct MemexError {
-    pub message: String,
+use serde::Serialize;
+
+/// Everything that can go wrong in a MemexFS call. Hosts branch on
+/// `code()` (or the `code` field of the serialized form) rather than on
+/// the message text, which is for people and may change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum MemexError {
+    /// No document lives at `path`. `suggestions` holds up to three
+    /// existing paths that look like what was meant.
+    DocumentNotFound { path: String, suggestions: Vec<String> },
+    /// The document at `path` has no heading `heading`.
+    SectionNotFound { path: String, heading: String },
+    /// `query_table` on a document that isn't `.csv` or `.tsv`.
+    NotATable { path: String },
+    /// A `query_table` filter or column names no column of the table;
+    /// `columns` lists the ones it has.
+    UnknownColumn { path: String, column: String, columns: Vec<String> },
+    /// `read_json` on a document that isn't `.json` or doesn't parse.
+    NotJson { path: String, reason: String },
+    /// A `read_json` pointer that leads nowhere. `parent` is the JSON
+    /// Pointer of the deepest value on the way that exists (`""` for the
+    /// root) and `keys` its keys, if it's an object.
+    JsonValueNotFound { path: String, pointer: String, parent: String, keys: Vec<String> },
+    /// A grep pattern the regex engine rejects.
+    InvalidRegex { pattern: String, reason: String },
+    /// Malformed or missing parameters, e.g. a tool call's JSON.
+    InvalidParams { reason: String },
+    /// Documents JSON that can't be parsed.
+    InvalidDocuments { reason: String },
+    /// A document that isn't text, loaded or written under
+    /// `BinaryPolicy::Reject`.
+    BinaryDocument { path: String, reason: String },
+    /// `call` was given a tool name that doesn't exist.
+    UnknownTool { name: String },
+    /// `call` was given a tool left out by `MemexFsBuilder::tools`.
+    ToolNotEnabled { name: String },
+    /// An empty grep pattern or search term.
+    EmptyPattern,
+    /// A pattern with nothing to search for once stop words are removed.
+    NoSearchTerms,
+    /// A document, write, or delete with an empty path.
+    EmptyPath,
+    /// A document path with a `..` segment, a trailing `/`, or control
+    /// characters.
+    InvalidPath { path: String, reason: String },
+    /// A load under `DuplicatePolicy::Error` repeating a path of the batch
+    /// or of a loaded document.
+    DuplicatePath { path: String },
+    /// `mount` at a prefix that overlaps another mount or documents
+    /// loaded outside one.
+    MountConflict { prefix: String, reason: String },
+    /// `unmount` of a prefix nothing is mounted at; `mounts` lists the
+    /// ones that are.
+    NotMounted { prefix: String, mounts: Vec<String> },
+    /// `finalize` or `from_json` with no documents.
+    NoDocuments,
+    /// `continue_grep` with a handle that is done, cancelled, or unknown.
+    UnknownGrepHandle { handle: u32 },
+    /// Bytes that `from_snapshot` can't restore.
+    InvalidSnapshot { reason: String },
+    /// Failure to encode a result. Not expected in practice.
+    Encoding { reason: String },
+    /// A file or directory `from_dir` can't read.
+    Io { path: String, reason: String },
 }
 
 impl MemexError {
-    pub fn new(msg: &str) -> Self {
-        Self {
-            message: msg.to_string(),
+    /// Stable machine-readable name of the error, e.g.
+    /// `"document_not_found"`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DocumentNotFound { .. } => "document_not_found",
+            Self::SectionNotFound { .. } => "section_not_found",
+            Self::NotATable { .. } => "not_a_table",
+            Self::UnknownColumn { .. } => "unknown_column",
+            Self::NotJson { .. } => "not_json",
+            Self::JsonValueNotFound { .. } => "json_value_not_found",
+            Self::InvalidRegex { .. } => "invalid_regex",
+            Self::InvalidParams { .. } => "invalid_params",
+            Self::InvalidDocuments { .. } => "invalid_documents",
+            Self::BinaryDocument { .. } => "binary_document",
+            Self::UnknownTool { .. } => "unknown_tool",
+            Self::ToolNotEnabled { .. } => "tool_not_enabled",
+            Self::EmptyPattern => "empty_pattern",
+            Self::NoSearchTerms => "no_search_terms",
+            Self::EmptyPath => "empty_path",
+            Self::InvalidPath { .. } => "invalid_path",
+            Self::DuplicatePath { .. } => "duplicate_path",
+            Self::MountConflict { .. } => "mount_conflict",
+            Self::NotMounted { .. } => "not_mounted",
+            Self::NoDocuments => "no_documents",
+            Self::UnknownGrepHandle { .. } => "unknown_grep_handle",
+            Self::InvalidSnapshot { .. } => "invalid_snapshot",
+            Self::Encoding { .. } => "encoding",
+            Self::Io { .. } => "io",
         }
     }
+
+    pub fn invalid_params(e: impl fmt::Display) -> Self {
+        Self::InvalidParams { reason: e.to_string() }
+    }
+
+    pub fn encoding(e: impl fmt::Display) -> Self {
+        Self::Encoding { reason: e.to_string() }
+    }
+
+    /// The error as a JSON object: `code`, `message`, and the variant's
+    /// fields.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).expect("errors serialize");
+        value["message"] = self.to_string().into();
+        value
+    }
 }
 
 impl fmt::Display for MemexError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.message)
+        write!(f, "MemexError: ")?;
+        match self {
+            Self::DocumentNotFound { path, suggestions } => {
+                write!(f, "document not found: {}", path)?;
+                if !suggestions.is_empty() {
+                    write!(f, " (did you mean: {}?)", suggestions.join(", "))?;
+                }
+                Ok(())
+            }
+            Self::SectionNotFound { heading, .. } => write!(f, "section not found: {}", heading),
+            Self::NotATable { path } => write!(f, "not a .csv or .tsv document: {}", path),
+            Self::UnknownColumn { column, columns, .. } => {
+                write!(f, "unknown column: {} (columns: {})", column, columns.join(", "))
+            }
+            Self::NotJson { path, reason } => write!(f, "not a JSON document: {}: {}", path, reason),
+            Self::JsonValueNotFound { pointer, parent, keys, .. } => {
+                write!(f, "no JSON value at {}", pointer)?;
+                let parent = if parent.is_empty() { "the root" } else { parent };
+                if keys.is_empty() {
+                    write!(f, " (found up to {})", parent)
+                } else {
+                    write!(f, " ({} has keys: {})", parent, keys.join(", "))
+                }
+            }
+            Self::InvalidRegex { reason, .. } => write!(f, "invalid regex: {}", reason),
+            Self::InvalidParams { reason } => write!(f, "invalid params: {}", reason),
+            Self::InvalidDocuments { reason } => write!(f, "invalid documents: {}", reason),
+            Self::BinaryDocument { path, reason } => write!(f, "binary document: {} ({})", path, reason),
+            Self::UnknownTool { name } => write!(f, "unknown tool: {}", name),
+            Self::ToolNotEnabled { name } => write!(f, "tool not enabled: {}", name),
+            Self::EmptyPattern => write!(f, "empty search pattern"),
+            Self::NoSearchTerms => write!(f, "no search terms in pattern"),
+            Self::EmptyPath => write!(f, "empty document path"),
+            Self::InvalidPath { path, reason } => write!(f, "invalid path: {} ({})", path, reason),
+            Self::DuplicatePath { path } => write!(f, "duplicate path: {}", path),
+            Self::MountConflict { prefix, reason } => write!(f, "cannot mount at {}/: {}", prefix, reason),
+            Self::NotMounted { prefix, .. } => write!(f, "nothing mounted at {}/", prefix),
+            Self::NoDocuments => write!(f, "no documents provided"),
+            Self::UnknownGrepHandle { handle } => write!(f, "unknown grep handle: {}", handle),
+            Self::InvalidSnapshot { reason } => write!(f, "{}", reason),
+            Self::Encoding { reason } => write!(f, "encoding failed: {}", reason),
+            Self::Io { path, reason } => write!(f, "can't read {}: {}", path, reason),
+        }
     }
 }
 
 impl std::error::Error for MemexError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_json() {
+        let err = MemexError::DocumentNotFound {
+            path: "a.md".to_string(),
+            suggestions: vec!["b.md".to_string()],
+        };
+        assert_eq!(err.code(), "document_not_found");
+        assert_eq!(err.to_string(), "MemexError: document not found: a.md (did you mean: b.md?)");
+        assert_eq!(
+            err.to_json(),
+            serde_json::json!({
+                "code": "document_not_found",
+                "path": "a.md",
+                "suggestions": ["b.md"],
+                "message": "MemexError: document not found: a.md (did you mean: b.md?)",
+            })
+        );
+        assert_eq!(MemexError::EmptyPattern.to_json()["code"], "empty_pattern");
+    }
+}
@@ -0,0 +1,170 @@
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::error::MemexError;
+use crate::{MemexFsCore, MemexOptions};
+
+// C ABI for embedding without wasm-bindgen; declared in include/memexfs.h.
+// Strings are NUL-terminated UTF-8. Every string returned to the caller,
+// results and errors alike, is freed with `memexfs_string_free`. On failure
+// a function returns null and, if `error` isn't null, stores the error's
+// `to_json` object there.
+
+/// An instance over documents JSON (as for `from_json`) and optional
+/// `MemexOptions` JSON, null for the defaults. Free it with `memexfs_free`.
+///
+/// # Safety
+///
+/// `docs_json` must be a valid C string, `options_json` null or a valid C
+/// string, and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn memexfs_new(
+    docs_json: *const c_char,
+    options_json: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut MemexFsCore {
+    guard(error, || {
+        let options: MemexOptions = if options_json.is_null() {
+            MemexOptions::default()
+        } else {
+            serde_json::from_str(str_arg(options_json)?).map_err(MemexError::invalid_params)?
+        };
+        let core = MemexFsCore::from_json_with_options(str_arg(docs_json)?, &options)?;
+        Ok(Box::into_raw(Box::new(core)))
+    })
+}
+
+/// Run tool `name` with params JSON (null for none), as `call` does, and
+/// return its output.
+///
+/// # Safety
+///
+/// `fs` must come from `memexfs_new` and not be freed or in use on another
+/// thread; `name` must be a valid C string, `params_json` null or one, and
+/// `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn memexfs_call(
+    fs: *mut MemexFsCore,
+    name: *const c_char,
+    params_json: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error, || {
+        let core = fs.as_mut().ok_or_else(|| MemexError::invalid_params("null instance"))?;
+        let params = if params_json.is_null() { "" } else { str_arg(params_json)? };
+        c_string(core.call_mut(str_arg(name)?, params)?)
+    })
+}
+
+/// The tool definitions JSON, as `tool_definitions`.
+///
+/// # Safety
+///
+/// `fs` must come from `memexfs_new` and not be freed; `error` must be null
+/// or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn memexfs_tool_definitions(
+    fs: *const MemexFsCore,
+    error: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error, || {
+        let core = fs.as_ref().ok_or_else(|| MemexError::invalid_params("null instance"))?;
+        c_string(core.tool_definitions())
+    })
+}
+
+/// Free a string returned by this library. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string from this library, not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn memexfs_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Free an instance. Null is ignored.
+///
+/// # Safety
+///
+/// `fs` must be null or come from `memexfs_new`, not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn memexfs_free(fs: *mut MemexFsCore) {
+    if !fs.is_null() {
+        drop(Box::from_raw(fs));
+    }
+}
+
+/// Run `f`, turning an error or a panic into a null return and, when
+/// `error` isn't null, an error JSON string there. Panics must not unwind
+/// into C.
+unsafe fn guard<T>(
+    error: *mut *mut c_char,
+    f: impl FnOnce() -> Result<*mut T, MemexError>,
+) -> *mut T {
+    if !error.is_null() {
+        *error = ptr::null_mut();
+    }
+    let message = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(e)) => e.to_json(),
+        Err(_) => serde_json::json!({ "code": "panic", "message": "MemexError: internal panic" }),
+    };
+    if !error.is_null() {
+        *error = CString::new(message.to_string()).map_or(ptr::null_mut(), CString::into_raw);
+    }
+    ptr::null_mut()
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Result<&'a str, MemexError> {
+    if s.is_null() {
+        return Err(MemexError::invalid_params("null string"));
+    }
+    CStr::from_ptr(s).to_str().map_err(MemexError::invalid_params)
+}
+
+fn c_string(s: String) -> Result<*mut c_char, MemexError> {
+    CString::new(s).map(CString::into_raw).map_err(MemexError::encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn take(s: *mut c_char) -> String {
+        let text = CStr::from_ptr(s).to_str().unwrap().to_string();
+        memexfs_string_free(s);
+        text
+    }
+
+    #[test]
+    fn test_ffi_roundtrip() {
+        let docs = CString::new(r#"[["billing/refund.md", "Contact support"]]"#).unwrap();
+        let read = CString::new("read").unwrap();
+        let params = CString::new(r#"{"path": "billing/refund.md"}"#).unwrap();
+        let missing = CString::new(r#"{"path": "refund.md"}"#).unwrap();
+        let mut error = ptr::null_mut();
+        unsafe {
+            let fs = memexfs_new(docs.as_ptr(), ptr::null(), &mut error);
+            assert!(!fs.is_null() && error.is_null());
+
+            let output = memexfs_call(fs, read.as_ptr(), params.as_ptr(), &mut error);
+            assert_eq!(take(output), "  1  Contact support");
+            let output = memexfs_call(fs, read.as_ptr(), missing.as_ptr(), &mut error);
+            assert!(output.is_null());
+            let error_json: serde_json::Value = serde_json::from_str(&take(error)).unwrap();
+            assert_eq!(error_json["code"], "document_not_found");
+
+            let definitions = take(memexfs_tool_definitions(fs, ptr::null_mut()));
+            let definitions: serde_json::Value = serde_json::from_str(&definitions).unwrap();
+            assert_eq!(definitions[0]["name"], "grep");
+            memexfs_free(fs);
+
+            let bad = CString::new("not json").unwrap();
+            assert!(memexfs_new(bad.as_ptr(), ptr::null(), ptr::null_mut()).is_null());
+        }
+    }
+}
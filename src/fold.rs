@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+/// Canonical case folding for everything compared case-insensitively:
+/// index tokens, lines folded for scanning, and query normalization.
+/// Keeping a single implementation guarantees the index and the queries
+/// against it can never fold text differently.
+///
+/// With the `unicode-folding` feature, text is normalized to NFC and fully
+/// case-folded, so a decomposed `cafe\u{301}` equals `café` and `ß` equals
+/// `ss`. ASCII text is simply lowercased either way.
+#[cfg(feature = "unicode-folding")]
+pub fn fold_case(s: &str) -> String {
+    if s.is_ascii() {
+        return s.to_ascii_lowercase();
+    }
+    // Folding can leave text denormalized (`İ` becomes `i` + U+0307), so
+    // normalize on both sides
+    let composed: String = s.nfc().collect();
+    caseless::default_case_fold_str(&composed).nfc().collect()
+}
+
+#[cfg(not(feature = "unicode-folding"))]
+pub fn fold_case(s: &str) -> String {
+    s.to_lowercase()
+}
+
+/// `s` without Latin diacritics: combining accents are dropped after
+/// canonical decomposition (`résumé` → `resume`, `Malmö` → `Malmo`) and a
+/// few letters with no decomposition are spelled out (`ø` → `o`, `æ` →
+/// `ae`). Other scripts are left alone.
+pub fn strip_diacritics(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    // Whether the last base character was a decomposed Latin letter
+    let mut latin = false;
+    for c in s.nfd() {
+        if !matches!(c, '\u{300}'..='\u{36f}') {
+            latin = c.is_ascii_alphabetic();
+        }
+        match c {
+            '\u{300}'..='\u{36f}' if latin => {}
+            'ø' => out.push('o'),
+            'Ø' => out.push('O'),
+            'æ' => out.push_str("ae"),
+            'Æ' => out.push_str("AE"),
+            'œ' => out.push_str("oe"),
+            'Œ' => out.push_str("OE"),
+            'đ' | 'ð' => out.push('d'),
+            'Đ' | 'Ð' => out.push('D'),
+            'ł' => out.push('l'),
+            'Ł' => out.push('L'),
+            'þ' => out.push_str("th"),
+            'Þ' => out.push_str("TH"),
+            'ı' => out.push('i'),
+            c => out.push(c),
+        }
+    }
+    // Recompose whatever other scripts decomposed
+    Cow::Owned(out.nfc().collect())
+}
+
+/// How an instance compares text case-insensitively: `fold_case`, and
+/// with `ascii` (`MemexOptions::ascii_fold`) also `strip_diacritics`, so
+/// `malmo` and `Malmö` fold alike. Index and queries must use the same one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Folding {
+    pub ascii: bool,
+}
+
+impl Folding {
+    pub fn fold(self, s: &str) -> String {
+        let folded = fold_case(s);
+        if self.ascii { strip_diacritics(&folded).into_owned() } else { folded }
+    }
+
+    /// Extra spelling `token` (already folded) is indexed under, if any.
+    pub fn variant(self, token: &str) -> Option<String> {
+        match strip_diacritics(token) {
+            Cow::Owned(plain) if self.ascii && plain != token => Some(plain),
+            _ => None,
+        }
+    }
+
+    /// `fold(line)`, borrowing when folding would not change it (ASCII
+    /// with no uppercase letters).
+    pub fn fold_line(self, line: &str) -> Cow<'_, str> {
+        if line.bytes().all(|b| b.is_ascii() && !b.is_ascii_uppercase()) {
+            Cow::Borrowed(line)
+        } else {
+            Cow::Owned(self.fold(line))
+        }
+    }
+
+    /// Whether `fold(haystack)` contains `needle_folded` (already folded).
+    /// ASCII haystacks are compared byte by byte without allocating;
+    /// anything else is folded first, since non-ASCII folding can change
+    /// lengths.
+    pub fn contains(self, haystack: &str, needle_folded: &str) -> bool {
+        if !haystack.is_ascii() {
+            return self.fold(haystack).contains(needle_folded);
+        }
+        contains_ascii_folded(haystack, needle_folded)
+    }
+}
+
+fn contains_ascii_folded(haystack: &str, needle_folded: &str) -> bool {
+    let needle = needle_folded.as_bytes();
+    needle.is_empty()
+        || haystack
+            .as_bytes()
+            .windows(needle.len())
+            .any(|window| window.iter().zip(needle).all(|(h, n)| h.to_ascii_lowercase() == *n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "unicode-folding")]
+    #[test]
+    fn test_fold_case_normalizes_and_folds_fully() {
+        assert_eq!(fold_case("cafe\u{301}"), fold_case("café"));
+        assert_eq!(fold_case("CAFE\u{301}"), "café");
+        assert_eq!(fold_case("Straße"), fold_case("STRASSE"));
+        assert_eq!(fold_case("ΣΊΣΥΦΟΣ"), fold_case("σίσυφος"));
+        assert_eq!(fold_case("MiXeD-123"), "mixed-123");
+    }
+
+    #[test]
+    fn test_ascii_folding() {
+        let folding = Folding { ascii: true };
+        assert_eq!(folding.fold("Malmö"), "malmo");
+        assert_eq!(folding.fold("RÉSUMÉ"), "resume");
+        assert_eq!(folding.fold("Søren Ærø"), "soren aero");
+        assert_eq!(folding.fold("日本語 Йошкар-Ола"), "日本語 йошкар-ола");
+        assert!(folding.contains("Flyg till Malmö idag", "malmo idag"));
+        assert_eq!(folding.variant("malmö").as_deref(), Some("malmo"));
+        assert_eq!(folding.variant("malmo"), None);
+        assert_eq!(Folding::default().variant("malmö"), None);
+    }
+
+    #[test]
+    fn test_contains_folded_agrees_with_fold_case() {
+        let lines = ["Reset your PASSWORD", "ÅNGSTRÖM units", "the KELVIN sign \u{212A}", "", "short"];
+        let needles = ["password", "ångström", "k", "sign k", "", "shorter", "units"];
+        let folding = Folding::default();
+        for line in lines {
+            for needle in needles {
+                assert_eq!(
+                    folding.contains(line, needle),
+                    fold_case(line).contains(needle),
+                    "{:?} in {:?}",
+                    needle,
+                    line
+                );
+            }
+            assert_eq!(folding.fold_line(line), fold_case(line));
+        }
+        assert!(matches!(folding.fold_line("already folded"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_fold_case_is_idempotent() {
+        for s in ["Hello", "ÅNGSTRÖM", "ΣΊΣΥΦΟΣ", "Straße", "İstanbul", "MiXeD-123"] {
+            let once = fold_case(s);
+            assert_eq!(fold_case(&once), once, "folding {:?} twice changed it", s);
+        }
+    }
+}
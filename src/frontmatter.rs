@@ -0,0 +1,133 @@
+use serde_json::{Map, Number, Value};
+
+/// Parse the lines between the `---` fences of a YAML frontmatter block.
+///
+/// Supports the flat subset knowledge-base headers use: `key: scalar`,
+/// flow lists (`tags: [a, b]`), and block lists of `- item` lines under an
+/// empty `key:`. Scalars become booleans, numbers, or `null` where YAML
+/// would, and strings otherwise. Nested mappings and anything else that is
+/// not `key: value` are skipped.
+pub fn parse(lines: &[&str]) -> Map<String, Value> {
+    let mut map = Map::new();
+    let mut list_key: Option<String> = None;
+
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let (Some(key), Some(item)) = (&list_key, trimmed.strip_prefix('-')) {
+            if item.is_empty() || item.starts_with([' ', '\t']) {
+                let item = scalar(item.trim());
+                match map.get_mut(key) {
+                    Some(Value::Array(items)) => items.push(item),
+                    _ => {
+                        map.insert(key.clone(), Value::Array(vec![item]));
+                    }
+                }
+                continue;
+            }
+        }
+
+        if line.starts_with([' ', '\t']) {
+            continue; // nested mapping
+        }
+        let Some((key, rest)) = line.split_once(':') else {
+            list_key = None;
+            continue;
+        };
+        let key = unquote(key.trim()).to_string();
+        let rest = rest.trim();
+
+        list_key = None;
+        let value = if rest.is_empty() {
+            list_key = Some(key.clone());
+            Value::Null
+        } else if let Some(inner) = rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            Value::Array(
+                inner
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(scalar)
+                    .collect(),
+            )
+        } else {
+            scalar(rest)
+        };
+        map.insert(key, value);
+    }
+
+    map
+}
+
+/// Convert a YAML scalar to JSON.
+fn scalar(raw: &str) -> Value {
+    if raw.starts_with(['"', '\'']) {
+        return Value::String(unquote(raw).to_string());
+    }
+    let raw = match raw.find(" #") {
+        Some(pos) => raw[..pos].trim_end(),
+        None => raw,
+    };
+    match raw {
+        "" | "~" | "null" => Value::Null,
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => raw
+            .parse::<i64>()
+            .map(Number::from)
+            .ok()
+            .or_else(|| raw.parse::<f64>().ok().and_then(Number::from_f64))
+            .map_or_else(|| Value::String(raw.to_string()), Value::Number),
+    }
+}
+
+/// Strip one pair of matching surrounding quotes.
+fn unquote(s: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = s.strip_prefix(quote).and_then(|r| r.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<&str> {
+        text.lines().collect()
+    }
+
+    #[test]
+    fn test_parse_scalars_and_lists() {
+        let map = parse(&lines(
+            "title: \"Refunds: how-to\"\ntags: [billing, faq]\ndraft: false\nversion: 3\nscore: 0.5\nowner: ~\nauthors:\n  - kim\n  - 'lee'\n# comment\nupdated_at: 2024-05-01 # last review",
+        ));
+        assert_eq!(
+            Value::Object(map),
+            serde_json::json!({
+                "title": "Refunds: how-to",
+                "tags": ["billing", "faq"],
+                "draft": false,
+                "version": 3,
+                "score": 0.5,
+                "owner": null,
+                "authors": ["kim", "lee"],
+                "updated_at": "2024-05-01",
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_skips_nested_and_garbage() {
+        let map = parse(&lines("seo:\n  description: x\nnot a pair\nempty: []"));
+        assert_eq!(
+            Value::Object(map),
+            serde_json::json!({ "seo": null, "empty": [] })
+        );
+    }
+}
@@ -0,0 +1,344 @@
+use std::borrow::Cow;
+
+/// Elements whose content is never text: skipped up to their closing tag.
+const SKIPPED: [&str; 4] = ["script", "style", "noscript", "template"];
+
+/// Elements set off by a blank line, like markdown paragraphs.
+const PARAGRAPHS: [&str; 9] = ["p", "pre", "blockquote", "ul", "ol", "dl", "table", "hr", "figure"];
+
+/// Elements that start and end a line.
+const LINES: [&str; 20] = [
+    "div", "li", "tr", "dt", "dd", "section", "article", "header", "footer", "nav", "aside",
+    "main", "title", "address", "details", "summary", "figcaption", "form", "caption", "legend",
+];
+
+/// Whether `path` names an HTML document: `.html` or `.htm`, in any case.
+pub fn is_html(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name.rsplit_once('.') {
+        Some((_, ext)) => ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm"),
+        None => false,
+    }
+}
+
+/// The readable text of an HTML document. Tags, comments, and the content
+/// of scripts and styles are dropped, entities decoded, and runs of
+/// whitespace collapsed. Block elements start new lines (paragraphs, lists,
+/// and tables after a blank one), `<br>` breaks a line, `<pre>` keeps its
+/// layout, list items become `- ` lines, table cells are joined by ` | `,
+/// and `<h1>`–`<h6>` become markdown headings so `outline` and sections
+/// work on them.
+pub fn to_text(html: &str) -> String {
+    let mut text = Text::default();
+    let mut rest = html;
+    while let Some(lt) = rest.find('<') {
+        text.push(&rest[..lt]);
+        rest = &rest[lt..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some((tag, after)) = parse_tag(rest) else {
+            text.push("<");
+            rest = &rest[1..];
+            continue;
+        };
+        rest = after;
+        if !tag.closing && SKIPPED.contains(&tag.name.as_str()) {
+            rest = skip_element(rest, &tag.name);
+            continue;
+        }
+        text.tag(&tag);
+    }
+    text.push(rest);
+    text.finish()
+}
+
+struct Tag {
+    /// Lowercased; empty for `<!DOCTYPE>` and other markup declarations.
+    name: String,
+    closing: bool,
+}
+
+/// The tag `s` starts with and the input after it, or `None` if the `<`
+/// doesn't open a tag (`a < b`). An unterminated tag runs to the end.
+fn parse_tag(s: &str) -> Option<(Tag, &str)> {
+    let body = &s[1..];
+    let (closing, body) = match body.strip_prefix('/') {
+        Some(body) => (true, body),
+        None => (false, body),
+    };
+    let first = body.chars().next()?;
+    if !closing && (first == '!' || first == '?') {
+        let rest = body.find('>').map_or("", |end| &body[end + 1..]);
+        return Some((Tag { name: String::new(), closing }, rest));
+    }
+    if !first.is_ascii_alphabetic() {
+        return None;
+    }
+    let name_len = body.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(body.len());
+    let tag = Tag { name: body[..name_len].to_ascii_lowercase(), closing };
+    let mut quote = None;
+    for (i, c) in body[name_len..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some((tag, &body[name_len + i + 1..])),
+            _ => {}
+        }
+    }
+    Some((tag, ""))
+}
+
+/// The input after the closing tag of the `name` element `rest` is inside.
+fn skip_element<'a>(rest: &'a str, name: &str) -> &'a str {
+    for (i, _) in rest.match_indices("</") {
+        let after = &rest[i + 2..];
+        if after.get(..name.len()).is_some_and(|n| n.eq_ignore_ascii_case(name)) {
+            return after.find('>').map_or("", |end| &after[end + 1..]);
+        }
+    }
+    ""
+}
+
+/// Text being assembled line by line.
+#[derive(Default)]
+struct Text {
+    out: String,
+    /// Whitespace was seen since the last character written.
+    space: bool,
+    /// At the start of a line or cell, where whitespace is dropped.
+    fresh: bool,
+    /// Depth of `<pre>` elements.
+    pre: usize,
+    /// Drop a newline that directly follows `<pre>`, as browsers do.
+    pre_newline: bool,
+}
+
+impl Text {
+    fn push(&mut self, raw: &str) {
+        if raw.is_empty() {
+            return;
+        }
+        for c in decode_entities(raw).chars() {
+            if self.pre > 0 {
+                if !(c == '\n' && self.pre_newline) {
+                    self.out.push(c);
+                }
+                self.pre_newline = false;
+                self.fresh = c == '\n';
+            } else if c.is_whitespace() {
+                self.space = true;
+            } else {
+                if self.space && !self.fresh {
+                    self.out.push(' ');
+                }
+                self.out.push(c);
+                self.space = false;
+                self.fresh = false;
+            }
+        }
+    }
+
+    fn tag(&mut self, tag: &Tag) {
+        let name = tag.name.as_str();
+        match name.as_bytes() {
+            [b'h', level @ b'1'..=b'6'] => {
+                self.paragraph();
+                if !tag.closing {
+                    let level = (level - b'0') as usize;
+                    self.out.push_str(&"#".repeat(level));
+                    self.out.push(' ');
+                }
+            }
+            _ if name == "br" => self.newline(),
+            _ if name == "li" => {
+                self.line();
+                if !tag.closing {
+                    self.out.push_str("- ");
+                }
+            }
+            _ if (name == "td" || name == "th") && !tag.closing && !self.fresh => {
+                self.trim_end();
+                self.out.push_str(" | ");
+                self.fresh = true;
+            }
+            _ if name == "pre" => {
+                self.paragraph();
+                if tag.closing {
+                    self.pre = self.pre.saturating_sub(1);
+                } else {
+                    self.pre += 1;
+                    self.pre_newline = true;
+                }
+            }
+            _ if PARAGRAPHS.contains(&name) => self.paragraph(),
+            _ if LINES.contains(&name) => self.line(),
+            _ => {}
+        }
+    }
+
+    fn trim_end(&mut self) {
+        let len = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(len);
+    }
+
+    /// End the line, allowing at most one blank line in a row.
+    fn newline(&mut self) {
+        self.trim_end();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+        self.fresh = true;
+        self.space = false;
+    }
+
+    /// End the line unless it's empty.
+    fn line(&mut self) {
+        self.trim_end();
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.newline();
+        }
+        self.fresh = true;
+        self.space = false;
+    }
+
+    /// End the line and leave a blank one.
+    fn paragraph(&mut self) {
+        self.line();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let len = self.out.trim_end().len();
+        self.out.truncate(len);
+        self.out
+    }
+}
+
+/// `raw` with character references decoded: numeric ones and the common
+/// named ones. Anything else is left as written.
+fn decode_entities(raw: &str) -> Cow<'_, str> {
+    if !raw.contains('&') {
+        return Cow::Borrowed(raw);
+    }
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((entity(&rest[1..end + 1])?, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+fn entity(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "bull" => '•',
+        "middot" => '·',
+        "times" => '×',
+        "deg" => '°',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "euro" => '€',
+        "pound" => '£',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_html() {
+        assert!(is_html("kb/refunds.html"));
+        assert!(is_html("kb/REFUNDS.HTM"));
+        assert!(!is_html("kb/refunds.md"));
+        assert!(!is_html("kb.html/refunds"));
+    }
+
+    #[test]
+    fn test_to_text() {
+        let html = r#"<!DOCTYPE html>
+<html><head><title>Refunds</title>
+<style>p { color: red; }</style>
+<script>if (a < b) { document.write("</p>"); }</SCRIPT>
+</head><body class="kb-article" data-id="refund">
+<!-- exported 2024-03-01 -->
+<h1 id="top">Refund   policy</h1>
+<p>Refunds take <b>5&ndash;7</b> days &amp; need a
+<a href="/billing?x=1&amp;y=2" title="a > b">receipt</a>.<br>Ask support.</p>
+<h2>Steps</h2>
+<ul><li>Open a ticket</li><li>Attach the receipt</li></ul>
+<table><tr><th>Plan</th><th>Window</th></tr><tr><td>Pro</td><td>30 days</td></tr></table>
+<pre>
+fn refund() {
+    ok()
+}</pre>
+<p>1 &lt; 2 &#38; 3 &#x3E; 2, a < b</p>
+</body></html>"#;
+        let expected = "\
+Refunds
+
+# Refund policy
+
+Refunds take 5–7 days & need a receipt.
+Ask support.
+
+## Steps
+
+- Open a ticket
+- Attach the receipt
+
+Plan | Window
+Pro | 30 days
+
+fn refund() {
+    ok()
+}
+
+1 < 2 & 3 > 2, a < b";
+        assert_eq!(to_text(html), expected);
+    }
+}
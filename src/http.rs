@@ -0,0 +1,112 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+
+use crate::error::MemexError;
+use crate::MemexFsCore;
+
+type Shared = Arc<Mutex<MemexFsCore>>;
+
+/// An axum router serving the tools over HTTP:
+///
+/// - `GET /tools`: `tool_definitions`, as JSON.
+/// - `POST /tools/{name}`: `call(name, body)`, the body being the JSON
+///   params (empty for none). Answers 200 with the tool output as text,
+///   or with the error's `to_json` and a status by code: 400 for bad
+///   params, 404 for a tool that isn't offered, 422 for a tool that ran
+///   and failed. With `call_envelope` every call answers 200 with the
+///   envelope as JSON.
+///
+/// The instance is shared so the host can keep using it, e.g. to add
+/// documents while serving.
+pub fn tool_router(core: Arc<Mutex<MemexFsCore>>) -> Router {
+    Router::new()
+        .route("/tools", get(list_tools))
+        .route("/tools/{name}", post(call_tool))
+        .with_state(core)
+}
+
+async fn list_tools(State(core): State<Shared>) -> Json<serde_json::Value> {
+    Json(lock(&core).tool_definitions_value())
+}
+
+async fn call_tool(State(core): State<Shared>, Path(name): Path<String>, body: String) -> Response {
+    let mut core = lock(&core);
+    match core.call_mut(&name, &body) {
+        Ok(output) if core.settings.call_envelope => {
+            ([(header::CONTENT_TYPE, "application/json")], output).into_response()
+        }
+        Ok(output) => output.into_response(),
+        Err(e) => (status(&e), Json(e.to_json())).into_response(),
+    }
+}
+
+fn status(error: &MemexError) -> StatusCode {
+    match error {
+        MemexError::InvalidParams { .. } => StatusCode::BAD_REQUEST,
+        MemexError::UnknownTool { .. } | MemexError::ToolNotEnabled { .. } => StatusCode::NOT_FOUND,
+        _ => StatusCode::UNPROCESSABLE_ENTITY,
+    }
+}
+
+/// The instance, even if a panicking call poisoned the lock: tool calls
+/// leave it consistent.
+fn lock(core: &Shared) -> std::sync::MutexGuard<'_, MemexFsCore> {
+    core.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn send(router: &Router, request: Request<Body>) -> (StatusCode, String) {
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn post(uri: &str, body: &str) -> Request<Body> {
+        Request::post(uri).body(Body::from(body.to_string())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tool_router() {
+        let docs = serde_json::to_string(&vec![("billing/refund.md", "# Refunds\nContact support")])
+            .unwrap();
+        let core = Arc::new(Mutex::new(MemexFsCore::from_json(&docs).unwrap()));
+        let router = tool_router(core.clone());
+
+        let list = Request::get("/tools").body(Body::empty()).unwrap();
+        let (status, tools) = send(&router, list).await;
+        assert_eq!(status, StatusCode::OK);
+        let tools: serde_json::Value = serde_json::from_str(&tools).unwrap();
+        assert_eq!(tools[0]["name"], "grep");
+
+        let read = post("/tools/read", r#"{"path": "billing/refund.md"}"#);
+        let (status, text) = send(&router, read).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(text, "  1  # Refunds\n  2  Contact support");
+        // `ls` needs a path, and an empty body is no params
+        assert_eq!(send(&router, post("/tools/ls", "")).await.0, StatusCode::BAD_REQUEST);
+
+        let (status, error) = send(&router, post("/tools/read", r#"{"path": "nope.md"}"#)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let error: serde_json::Value = serde_json::from_str(&error).unwrap();
+        assert_eq!(error["code"], "document_not_found");
+        let (status, _) = send(&router, post("/tools/rm", "{}")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Writes through the router show up in the host's instance
+        let write = r#"{"path": "notes.md", "content": "hello"}"#;
+        assert_eq!(send(&router, post("/tools/write", write)).await.0, StatusCode::OK);
+        assert_eq!(core.lock().unwrap().document_count(), 2);
+    }
+}
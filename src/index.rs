@@ -1,69 +1,583 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
 
-/// Inverted index mapping tokens to their source locations (doc_path, line_number).
-/// Line numbers are 1-indexed.
-#[derive(Debug, Default)]
+use serde::{Deserialize, Serialize};
+
+use crate::fold::Folding;
+use crate::stem::StemIndex;
+use crate::tokenizer::{Tokenizer, TokenizerConfig};
+use crate::trigram::TrigramIndex;
+
+/// Compact id of a document, assigned by the store's path table
+/// (`DocumentStore::doc_id`), so postings don't repeat path strings.
+pub type DocId = u32;
+
+/// The occurrences of one token.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Postings {
+    /// (doc_id, line_number) of each line containing the token.
+    locations: Vec<(DocId, u32)>,
+    /// 0-indexed token positions of the token within each line, parallel
+    /// to `locations`.
+    positions: Vec<Vec<u32>>,
+}
+
+/// A document's lines tokenized for `InvertedIndex::insert_tokenized`:
+/// per line, its token count and the positions of each distinct token.
+#[derive(Debug)]
+pub struct TokenizedDocument {
+    lines: Vec<(usize, HashMap<String, Vec<u32>>)>,
+}
+
+/// Inverted index mapping tokens to their source locations (doc_id, line_number)
+/// and positions within each line. Line numbers are 1-indexed.
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct InvertedIndex {
-    index: HashMap<String, Vec<(String, u32)>>,
+    /// Sorted by token, so prefix queries are a range scan.
+    index: BTreeMap<String, Postings>,
+    /// Index-time synonym expansion: token → extra tokens to post it under.
+    expansions: HashMap<String, Vec<String>>,
+    /// Optional file-level index (token → doc ids) for "which files
+    /// contain this" queries without walking line postings.
+    files: Option<HashMap<String, BTreeSet<DocId>>>,
+    /// Token count of each document, for length-normalized ranking.
+    doc_lens: HashMap<DocId, usize>,
+    /// Sum of `doc_lens`.
+    total_len: usize,
+    /// Tokens grouped by stem, when built with stemming.
+    stems: Option<StemIndex>,
+    /// How lines are split into tokens. With `Folding::ascii`, tokens are
+    /// also posted without diacritics.
+    tokenizer: Tokenizer,
+    /// Trigrams of the tokens in `index`, for substring lookups. Rebuilt
+    /// by `restore` rather than stored in snapshots.
+    #[serde(skip)]
+    trigrams: TrigramIndex,
 }
 
 impl InvertedIndex {
     pub fn new() -> Self {
         Self {
-            index: HashMap::new(),
+            index: BTreeMap::new(),
+            expansions: HashMap::new(),
+            files: None,
+            doc_lens: HashMap::new(),
+            total_len: 0,
+            stems: None,
+            tokenizer: Tokenizer::default(),
+            trigrams: TrigramIndex::default(),
+        }
+    }
+
+    /// An empty index built like this one: the same tokenizer, index-time
+    /// synonyms, file index, and stemming.
+    pub fn empty_like(&self) -> Self {
+        Self {
+            expansions: self.expansions.clone(),
+            files: self.files.as_ref().map(|_| HashMap::new()),
+            stems: self.stems.as_ref().map(StemIndex::empty_like),
+            tokenizer: self.tokenizer.clone(),
+            ..Self::new()
         }
     }
 
+    /// Also record each occurrence of a token under the tokens listed for it
+    /// in `expansions`.
+    pub fn with_expansions(mut self, expansions: HashMap<String, Vec<String>>) -> Self {
+        self.expansions = expansions;
+        self
+    }
+
+    /// Also post each token with diacritics under its plain spelling, so
+    /// `malmo` finds `Malmö` (`MemexOptions::ascii_fold`).
+    pub fn with_ascii_folding(mut self) -> Self {
+        self.tokenizer.folding = Folding { ascii: true };
+        self
+    }
+
+    /// Index runs of CJK characters as overlapping `n`-character grams
+    /// (`MemexOptions::cjk_ngram`).
+    pub fn with_cjk_ngrams(mut self, n: usize) -> Self {
+        self.tokenizer.cjk_ngram = Some(n);
+        self
+    }
+
+    /// Also index the words of camelCase identifiers
+    /// (`MemexOptions::split_identifiers`).
+    pub fn with_identifier_splitting(mut self) -> Self {
+        self.tokenizer.split_identifiers = true;
+        self
+    }
+
+    /// How queries against this index must fold their text.
+    pub fn folding(&self) -> Folding {
+        self.tokenizer.folding
+    }
+
+    /// How queries against this index must split their text into tokens.
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    /// Split lines by `config` instead of the default rules.
+    pub fn with_tokenizer_config(mut self, config: TokenizerConfig) -> Self {
+        self.tokenizer.config = config;
+        self
+    }
+
+    /// Give a restored index back the split function it was built with
+    /// (`TokenizerConfig::split`), which snapshots can't store.
+    pub fn restore_split(&mut self, config: &TokenizerConfig) {
+        self.tokenizer.config.restore_split(config);
+    }
+
+    /// Maintain the secondary token → doc ids index used by `find_files_containing`.
+    pub fn with_file_index(mut self) -> Self {
+        self.files = Some(HashMap::new());
+        self
+    }
+
+    /// Group tokens by their `language` stem for `stem_variants`.
+    #[cfg(feature = "stemming")]
+    pub fn with_stemming(mut self, language: crate::stem::StemLanguage) -> Self {
+        self.stems = Some(StemIndex::new(language));
+        self
+    }
+
     /// Index a single document's lines.
-    /// Each (path, line) pair is stored at most once per token.
-    pub fn add_document(&mut self, path: &str, lines: &[String]) {
-        for (i, line) in lines.iter().enumerate() {
+    /// Each (doc, line) pair is stored at most once per token, with every
+    /// position the token occupies. Index-time synonyms share the position
+    /// of the token they expand.
+    #[cfg(test)]
+    pub fn add_document(&mut self, doc: DocId, lines: impl IntoIterator<Item = impl AsRef<str>>) {
+        let tokenized = self.tokenize_document(lines);
+        self.insert_tokenized(doc, tokenized);
+    }
+
+    /// The tokenizing half of `add_document`, which only reads the index
+    /// and so can run for many documents at once.
+    pub fn tokenize_document(&self, lines: impl IntoIterator<Item = impl AsRef<str>>) -> TokenizedDocument {
+        let lines = lines
+            .into_iter()
+            .map(|line| {
+                let tokens = self.tokenizer.tokenize_parts(line.as_ref());
+                let count = tokens.len();
+                let mut seen: HashMap<String, Vec<u32>> = HashMap::new();
+                for (pos, (token, sub)) in tokens.into_iter().enumerate() {
+                    let pos = pos as u32;
+                    for extra in self.expansions.get(&token).into_iter().flatten() {
+                        seen.entry(extra.clone()).or_default().push(pos);
+                    }
+                    if let Some(plain) = self.tokenizer.folding.variant(&token) {
+                        seen.entry(plain).or_default().push(pos);
+                    }
+                    for part in sub {
+                        seen.entry(part).or_default().push(pos);
+                    }
+                    seen.entry(token).or_default().push(pos);
+                }
+                (count, seen)
+            })
+            .collect();
+        TokenizedDocument { lines }
+    }
+
+    /// The indexing half of `add_document`.
+    pub fn insert_tokenized(&mut self, doc: DocId, tokenized: TokenizedDocument) {
+        for (i, (count, seen)) in tokenized.lines.into_iter().enumerate() {
             let line_num = (i + 1) as u32; // 1-indexed
-            let mut seen = std::collections::HashSet::new();
-            for token in tokenize(line) {
-                if seen.insert(token.clone()) {
-                    self.index
-                        .entry(token)
-                        .or_default()
-                        .push((path.to_string(), line_num));
+            *self.doc_lens.entry(doc).or_default() += count;
+            self.total_len += count;
+
+            for (token, mut positions) in seen {
+                positions.sort_unstable();
+                positions.dedup();
+                if let Some(files) = &mut self.files {
+                    files.entry(token.clone()).or_default().insert(doc);
+                }
+                self.trigrams.insert(&token);
+                if let Some(stems) = &mut self.stems {
+                    if !self.index.contains_key(&token) {
+                        stems.insert(&token);
+                    }
                 }
+                let postings = self.index.entry(token).or_default();
+                postings.locations.push((doc, line_num));
+                postings.positions.push(positions);
             }
         }
     }
 
-    #[cfg(test)]
-    pub fn lookup(&self, token: &str) -> Option<&Vec<(String, u32)>> {
-        self.index.get(&token.to_lowercase())
+    /// Drop every posting for `doc`, given the lines it was indexed from.
+    /// Only the tokens on those lines are visited.
+    pub fn remove_document(&mut self, doc: DocId, lines: impl IntoIterator<Item = impl AsRef<str>>) {
+        if let Some(len) = self.doc_lens.remove(&doc) {
+            self.total_len -= len;
+        }
+
+        let mut tokens = std::collections::HashSet::new();
+        for line in lines {
+            for (token, sub) in self.tokenizer.tokenize_parts(line.as_ref()) {
+                tokens.extend(sub);
+                for extra in self.expansions.get(&token).into_iter().flatten() {
+                    tokens.insert(extra.clone());
+                }
+                tokens.extend(self.tokenizer.folding.variant(&token));
+                tokens.insert(token);
+            }
+        }
+
+        for token in tokens {
+            if let Some(postings) = self.index.get_mut(&token) {
+                let keep: Vec<bool> = postings.locations.iter().map(|(d, _)| *d != doc).collect();
+                let mut kept = keep.iter();
+                postings.locations.retain(|_| *kept.next().unwrap());
+                let mut kept = keep.iter();
+                postings.positions.retain(|_| *kept.next().unwrap());
+                if postings.locations.is_empty() {
+                    self.index.remove(&token);
+                    self.trigrams.remove(&token);
+                    if let Some(stems) = &mut self.stems {
+                        stems.remove(&token);
+                    }
+                }
+            }
+            if let Some(files) = &mut self.files {
+                if let Some(docs) = files.get_mut(&token) {
+                    docs.remove(&doc);
+                    if docs.is_empty() {
+                        files.remove(&token);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rebuild the trigram index after deserializing.
+    pub fn restore(&mut self) {
+        self.trigrams = TrigramIndex::default();
+        for token in self.index.keys() {
+            self.trigrams.insert(token);
+        }
+    }
+
+    /// Release spare capacity in the posting lists.
+    pub fn shrink_to_fit(&mut self) {
+        for postings in self.index.values_mut() {
+            postings.locations.shrink_to_fit();
+            postings.positions.shrink_to_fit();
+        }
+        self.doc_lens.shrink_to_fit();
+    }
+
+    /// Estimated heap bytes of the postings and the indexes derived from
+    /// them, counting each map entry at its inline size.
+    pub fn heap_size(&self) -> usize {
+        let postings: usize = self
+            .index
+            .iter()
+            .map(|(token, p)| {
+                size_of::<(String, Postings)>()
+                    + token.capacity()
+                    + p.locations.capacity() * size_of::<(DocId, u32)>()
+                    + p.positions.capacity() * size_of::<Vec<u32>>()
+                    + p.positions.iter().map(|pos| pos.capacity() * size_of::<u32>()).sum::<usize>()
+            })
+            .sum();
+        let expansions: usize = self
+            .expansions
+            .iter()
+            .map(|(token, extra)| {
+                size_of::<(String, Vec<String>)>()
+                    + token.capacity()
+                    + extra.iter().map(|t| size_of::<String>() + t.capacity()).sum::<usize>()
+            })
+            .sum();
+        let files: usize = self
+            .files
+            .iter()
+            .flatten()
+            .map(|(token, docs)| size_of::<(String, BTreeSet<DocId>)>() + token.capacity() + docs.len() * size_of::<DocId>())
+            .sum();
+        postings
+            + expansions
+            + files
+            + self.doc_lens.capacity() * size_of::<(DocId, usize)>()
+            + self.stems.as_ref().map_or(0, StemIndex::heap_size)
+            + self.trigrams.heap_size()
+    }
+
+    /// Exact-token lookup of all (doc_id, line_number) postings.
+    pub fn lookup(&self, token: &str) -> Option<&Vec<(DocId, u32)>> {
+        self.index.get(&self.tokenizer.normalize(token)).map(|p| &p.locations)
     }
 
     pub fn token_count(&self) -> usize {
         self.index.len()
     }
 
-    /// Find all (path, line_number) locations where a token contains the given
-    /// substring. Returns deduplicated results sorted by (path, line).
-    pub fn find_containing(&self, substring: &str) -> Vec<(String, u32)> {
+    /// Number of tokens in document `doc` (0 if unknown).
+    pub fn doc_len(&self, doc: DocId) -> usize {
+        self.doc_lens.get(&doc).copied().unwrap_or(0)
+    }
+
+    /// Mean document length in tokens across all indexed documents.
+    pub fn avg_doc_len(&self) -> f64 {
+        if self.doc_lens.is_empty() {
+            0.0
+        } else {
+            self.total_len as f64 / self.doc_lens.len() as f64
+        }
+    }
+
+    /// Find all (doc_id, line_number) locations where a token contains the
+    /// given substring. Returns deduplicated results sorted by (doc, line).
+    pub fn find_containing(&self, substring: &str) -> Vec<(DocId, u32)> {
         let mut seen = std::collections::BTreeSet::new();
 
-        for (token, locations) in &self.index {
-            if token.contains(substring) {
-                for (path, line_num) in locations {
-                    seen.insert((path.clone(), *line_num));
+        for token in self.tokens_containing(substring) {
+            seen.extend(self.locations(token).copied());
+        }
+
+        seen.into_iter().collect()
+    }
+
+    /// Ids of all documents with a token containing the given substring.
+    /// Uses the file-level index when enabled, otherwise dedupes line postings.
+    pub fn find_files_containing(&self, substring: &str) -> BTreeSet<DocId> {
+        let mut docs = BTreeSet::new();
+
+        for token in self.tokens_containing(substring) {
+            match &self.files {
+                Some(files) => {
+                    docs.extend(files.get(token).into_iter().flatten().copied());
+                }
+                None => {
+                    docs.extend(self.locations(token).map(|(d, _)| *d));
                 }
             }
         }
 
-        seen.into_iter().collect()
+        docs
+    }
+
+    /// Lines where consecutive tokens spell out `words`, which must be
+    /// case-folded: the first token ends with the first word, the last
+    /// starts with the last word, and any tokens between match exactly.
+    /// These are exactly the lines that can contain the words joined by
+    /// single spaces, so a substring check on the candidates confirms a
+    /// phrase match. Sorted by (doc, line).
+    pub fn find_phrase(&self, words: &[&str]) -> Vec<(DocId, u32)> {
+        let last = words.len().saturating_sub(1);
+        // For each word, the positions of its matching tokens per line
+        let mut occurrences: Vec<HashMap<(DocId, u32), Vec<u32>>> = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            let tokens: Vec<&str> = if i == 0 && last > 0 {
+                let tokens = self.tokens_containing(word);
+                tokens.into_iter().filter(|t| t.ends_with(word)).collect()
+            } else if i == last && last > 0 {
+                let tokens = self.tokens_containing(word);
+                tokens.into_iter().filter(|t| t.starts_with(word)).collect()
+            } else {
+                vec![*word]
+            };
+
+            let lines = self.positions_of(&tokens);
+            if lines.is_empty() {
+                return Vec::new();
+            }
+            occurrences.push(lines);
+        }
+
+        let Some(first) = occurrences.first() else {
+            return Vec::new();
+        };
+        let mut found: Vec<(DocId, u32)> = first
+            .iter()
+            .filter(|(location, starts)| {
+                starts.iter().any(|&start| {
+                    occurrences[1..].iter().enumerate().all(|(i, lines)| {
+                        lines
+                            .get(location)
+                            .is_some_and(|positions| positions.contains(&(start + i as u32 + 1)))
+                    })
+                })
+            })
+            .map(|(location, _)| *location)
+            .collect();
+        found.sort();
+        found
     }
+
+    /// Token positions of `term` on every line it occurs on, keyed by
+    /// (doc_id, line_number). Matches tokens containing `term`, or only
+    /// the exact token with `whole_word`.
+    pub fn term_positions(&self, term: &str, whole_word: bool) -> HashMap<(DocId, u32), Vec<u32>> {
+        let term = self.tokenizer.normalize(term);
+        if whole_word {
+            self.positions_of(&[&term])
+        } else {
+            self.positions_of(&self.tokens_containing(&term))
+        }
+    }
+
+    /// Union of the positions of `tokens` per line.
+    fn positions_of(&self, tokens: &[&str]) -> HashMap<(DocId, u32), Vec<u32>> {
+        let mut lines: HashMap<(DocId, u32), Vec<u32>> = HashMap::new();
+        for postings in tokens.iter().filter_map(|t| self.index.get(*t)) {
+            for (location, positions) in postings.locations.iter().zip(&postings.positions) {
+                lines.entry(*location).or_default().extend(positions);
+            }
+        }
+        lines
+    }
+
+    /// How many times `token` occurs on each line it occurs on, as
+    /// (doc_id, line_number, count), for term frequencies without
+    /// re-tokenizing documents.
+    pub fn line_counts(&self, token: &str) -> impl Iterator<Item = (DocId, u32, usize)> + '_ {
+        let postings = self.index.get(&self.tokenizer.normalize(token));
+        postings.into_iter().flat_map(|p| {
+            p.locations.iter().zip(&p.positions).map(|(&(doc, line), positions)| (doc, line, positions.len()))
+        })
+    }
+
+    /// Indexed tokens with the same stem as `word`, including `word` itself
+    /// if indexed. Empty unless built `with_stemming`.
+    pub fn stem_variants(&self, word: &str) -> Vec<&str> {
+        match &self.stems {
+            Some(stems) => stems.variants(&self.tokenizer.normalize(word)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Stem of a case-folded token, if built `with_stemming`.
+    pub fn stem(&self, token: &str) -> Option<String> {
+        self.stems.as_ref().map(|stems| stems.stem(token))
+    }
+
+    /// Tokens matching a wildcard `pattern` (already case-folded), where `*`
+    /// stands for any run of characters: `deploy*` finds `deploy`,
+    /// `deployment`, and `deploying`. The literal prefix before the first
+    /// `*` narrows the sorted dictionary to a range; only a leading `*`
+    /// checks every token.
+    pub fn wildcard_tokens(&self, pattern: &str) -> Vec<&str> {
+        let prefix = pattern.split('*').next().unwrap_or_default();
+        let rest = &pattern[prefix.len()..];
+        self.index
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .map(|(token, _)| token.as_str())
+            .take_while(|token| token.starts_with(prefix))
+            .filter(|token| rest == "*" || wildcard_match(rest, &token[prefix.len()..]))
+            .collect()
+    }
+
+    /// Tokens at most `max_edits` insertions, deletions, or substitutions
+    /// away from `term`, which must be case-folded. Tokens whose length
+    /// rules them out are skipped without computing a distance.
+    pub fn fuzzy_tokens(&self, term: &str, max_edits: usize) -> Vec<&str> {
+        let term: Vec<char> = term.chars().collect();
+        let mut tokens: Vec<&str> = self
+            .index
+            .keys()
+            .filter(|t| t.chars().count().abs_diff(term.len()) <= max_edits)
+            .filter(|t| within_edits(&term, t, max_edits))
+            .map(String::as_str)
+            .collect();
+        tokens.sort_unstable();
+        tokens
+    }
+
+    /// Up to `n` indexed tokens within `max_edits` of `term` (already
+    /// folded), for did-you-mean hints: closest first, then most
+    /// frequent.
+    pub fn suggestions(&self, term: &str, max_edits: usize, n: usize) -> Vec<&str> {
+        let chars: Vec<char> = term.chars().collect();
+        let mut scored: Vec<(usize, Reverse<usize>, &str)> = self
+            .fuzzy_tokens(term, max_edits)
+            .into_iter()
+            .filter(|&t| t != term)
+            .map(|t| {
+                let edits = (1..max_edits).find(|&d| within_edits(&chars, t, d)).unwrap_or(max_edits);
+                (edits, Reverse(self.locations(t).count()), t)
+            })
+            .collect();
+        scored.sort_unstable();
+        scored.into_iter().take(n).map(|(_, _, t)| t).collect()
+    }
+
+    /// (doc_id, line_number) postings of an exact, already folded token.
+    fn locations<'a>(&'a self, token: &str) -> impl Iterator<Item = &'a (DocId, u32)> {
+        self.index.get(token).into_iter().flat_map(|p| &p.locations)
+    }
+
+    /// Indexed tokens containing `substring`. Only tokens sharing its
+    /// trigrams are checked; substrings under three characters fall back
+    /// to checking every token.
+    fn tokens_containing<'a>(&'a self, substring: &str) -> Vec<&'a str> {
+        match self.trigrams.candidates(substring) {
+            Some(candidates) => candidates
+                .into_iter()
+                .filter(|t| t.contains(substring) && self.index.contains_key(*t))
+                .collect(),
+            None => self
+                .index
+                .keys()
+                .filter(|t| t.contains(substring))
+                .map(String::as_str)
+                .collect(),
+        }
+    }
+}
+
+/// Whether `text` matches `pattern` in full, where `*` in the pattern
+/// matches any run of characters (including none).
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty(); // no `*` at all
+    };
+    // Each middle literal is matched at its earliest position, which
+    // leaves the most room for the rest
+    for part in middle {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Whether the Levenshtein distance between `a` and `b` is at most `max`.
+/// Stops as soon as every cell of a row exceeds `max`.
+fn within_edits(a: &[char], b: &str, max: usize) -> bool {
+    let mut prev: Vec<usize> = (0..=a.len()).collect();
+    let mut row = vec![0; a.len() + 1];
+    for (i, cb) in b.chars().enumerate() {
+        row[0] = i + 1;
+        for (j, &ca) in a.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            row[j + 1] = substitution.min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        if row.iter().all(|&d| d > max) {
+            return false;
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev[a.len()] <= max
 }
 
-/// Tokenize a line: lowercase, split on non-alphanumeric boundaries.
-fn tokenize(line: &str) -> Vec<String> {
-    line.to_lowercase()
-        .split(|c: char| !c.is_alphanumeric())
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .collect()
+/// Tokenize a line with the default rules: lowercase, split on
+/// non-alphanumeric boundaries.
+#[cfg(test)]
+pub fn tokenize(line: &str) -> Vec<String> {
+    Tokenizer::default().tokenize(line)
 }
 
 #[cfg(test)]
@@ -87,7 +601,7 @@ mod tests {
     fn test_index_and_lookup() {
         let mut idx = InvertedIndex::new();
         idx.add_document(
-            "test.md",
+            0,
             &[
                 "Hello world".to_string(),
                 "Goodbye world".to_string(),
@@ -96,19 +610,148 @@ mod tests {
 
         let results = idx.lookup("world").unwrap();
         assert_eq!(results.len(), 2);
-        assert_eq!(results[0], ("test.md".to_string(), 1));
-        assert_eq!(results[1], ("test.md".to_string(), 2));
+        assert_eq!(results[0], (0, 1));
+        assert_eq!(results[1], (0, 2));
     }
 
     #[test]
     fn test_lookup_case_insensitive() {
         let mut idx = InvertedIndex::new();
-        idx.add_document("test.md", &["Hello World".to_string()]);
+        idx.add_document(0, &["Hello World".to_string()]);
 
         assert!(idx.lookup("hello").is_some());
         assert!(idx.lookup("HELLO").is_some());
     }
 
+    #[test]
+    fn test_expansions_add_postings() {
+        let expansions = HashMap::from([("invoice".to_string(), vec!["bill".to_string()])]);
+        let mut idx = InvertedIndex::new().with_expansions(expansions);
+        idx.add_document(
+            0,
+            &["Your invoice is ready".to_string(), "Pay the bill and the invoice".to_string()],
+        );
+
+        // Line 2 is posted once under "bill" despite both words appearing
+        let bill = idx.lookup("bill").unwrap();
+        assert_eq!(bill, &vec![(0, 1), (0, 2)]);
+        assert_eq!(idx.lookup("invoice").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_file_index_matches_line_postings() {
+        let docs = [
+            (0, vec!["alpha beta".to_string(), "beta gamma".to_string()]),
+            (1, vec!["gamma".to_string(), "alphabet soup".to_string()]),
+            (2, vec!["delta".to_string()]),
+        ];
+        let mut lines_only = InvertedIndex::new();
+        let mut with_files = InvertedIndex::new().with_file_index();
+        for (doc, lines) in &docs {
+            lines_only.add_document(*doc, lines);
+            with_files.add_document(*doc, lines);
+        }
+
+        for query in ["alpha", "beta", "gamma", "delta", "a", "missing"] {
+            let deduped: BTreeSet<DocId> = lines_only
+                .find_containing(query)
+                .into_iter()
+                .map(|(p, _)| p)
+                .collect();
+            assert_eq!(with_files.find_files_containing(query), deduped, "query {:?}", query);
+            assert_eq!(lines_only.find_files_containing(query), deduped, "query {:?}", query);
+        }
+    }
+
+    #[test]
+    fn test_remove_document() {
+        let mut idx = InvertedIndex::new().with_file_index();
+        let a = vec!["shared alpha".to_string()];
+        idx.add_document(0, &a);
+        idx.add_document(1, &["shared beta".to_string()]);
+
+        idx.remove_document(0, &a);
+        assert!(idx.lookup("alpha").is_none());
+        assert_eq!(idx.lookup("shared").unwrap(), &vec![(1, 1)]);
+        assert_eq!(idx.find_files_containing("shared"), BTreeSet::from([1]));
+        assert_eq!(idx.token_count(), 2);
+        assert_eq!(idx.doc_len(0), 0);
+        assert_eq!(idx.avg_doc_len(), 2.0);
+    }
+
+    #[test]
+    fn test_find_containing_after_remove_and_restore() {
+        let mut idx = InvertedIndex::new();
+        let a = vec!["passwords here".to_string()];
+        idx.add_document(0, &a);
+        idx.add_document(1, &["sword fight".to_string(), "password".to_string()]);
+        assert_eq!(idx.find_containing("word").len(), 3);
+
+        idx.remove_document(0, &a);
+        assert_eq!(
+            idx.find_containing("word"),
+            vec![(1, 1), (1, 2)]
+        );
+
+        let mut restored: InvertedIndex = bincode::deserialize(&bincode::serialize(&idx).unwrap()).unwrap();
+        assert!(restored.find_containing("word").is_empty());
+        restored.restore();
+        assert_eq!(restored.find_containing("word"), idx.find_containing("word"));
+        assert_eq!(restored.find_containing("wo"), idx.find_containing("wo"));
+    }
+
+    #[test]
+    fn test_find_phrase() {
+        let mut idx = InvertedIndex::new();
+        idx.add_document(
+            0,
+            &[
+                "The invoice due date is Friday".to_string(),
+                "Invoice: date due soon".to_string(),
+                "reinvoice due dates".to_string(),
+                "invoice and due date".to_string(),
+            ],
+        );
+        idx.add_document(1, &["invoice due, date".to_string()]);
+
+        assert_eq!(idx.find_phrase(&["invoice", "due", "date"]), vec![(0, 1), (0, 3), (1, 1)]);
+        assert_eq!(idx.find_phrase(&["due", "date"]), vec![(0, 1), (0, 3), (0, 4), (1, 1)]);
+        assert_eq!(idx.find_phrase(&["date", "due"]), vec![(0, 2)]);
+        assert!(idx.find_phrase(&["invoice", "friday"]).is_empty());
+        assert_eq!(idx.find_phrase(&["friday"]), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_wildcard_tokens() {
+        let mut idx = InvertedIndex::new();
+        idx.add_document(
+            0,
+            &["deploy deployment deploying redeploy deploys dep".to_string()],
+        );
+
+        assert_eq!(idx.wildcard_tokens("deploy*"), vec!["deploy", "deploying", "deployment", "deploys"]);
+        assert_eq!(idx.wildcard_tokens("deploy*s"), vec!["deploys"]);
+        assert_eq!(idx.wildcard_tokens("*deploy"), vec!["deploy", "redeploy"]);
+        assert_eq!(idx.wildcard_tokens("d*p*ing"), vec!["deploying"]);
+        assert!(idx.wildcard_tokens("x*").is_empty());
+        assert!(wildcard_match("*a*", "banana"));
+        assert!(!wildcard_match("a*a", "ab"));
+    }
+
+    #[test]
+    fn test_fuzzy_tokens() {
+        let mut idx = InvertedIndex::new();
+        idx.add_document(
+            0,
+            &["password passwords passport pass sword".to_string()],
+        );
+
+        assert_eq!(idx.fuzzy_tokens("pasword", 1), vec!["password"]);
+        assert_eq!(idx.fuzzy_tokens("pasword", 2), vec!["password", "passwords", "sword"]);
+        assert_eq!(idx.fuzzy_tokens("pass", 0), vec!["pass"]);
+        assert!(idx.fuzzy_tokens("xyzzy", 2).is_empty());
+    }
+
     #[test]
     fn test_lookup_miss() {
         let idx = InvertedIndex::new();
@@ -0,0 +1,189 @@
+use serde_json::Value;
+
+use crate::document::Document;
+use crate::error::MemexError;
+
+/// Whether `path` names a JSON document: `.json`, in any case.
+pub fn is_json(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.rsplit_once('.').is_some_and(|(_, ext)| ext.eq_ignore_ascii_case("json"))
+}
+
+/// The value at `pointer` in `doc`, a `.json` document. See `steps` for
+/// the pointer syntax.
+pub fn value_at(doc: &Document, pointer: &str) -> Result<Value, MemexError> {
+    let not_json = |reason: String| MemexError::NotJson { path: doc.path.clone(), reason };
+    if !is_json(&doc.path) {
+        return Err(not_json("only .json documents can be read as JSON".to_string()));
+    }
+    let steps = steps(pointer)?;
+    let mut value: Value = serde_json::from_str(doc.content()).map_err(|e| not_json(e.to_string()))?;
+    let mut parent = String::new();
+    for step in steps {
+        let next = match &mut value {
+            Value::Object(map) => map.remove(&step),
+            Value::Array(items) => index(&step, items.len()).map(|i| items.swap_remove(i)),
+            _ => None,
+        };
+        let Some(next) = next else {
+            let keys = match value {
+                Value::Object(map) => map.into_iter().map(|(key, _)| key).collect(),
+                _ => Vec::new(),
+            };
+            return Err(MemexError::JsonValueNotFound {
+                path: doc.path.clone(),
+                pointer: pointer.to_string(),
+                parent,
+                keys,
+            });
+        };
+        parent.push('/');
+        parent.push_str(&step.replace('~', "~0").replace('/', "~1"));
+        value = next;
+    }
+    Ok(value)
+}
+
+/// Position `step` names in an array of `len` items, counting from the
+/// end when negative.
+fn index(step: &str, len: usize) -> Option<usize> {
+    let i: i64 = step.parse().ok()?;
+    let i = if i < 0 { len as i64 + i } else { i };
+    (0..len as i64).contains(&i).then_some(i as usize)
+}
+
+/// The keys and indexes `pointer` walks through. It is a JSON Pointer
+/// (`/plans/0/price`, with `~1` for `/` and `~0` for `~` in keys), a
+/// JSONPath (`$.plans[0].price`, `$['a.b']`, `$.plans[-1]` for the last
+/// item), or the same path without the `$` (`plans[0].price`). Empty, `$`,
+/// and `/` select the whole document. Wildcards, slices, filters, and
+/// `..` are not supported, since each read returns one value.
+fn steps(pointer: &str) -> Result<Vec<String>, MemexError> {
+    let pointer = pointer.trim();
+    if matches!(pointer, "" | "$" | "/") {
+        return Ok(Vec::new());
+    }
+    if let Some(rest) = pointer.strip_prefix('/') {
+        return Ok(rest.split('/').map(|key| key.replace("~1", "/").replace("~0", "~")).collect());
+    }
+    let invalid = |reason: &str| MemexError::invalid_params(format!("JSON path `{}`: {}", pointer, reason));
+    let path = pointer.strip_prefix('$').unwrap_or(pointer);
+    let mut steps = Vec::new();
+    let mut chars = path.chars().peekable();
+    let mut first = true;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '[' => {
+                chars.next();
+                match chars.peek() {
+                    Some(&quote @ ('\'' | '"')) => {
+                        chars.next();
+                        let mut key = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('\\') => key.extend(chars.next()),
+                                Some(c) if c == quote => break,
+                                Some(c) => key.push(c),
+                                None => return Err(invalid("unclosed quote")),
+                            }
+                        }
+                        if chars.next() != Some(']') {
+                            return Err(invalid("expected `]` after a quoted key"));
+                        }
+                        steps.push(key);
+                    }
+                    _ => {
+                        let index: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                        let index = index.trim();
+                        if index.parse::<i64>().is_err() {
+                            return Err(invalid(
+                                "brackets take an index or a quoted key; wildcards, slices, and filters are not supported",
+                            ));
+                        }
+                        steps.push(index.to_string());
+                    }
+                }
+            }
+            '.' if chars.clone().nth(1) == Some('.') => {
+                return Err(invalid("recursive descent (`..`) is not supported"));
+            }
+            _ => {
+                if c == '.' {
+                    chars.next();
+                } else if !first {
+                    return Err(invalid("expected `.` or `[` between steps"));
+                }
+                let mut key = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '.' || c == '[' {
+                        break;
+                    }
+                    key.push(c);
+                    chars.next();
+                }
+                if key.is_empty() || key == "*" {
+                    return Err(invalid("expected a key after `.`"));
+                }
+                steps.push(key);
+            }
+        }
+        first = false;
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps() {
+        let cases: [(&str, &[&str]); 8] = [
+            ("", &[]),
+            ("$", &[]),
+            ("/plans/0/price", &["plans", "0", "price"]),
+            ("/a~1b/c~0d", &["a/b", "c~d"]),
+            ("$.plans[0].price", &["plans", "0", "price"]),
+            ("plans[-1].name", &["plans", "-1", "name"]),
+            ("$['a.b'][\"c]\"]", &["a.b", "c]"]),
+            ("$[2]", &["2"]),
+        ];
+        for (pointer, expected) in cases {
+            assert_eq!(steps(pointer).unwrap(), expected, "{:?}", pointer);
+        }
+        for bad in ["$.plans[*]", "$..price", "$.a[1:2]", "$.", "$['a'", "$[0]x"] {
+            assert_eq!(steps(bad).unwrap_err().code(), "invalid_params", "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_value_at() {
+        let doc = Document::new(
+            "config/app.json".into(),
+            r#"{"server": {"port": 8080, "hosts": ["a", "b", "c"]}, "a/b": true}"#,
+        );
+        let at = |pointer: &str| value_at(&doc, pointer);
+        assert_eq!(at("/server/port").unwrap(), 8080);
+        assert_eq!(at("$.server.hosts[-1]").unwrap(), "c");
+        assert_eq!(at("server.hosts[0]").unwrap(), "a");
+        assert_eq!(at("/a~1b").unwrap(), true);
+        assert_eq!(at("").unwrap()["server"]["hosts"][1], "b");
+
+        let missing = at("$.server.tls.cert").unwrap_err();
+        assert_eq!(
+            missing,
+            MemexError::JsonValueNotFound {
+                path: "config/app.json".into(),
+                pointer: "$.server.tls.cert".into(),
+                parent: "/server".into(),
+                keys: vec!["hosts".into(), "port".into()],
+            }
+        );
+        assert_eq!(at("/server/hosts/3").unwrap_err().code(), "json_value_not_found");
+
+        let broken = Document::new("broken.json".into(), "{\"a\": ");
+        assert_eq!(value_at(&broken, "/a").unwrap_err().code(), "not_json");
+        let md = Document::new("a.md".into(), "{}");
+        assert_eq!(value_at(&md, "").unwrap_err().code(), "not_json");
+    }
+}
@@ -1,13 +1,90 @@
+// The tool definitions are one large `serde_json::json!` literal
+#![recursion_limit = "256"]
+
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod archive;
+mod bloom;
+mod builder;
+#[cfg(any(feature = "gzip", feature = "brotli"))]
+mod compress;
+mod context;
+mod deadline;
+mod diff;
+#[cfg(not(target_arch = "wasm32"))]
+mod dir;
 mod document;
 mod error;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fold;
+mod frontmatter;
+mod html;
+#[cfg(all(feature = "http", not(target_arch = "wasm32")))]
+mod http;
 mod index;
+mod jsonpath;
+mod markdown;
+mod mcp;
+mod pattern;
+#[cfg(all(feature = "python", not(target_arch = "wasm32")))]
+mod python;
+mod search;
+mod snapshot;
+mod snippet;
+mod sniff;
+mod stem;
+mod stopwords;
 mod store;
+mod synonyms;
+mod table;
+mod tokenizer;
+mod tools;
+mod trigram;
+mod validate;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
 
+use aho_corasick::AhoCorasick;
 use serde::{Deserialize, Serialize};
-use wasm_bindgen::prelude::*;
 
-use error::MemexError;
+use bloom::FilterQuery;
+use builder::Settings;
+use context::Passage;
+use deadline::Deadline;
+use document::Document;
+use fold::Folding;
+use index::{DocId, InvertedIndex};
+use pattern::{Pattern, PatternCache};
+use stopwords::StopWords;
 use store::DocumentStore;
+use synonyms::Synonyms;
+use tokenizer::Tokenizer;
+
+pub use builder::{MemexFsBuilder, MemoryMode};
+pub use diff::{DiffHunk, DocumentDiff, StoreDiff};
+#[cfg(not(target_arch = "wasm32"))]
+pub use dir::{DirOptions, TOOL_FILE_GLOBS};
+#[cfg(all(feature = "http", not(target_arch = "wasm32")))]
+pub use http::tool_router;
+pub use tokenizer::{SplitFn, TokenizerConfig};
+pub use tools::{ToolOverride, ToolText};
+
+pub use document::{DocInput, Heading, NumberedLine, ReadOptions};
+pub use error::MemexError;
+pub use store::{MemoryStats, NodeKind, TreeNode};
+pub use snippet::SnippetMode;
+pub use sniff::BinaryPolicy;
+pub use stem::StemLanguage;
+pub use stopwords::{StopLanguage, StopWordList};
+pub use synonyms::SynonymMode;
+pub use table::{TableQuery, TableRow, TableRows};
+pub use validate::{DuplicatePolicy, LoadIssue, LoadIssueKind, LoadReport};
+#[cfg(feature = "wasm")]
+pub use wasm::MemexFS;
 
 /// A single grep match.
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,616 +92,6170 @@ pub struct GrepResult {
     pub path: String,
     pub line: u32,
     pub content: String,
+    /// Number of lines a `multiline` match covers; `content` then holds
+    /// all of them, joined by `\n`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_count: Option<u32>,
+    /// 1-indexed character column where the first match starts.
+    #[serde(default)]
+    pub column: u32,
+    /// Every match in the line as `[start, end)` character offsets,
+    /// present when requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spans: Option<Vec<(u32, u32)>>,
+    /// Inferred title of the source document, present when requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Metadata of the source document, present when requested and the
+    /// document has any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    /// Excerpt around the first match, present when a snippet was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    /// Lines preceding the match, nearest last.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context_before: Vec<ContextLine>,
+    /// Lines following the match, nearest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context_after: Vec<ContextLine>,
 }
 
-/// Core MemexFS logic, independent of WASM.
-#[derive(Debug)]
-pub struct MemexFsCore {
-    store: DocumentStore,
-}
+impl GrepResult {
+    fn new(path: String, line: u32, content: String) -> Self {
+        Self {
+            path,
+            line,
+            content,
+            line_count: None,
+            column: 1,
+            spans: None,
+            title: None,
+            metadata: None,
+            snippet: None,
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+        }
+    }
 
-impl MemexFsCore {
-    pub fn from_json(docs_json: &str) -> Result<Self, MemexError> {
-        let docs: Vec<(String, String)> = serde_json::from_str(docs_json)
-            .map_err(|e| MemexError::new(&e.to_string()))?;
+    /// 0-indexed last line this result covers.
+    fn last_line_index(&self) -> usize {
+        (self.line + self.line_count.unwrap_or(1) - 2) as usize
+    }
+}
 
-        if docs.is_empty() {
-            return Err(MemexError::new("MemexError: no documents provided"));
-        }
+/// Counts every match in (path, line) order while keeping only the
+/// requested page.
+struct Pager {
+    offset: usize,
+    limit: usize,
+    total: usize,
+    results: Vec<GrepResult>,
+    /// Keep every match so they can be ranked before the page is cut.
+    ranked: bool,
+    /// Keep at most this many matches per file, dropping the rest as they
+    /// arrive (or after ranking, when ranked), and every kept match so the
+    /// files can be interleaved before the page is cut.
+    max_per_file: Option<usize>,
+    /// Matches kept so far per path, for `max_per_file`.
+    per_file: HashMap<String, usize>,
+    /// Skip frontmatter lines.
+    body_only: bool,
+    /// Skip lines matching the `exclude` pattern.
+    exclude: Option<Pattern>,
+    /// Skip lines inside or outside fenced code blocks.
+    blocks: GrepScope,
+    /// Only search documents whose path matches this glob.
+    glob: Option<String>,
+    /// Only search paths in `[start, end)`, for one slice of a resumable
+    /// grep; `end` is open when `None`.
+    scope: Option<(String, Option<String>)>,
+    /// Paths an overlay layer above decides, skipped while collecting from
+    /// its base (see `MemexFsCore::collect_layered`).
+    hidden: HashSet<String>,
+    /// Keep every match, since layers are collected one after another and
+    /// their matches must be put back in path order before the page is cut.
+    /// Only set when an upper layer has documents the search could match.
+    layered: bool,
+    /// Count matches per path instead of building results.
+    counts: Option<BTreeMap<String, usize>>,
+    /// When to stop scanning, from `timeout_ms`.
+    deadline: Option<Deadline>,
+    /// Whether scanning stopped at the deadline, leaving matches unseen.
+    timed_out: bool,
+}
 
-        let mut store = DocumentStore::new();
-        store.load_documents(docs);
+impl Pager {
+    fn new(options: &GrepOptions, patterns: &PatternCache) -> Result<Self, MemexError> {
+        Ok(Self {
+            offset: options.offset.unwrap_or(0),
+            limit: options.max_results.unwrap_or(DEFAULT_MAX_RESULTS),
+            total: 0,
+            results: Vec::new(),
+            ranked: options.is_ranked(),
+            max_per_file: options.max_per_file,
+            per_file: HashMap::new(),
+            body_only: options.is_body_only(),
+            exclude: options.exclude_regex(patterns)?,
+            blocks: options.scope.unwrap_or_default(),
+            glob: options.glob.clone(),
+            scope: None,
+            hidden: HashSet::new(),
+            layered: false,
+            counts: None,
+            deadline: options.timeout_ms.map(Deadline::after_ms),
+            timed_out: false,
+        })
+    }
 
-        Ok(Self { store })
+    /// A pager that only tallies matches per path, for `grep_counts`.
+    /// Counts are all or nothing, so there is no deadline.
+    fn counting(options: &GrepOptions, patterns: &PatternCache) -> Result<Self, MemexError> {
+        Ok(Self {
+            counts: Some(BTreeMap::new()),
+            deadline: None,
+            ..Self::new(options, patterns)?
+        })
     }
 
-    pub fn grep(&self, pattern: &str, glob: Option<&str>) -> Result<Vec<GrepResult>, MemexError> {
-        if pattern.is_empty() {
-            return Err(MemexError::new("MemexError: empty search pattern"));
-        }
+    /// Whether documents at `path` are searched, by `glob`, `scope`, and
+    /// `hidden`.
+    fn matches_path(&self, path: &str) -> bool {
+        !self.hidden.contains(path)
+            && self.glob.as_deref().is_none_or(|g| glob_match::glob_match(g, path))
+            && self
+                .scope
+                .as_ref()
+                .is_none_or(|(start, end)| path >= start.as_str() && end.as_deref().is_none_or(|end| path < end))
+    }
 
-        let max_results = 100;
+    /// Whether the deadline has passed. Scans check this every
+    /// `DEADLINE_STRIDE` lines rather than on each one.
+    fn expired(&self) -> bool {
+        self.deadline.is_some_and(|d| d.expired())
+    }
 
-        let mut results = if has_regex_metacharacters(pattern) {
-            self.grep_regex(pattern, glob, max_results)?
-        } else {
-            let pattern_lower = pattern.to_lowercase();
-            let is_single_token = pattern_lower.len() >= 3
-                && pattern_lower.chars().all(|c| c.is_alphanumeric());
+    /// `expired`, recording the timeout so the response reports it.
+    fn out_of_time(&mut self) -> bool {
+        self.timed_out |= self.expired();
+        self.timed_out
+    }
 
-            if is_single_token {
-                self.grep_index(&pattern_lower, glob, max_results)
-            } else {
-                self.grep_scan(&pattern_lower, glob, max_results)
-            }
-        };
+    /// Per-path tallies of a counting pager, in path order.
+    fn into_counts(self) -> Vec<FileCount> {
+        self.counts
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(path, count)| FileCount { path, count })
+            .collect()
+    }
 
-        results.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
-        Ok(results)
+    /// Whether line `idx` of `doc` should be excluded from matching: it is
+    /// frontmatter (unless searched), outside the `scope`, or matches
+    /// `exclude`.
+    fn skips_line(&self, doc: &Document, idx: usize) -> bool {
+        (self.body_only && doc.is_frontmatter(idx))
+            || (self.blocks != GrepScope::All && !self.blocks.includes(doc.is_code(idx)))
+            || self
+                .exclude
+                .as_ref()
+                .is_some_and(|re| re.is_match(doc.line(idx)))
     }
 
-    /// Fast path: scan inverted index tokens for substring match.
-    /// Used for single alphanumeric patterns (≥3 chars) where the index
-    /// is much smaller than the total line count.
-    fn grep_index(
-        &self,
-        pattern_lower: &str,
-        glob: Option<&str>,
-        max_results: usize,
-    ) -> Vec<GrepResult> {
-        let locations = self.store.index().find_containing(pattern_lower);
-        let mut results = Vec::new();
+    /// Whether every match is kept until `finish_page` cuts the page.
+    fn keeps_all(&self) -> bool {
+        self.ranked || self.max_per_file.is_some() || self.layered
+    }
 
-        for (path, line_num) in &locations {
-            if results.len() >= max_results {
-                break;
-            }
-            if let Some(g) = glob {
-                if !glob_match::glob_match(g, path) {
-                    continue;
+    /// Record a match in `path`, building the result only if it lands on
+    /// the page.
+    fn push(&mut self, path: &str, make: impl FnOnce() -> GrepResult) {
+        if let Some(counts) = &mut self.counts {
+            match counts.get_mut(path) {
+                Some(count) => *count += 1,
+                None => {
+                    counts.insert(path.to_string(), 1);
                 }
             }
-            if let Some(doc) = self.store.get_document(path) {
-                let idx = (*line_num - 1) as usize;
-                if idx < doc.lines.len() {
-                    results.push(GrepResult {
-                        path: path.clone(),
-                        line: *line_num,
-                        content: doc.lines[idx].clone(),
-                    });
+        } else {
+            // Ranking decides which matches of a file make the cut, so it
+            // sees them all
+            if let (Some(cap), false) = (self.max_per_file, self.ranked) {
+                let kept = self.per_file.get(path).copied().unwrap_or(0);
+                if kept >= cap {
+                    return;
                 }
+                self.per_file.insert(path.to_string(), kept + 1);
+            }
+            if self.keeps_all() || (self.total >= self.offset && self.results.len() < self.limit) {
+                self.results.push(make());
             }
         }
-
-        results
+        self.total += 1;
     }
+}
 
-    /// Scan pre-lowercased lines. Used for multi-word patterns or short
-    /// patterns where the index would match too many tokens.
-    fn grep_scan(
-        &self,
-        pattern_lower: &str,
-        glob: Option<&str>,
-        max_results: usize,
-    ) -> Vec<GrepResult> {
-        let mut results = Vec::new();
-        let paths = self.store.paths();
+/// Number of matching lines in one document, from `grep_counts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileCount {
+    pub path: String,
+    pub count: usize,
+}
 
-        for path in paths {
-            if results.len() >= max_results {
-                break;
-            }
-            if let Some(g) = glob {
-                if !glob_match::glob_match(g, path) {
-                    continue;
-                }
-            }
-            if let Some(doc) = self.store.get_document(path) {
-                for (i, line_lower) in doc.lines_lower.iter().enumerate() {
-                    if results.len() >= max_results {
-                        break;
-                    }
-                    if line_lower.contains(pattern_lower) {
-                        results.push(GrepResult {
-                            path: path.to_string(),
-                            line: (i + 1) as u32,
-                            content: doc.lines[i].clone(),
-                        });
-                    }
+/// A line of surrounding context attached to a `GrepResult`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextLine {
+    pub line: u32,
+    pub content: String,
+}
+
+/// A directory entry from `MemexFsCore::ls_with_metadata`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LsEntry {
+    /// File name, or subdirectory name with a trailing `/`.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// A directory entry from `MemexFsCore::ls_detailed`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LsDetail {
+    /// File name, or subdirectory name with a trailing `/`.
+    pub name: String,
+    pub kind: NodeKind,
+    /// Line count, for files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines: Option<usize>,
+    /// Content size in bytes, for files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<usize>,
+    /// Number of documents anywhere below, for directories.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub children: Option<usize>,
+    /// The file's metadata, if it has any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// One slice of a resumable grep, from `MemexFsCore::continue_grep`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrepChunk {
+    /// Matches found in this slice, continuing the path and line order of
+    /// earlier slices.
+    pub results: Vec<GrepResult>,
+    /// Documents searched so far, across all slices.
+    pub searched: usize,
+    /// Documents in the corpus when this slice ran.
+    pub total_documents: usize,
+    /// Every document has been searched or `max_results` reached; the
+    /// handle is released.
+    pub done: bool,
+}
+
+/// A grep started by `start_grep`, searched a batch of documents at a
+/// time by `continue_grep`.
+#[derive(Debug)]
+struct GrepCursor {
+    pattern: String,
+    options: GrepOptions,
+    /// First path not searched yet. Paths are visited in sorted order, so
+    /// documents written between slices are picked up if they sort later.
+    next: String,
+    searched: usize,
+    /// Results still allowed by `max_results`.
+    remaining: usize,
+}
+
+#[derive(Debug, Default)]
+struct GrepCursors {
+    next_handle: u32,
+    active: HashMap<u32, GrepCursor>,
+}
+
+/// One page of grep matches plus the size of the full result set.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrepResponse {
+    pub results: Vec<GrepResult>,
+    /// Number of matching lines across all pages.
+    pub total_matches: usize,
+    /// Offset of the next page, present when this page made progress and
+    /// more matches remain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+    /// The search hit `timeout_ms` and stopped early: the results and
+    /// `total_matches` cover only what was scanned before then.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// With `GrepOptions::suggest` and no matches: indexed words close to
+    /// the pattern's words, e.g. `invoicing` for `invoicng`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+impl GrepResponse {
+    /// The same page with results grouped by document, files in order of
+    /// their first result.
+    pub fn grouped(self) -> GroupedGrepResponse {
+        let mut groups: Vec<GrepGroup> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for result in self.results {
+            match positions.get(&result.path) {
+                Some(&pos) => groups[pos].matches.push(result),
+                None => {
+                    positions.insert(result.path.clone(), groups.len());
+                    groups.push(GrepGroup {
+                        path: result.path.clone(),
+                        matches: vec![result],
+                    });
                 }
             }
         }
+        GroupedGrepResponse {
+            groups,
+            total_matches: self.total_matches,
+            next_offset: self.next_offset,
+            timed_out: self.timed_out,
+            suggestions: self.suggestions,
+        }
+    }
+}
 
-        results
+/// The matches from one document in a `GroupedGrepResponse`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrepGroup {
+    pub path: String,
+    pub matches: Vec<GrepResult>,
+}
+
+/// A `GrepResponse` page with results grouped by document.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupedGrepResponse {
+    pub groups: Vec<GrepGroup>,
+    /// Number of matching lines across all pages.
+    pub total_matches: usize,
+    /// Offset of the next page, present when more matches remain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+    /// See `GrepResponse::timed_out`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// See `GrepResponse::suggestions`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+/// One call in a `MemexFsCore::call_batch`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub name: String,
+    /// The tool's input, as for `call_value`.
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+/// What `call` returns with `MemexOptions::call_envelope`: the same shape
+/// for every tool, success or failure.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CallEnvelope {
+    pub ok: bool,
+    pub tool: String,
+    /// The tool's output: parsed JSON, or a string for the text tools
+    /// (`read`, `write`, text `tree`). `null` on error.
+    pub data: Option<serde_json::Value>,
+    /// `MemexError::to_json` of the failure. `null` on success.
+    pub error: Option<serde_json::Value>,
+    /// Whether `data` leaves some of the answer out: a grep page with
+    /// more pages or cut short by `timeout_ms`, a `query_table` page with
+    /// more rows, or a read of part of a document.
+    pub truncated: bool,
+    /// Size of the whole answer: matching lines for grep (paths or counts
+    /// for `files` and `count`), lines of the document or section for
+    /// reads, and entries for other list results.
+    pub total: Option<usize>,
+}
+
+/// A document ranked by `MemexFsCore::search`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub path: String,
+    /// BM25 relevance score; higher is more relevant.
+    pub score: f64,
+    /// 1-indexed line of the best-matching line.
+    pub line: u32,
+    /// Excerpt of the best-matching line around the first query term.
+    pub snippet: String,
+}
+
+/// Order of grep results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrepSort {
+    /// By path, then line number.
+    #[default]
+    Path,
+    /// Best matches first: headings, lines in documents whose file name
+    /// matches, and lines near the top of a document rank higher. Ties keep
+    /// path order.
+    Relevance,
+}
+
+/// How a grep pattern is matched against lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrepMode {
+    /// The pattern as a whole: a regex, or an exact (case-insensitive)
+    /// substring.
+    #[default]
+    Exact,
+    /// Every word of the pattern, in any order, so `password reset` also
+    /// finds "reset your password". Regex metacharacters are ignored.
+    AllTerms,
+    /// Two terms within a number of tokens of each other, written
+    /// `refund NEAR/5 deadline` (`NEAR` alone allows 5).
+    Near,
+}
+
+/// Where all of the terms must occur in `all_terms` and `near` modes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TermsWithin {
+    /// On a single line.
+    #[default]
+    Line,
+    /// Anywhere in the document; every line with any of the terms matches.
+    /// In `near` mode the window may span lines, and the lines holding each
+    /// close pair match.
+    Document,
+}
+
+/// Which lines a grep searches, by markdown block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrepScope {
+    /// Every line.
+    #[default]
+    All,
+    /// Lines outside fenced code blocks.
+    Prose,
+    /// Lines of fenced code blocks, the fences included.
+    Code,
+}
+
+impl GrepScope {
+    /// Whether a line in this scope may be a code line (`code`) or not.
+    fn includes(self, code: bool) -> bool {
+        match self {
+            GrepScope::All => true,
+            GrepScope::Prose => !code,
+            GrepScope::Code => code,
+        }
     }
+}
 
-    /// Regex path: compile pattern and scan all lines.
-    fn grep_regex(
-        &self,
-        pattern: &str,
-        glob: Option<&str>,
-        max_results: usize,
-    ) -> Result<Vec<GrepResult>, MemexError> {
-        let re = regex::RegexBuilder::new(pattern)
-            .case_insensitive(true)
-            .build()
-            .map_err(|e| MemexError::new(&format!("MemexError: invalid regex: {}", e)))?;
+/// Options for `MemexFsCore::grep_with_options`. All fields are optional and
+/// default to the behavior of a plain `grep(pattern, None)`.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct GrepOptions {
+    /// Only search documents whose path matches this glob.
+    pub glob: Option<String>,
+    /// Skip YAML frontmatter lines when matching. Defaults to `true`, since
+    /// frontmatter keys are exposed as metadata; pass `false` to search them
+    /// like any other line.
+    pub body_only: Option<bool>,
+    /// Match a multi-word literal pattern as a phrase whose words may be
+    /// separated by any run of whitespace, e.g. `reset  your\tpassword`.
+    pub flexible_whitespace: Option<bool>,
+    /// Attach each result's document title (first heading or non-empty line).
+    pub with_title: Option<bool>,
+    /// Attach a `snippet` around the first match of each result.
+    pub snippet: Option<SnippetMode>,
+    /// Characters kept on each side of the match in snippets. Setting this
+    /// alone enables `chars` snippets.
+    pub snippet_radius: Option<usize>,
+    /// Lines of context to attach before each match.
+    pub context_before: Option<usize>,
+    /// Lines of context to attach after each match.
+    pub context_after: Option<usize>,
+    /// Total context lines allowed across all results. Matches themselves
+    /// count toward the result cap, never this budget. When set, context is
+    /// handed out one line per side per result in turns, nearest lines
+    /// first, so early results cannot starve later ones. Unlimited if unset.
+    pub max_context_lines: Option<usize>,
+    /// Maximum results per page. Defaults to 100.
+    pub max_results: Option<usize>,
+    /// Maximum matches kept per document, so one long document cannot
+    /// fill the page. When set, results are interleaved across files (each
+    /// file's first match, then each file's second, and so on) and
+    /// `total_matches` counts only the kept matches. Ignored by
+    /// `grep_counts`.
+    pub max_per_file: Option<usize>,
+    /// Number of matches to skip before the page starts (from a previous
+    /// response's `next_offset`). Defaults to 0.
+    pub offset: Option<usize>,
+    /// Only match at word boundaries, so `cat` no longer matches `category`.
+    pub whole_word: Option<bool>,
+    /// Match letter case exactly. Synonyms are not expanded in this mode.
+    pub case_sensitive: Option<bool>,
+    /// Attach `spans`: every match range within each result line.
+    pub spans: Option<bool>,
+    /// Treat the pattern as a fixed string even if it contains regex
+    /// metacharacters, e.g. `config.toml` or `foo(bar)`. Always scans lines
+    /// for the exact substring; synonyms are not expanded.
+    pub literal: Option<bool>,
+    /// Result order. Defaults to `path`.
+    pub sort: Option<GrepSort>,
+    /// Only search documents whose metadata matches, e.g.
+    /// `{"tags": ["billing"]}`. See `Document::matches_metadata`.
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Attach each result's document metadata.
+    pub with_metadata: Option<bool>,
+    /// How the pattern is matched. Defaults to `exact`.
+    pub mode: Option<GrepMode>,
+    /// Where the terms must co-occur in `all_terms` mode. Defaults to `line`.
+    pub within: Option<TermsWithin>,
+    /// Drop lines that also match this pattern, e.g. find `token` but not
+    /// on lines mentioning `deprecated`. Interpreted like the main pattern
+    /// (regex if it has metacharacters, unless `literal`) and subject to
+    /// `case_sensitive` and `whole_word`.
+    pub exclude: Option<String>,
+    /// Tolerate typos in a single-word pattern: match whole tokens within
+    /// one edit of it (words of 3–5 characters) or two (longer words), so
+    /// `pasword` finds `password`. Shorter words and other patterns match
+    /// as usual.
+    pub fuzzy: Option<bool>,
+    /// In `all_terms` mode, leave out the stop words configured with
+    /// `MemexOptions::stop_words`. Off by default.
+    pub stop_words: Option<bool>,
+    /// Like ripgrep's `--smart-case`: a pattern with an uppercase letter
+    /// matches case-sensitively, an all-lowercase one case-insensitively.
+    /// Regex escapes such as `\S` do not count. An explicit
+    /// `case_sensitive` wins. Off by default.
+    pub smart_case: Option<bool>,
+    /// Match the pattern as a regex against each whole document, so it can
+    /// span line breaks (`\n`), e.g. `## Refunds\n+Contact`. `^` and `$`
+    /// match at line boundaries and `.` stops at them. Each result starts
+    /// at the first matched line and sets `line_count`.
+    pub multiline: Option<bool>,
+    /// Stop scanning after this many milliseconds and return the matches
+    /// found so far, with `timed_out` set, instead of blocking on a huge
+    /// scan or pathological regex. Checked between documents and every
+    /// few hundred lines. Ignored by `grep_counts`.
+    pub timeout_ms: Option<u64>,
+    /// When nothing matches, fill the response's `suggestions` with indexed
+    /// words close to the pattern's unknown words, so a misspelled query
+    /// can be retried. Off by default. Ignored by `grep_counts`.
+    pub suggest: Option<bool>,
+    /// Only search prose, or only fenced code blocks. Defaults to `all`.
+    pub scope: Option<GrepScope>,
+    /// Match literal and regex patterns against each line as rendered
+    /// (`markdown::plain`): without heading and list markers, emphasis
+    /// delimiters, backticks, or link targets, so `*` finds only literal
+    /// asterisks and a URL fragment no longer matches `[text](url)`.
+    /// Results still show the line as written. Always scans lines and
+    /// doesn't expand synonyms; `mode`, `multiline`, `fuzzy`, and
+    /// wildcard patterns ignore it.
+    pub ignore_markdown: Option<bool>,
+}
 
-        let mut results = Vec::new();
-        let paths = self.store.paths();
+impl GrepOptions {
 
-        for path in paths {
-            if results.len() >= max_results {
-                break;
-            }
-            if let Some(g) = glob {
-                if !glob_match::glob_match(g, path) {
-                    continue;
-                }
-            }
-            if let Some(doc) = self.store.get_document(path) {
-                for (i, line) in doc.lines.iter().enumerate() {
-                    if results.len() >= max_results {
-                        break;
-                    }
-                    if re.is_match(line) {
-                        results.push(GrepResult {
-                            path: path.to_string(),
-                            line: (i + 1) as u32,
-                            content: line.clone(),
-                        });
-                    }
-                }
-            }
+    /// Regex for `pattern` as a flexible-whitespace phrase, when that option
+    /// is enabled and the pattern is a multi-word literal.
+    fn flexible_phrase(&self, pattern: &str) -> Option<String> {
+        if !self.flexible_whitespace.unwrap_or(false) || self.is_regex(pattern) {
+            return None;
         }
+        let words: Vec<String> = pattern.split_whitespace().map(regex::escape).collect();
+        (words.len() > 1).then(|| words.join(r"\s+"))
+    }
 
-        Ok(results)
+    /// Whether `pattern` should be compiled as a regex: it contains regex
+    /// metacharacters and `literal` is not set.
+    fn is_regex(&self, pattern: &str) -> bool {
+        !self.literal.unwrap_or(false) && has_regex_metacharacters(pattern)
     }
 
-    pub fn read(
-        &self,
-        path: &str,
-        offset: Option<usize>,
-        limit: Option<usize>,
-    ) -> Result<String, MemexError> {
-        let doc = self
-            .store
-            .get_document(path)
-            .ok_or_else(|| MemexError::new(&format!("MemexError: document not found: {}", path)))?;
+    /// These options with `case_sensitive` decided by `smart_case`: on if
+    /// any of `patterns` has an uppercase letter, off otherwise. Unchanged
+    /// when `smart_case` is off or `case_sensitive` was given explicitly.
+    fn with_smart_case<S: AsRef<str>>(&self, patterns: &[S]) -> Cow<'_, GrepOptions> {
+        if !self.smart_case.unwrap_or(false) || self.case_sensitive.is_some() {
+            return Cow::Borrowed(self);
+        }
+        let has_uppercase = patterns.iter().any(|p| {
+            let pattern = p.as_ref();
+            if self.is_regex(pattern) {
+                has_uppercase_literal(pattern)
+            } else {
+                pattern.chars().any(char::is_uppercase)
+            }
+        });
+        Cow::Owned(GrepOptions {
+            case_sensitive: Some(has_uppercase),
+            ..self.clone()
+        })
+    }
 
-        Ok(doc.read(offset, limit))
+    /// Whether `pattern` is a wildcard token query like `deploy*`: letters
+    /// and digits with at least one `*`, matched against index tokens
+    /// instead of compiled as a regex. Not with `literal` or
+    /// `case_sensitive`.
+    fn is_wildcard(&self, pattern: &str) -> bool {
+        !self.literal.unwrap_or(false)
+            && !self.is_case_sensitive()
+            && pattern.contains('*')
+            && pattern.chars().any(char::is_alphanumeric)
+            && pattern.chars().all(|c| c == '*' || c.is_alphanumeric())
     }
 
-    pub fn ls(&self, path: &str) -> Vec<String> {
-        self.store.ls(path)
+    fn is_whole_word(&self) -> bool {
+        self.whole_word.unwrap_or(false)
     }
 
-    pub fn call(&self, name: &str, params_json: &str) -> Result<String, MemexError> {
-        match name {
-            "grep" => {
-                let params: GrepParams = serde_json::from_str(params_json)
-                    .map_err(|e| MemexError::new(&e.to_string()))?;
-                let results = self.grep(&params.pattern, params.glob.as_deref())?;
-                serde_json::to_string(&results).map_err(|e| MemexError::new(&e.to_string()))
-            }
-            "read" => {
-                let params: ReadParams = serde_json::from_str(params_json)
-                    .map_err(|e| MemexError::new(&e.to_string()))?;
-                self.read(
-                    &params.path,
-                    params.offset.map(|o| o as usize),
-                    params.limit.map(|l| l as usize),
-                )
-            }
-            "ls" => {
-                let params: LsParams = serde_json::from_str(params_json)
-                    .map_err(|e| MemexError::new(&e.to_string()))?;
-                let entries = self.ls(&params.path);
-                serde_json::to_string(&entries).map_err(|e| MemexError::new(&e.to_string()))
-            }
-            _ => Err(MemexError::new(&format!(
-                "MemexError: unknown tool: {}",
-                name
-            ))),
-        }
+    fn is_case_sensitive(&self) -> bool {
+        self.case_sensitive.unwrap_or(false)
     }
 
-    pub fn tool_definitions(&self) -> String {
-        serde_json::to_string(&tool_definitions_json()).unwrap()
+    /// Compile regex `source` through `patterns`, case-insensitive unless
+    /// `case_sensitive` is set and wrapped in word boundaries when
+    /// `whole_word` is set.
+    fn build_regex(&self, patterns: &PatternCache, source: &str) -> Result<Pattern, MemexError> {
+        let source = if self.is_whole_word() {
+            format!(r"\b(?:{})\b", source)
+        } else {
+            source.to_string()
+        };
+        patterns.get(&source, !self.is_case_sensitive())
     }
 
-    pub fn document_count(&self) -> usize {
-        self.store.document_count()
+    /// Whether `doc` passes the `metadata` filter.
+    fn matches_doc(&self, doc: &Document) -> bool {
+        self.metadata
+            .as_ref()
+            .is_none_or(|filter| doc.matches_metadata(filter))
     }
 
-    pub fn token_count(&self) -> usize {
-        self.store.token_count()
+    /// Compiled `exclude` pattern, if any.
+    fn exclude_regex(&self, patterns: &PatternCache) -> Result<Option<Pattern>, MemexError> {
+        match self.exclude.as_deref().filter(|e| !e.is_empty()) {
+            Some(exclude) if self.is_regex(exclude) => self.build_regex(patterns, exclude).map(Some),
+            Some(exclude) => self.build_regex(patterns, &regex::escape(exclude)).map(Some),
+            None => Ok(None),
+        }
     }
+
+    fn is_body_only(&self) -> bool {
+        self.body_only.unwrap_or(true)
+    }
+
+    fn is_ranked(&self) -> bool {
+        self.sort == Some(GrepSort::Relevance)
+    }
+}
+
+/// A session's changes to its base, from `MemexFsCore::export_overlay`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverlayChanges {
+    /// Documents written in the session, sorted by path, whether new or
+    /// replacing a base document.
+    pub documents: Vec<DocInput>,
+    /// Sorted paths of base documents the session deleted.
+    pub deleted: Vec<String>,
+}
+
+/// Core MemexFS logic, independent of WASM.
+#[derive(Debug)]
+pub struct MemexFsCore {
+    store: DocumentStore,
+    synonyms: Synonyms,
+    synonym_mode: SynonymMode,
+    stop_words: StopWords,
+    /// Distinct paths read this session, most recent first.
+    recent: Mutex<VecDeque<String>>,
+    /// Compiled grep regexes, reused across calls.
+    patterns: PatternCache,
+    /// Resumable greps in progress, by handle.
+    cursors: Mutex<GrepCursors>,
+    /// Host defaults from `MemexFsBuilder`.
+    settings: Settings,
+    /// The shared instance under an overlay (see `overlay`), seen through
+    /// this one wherever it has no say.
+    base: Option<Arc<MemexFsCore>>,
+    /// Base documents an overlay deleted. A path stays here when it is
+    /// written again, so deleting it once more leaves nothing behind.
+    whiteouts: BTreeSet<String>,
+}
+
+/// Maximum number of documents remembered by `recently_read`.
+const RECENT_CAPACITY: usize = 100;
+
+/// Number of grep results per page when `max_results` is not given.
+const DEFAULT_MAX_RESULTS: usize = 100;
+
+/// Documents searched per batch of a resumable grep. `continue_grep`
+/// checks its budget between batches.
+const CURSOR_BATCH: usize = 64;
+
+/// Lines (or index candidates) scanned between checks of `timeout_ms`.
+const DEADLINE_STRIDE: usize = 256;
+
+/// Edits tolerated by `fuzzy` for a word of `len` (at least 3) characters.
+fn fuzzy_edits(len: usize) -> usize {
+    if len <= 5 { 1 } else { 2 }
 }
 
-// ── WASM bindings ──────────────────────────────────────────────────
+/// Most words `GrepOptions::suggest` offers.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Token distance allowed by a bare `NEAR`.
+const DEFAULT_NEAR_DISTANCE: u32 = 5;
+
+/// Number of documents `search` returns when no limit is given.
+const DEFAULT_SEARCH_LIMIT: usize = 10;
 
-/// WASM-exported MemexFS. Thin wrapper over MemexFsCore that converts errors to JsError.
-#[wasm_bindgen]
-pub struct MemexFS {
-    core: MemexFsCore,
+/// Construction-time configuration for `MemexFsCore`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct MemexOptions {
+    /// Synonym map: each key also matches its listed synonyms.
+    pub synonyms: HashMap<String, Vec<String>>,
+    /// Expand synonyms per query (default) or bake them into the index.
+    pub synonym_mode: SynonymMode,
+    /// Keep a secondary token → paths index so file-level queries
+    /// (`grep_files`, `missing_in_glob`) skip the line postings. Costs
+    /// extra memory; off by default.
+    pub file_index: bool,
+    /// Keep a case-folded copy of every document so case-insensitive
+    /// scans never fold lines at query time. Faster scans on non-ASCII
+    /// text for roughly twice the content memory; off by default, in which
+    /// case ASCII lines are compared in place and others folded per scan.
+    pub fold_cache: bool,
+    /// Ignore diacritics, so `Malmo` matches `Malmö` and `resume` matches
+    /// `résumé` (and the other way round). Tokens are also indexed under
+    /// their plain spelling, and queries are folded the same way. Applies
+    /// to literal grep, `grep_files`, `missing_in_glob`, `search`, and
+    /// `context_for`; regex patterns still match the text as stored.
+    pub ascii_fold: bool,
+    /// Index runs of Chinese, Japanese, and Korean characters as
+    /// overlapping n-grams of this many characters (2 is the usual
+    /// choice), so words inside unspaced text can be found through the
+    /// index. Without it a whole CJK run is one token, and literal grep
+    /// only finds it by scanning. Off by default; 0 also means off.
+    pub cjk_ngram: Option<usize>,
+    /// Also index the words of camelCase identifiers, so `user` finds
+    /// `getUserById` as a whole word and in `search`. snake_case
+    /// identifiers like `user_id` are split either way. Off by default.
+    pub split_identifiers: bool,
+    /// Index `.html` and `.htm` documents as plain text: tags, scripts,
+    /// and styles are dropped, entities decoded, block elements kept on
+    /// their own lines, and `<h1>`–`<h6>` turned into markdown headings.
+    /// `read` returns the text too. Off by default.
+    pub html_to_text: bool,
+    /// What to do with documents that aren't text (invalid UTF-8, NUL
+    /// bytes, or mostly control characters, like an image decoded as a
+    /// string): skip them (default), reject the load, or keep unsearchable
+    /// stubs. Applies to every way of loading, and to writes, except that
+    /// a skipped write fails instead. Snapshots keep the setting.
+    pub binary_policy: BinaryPolicy,
+    /// What loading does with a document whose path is already loaded or
+    /// repeated later in the batch: replace it (default), keep the first,
+    /// or fail the load. Paths are compared once normalized, so `./a.md`
+    /// and `a.md` are the same document. Writes always replace. Snapshots
+    /// keep the setting.
+    pub duplicate_paths: DuplicatePolicy,
+    /// Largest document, in bytes of content, that `add_documents_lenient`
+    /// loads; bigger ones are reported as `oversized` and left out. No
+    /// limit by default. A host setting: snapshots don't keep it.
+    pub max_document_bytes: Option<usize>,
+    /// Make `call` return a `CallEnvelope` (`{ok, tool, data, error,
+    /// truncated, total}`) for every tool instead of the tool's raw
+    /// output, and report errors inside it rather than failing. Off by
+    /// default. A host setting: snapshots don't keep it.
+    pub call_envelope: bool,
+    /// Wording of the tool definitions: a name for the corpus used in
+    /// place of "knowledge base", and per-tool names, descriptions, and
+    /// parameter descriptions. A host setting: snapshots don't keep it.
+    pub tool_text: ToolText,
+    /// Words `search` and `context_for` leave out of queries, e.g.
+    /// `"english"` or `["acme", "inc"]`, so "how to reset the password"
+    /// ranks by `reset` and `password`. Grep only drops them when asked.
+    pub stop_words: Option<StopWordList>,
+    /// Also match other inflections of a word (`running` ↔ `run`,
+    /// `invoices` ↔ `invoice`) in single-word grep and in `search`, using
+    /// the Snowball stemmer for this language.
+    #[cfg(feature = "stemming")]
+    pub stemmer: Option<StemLanguage>,
 }
 
-#[wasm_bindgen]
-impl MemexFS {
-    #[wasm_bindgen(constructor)]
-    pub fn new(docs_json: &str) -> Result<MemexFS, JsError> {
-        let core = MemexFsCore::from_json(docs_json).map_err(|e| JsError::new(&e.message))?;
-        Ok(MemexFS { core })
+impl MemexFsCore {
+    pub fn from_json(docs_json: &str) -> Result<Self, MemexError> {
+        Self::from_json_with_options(docs_json, &MemexOptions::default())
     }
 
-    pub fn grep(&self, pattern: &str, glob: Option<String>) -> Result<String, JsError> {
-        let results = self
-            .core
-            .grep(pattern, glob.as_deref())
-            .map_err(|e| JsError::new(&e.message))?;
-        serde_json::to_string(&results).map_err(|e| JsError::new(&e.to_string()))
+    pub fn from_json_with_options(
+        docs_json: &str,
+        options: &MemexOptions,
+    ) -> Result<Self, MemexError> {
+        MemexFsBuilder::new().options(options.clone()).from_json(docs_json)
     }
 
-    pub fn read(
-        &self,
-        path: &str,
-        offset: Option<u32>,
-        limit: Option<u32>,
-    ) -> Result<String, JsError> {
-        self.core
-            .read(path, offset.map(|o| o as usize), limit.map(|l| l as usize))
-            .map_err(|e| JsError::new(&e.message))
+    /// `from_json_with_options` that loads what it can instead of failing
+    /// on the first bad document, and reports the rest (see
+    /// `add_documents_lenient`).
+    pub fn from_json_lenient(docs_json: &str, options: &MemexOptions) -> Result<(Self, LoadReport), MemexError> {
+        MemexFsBuilder::new().options(options.clone()).from_json_lenient(docs_json)
     }
 
-    pub fn ls(&self, path: &str) -> Result<String, JsError> {
-        let entries = self.core.ls(path);
-        serde_json::to_string(&entries).map_err(|e| JsError::new(&e.to_string()))
+    /// An instance over the text files under the directory `path` (see
+    /// `DirOptions` for which), with paths relative to it. Native only.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_dir(
+        path: impl AsRef<std::path::Path>,
+        options: &DirOptions,
+    ) -> Result<Self, MemexError> {
+        let docs = dir::collect(path.as_ref(), options)?;
+        let mut core = Self::empty(&options.options);
+        core.load_documents(docs)?;
+        core.finalize()?;
+        Ok(core)
     }
 
-    pub fn tool_definitions(&self) -> String {
-        self.core.tool_definitions()
+    /// `from_json` over compressed JSON (see `add_documents_compressed`).
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    pub fn from_compressed(bytes: &[u8]) -> Result<Self, MemexError> {
+        MemexFsBuilder::new().from_compressed(bytes)
     }
 
-    pub fn call(&self, name: &str, params_json: &str) -> Result<String, JsError> {
-        self.core
-            .call(name, params_json)
-            .map_err(|e| JsError::new(&e.message))
+    /// An instance over the text files in a tar or tar.gz archive, with
+    /// entry paths as document paths (see `MemexFsBuilder::from_tar`).
+    #[cfg(feature = "tar")]
+    pub fn from_tar(bytes: &[u8]) -> Result<Self, MemexError> {
+        MemexFsBuilder::new().from_tar(bytes)
     }
 
-    pub fn document_count(&self) -> usize {
-        self.core.document_count()
+    /// An instance over the text files in a zip archive (see
+    /// `MemexFsBuilder::from_zip`).
+    #[cfg(feature = "zip")]
+    pub fn from_zip(bytes: &[u8]) -> Result<Self, MemexError> {
+        MemexFsBuilder::new().from_zip(bytes)
     }
 
-    pub fn token_count(&self) -> usize {
-        self.core.token_count()
+    /// An instance with no documents yet, for loading a large corpus in
+    /// batches with `add_documents` followed by `finalize`.
+    pub fn empty(options: &MemexOptions) -> Self {
+        Self::build(options, TokenizerConfig::default())
     }
-}
 
-// ── Helpers ────────────────────────────────────────────────────────
+    /// `empty`, splitting text by `tokenizer` (see `MemexFsBuilder`).
+    fn build(options: &MemexOptions, tokenizer: TokenizerConfig) -> Self {
+        let synonyms = Synonyms::new(&options.synonyms);
+        let mut index = InvertedIndex::new().with_tokenizer_config(tokenizer);
+        if options.synonym_mode == SynonymMode::Index {
+            index = index.with_expansions(synonyms.reverse());
+        }
+        if options.file_index {
+            index = index.with_file_index();
+        }
+        if options.ascii_fold {
+            index = index.with_ascii_folding();
+        }
+        if let Some(n) = options.cjk_ngram.filter(|&n| n > 0) {
+            index = index.with_cjk_ngrams(n);
+        }
+        if options.split_identifiers {
+            index = index.with_identifier_splitting();
+        }
+        #[cfg(feature = "stemming")]
+        if let Some(language) = options.stemmer {
+            index = index.with_stemming(language);
+        }
 
-#[derive(Deserialize)]
-struct GrepParams {
-    pattern: String,
-    glob: Option<String>,
-}
+        let mut store = DocumentStore::with_index(index);
+        if options.fold_cache {
+            store = store.with_fold_cache();
+        }
+        if options.html_to_text {
+            store = store.with_html_to_text();
+        }
+        store = store.with_binary_policy(options.binary_policy);
+        store = store.with_duplicate_policy(options.duplicate_paths);
 
-#[derive(Deserialize)]
-struct ReadParams {
-    path: String,
-    offset: Option<u32>,
-    limit: Option<u32>,
-}
+        Self {
+            store,
+            synonyms,
+            synonym_mode: options.synonym_mode,
+            stop_words: options.stop_words.as_ref().map(StopWords::new).unwrap_or_default(),
+            recent: Mutex::default(),
+            patterns: PatternCache::default(),
+            cursors: Mutex::default(),
+            settings: Settings::default().with_options(options),
+            base: None,
+            whiteouts: BTreeSet::new(),
+        }
+    }
 
-#[derive(Deserialize)]
-struct LsParams {
-    path: String,
-}
+    /// A copy-on-write session over `base`, so each agent or user can get
+    /// a writable view of one shared corpus. Reads and queries see `base`
+    /// through this layer, which wins where it wrote or deleted a path;
+    /// writes and deletes only touch the layer, leaving `base` (and every
+    /// other session over it) as it was, and its documents are never
+    /// copied. Options and host settings are those of `base`.
+    /// `export_overlay` returns what the session changed.
+    pub fn overlay(base: Arc<MemexFsCore>) -> Self {
+        Self {
+            store: base.store.empty_like(),
+            synonyms: base.synonyms.clone(),
+            synonym_mode: base.synonym_mode,
+            stop_words: base.stop_words.clone(),
+            recent: Mutex::default(),
+            patterns: PatternCache::default(),
+            cursors: Mutex::default(),
+            settings: base.settings.clone(),
+            base: Some(base),
+            whiteouts: BTreeSet::new(),
+        }
+    }
 
-fn has_regex_metacharacters(pattern: &str) -> bool {
-    pattern.contains(|c: char| {
-        matches!(
-            c,
-            '|' | '*'
-                | '+'
-                | '?'
-                | '('
-                | ')'
-                | '['
-                | ']'
-                | '{'
-                | '}'
-                | '\\'
-                | '^'
-                | '$'
-                | '.'
-        )
-    })
-}
+    /// Index a batch of documents given as a JSON array of `[path, content]`
+    /// pairs or `{ path, content, metadata? }` objects, incrementally.
+    /// Paths are normalized (see `validate::normalize_path`), and a path
+    /// already loaded or repeated in the batch is handled by
+    /// `MemexOptions::duplicate_paths`. Returns the number of documents in
+    /// the batch.
+    pub fn add_documents(&mut self, docs_json: &str) -> Result<usize, MemexError> {
+        let docs: Vec<DocInput> = serde_json::from_str(docs_json)
+            .map_err(|e| MemexError::InvalidDocuments { reason: e.to_string() })?;
+        self.load_documents(docs)
+    }
 
-fn tool_definitions_json() -> serde_json::Value {
-    serde_json::json!([
-        {
-            "name": "grep",
-            "description": "Search for a pattern across all documents. Returns matching file paths, line numbers, and content. Use this to find relevant documents before reading them.",
-            "parameters": {
-                "pattern": { "type": "string", "description": "Search pattern (supports regex)" },
-                "glob": { "type": "string", "description": "Optional file pattern filter, e.g. 'billing/**/*.md'" }
-            },
-            "required": ["pattern"]
-        },
-        {
-            "name": "read",
-            "description": "Read the contents of a document. Returns the full document or a specific line range. Use this after grep to get the full context of a matching document.",
-            "parameters": {
-                "path": { "type": "string", "description": "Document path relative to the knowledge base root" },
-                "offset": { "type": "number", "description": "Line number to start reading from (1-indexed)" },
-                "limit": { "type": "number", "description": "Number of lines to return" }
-            },
-            "required": ["path"]
-        },
-        {
-            "name": "ls",
-            "description": "List the contents of a directory. Returns immediate children: file names and subdirectory names (with trailing '/'). Use this to explore the document structure before grepping or reading.",
-            "parameters": {
-                "path": { "type": "string", "description": "Directory path to list, e.g. 'account' or 'billing/invoices'. Use empty string or '.' for root." }
+    /// `add_documents` for documents already parsed, e.g. by a binding.
+    /// Fails on the first empty or invalid path, binary document under
+    /// `BinaryPolicy::Reject`, or duplicate path under
+    /// `DuplicatePolicy::Error`, before any is loaded.
+    pub fn load_documents(&mut self, docs: Vec<DocInput>) -> Result<usize, MemexError> {
+        let count = docs.len();
+        let docs = docs.into_iter().map(validate::normalize).collect::<Result<Vec<_>, _>>()?;
+        let docs = sniff::screen_all(docs, self.store.binary_policy())?;
+        let docs = validate::dedupe(docs, self.store.duplicate_policy(), |path| self.document(path).is_some())?;
+        self.store.load_documents(docs);
+        Ok(count)
+    }
+
+    /// Index what can be loaded of a JSON array of documents, checking
+    /// each entry on its own instead of failing the batch on the first
+    /// problem. The report lists entries that aren't documents, invalid
+    /// paths, empty content, duplicate paths (left out as `duplicate_paths`
+    /// says, the first kept under `DuplicatePolicy::Error`), documents over
+    /// `max_document_bytes`, and binary content (see `validate::check`).
+    /// Fails only if `docs_json` isn't an array.
+    pub fn add_documents_lenient(&mut self, docs_json: &str) -> Result<LoadReport, MemexError> {
+        let entries: Vec<serde_json::Value> = serde_json::from_str(docs_json)
+            .map_err(|e| MemexError::InvalidDocuments { reason: e.to_string() })?;
+        Ok(self.load_documents_lenient(entries))
+    }
+
+    /// `add_documents_lenient` for entries already parsed as JSON values.
+    pub fn load_documents_lenient(&mut self, entries: Vec<serde_json::Value>) -> LoadReport {
+        let max_bytes = self.settings.max_document_bytes;
+        let (docs, report) = validate::check(entries, &self.store, max_bytes, |path| self.document(path).is_some());
+        self.store.load_documents(docs);
+        report
+    }
+
+    /// `add_documents` for the same JSON compressed with gzip (or brotli,
+    /// with the `brotli` feature), decompressed here so a host only has to
+    /// hand over the smaller bytes.
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    pub fn add_documents_compressed(&mut self, bytes: &[u8]) -> Result<usize, MemexError> {
+        let json = String::from_utf8(compress::decompress(bytes)?)
+            .map_err(|e| MemexError::InvalidDocuments { reason: e.to_string() })?;
+        self.add_documents(&json)
+    }
+
+    /// Finish batch loading: fails if nothing was loaded, and releases the
+    /// spare capacity the index accumulated while growing.
+    pub fn finalize(&mut self) -> Result<(), MemexError> {
+        if self.document_count() == 0 {
+            return Err(MemexError::NoDocuments);
+        }
+        self.store.shrink_to_fit();
+        Ok(())
+    }
+
+    /// Serialize the documents, the built index, and the synonym and stop-word
+    /// settings into a compact binary snapshot for `from_snapshot`. Of an
+    /// overlay only the session's own documents are saved, without its
+    /// base or deletions; see `export_overlay` for those.
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        snapshot::encode(&snapshot::SnapshotRef {
+            unicode_folding: snapshot::UNICODE_FOLDING,
+            store: &self.store,
+            synonyms: &self.synonyms,
+            synonym_mode: self.synonym_mode,
+            stop_words: &self.stop_words,
+        })
+    }
+
+    /// Restore an instance from `to_snapshot` bytes without re-tokenizing.
+    pub fn from_snapshot(bytes: &[u8]) -> Result<Self, MemexError> {
+        let snapshot = snapshot::decode(bytes)?;
+        if snapshot.store.index().tokenizer().config.missing_split() {
+            return Err(MemexError::InvalidSnapshot {
+                reason: "snapshot was built with a custom split function; restore it with MemexFsBuilder::from_snapshot"
+                    .to_string(),
+            });
+        }
+        Ok(Self::restored(snapshot))
+    }
+
+    fn restored(snapshot: snapshot::Snapshot) -> Self {
+        Self {
+            store: snapshot.store,
+            synonyms: snapshot.synonyms,
+            synonym_mode: snapshot.synonym_mode,
+            stop_words: snapshot.stop_words,
+            recent: Mutex::default(),
+            patterns: PatternCache::default(),
+            cursors: Mutex::default(),
+            settings: Settings::default(),
+            base: None,
+            whiteouts: BTreeSet::new(),
+        }
+    }
+
+    pub fn grep(&self, pattern: &str, glob: Option<&str>) -> Result<Vec<GrepResult>, MemexError> {
+        let options = GrepOptions {
+            glob: glob.map(|g| g.to_string()),
+            ..Default::default()
+        };
+        self.grep_with_options(pattern, &options)
+    }
+
+    /// One page of matches (see `grep_page`).
+    pub fn grep_with_options(
+        &self,
+        pattern: &str,
+        options: &GrepOptions,
+    ) -> Result<Vec<GrepResult>, MemexError> {
+        self.grep_page(pattern, options).map(|page| page.results)
+    }
+
+    /// Matches `offset..offset + max_results` (default `0..100`) in path,
+    /// then line order, with the total match count for paging.
+    pub fn grep_page(
+        &self,
+        pattern: &str,
+        options: &GrepOptions,
+    ) -> Result<GrepResponse, MemexError> {
+        let options = &self.resolve(options, &[pattern]);
+        let mut pager = Pager::new(options, &self.patterns)?;
+        let locator = self.collect_layered(&mut pager, &|layer, pager| layer.collect_matches(pattern, options, pager))?;
+        Ok(self.finish_page(pager, &locator, options, &[pattern]))
+    }
+
+    /// Number of matching lines per document, sorted by path, without
+    /// building any results. Paging options are ignored.
+    pub fn grep_counts(
+        &self,
+        pattern: &str,
+        options: &GrepOptions,
+    ) -> Result<Vec<FileCount>, MemexError> {
+        let options = &self.resolve(options, &[pattern]);
+        let mut pager = Pager::counting(options, &self.patterns)?;
+        self.collect_layered(&mut pager, &|layer, pager| layer.collect_matches(pattern, options, pager))?;
+        Ok(pager.into_counts())
+    }
+
+    /// Begin a grep to be run in slices with `continue_grep`, so a host can
+    /// search a large corpus without blocking for the whole scan (e.g.
+    /// between animation frames, or in a Web Worker it can cancel). Returns
+    /// a handle. Options apply as in `grep_page`, except that results come
+    /// in path order (`sort` is ignored), `offset` and `timeout_ms` are
+    /// ignored, and `max_results` caps the total across all slices.
+    pub fn start_grep(&self, pattern: &str, options: &GrepOptions) -> Result<u32, MemexError> {
+        let options = GrepOptions {
+            sort: None,
+            offset: None,
+            timeout_ms: None,
+            ..options.clone()
+        };
+        // Surface pattern errors now rather than on the first slice
+        self.grep_slice(pattern, &options, String::new(), Some(String::new()))?;
+
+        let mut cursors = self.cursors.lock().unwrap();
+        let handle = cursors.next_handle;
+        cursors.next_handle = cursors.next_handle.wrapping_add(1);
+        cursors.active.insert(
+            handle,
+            GrepCursor {
+                pattern: pattern.to_string(),
+                remaining: options.max_results.or(self.settings.max_results).unwrap_or(DEFAULT_MAX_RESULTS),
+                options,
+                next: String::new(),
+                searched: 0,
             },
-            "required": ["path"]
+        );
+        Ok(handle)
+    }
+
+    /// Search further for the grep behind `handle`, a batch of documents
+    /// at a time until `budget_ms` has passed (at least one batch runs).
+    /// Once `done` is set the handle is released.
+    pub fn continue_grep(&self, handle: u32, budget_ms: u64) -> Result<GrepChunk, MemexError> {
+        let mut cursor = self
+            .cursors
+            .lock()
+            .unwrap()
+            .active
+            .remove(&handle)
+            .ok_or(MemexError::UnknownGrepHandle { handle })?;
+        let deadline = Deadline::after_ms(budget_ms);
+        let paths = self.paths();
+        let mut results = Vec::new();
+        let mut done = false;
+
+        while !done {
+            let start = paths.partition_point(|p| *p < cursor.next.as_str());
+            let end = paths.get(start + CURSOR_BATCH).map(|p| p.to_string());
+            let options = GrepOptions {
+                max_results: Some(cursor.remaining),
+                ..cursor.options.clone()
+            };
+            let page = self.grep_slice(&cursor.pattern, &options, std::mem::take(&mut cursor.next), end.clone())?;
+
+            cursor.searched += paths.len().min(start + CURSOR_BATCH) - start;
+            cursor.remaining -= page.results.len();
+            results.extend(page.results);
+            done = page.next_offset.is_some() || cursor.remaining == 0 || end.is_none();
+            cursor.next = end.unwrap_or_default();
+            if deadline.expired() {
+                break;
+            }
         }
-    ])
-}
 
-// ── Tests ──────────────────────────────────────────────────────────
-// Tests use MemexFsCore directly to avoid JsError on non-wasm targets.
+        let searched = cursor.searched;
+        if !done {
+            self.cursors.lock().unwrap().active.insert(handle, cursor);
+        }
+        Ok(GrepChunk {
+            results,
+            searched,
+            total_documents: paths.len(),
+            done,
+        })
+    }
+
+    /// Release a grep started by `start_grep` before it is done. Returns
+    /// `false` if `handle` is not active.
+    pub fn cancel_grep(&self, handle: u32) -> bool {
+        self.cursors.lock().unwrap().active.remove(&handle).is_some()
+    }
+
+    /// One page of matches in documents whose paths are in `[start, end)`.
+    fn grep_slice(
+        &self,
+        pattern: &str,
+        options: &GrepOptions,
+        start: String,
+        end: Option<String>,
+    ) -> Result<GrepResponse, MemexError> {
+        let options = &self.resolve(options, &[pattern]);
+        let mut pager = Pager::new(options, &self.patterns)?;
+        pager.scope = Some((start, end));
+        let locator = self.collect_layered(&mut pager, &|layer, pager| layer.collect_matches(pattern, options, pager))?;
+        // No suggestions: a slice with no matches says nothing of the rest
+        Ok(self.finish_page(pager, &locator, options, &[]))
+    }
+
+    /// Run the matching strategy for `pattern`, feeding every match to
+    /// `pager`. Returns a regex that locates the match within a line.
+    fn collect_matches(
+        &self,
+        pattern: &str,
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) -> Result<Pattern, MemexError> {
+        if pattern.is_empty() {
+            return Err(MemexError::EmptyPattern);
+        }
+
+        if options.mode == Some(GrepMode::AllTerms) {
+            let mut terms = query_words(pattern);
+            if options.stop_words.unwrap_or(false) {
+                terms = self.stop_words.filter(terms);
+            }
+            if terms.is_empty() {
+                return Err(MemexError::NoSearchTerms);
+            }
+            self.grep_all_terms(&terms, options, pager)?;
+            let alternation: Vec<String> = terms.iter().map(|t| regex::escape(t)).collect();
+            return options.build_regex(&self.patterns, &alternation.join("|"));
+        }
+
+        if options.mode == Some(GrepMode::Near) {
+            let (first, distance, second) = near_query(pattern).ok_or_else(|| {
+                MemexError::invalid_params("near mode expects `term NEAR/n term`")
+            })?;
+            self.grep_near([first, second], distance, options, pager)?;
+            return options.build_regex(&self.patterns, &format!("{}|{}", regex::escape(first), regex::escape(second)));
+        }
+
+        let fuzzy_word = pattern.chars().count() >= 3 && pattern.chars().all(char::is_alphanumeric);
+        if options.multiline.unwrap_or(false) {
+            let source = if options.literal.unwrap_or(false) {
+                regex::escape(pattern)
+            } else {
+                pattern.to_string()
+            };
+            let re = options.build_regex(&self.patterns, &format!("(?m){}", source))?;
+            self.grep_multiline(&re, options, pager);
+            return Ok(re);
+        }
+
+        if options.fuzzy.unwrap_or(false) && fuzzy_word {
+            let term = self.tokenizer().fold(pattern);
+            let index = self.store.index();
+            let tokens = index.fuzzy_tokens(&term, fuzzy_edits(term.chars().count()));
+            return self.grep_tokens(&tokens, options, pager);
+        }
+
+        if options.is_wildcard(pattern) {
+            let tokens = self.store.index().wildcard_tokens(&self.tokenizer().fold(pattern));
+            return self.grep_tokens(&tokens, options, pager);
+        }
+
+        if options.ignore_markdown.unwrap_or(false) {
+            let source = if options.is_regex(pattern) {
+                pattern.to_string()
+            } else {
+                options.flexible_phrase(pattern).unwrap_or_else(|| regex::escape(pattern))
+            };
+            let re = options.build_regex(&self.patterns, &source)?;
+            // No filter query: removing markup can join words into new tokens
+            self.scan_lines(options, None, pager, |doc, i| re.is_match(&markdown::plain(doc.line(i))));
+            return Ok(re);
+        }
+
+        if options.is_regex(pattern) {
+            self.grep_regex(&options.build_regex(&self.patterns, pattern)?, options, pager);
+        } else if let Some(phrase) = options.flexible_phrase(pattern) {
+            self.grep_regex(&options.build_regex(&self.patterns, &phrase)?, options, pager);
+        } else {
+            let pattern_lower = self.fold(pattern);
+            let is_single_token = pattern_lower.len() >= 3
+                && pattern_lower.chars().all(|c| c.is_alphanumeric());
+
+            let literal = options.literal.unwrap_or(false);
+            let indexed = self.tokenizer().indexes_words();
+            let cjk_grams = self.tokenizer().cjk_grams(&pattern_lower).filter(|_| indexed);
+
+            if options.is_case_sensitive()
+                || (options.is_whole_word() && (literal || !is_single_token || cjk_grams.is_some() || !indexed))
+            {
+                let re = options.build_regex(&self.patterns, &regex::escape(pattern))?;
+                self.grep_regex(&re, options, pager);
+            } else if !indexed {
+                self.grep_scan(&pattern_lower, options, pager);
+            } else if let Some(grams) = cjk_grams {
+                let candidates = self.cjk_candidates(&grams);
+                self.grep_verified(&pattern_lower, &candidates, options, pager);
+            } else if is_single_token && !literal {
+                let terms = match self.synonym_mode {
+                    SynonymMode::Query => self.synonyms.expand(&pattern_lower),
+                    SynonymMode::Index => vec![pattern_lower],
+                };
+                self.grep_index(&terms, options, pager);
+            } else if let Some(words) = phrase_words(&pattern_lower) {
+                let candidates = self.store.index().find_phrase(&words);
+                self.grep_verified(&pattern_lower, &candidates, options, pager);
+            } else if let Some(candidates) = self.literal_candidates(&pattern_lower) {
+                self.grep_verified(&pattern_lower, &candidates, options, pager);
+            } else {
+                self.grep_scan(&pattern_lower, options, pager);
+            }
+        }
+
+        self.match_locator(pattern, options)
+    }
+
+    /// Like `grep_page`, but a line matches if it contains any of
+    /// `patterns`, found in a single pass over the corpus. Patterns are
+    /// always literal strings and synonyms are not expanded.
+    pub fn grep_any_page(
+        &self,
+        patterns: &[String],
+        options: &GrepOptions,
+    ) -> Result<GrepResponse, MemexError> {
+        let options = &self.resolve(options, patterns);
+        let mut pager = Pager::new(options, &self.patterns)?;
+        let collect = |layer: &MemexFsCore, pager: &mut Pager| layer.collect_any_matches(patterns, options, pager);
+        let locator = self.collect_layered(&mut pager, &collect)?;
+        let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+        Ok(self.finish_page(pager, &locator, options, &patterns))
+    }
+
+    /// `grep_counts` for lines containing any of `patterns`.
+    pub fn grep_any_counts(
+        &self,
+        patterns: &[String],
+        options: &GrepOptions,
+    ) -> Result<Vec<FileCount>, MemexError> {
+        let options = &self.resolve(options, patterns);
+        let mut pager = Pager::counting(options, &self.patterns)?;
+        let collect = |layer: &MemexFsCore, pager: &mut Pager| layer.collect_any_matches(patterns, options, pager);
+        self.collect_layered(&mut pager, &collect)?;
+        Ok(pager.into_counts())
+    }
+
+    fn collect_any_matches(
+        &self,
+        patterns: &[String],
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) -> Result<Pattern, MemexError> {
+        if patterns.is_empty() || patterns.iter().any(|p| p.is_empty()) {
+            return Err(MemexError::EmptyPattern);
+        }
+
+        let alternation: Vec<String> = patterns.iter().map(|p| regex::escape(p)).collect();
+        let locator = options.build_regex(&self.patterns, &alternation.join("|"))?;
+
+        if options.is_whole_word() {
+            self.grep_regex(&locator, options, pager);
+        } else {
+            self.grep_multi(patterns, options, pager)?;
+        }
+        Ok(locator)
+    }
+
+    /// Run `collect` on this instance and, in an overlay, on each layer
+    /// below it, skipping there the paths decided by the layers above.
+    /// Returns this instance's locator.
+    fn collect_layered(
+        &self,
+        pager: &mut Pager,
+        collect: &dyn Fn(&MemexFsCore, &mut Pager) -> Result<Pattern, MemexError>,
+    ) -> Result<Pattern, MemexError> {
+        // The layers' matches only need putting back in path order when
+        // this layer holds a document that the base's could sort around
+        if self.base.is_some() && self.store.keys().any(|path| pager.matches_path(path)) {
+            pager.layered = true;
+        }
+        let locator = collect(self, pager)?;
+        if let Some(base) = &self.base {
+            let decided = self.store.keys().chain(self.whiteouts.iter().map(String::as_str));
+            pager.hidden.extend(decided.map(String::from));
+            base.collect_layered(pager, collect)?;
+        }
+        Ok(locator)
+    }
+
+    /// Turn collected matches into a response: rank if requested, then fill
+    /// in columns, spans, snippets, titles, metadata, and context using
+    /// `locator` to find the match within each line.
+    fn finish_page(
+        &self,
+        pager: Pager,
+        locator: &Pattern,
+        options: &GrepOptions,
+        patterns: &[&str],
+    ) -> GrepResponse {
+        let keeps_all = pager.keeps_all();
+        let Pager {
+            offset,
+            limit,
+            mut total,
+            mut results,
+            ranked,
+            max_per_file,
+            layered,
+            timed_out,
+            ..
+        } = pager;
+
+        if keeps_all {
+            if layered {
+                results.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+            }
+            if ranked {
+                results = self.rank(results, locator);
+            }
+            if let Some(cap) = max_per_file {
+                results = interleave_files(results, cap);
+                total = results.len();
+            }
+            results = results.into_iter().skip(offset).take(limit).collect();
+        }
+        let want_snippet = options.snippet.is_some() || options.snippet_radius.is_some();
+        let mode = options.snippet.unwrap_or_default();
+        let radius = options.snippet_radius.unwrap_or(snippet::DEFAULT_RADIUS);
+        for result in &mut results {
+            let content = &result.content;
+            if let Some(m) = locator.find(content) {
+                result.column = char_offset(content, m.start) + 1;
+                if want_snippet {
+                    result.snippet =
+                        Some(snippet::extract(content, m.start, m.end, radius, mode));
+                }
+            }
+            if options.spans.unwrap_or(false) {
+                let spans = locator
+                    .find_iter(content)
+                    .map(|m| (char_offset(content, m.start), char_offset(content, m.end)))
+                    .collect();
+                result.spans = Some(spans);
+            }
+        }
+
+        if options.with_title.unwrap_or(false) {
+            for result in &mut results {
+                result.title = self
+                    .document(&result.path)
+                    .and_then(|doc| doc.title())
+                    .map(|t| t.to_string());
+            }
+        }
+
+        if options.with_metadata.unwrap_or(false) {
+            for result in &mut results {
+                result.metadata = self
+                    .document(&result.path)
+                    .and_then(|doc| doc.metadata.clone());
+            }
+        }
+
+        self.attach_context(&mut results, options);
+
+        let end = offset + results.len();
+        let suggest = total == 0 && !timed_out && options.suggest.unwrap_or(false);
+        let suggestions = if suggest { self.suggest(patterns) } else { Vec::new() };
+        GrepResponse {
+            results,
+            total_matches: total,
+            // An empty page (`max_results: 0`) would hand back its own offset
+            next_offset: (end > offset && end < total).then_some(end),
+            timed_out,
+            suggestions,
+        }
+    }
+
+    /// Indexed words close to the words of `patterns` that are not in the
+    /// index, for a grep that found nothing. Words under three characters
+    /// get none.
+    fn suggest(&self, patterns: &[&str]) -> Vec<String> {
+        let mut suggestions: Vec<String> = Vec::new();
+        for word in patterns.iter().flat_map(|p| query_words(p)) {
+            let len = word.chars().count();
+            let term = self.fold(word);
+            if len < 3 || !self.indexes_word(word) || self.layers().any(|l| l.store.index().lookup(&term).is_some()) {
+                continue;
+            }
+            let indexes = self.layers().map(|l| l.store.index());
+            for token in indexes.flat_map(|index| index.suggestions(&term, fuzzy_edits(len), MAX_SUGGESTIONS)) {
+                if !suggestions.iter().any(|s| s == token) {
+                    suggestions.push(token.to_string());
+                }
+            }
+        }
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+
+    /// Sort results best first by `search::line_relevance`. The sort is
+    /// stable, so equal scores stay in path/line order.
+    fn rank(&self, results: Vec<GrepResult>, locator: &Pattern) -> Vec<GrepResult> {
+        let mut headings: HashMap<&str, HashMap<u32, u8>> = HashMap::new();
+        let mut scores = Vec::with_capacity(results.len());
+        for result in results.iter() {
+            let Some(doc) = self.document(&result.path) else {
+                scores.push(0.0);
+                continue;
+            };
+            let levels = headings.entry(result.path.as_str()).or_insert_with(|| {
+                doc.headings().into_iter().map(|h| (h.line, h.level)).collect()
+            });
+            let file_name = result.path.rsplit('/').next().unwrap_or(&result.path);
+            let body_line = (result.line as usize - 1).saturating_sub(doc.frontmatter_len);
+            scores.push(search::line_relevance(
+                levels.get(&result.line).copied(),
+                locator.is_match(file_name),
+                body_line,
+            ));
+        }
+
+        let mut scored: Vec<(f64, GrepResult)> = scores.into_iter().zip(results).collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Regex that finds where `pattern` matches within a line, treating it
+    /// as a literal (or one of its synonyms) unless it contains regex
+    /// metacharacters. Honors `case_sensitive` and `whole_word`.
+    fn match_locator(
+        &self,
+        pattern: &str,
+        options: &GrepOptions,
+    ) -> Result<Pattern, MemexError> {
+        let source = if options.is_regex(pattern) {
+            pattern.to_string()
+        } else if let Some(phrase) = options.flexible_phrase(pattern) {
+            phrase
+        } else if options.is_case_sensitive() || options.literal.unwrap_or(false) {
+            regex::escape(pattern)
+        } else {
+            let variants = self.layers().flat_map(|l| l.store.index().stem_variants(pattern));
+            let terms: Vec<String> = self
+                .synonyms
+                .expand(pattern)
+                .iter()
+                .map(String::as_str)
+                .chain(variants)
+                .map(regex::escape)
+                .collect();
+            terms.join("|")
+        };
+        options.build_regex(&self.patterns, &source)
+    }
+
+    /// Fill `context_before`/`context_after` on sorted results, sharing
+    /// `max_context_lines` between them round-robin.
+    fn attach_context(&self, results: &mut [GrepResult], options: &GrepOptions) {
+        let before = options.context_before.unwrap_or(0);
+        let after = options.context_after.unwrap_or(0);
+        if before == 0 && after == 0 {
+            return;
+        }
+
+        // Lines available on each side, clamped to the document and the
+        // frontmatter boundary unless frontmatter is searched.
+        let mut wanted: Vec<(usize, usize)> = results
+            .iter()
+            .map(|r| {
+                let Some(doc) = self.document(&r.path) else {
+                    return (0, 0);
+                };
+                let idx = (r.line - 1) as usize;
+                let end = r.last_line_index();
+                let first = if options.is_body_only() {
+                    doc.frontmatter_len.min(idx)
+                } else {
+                    0
+                };
+                let last = doc.line_count().saturating_sub(1);
+                (before.min(idx - first), after.min(last.saturating_sub(end)))
+            })
+            .collect();
+
+        if let Some(budget) = options.max_context_lines {
+            let mut granted = vec![(0, 0); wanted.len()];
+            let mut remaining = budget;
+            'rounds: for round in 1..=before.max(after) {
+                for (g, w) in granted.iter_mut().zip(&wanted) {
+                    for (grant, want) in [(&mut g.0, w.0), (&mut g.1, w.1)] {
+                        if round <= want {
+                            if remaining == 0 {
+                                break 'rounds;
+                            }
+                            *grant += 1;
+                            remaining -= 1;
+                        }
+                    }
+                }
+            }
+            wanted = granted;
+        }
+
+        for (result, (b, a)) in results.iter_mut().zip(wanted) {
+            let Some(doc) = self.document(&result.path) else {
+                continue;
+            };
+            let idx = (result.line - 1) as usize;
+            let end = result.last_line_index();
+            let line_at = |i: usize| ContextLine {
+                line: (i + 1) as u32,
+                content: doc.line(i).to_string(),
+            };
+            result.context_before = (idx - b..idx).map(line_at).collect();
+            result.context_after = (end + 1..=end + a).map(line_at).collect();
+        }
+    }
+
+    /// `grep_with_options` with results encoded as MessagePack instead of
+    /// JSON, for native callers exchanging many results. Decode with
+    /// `decode_grep_results`.
+    #[cfg(feature = "binary")]
+    pub fn grep_binary(
+        &self,
+        pattern: &str,
+        options: &GrepOptions,
+    ) -> Result<Vec<u8>, MemexError> {
+        let results = self.grep_with_options(pattern, options)?;
+        // Named (map) encoding so optional fields can be omitted
+        rmp_serde::to_vec_named(&results).map_err(MemexError::encoding)
+    }
+
+    /// Fast path: scan inverted index tokens for substring match.
+    /// Used for single alphanumeric patterns (≥3 chars) where the index
+    /// is much smaller than the total line count. Lines matching any of
+    /// `terms` (the pattern plus query-time synonyms) are returned; with
+    /// `whole_word` only exact tokens count.
+    fn grep_index(
+        &self,
+        terms: &[String],
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) {
+        let index = self.store.index();
+        let mut locations: BTreeSet<(DocId, u32)> = if options.is_whole_word() {
+            terms
+                .iter()
+                .filter_map(|t| index.lookup(t))
+                .flat_map(|postings| postings.iter().copied())
+                .collect()
+        } else {
+            terms.iter().flat_map(|t| index.find_containing(t)).collect()
+        };
+        let variants = terms.iter().flat_map(|t| index.stem_variants(t));
+        let variants: Vec<_> = variants.filter_map(|t| index.lookup(t)).collect();
+        locations.extend(variants.into_iter().flatten().copied());
+        self.push_locations(&locations, options, pager);
+    }
+
+    /// Token path: lines containing any of the exact index `tokens`, for
+    /// queries resolved against the token dictionary (fuzzy, wildcard).
+    /// Returns a locator for those tokens.
+    fn grep_tokens(
+        &self,
+        tokens: &[&str],
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) -> Result<Pattern, MemexError> {
+        let index = self.store.index();
+        let locations = tokens.iter().filter_map(|t| index.lookup(t)).flatten();
+        self.push_locations(locations, options, pager);
+
+        let alternation: Vec<String> = tokens.iter().map(|t| regex::escape(t)).collect();
+        self.patterns.get(&format!(r"\b(?:{})\b", alternation.join("|")), true)
+    }
+
+    /// Feed index postings to `pager` in (path, line) order, applying the
+    /// path, metadata, and line filters.
+    fn push_locations<'a>(
+        &self,
+        locations: impl IntoIterator<Item = &'a (DocId, u32)>,
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) {
+        for (path, line_num) in self.store.resolve(locations) {
+            if !pager.matches_path(path) {
+                continue;
+            }
+            if let Some(doc) = self.store.get_document(path).filter(|d| options.matches_doc(d)) {
+                let idx = (line_num - 1) as usize;
+                if idx < doc.line_count() && !pager.skips_line(doc, idx) {
+                    pager.push(path, || GrepResult::new(path.to_string(), line_num, doc.line(idx).to_string()));
+                }
+            }
+        }
+    }
+
+    /// Index+verify path: candidate lines come from the index (token
+    /// positions for a phrase, or `literal_candidates`) and are confirmed
+    /// with a substring check, so results match `grep_scan` without
+    /// reading every line.
+    fn grep_verified<'a>(
+        &self,
+        pattern_lower: &str,
+        candidates: impl IntoIterator<Item = &'a (DocId, u32)>,
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) {
+        for (n, (path, line_num)) in self.store.resolve(candidates).into_iter().enumerate() {
+            if n % DEADLINE_STRIDE == 0 && pager.out_of_time() {
+                break;
+            }
+            if !pager.matches_path(path) {
+                continue;
+            }
+            if let Some(doc) = self.store.get_document(path).filter(|d| options.matches_doc(d)) {
+                let idx = (line_num - 1) as usize;
+                if !pager.skips_line(doc, idx) && doc.line_contains_folded(idx, pattern_lower, self.folding()) {
+                    pager.push(path, || GrepResult::new(path.to_string(), line_num, doc.line(idx).to_string()));
+                }
+            }
+        }
+    }
+
+    /// Lines that may contain the folded literal `pattern_lower`, from the
+    /// index postings of its rarest word that must be a whole token (one
+    /// with punctuation on both sides, like `reset` in `-reset-`), or
+    /// failing that the tokens containing its longest edge word, e.g.
+    /// `password` in `password:`. `None` if no word is usable and the
+    /// pattern needs a full scan.
+    fn literal_candidates(&self, pattern_lower: &str) -> Option<Vec<(DocId, u32)>> {
+        let index = self.store.index();
+        let pieces: Vec<&str> = pattern_lower.split(|c: char| !c.is_alphanumeric()).collect();
+        let last = pieces.len() - 1;
+        let whole = pieces[1..last.max(1)].iter().filter(|w| !w.is_empty());
+        if let Some(rarest) = whole.min_by_key(|w| index.lookup(w).map_or(0, |p| p.len())) {
+            return Some(index.lookup(rarest).cloned().unwrap_or_default());
+        }
+
+        let edge = [pieces[0], pieces[last]]
+            .into_iter()
+            .max_by_key(|w| w.chars().count())
+            .filter(|w| w.chars().count() >= 3)?;
+        Some(index.find_containing(edge))
+    }
+
+    /// Lines that may contain a literal with the CJK n-grams `grams`
+    /// (`Tokenizer::cjk_grams`): those holding them at consecutive
+    /// positions, or for a run shorter than an n-gram, the lines of every
+    /// token containing it.
+    fn cjk_candidates(&self, grams: &[String]) -> Vec<(DocId, u32)> {
+        let index = self.store.index();
+        let n = self.tokenizer().cjk_ngram.unwrap_or(0);
+        match grams {
+            [run] if run.chars().count() < n => index.find_containing(run),
+            _ => index.find_phrase(&grams.iter().map(String::as_str).collect::<Vec<_>>()),
+        }
+    }
+
+    /// Scan case-folded lines. Used for short or punctuated patterns
+    /// with no word the index can narrow down.
+    fn grep_scan(
+        &self,
+        pattern_lower: &str,
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) {
+        let query = FilterQuery::literal(pattern_lower);
+        let folding = self.folding();
+        self.scan_lines(options, Some(&query), pager, |doc, i| doc.line_contains_folded(i, pattern_lower, folding));
+    }
+
+    /// Shared body of the line-scanning paths: each line accepted by
+    /// `matches`, in a document passing `options` and the `query` filter,
+    /// goes to `pager` in path and line order. With the `rayon` feature,
+    /// documents are scanned in parallel.
+    fn scan_lines(
+        &self,
+        options: &GrepOptions,
+        query: Option<&FilterQuery>,
+        pager: &mut Pager,
+        matches: impl Fn(&Document, usize) -> bool + Sync,
+    ) {
+        let docs: Vec<(&str, &Document)> = self
+            .store
+            .paths()
+            .into_iter()
+            .filter(|path| pager.matches_path(path) && query.is_none_or(|q| self.store.may_match(path, q)))
+            .filter_map(|path| Some((path, self.store.get_document(path).filter(|d| options.matches_doc(d))?)))
+            .collect();
+
+        // Each document's matching lines, and whether it was scanned to
+        // the end before the deadline
+        let shared = &*pager;
+        let scan = |&(_, doc): &(&str, &Document)| -> (Vec<usize>, bool) {
+            let mut hits = Vec::new();
+            for i in 0..doc.line_count() {
+                if i % DEADLINE_STRIDE == 0 && shared.expired() {
+                    return (hits, false);
+                }
+                if !shared.skips_line(doc, i) && matches(doc, i) {
+                    hits.push(i);
+                }
+            }
+            (hits, true)
+        };
+        #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+        let hits: Vec<(Vec<usize>, bool)> = {
+            use rayon::prelude::*;
+            docs.par_iter().map(scan).collect()
+        };
+        #[cfg(not(all(feature = "rayon", not(target_arch = "wasm32"))))]
+        let hits: Vec<(Vec<usize>, bool)> = docs.iter().map(scan).collect();
+
+        for ((path, doc), (lines, complete)) in docs.iter().zip(hits) {
+            pager.timed_out |= !complete;
+            for i in lines {
+                pager.push(path, || GrepResult::new(path.to_string(), (i + 1) as u32, doc.line(i).to_string()));
+            }
+        }
+    }
+
+    /// All-terms path: candidates come from intersecting each term's index
+    /// postings (lines, or documents with `within: document`), then every
+    /// term is verified with a regex honoring `case_sensitive` and
+    /// `whole_word`.
+    fn grep_all_terms(
+        &self,
+        terms: &[&str],
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) -> Result<(), MemexError> {
+        let checks = terms
+            .iter()
+            .map(|t| options.build_regex(&self.patterns, &regex::escape(t)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let index = self.store.index();
+        let mut postings: Vec<BTreeSet<(DocId, u32)>> = terms
+            .iter()
+            .map(|t| index.find_containing(&index.tokenizer().normalize(t)).into_iter().collect())
+            .collect();
+        postings.sort_by_key(|p| p.len());
+        let (rarest, rest) = postings.split_first().expect("at least one term");
+
+        match options.within.unwrap_or_default() {
+            TermsWithin::Line => {
+                let shared = rarest.iter().filter(|l| rest.iter().all(|p| p.contains(l)));
+                for (path, line_num) in self.store.resolve(shared) {
+                    if !pager.matches_path(path) {
+                        continue;
+                    }
+                    let Some(doc) = self.store.get_document(path).filter(|d| options.matches_doc(d)) else {
+                        continue;
+                    };
+                    let idx = (line_num - 1) as usize;
+                    let line = doc.line(idx);
+                    if !pager.skips_line(doc, idx) && checks.iter().all(|re| re.is_match(line)) {
+                        pager.push(path, || GrepResult::new(path.to_string(), line_num, line.to_string()));
+                    }
+                }
+            }
+            TermsWithin::Document => {
+                let files = |p: &BTreeSet<(DocId, u32)>| -> BTreeSet<DocId> {
+                    p.iter().map(|(doc, _)| *doc).collect()
+                };
+                let mut candidates = files(rarest);
+                for p in rest {
+                    let other = files(p);
+                    candidates.retain(|doc| other.contains(doc));
+                }
+                let candidates: BTreeSet<&str> = candidates.iter().map(|&id| self.store.path_of(id)).collect();
+
+                for path in candidates {
+                    if !pager.matches_path(path) {
+                        continue;
+                    }
+                    let Some(doc) = self.store.get_document(path).filter(|d| options.matches_doc(d)) else {
+                        continue;
+                    };
+                    let body: Vec<(usize, &str)> = doc
+                        .lines()
+                        .enumerate()
+                        .filter(|(i, _)| !pager.skips_line(doc, *i))
+                        .collect();
+                    if !checks.iter().all(|re| body.iter().any(|(_, line)| re.is_match(line))) {
+                        continue;
+                    }
+                    for (i, line) in body {
+                        if checks.iter().any(|re| re.is_match(line)) {
+                            pager.push(path, || GrepResult::new(path.to_string(), (i + 1) as u32, line.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Proximity path: token positions of both terms come from the index
+    /// and pairs at most `distance` tokens apart match. Within a line by
+    /// default; with `within: document` positions are offset by the tokens
+    /// on earlier lines so windows can span lines. Term lines are checked
+    /// with a regex honoring `case_sensitive` and `whole_word`.
+    fn grep_near(
+        &self,
+        terms: [&str; 2],
+        distance: u32,
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) -> Result<(), MemexError> {
+        let index = self.store.index();
+        let mut occurrences = Vec::with_capacity(2);
+        for term in terms {
+            let check = options.build_regex(&self.patterns, &regex::escape(term))?;
+            let mut lines = index.term_positions(term, options.is_whole_word());
+            lines.retain(|(doc, line), _| {
+                let path = self.store.path_of(*doc);
+                pager.matches_path(path)
+                    && self
+                        .store
+                        .get_document(path)
+                        .filter(|d| options.matches_doc(d))
+                        .is_some_and(|doc| {
+                            let idx = (*line - 1) as usize;
+                            !pager.skips_line(doc, idx) && check.is_match(doc.line(idx))
+                        })
+            });
+            occurrences.push(lines);
+        }
+        let (a, b) = (&occurrences[0], &occurrences[1]);
+        let close = |x: u32, y: u32| x != y && x.abs_diff(y) <= distance;
+
+        let mut hits: BTreeSet<(&str, u32)> = BTreeSet::new();
+        match options.within.unwrap_or_default() {
+            TermsWithin::Line => {
+                for (location, xs) in a {
+                    let Some(ys) = b.get(location) else {
+                        continue;
+                    };
+                    if xs.iter().any(|&x| ys.iter().any(|&y| close(x, y))) {
+                        hits.insert((self.store.path_of(location.0), location.1));
+                    }
+                }
+            }
+            TermsWithin::Document => {
+                let docs: BTreeSet<DocId> = a.keys().map(|(doc, _)| *doc).collect();
+                for id in docs {
+                    if !b.keys().any(|(d, _)| *d == id) {
+                        continue;
+                    }
+                    let path = self.store.path_of(id);
+                    let Some(doc) = self.store.get_document(path) else {
+                        continue;
+                    };
+                    // Document-wide position of each line's first token
+                    let mut offsets = Vec::with_capacity(doc.line_count());
+                    let mut total = 0;
+                    for line in doc.lines() {
+                        offsets.push(total);
+                        total += self.tokenizer().tokenize(line).len() as u32;
+                    }
+                    let spread = |lines: &HashMap<(DocId, u32), Vec<u32>>| -> Vec<(u32, u32)> {
+                        lines
+                            .iter()
+                            .filter(|((d, _), _)| *d == id)
+                            .flat_map(|((_, line), positions)| {
+                                let offset = offsets[(*line - 1) as usize];
+                                positions.iter().map(move |pos| (offset + pos, *line))
+                            })
+                            .collect()
+                    };
+                    let (xs, ys) = (spread(a), spread(b));
+                    for &(x, line_x) in &xs {
+                        for &(y, line_y) in &ys {
+                            if close(x, y) {
+                                hits.insert((path, line_x));
+                                hits.insert((path, line_y));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (path, line_num) in hits {
+            if let Some(doc) = self.store.get_document(path) {
+                let line = doc.line((line_num - 1) as usize);
+                pager.push(path, || GrepResult::new(path.to_string(), line_num, line.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Multi-pattern path: one Aho-Corasick automaton over every pattern,
+    /// run against case-folded lines unless `case_sensitive` is set.
+    fn grep_multi(
+        &self,
+        patterns: &[String],
+        options: &GrepOptions,
+        pager: &mut Pager,
+    ) -> Result<(), MemexError> {
+        let case_sensitive = options.is_case_sensitive();
+        let needles: Vec<String> = if case_sensitive {
+            patterns.to_vec()
+        } else {
+            patterns.iter().map(|p| self.fold(p)).collect()
+        };
+        let automaton = AhoCorasick::new(&needles).map_err(MemexError::encoding)?;
+        let folding = self.folding();
+
+        self.scan_lines(options, None, pager, |doc, i| {
+            let line = if case_sensitive { Cow::Borrowed(doc.line(i)) } else { doc.folded_line(i, folding) };
+            automaton.is_match(line.as_ref())
+        });
+        Ok(())
+    }
+
+    /// Multiline path: run `re` over each document's full text and report
+    /// every match by the lines it covers. A match starting on a line
+    /// already covered by the previous result is skipped, so results never
+    /// overlap; the line filters apply to the first line.
+    fn grep_multiline(&self, re: &Pattern, options: &GrepOptions, pager: &mut Pager) {
+        let query = re.filter_query();
+        for path in self.store.paths() {
+            if !pager.matches_path(path) || !self.store.may_match(path, &query) {
+                continue;
+            }
+            if pager.out_of_time() {
+                break;
+            }
+            let Some(doc) = self.store.get_document(path).filter(|d| options.matches_doc(d)) else {
+                continue;
+            };
+            let lines: Vec<&str> = doc.lines().collect();
+            if lines.is_empty() {
+                // No line for an empty-text match to land on
+                continue;
+            }
+            let text = lines.join("\n");
+            // Byte offset where each line starts
+            let mut starts = Vec::with_capacity(lines.len());
+            let mut offset = 0;
+            for line in &lines {
+                starts.push(offset);
+                offset += line.len() + 1;
+            }
+            let line_of = |byte: usize| starts.partition_point(|&s| s <= byte) - 1;
+
+            let mut covered: Option<usize> = None;
+            for m in re.find_iter(&text) {
+                let first = line_of(m.start);
+                if covered.is_some_and(|last| first <= last) || pager.skips_line(doc, first) {
+                    continue;
+                }
+                // A match ending with a line break does not cover the next line
+                let last = line_of(m.end.saturating_sub(1).max(m.start));
+                covered = Some(last);
+                pager.push(path, || {
+                    let mut result = GrepResult::new(
+                        path.to_string(),
+                        (first + 1) as u32,
+                        lines[first..=last].join("\n"),
+                    );
+                    result.line_count = Some((last - first + 1) as u32);
+                    result
+                });
+            }
+        }
+    }
+
+    /// Regex path: scan all lines with a compiled pattern.
+    fn grep_regex(&self, re: &Pattern, options: &GrepOptions, pager: &mut Pager) {
+        let query = re.filter_query();
+        self.scan_lines(options, Some(&query), pager, |doc, i| re.is_match(doc.line(i)));
+    }
+
+    pub fn read(
+        &self,
+        path: &str,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<String, MemexError> {
+        let options = ReadOptions {
+            offset,
+            limit,
+            ..Default::default()
+        };
+        self.read_with_options(path, &options)
+    }
+
+    pub fn read_with_options(
+        &self,
+        path: &str,
+        options: &ReadOptions,
+    ) -> Result<String, MemexError> {
+        let doc = self.read_document(path)?;
+        check_section(doc, options)?;
+
+        Ok(doc.read_with_options(options))
+    }
+
+    /// Look up a document for reading, recording it in the recency list.
+    fn read_document(&self, path: &str) -> Result<&Document, MemexError> {
+        let doc = self.document(path).ok_or_else(|| self.not_found(path))?;
+
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|p| p != path);
+        recent.push_front(path.to_string());
+        recent.truncate(RECENT_CAPACITY);
+
+        Ok(doc)
+    }
+
+    /// Error for a missing `path`, suggesting the closest existing paths.
+    fn not_found(&self, path: &str) -> MemexError {
+        let suggestions = store::similar_paths(self.visible_paths(), path, 3);
+        MemexError::DocumentNotFound { path: path.to_string(), suggestions }
+    }
+
+    /// The last `n` distinct documents read this session (via `read`,
+    /// `read_lines`, or `read_focused`), most recent first.
+    pub fn recently_read(&self, n: usize) -> Vec<String> {
+        self.recent.lock().unwrap().iter().take(n).cloned().collect()
+    }
+
+    /// Like `read_with_options`, but returns `{number, text}` entries instead
+    /// of a single numbered string.
+    pub fn read_lines(
+        &self,
+        path: &str,
+        options: &ReadOptions,
+    ) -> Result<Vec<NumberedLine>, MemexError> {
+        let doc = self.read_document(path)?;
+        check_section(doc, options)?;
+
+        Ok(doc.read_lines(options))
+    }
+
+    /// The document's markdown headings in order, with levels and line
+    /// numbers, for choosing a `read` offset in a long document.
+    pub fn outline(&self, path: &str) -> Result<Vec<Heading>, MemexError> {
+        let doc = self.document(path).ok_or_else(|| self.not_found(path))?;
+        Ok(doc.headings())
+    }
+
+    /// Rows of the CSV or TSV document at `path` that pass `query`'s
+    /// filters, with the columns it selects (see `TableQuery`).
+    pub fn query_table(&self, path: &str, query: &TableQuery) -> Result<TableRows, MemexError> {
+        let doc = self.document(path).ok_or_else(|| self.not_found(path))?;
+        table::query(doc, query)
+    }
+
+    /// The value at `pointer` in the `.json` document at `path`, so one
+    /// setting can be pulled from a large file without reading all of it.
+    /// `pointer` is a JSON Pointer (`/server/port`) or a simple JSONPath
+    /// (`$.server.hosts[0]`, `$.plans[-1]`); empty for the whole document.
+    pub fn read_json(&self, path: &str, pointer: &str) -> Result<serde_json::Value, MemexError> {
+        let doc = self.document(path).ok_or_else(|| self.not_found(path))?;
+        jsonpath::value_at(doc, pointer)
+    }
+
+    /// The document's heading outline followed by the line-numbered content
+    /// of just the section under `heading`, so a model gets both a map and
+    /// the relevant detail in one call.
+    pub fn read_focused(&self, path: &str, heading: &str) -> Result<String, MemexError> {
+        let doc = self.read_document(path)?;
+        let range = doc.section_range(heading).ok_or_else(|| {
+            MemexError::SectionNotFound { path: path.to_string(), heading: heading.to_string() }
+        })?;
+
+        let mut out = String::from("Outline:\n");
+        for h in doc.headings() {
+            let level = h.level as usize;
+            out.push_str(&format!(
+                "{}{} {} (line {})\n",
+                "  ".repeat(level - 1),
+                "#".repeat(level),
+                h.text,
+                h.line
+            ));
+        }
+        out.push('\n');
+        out.push_str(&doc.read_with_options(&ReadOptions {
+            offset: Some(range.start + 1),
+            limit: Some(range.len()),
+            ..Default::default()
+        }));
+        Ok(out)
+    }
+
+    pub fn ls(&self, path: &str) -> Vec<String> {
+        store::ls(self.visible_paths(), path)
+    }
+
+    /// Sorted paths of every document matching the glob `pattern`, e.g.
+    /// `billing/**/*invoice*.md`, without looking at content.
+    pub fn glob(&self, pattern: &str) -> Vec<String> {
+        self.paths()
+            .into_iter()
+            .filter(|path| glob_match::glob_match(pattern, path))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// `ls` with each file's metadata attached. Directories never carry
+    /// metadata.
+    pub fn ls_with_metadata(&self, path: &str) -> Vec<LsEntry> {
+        self.ls_detailed(path)
+            .into_iter()
+            .map(|entry| LsEntry { name: entry.name, metadata: entry.metadata })
+            .collect()
+    }
+
+    /// `ls` as structured entries: files with their line count, size, and
+    /// metadata, directories with how many documents they contain, so an
+    /// agent can decide where to explore.
+    pub fn ls_detailed(&self, path: &str) -> Vec<LsDetail> {
+        let prefix = store::normalize_dir(path);
+        let mut doc_counts: HashMap<&str, usize> = HashMap::new();
+        for doc_path in self.visible_paths() {
+            if let Some((dir, _)) = doc_path.strip_prefix(&prefix).and_then(|r| r.split_once('/')) {
+                *doc_counts.entry(dir).or_default() += 1;
+            }
+        }
+
+        self.ls(path)
+            .into_iter()
+            .map(|name| match name.strip_suffix('/') {
+                Some(dir) => LsDetail {
+                    kind: NodeKind::Dir,
+                    lines: None,
+                    bytes: None,
+                    children: doc_counts.get(dir).copied(),
+                    metadata: None,
+                    name,
+                },
+                None => {
+                    let doc = self.document(&format!("{}{}", prefix, name));
+                    LsDetail {
+                        kind: NodeKind::File,
+                        lines: doc.map(|d| d.line_count()),
+                        bytes: doc.map(|d| d.byte_len()),
+                        children: None,
+                        metadata: doc.and_then(|d| d.metadata.clone()),
+                        name,
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Sorted paths of every document with at least one line matching
+    /// `pattern` (same matching rules as `grep`, but uncapped). Single-word
+    /// patterns are answered from the index without touching lines.
+    pub fn grep_files(&self, pattern: &str, glob: Option<&str>) -> Result<Vec<String>, MemexError> {
+        if pattern.is_empty() {
+            return Err(MemexError::EmptyPattern);
+        }
+        let glob = glob.or(self.settings.glob.as_deref());
+        let re = if has_regex_metacharacters(pattern) {
+            Some(self.patterns.get(pattern, true)?)
+        } else {
+            None
+        };
+        let mut paths = self.layered(&|layer| layer.files_containing(pattern, re.as_ref(), glob), String::as_str);
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// `grep_files` among this layer's documents, with `re` compiled from
+    /// `pattern` if it is a regex.
+    fn files_containing(&self, pattern: &str, re: Option<&Pattern>, glob: Option<&str>) -> Vec<String> {
+        let in_glob = |path: &str| glob.is_none_or(|g| glob_match::glob_match(g, path));
+        let pattern_lower = self.fold(pattern);
+
+        // An alphanumeric term can only occur inside a single token, so
+        // token-level membership is exact.
+        if re.is_none() && self.indexes_word(&pattern_lower) {
+            let docs = self.store.index().find_files_containing(&pattern_lower);
+            let paths: BTreeSet<&str> = docs.into_iter().map(|id| self.store.path_of(id)).collect();
+            return paths.into_iter().filter(|p| in_glob(p)).map(String::from).collect();
+        }
+
+        self.store
+            .paths()
+            .into_iter()
+            .filter(|path| in_glob(path))
+            .filter(|path| {
+                self.store.get_document(path).is_some_and(|doc| match re {
+                    Some(re) => doc.lines().any(|l| re.is_match(l)),
+                    None => doc.contains_folded(&pattern_lower, self.folding()),
+                })
+            })
+            .map(|path| path.to_string())
+            .collect()
+    }
+
+    /// Paths matching `glob` whose content never contains `term`
+    /// (case-insensitive substring), e.g. billing docs lacking a required
+    /// clause. Returned sorted.
+    pub fn missing_in_glob(&self, term: &str, glob: &str) -> Result<Vec<String>, MemexError> {
+        if term.is_empty() {
+            return Err(MemexError::EmptyPattern);
+        }
+        let term_lower = self.fold(term);
+        let mut paths = self.layered(&|layer| layer.files_lacking(&term_lower, glob), String::as_str);
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// `missing_in_glob` among this layer's documents, for the folded
+    /// `term_lower`.
+    fn files_lacking(&self, term_lower: &str, glob: &str) -> Vec<String> {
+        if self.indexes_word(term_lower) {
+            let present = self.store.index().find_files_containing(term_lower);
+            let is_present = |path: &str| self.store.doc_id(path).is_some_and(|id| present.contains(&id));
+            return self
+                .store
+                .paths()
+                .into_iter()
+                .filter(|path| glob_match::glob_match(glob, path) && !is_present(path))
+                .map(|path| path.to_string())
+                .collect();
+        }
+
+        self.store
+            .paths()
+            .into_iter()
+            .filter(|path| glob_match::glob_match(glob, path))
+            .filter(|path| {
+                self.store
+                    .get_document(path)
+                    .is_some_and(|doc| !doc.contains_folded(term_lower, self.folding()))
+            })
+            .map(|path| path.to_string())
+            .collect()
+    }
+
+    /// Other documents containing `token`, provided `token` also appears in
+    /// `path`. Matching is on whole index tokens (case-insensitive); returns
+    /// sorted paths, or an empty list if `path` does not contain the token.
+    pub fn co_occurring(&self, path: &str, token: &str) -> Vec<String> {
+        let mut paths = self.layered(&|layer| layer.posted_paths(token), |p| *p);
+        if !paths.contains(&path) {
+            return Vec::new();
+        }
+        paths.sort();
+        paths.into_iter().filter(|p| *p != path).map(String::from).collect()
+    }
+
+    /// Sorted paths of this layer's documents with `token` in the index.
+    fn posted_paths(&self, token: &str) -> Vec<&str> {
+        let postings = self.store.index().lookup(token).into_iter().flatten();
+        let paths: BTreeSet<&str> = postings.map(|(d, _)| self.store.path_of(*d)).collect();
+        paths.into_iter().collect()
+    }
+
+    /// Documents containing every token of `query`, ranked by how closely
+    /// the tokens occur: the smallest window of consecutive document tokens
+    /// containing all of them, narrowest first (ties by path). Candidates
+    /// come from intersecting the index postings.
+    pub fn rank_by_proximity(&self, query: &str) -> Vec<String> {
+        let tokenizer = self.tokenizer();
+        let mut terms = tokenizer.query_tokens(query);
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        // Documents that contain all terms
+        let mut candidates: Option<BTreeSet<&str>> = None;
+        for term in &terms {
+            let docs: BTreeSet<&str> = self.layered(&|layer| layer.posted_paths(term), |p| *p).into_iter().collect();
+            candidates = Some(match candidates {
+                Some(c) => c.intersection(&docs).copied().collect(),
+                None => docs,
+            });
+        }
+
+        let mut ranked: Vec<(usize, &str)> = candidates
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|path| {
+                let doc = self.document(path)?;
+                min_span(doc, &terms, tokenizer).map(|span| (span, path))
+            })
+            .collect();
+        ranked.sort();
+        ranked.into_iter().map(|(_, p)| p.to_string()).collect()
+    }
+
+    /// Documents ranked by BM25 relevance to the words of `query`, best
+    /// first, each with the line matching the most query words as a snippet.
+    /// Words are matched as whole tokens, case-insensitively, together with
+    /// their configured synonyms (counted as the word itself). In an
+    /// overlay, document counts, lengths, and frequencies are taken over
+    /// every visible document, whichever layer it lives in.
+    pub fn search(&self, query: &str, limit: Option<usize>) -> Vec<SearchResult> {
+        let index = self.store.index();
+        // With stemming, words are compared by stem
+        let key = |token: String| index.stem(&token).unwrap_or(token);
+        let mut words = self.stop_words.filter(self.tokenizer().query_tokens(query));
+        words.sort_by_cached_key(|w| key(w.clone()));
+        words.dedup_by_key(|w| key(w.clone()));
+
+        let hits = self.layered(&|layer| layer.search_layer(&words), |(r, _, _)| r.path.as_str());
+        let stats = self.corpus_stats();
+        let idfs: Vec<f64> = (0..words.len())
+            .map(|t| search::idf(stats, hits.iter().filter(|(_, tf, _)| tf[t] > 0).count()))
+            .collect();
+        let mut results: Vec<SearchResult> = hits
+            .into_iter()
+            .map(|(result, tf, doc_len)| {
+                let score = tf
+                    .iter()
+                    .zip(&idfs)
+                    .map(|(&tf, &idf)| search::term_score(stats, idf, tf, doc_len))
+                    .sum();
+                SearchResult { score, ..result }
+            })
+            .collect();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+        results.truncate(limit.unwrap_or(DEFAULT_SEARCH_LIMIT));
+        results
+    }
+
+    /// Number and average token length of the visible documents, for
+    /// scoring `search` results.
+    fn corpus_stats(&self) -> search::CorpusStats {
+        if self.base.is_none() {
+            return search::CorpusStats {
+                docs: self.store.document_count(),
+                avg_len: self.store.index().avg_doc_len(),
+            };
+        }
+        let (docs, total_len) = self.visible_paths().fold((0, 0), |(docs, len), path| {
+            // The layer holding the document has its length
+            let layer = self.layers().find(|layer| layer.hides(path));
+            let doc_len = layer.and_then(|l| Some(l.store.index().doc_len(l.store.doc_id(path)?)));
+            (docs + 1, len + doc_len.unwrap_or(0))
+        });
+        let avg_len = if docs == 0 { 0.0 } else { total_len as f64 / docs as f64 };
+        search::CorpusStats { docs, avg_len }
+    }
+
+    /// This layer's documents holding any of `words` (or their synonyms),
+    /// unscored: each with its best line and snippet, the occurrences of
+    /// each word, and its length in tokens.
+    fn search_layer(&self, words: &[String]) -> Vec<(SearchResult, Vec<usize>, usize)> {
+        let index = self.store.index();
+        // Synonyms are looked up case-insensitively, but the word itself
+        // keeps the index's spelling (which is case-sensitive without
+        // `TokenizerConfig::lowercase`)
+        let expand = |word: &String| {
+            let mut terms = self.synonyms.expand(word);
+            terms[0] = word.clone();
+            terms
+        };
+
+        // Occurrences of each query word in a document, and the query words
+        // on each of its lines, straight from the postings
+        type Hits = (Vec<usize>, BTreeMap<u32, BTreeSet<usize>>);
+        let mut hits: HashMap<DocId, Hits> = HashMap::new();
+        let mut snippet_terms = Vec::new();
+        for (t, word) in words.iter().enumerate() {
+            let mut tokens = Vec::new();
+            for synonym in expand(word) {
+                let variants = index.stem_variants(&synonym);
+                if variants.is_empty() {
+                    tokens.push(synonym);
+                } else {
+                    tokens.extend(variants.into_iter().map(String::from));
+                }
+            }
+            tokens.sort();
+            tokens.dedup();
+            for (doc, line, count) in tokens.iter().flat_map(|token| index.line_counts(token)) {
+                let (tf, lines) = hits.entry(doc).or_insert_with(|| (vec![0; words.len()], BTreeMap::new()));
+                tf[t] += count;
+                lines.entry(line).or_default().insert(t);
+            }
+            snippet_terms.extend(tokens);
+        }
+
+        hits.into_iter()
+            .filter_map(|(id, (tf, lines))| {
+                let path = self.store.path_of(id);
+                let doc = self.store.get_document(path)?;
+                // The line with the most query words, the first of any tie
+                let (&line, _) = lines.iter().rev().max_by_key(|(_, on_line)| on_line.len())?;
+                let result = SearchResult {
+                    path: path.to_string(),
+                    score: 0.0,
+                    line,
+                    snippet: self.term_snippet(doc.line(line as usize - 1), &snippet_terms),
+                };
+                Some((result, tf, index.doc_len(id)))
+            })
+            .collect()
+    }
+
+    /// Excerpt of `line` around the first occurrence of any of `terms`, or
+    /// the trimmed line if none occurs.
+    fn term_snippet(&self, line: &str, terms: &[String]) -> String {
+        let alternatives: Vec<String> = terms.iter().map(|t| regex::escape(t)).collect();
+        let locator = regex::RegexBuilder::new(&alternatives.join("|"))
+            .case_insensitive(true)
+            .build()
+            .expect("escaped terms form a valid regex");
+        let text = match locator.find(line) {
+            Some(m) => snippet::extract(
+                line,
+                m.start(),
+                m.end(),
+                snippet::DEFAULT_RADIUS,
+                SnippetMode::Chars,
+            ),
+            None => line.to_string(),
+        };
+        text.trim().to_string()
+    }
+
+    /// Retrieval-augmented context for `query`: the lines matching the most
+    /// query words (ties broken by how many of the words their document
+    /// covers), trimmed to snippets and packed as `[path:line] text` lines
+    /// until `budget_tokens` (estimated at ~4 characters per token) is spent.
+    pub fn context_for(&self, query: &str, budget_tokens: usize) -> String {
+        let mut terms = self.stop_words.filter(self.tokenizer().query_tokens(query));
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() {
+            return String::new();
+        }
+
+        let mut ranked = self.layered(&|layer| layer.passages(&terms), |(_, _, p)| p.path.as_str());
+        ranked.sort_by(|(a_hits, a_docs, a), (b_hits, b_docs, b)| {
+            b_hits
+                .cmp(a_hits)
+                .then_with(|| b_docs.cmp(a_docs))
+                .then_with(|| (&a.path, a.line).cmp(&(&b.path, b.line)))
+        });
+        let passages: Vec<Passage> = ranked.into_iter().map(|(_, _, passage)| passage).collect();
+        context::pack(&passages, budget_tokens)
+    }
+
+    /// This layer's lines holding any of `terms` as passages for
+    /// `context_for`, each with the number of terms on the line and in its
+    /// document.
+    fn passages(&self, terms: &[String]) -> Vec<(usize, usize, Passage)> {
+        // (path, line) → query terms on that line; path → terms in the doc
+        let mut line_terms: HashMap<(&str, u32), usize> = HashMap::new();
+        let mut doc_terms: HashMap<&str, usize> = HashMap::new();
+        for term in terms {
+            let Some(postings) = self.store.index().lookup(term) else {
+                continue;
+            };
+            let mut docs_seen = BTreeSet::new();
+            for &(doc, line) in postings {
+                let path = self.store.path_of(doc);
+                *line_terms.entry((path, line)).or_default() += 1;
+                if docs_seen.insert(path) {
+                    *doc_terms.entry(path).or_default() += 1;
+                }
+            }
+        }
+
+        line_terms
+            .into_iter()
+            .filter_map(|((path, line), hits)| {
+                let doc = self.store.get_document(path)?;
+                let text = doc.lines().nth(line as usize - 1)?;
+                let passage = Passage {
+                    path: path.to_string(),
+                    line,
+                    text: self.term_snippet(text, terms),
+                };
+                Some((hits, doc_terms[path], passage))
+            })
+            .collect()
+    }
+
+    /// Distinct file extensions in the corpus with their document counts,
+    /// most common first.
+    pub fn extensions(&self) -> Vec<(String, usize)> {
+        store::extensions(self.visible_paths())
+    }
+
+    /// The subtree under `dir`. Directories deeper than `max_depth` levels
+    /// are returned without `children`.
+    pub fn tree(&self, dir: &str, max_depth: Option<usize>) -> TreeNode {
+        store::tree(self.visible_paths(), dir, max_depth)
+    }
+
+    /// Nested `{name, type, children}` JSON of the subtree under `dir`, for
+    /// file-explorer style UIs.
+    pub fn tree_json(&self, dir: &str, max_depth: Option<usize>) -> String {
+        serde_json::to_string(&self.tree(dir, max_depth)).unwrap()
+    }
+
+    /// The subtree under `dir` as an indented ASCII tree, for showing a
+    /// model the whole layout in one call.
+    pub fn tree_text(&self, dir: &str, max_depth: Option<usize>) -> String {
+        self.tree(dir, max_depth).render()
+    }
+
+    /// Insert or replace the document at `path`, normalized as on load,
+    /// reindexing only that document. Returns `true` if an existing
+    /// document was replaced. An overlay writes to its own layer, even
+    /// over a base document.
+    pub fn write_document(&mut self, path: &str, content: &str) -> Result<bool, MemexError> {
+        self.write_document_with_metadata(path, content, None)
+    }
+
+    /// `write_document` that also sets the document's metadata, replacing
+    /// any it had before.
+    pub fn write_document_with_metadata(
+        &mut self,
+        path: &str,
+        content: &str,
+        metadata: Option<serde_json::Value>,
+    ) -> Result<bool, MemexError> {
+        let path = &validate::normalize_path(path)?;
+        let replaced = self.document(path).is_some();
+        if let Some(reason) = sniff::detect(content) {
+            // A skipped write would look like it succeeded, so only stubs
+            // get through
+            if self.store.binary_policy() != BinaryPolicy::Stub {
+                return Err(MemexError::BinaryDocument { path: path.to_string(), reason: reason.to_string() });
+            }
+            let metadata = sniff::stub_metadata(metadata, content.len());
+            self.store.write_document(path, "", Some(metadata));
+            return Ok(replaced);
+        }
+        self.store.write_document(path, content, metadata);
+        Ok(replaced)
+    }
+
+    /// Remove the document at `path` and purge its postings from the index,
+    /// without rebuilding the rest of the store. An overlay deleting a base
+    /// document only hides it from this session.
+    pub fn delete_document(&mut self, path: &str) -> Result<(), MemexError> {
+        let in_base = !self.whiteouts.contains(path) && self.base.as_ref().is_some_and(|b| b.document(path).is_some());
+        if !self.store.delete_document(path) && !in_base {
+            return Err(self.not_found(path));
+        }
+        if in_base {
+            self.whiteouts.insert(path.to_string());
+        }
+        self.recent.lock().unwrap().retain(|p| p != path);
+        Ok(())
+    }
+
+    /// Copy the documents of `other` into this instance, e.g. stores built
+    /// per team into one for company-wide search. They are indexed with
+    /// this instance's options. A path both have is settled by `policy`:
+    /// `LastWins` takes `other`'s document, `FirstWins` keeps this one's,
+    /// and `Error` fails with `duplicate_path` before anything is merged.
+    /// `other`'s mount points are not carried over. Returns the number of
+    /// documents taken from `other`.
+    pub fn merge(&mut self, other: &MemexFsCore, policy: DuplicatePolicy) -> Result<usize, MemexError> {
+        let docs = sniff::screen_all(other.documents(), self.store.binary_policy())?;
+        let docs = validate::dedupe(docs, policy, |path| self.document(path).is_some())?;
+        let count = docs.len();
+        self.store.load_documents(docs);
+        Ok(count)
+    }
+
+    /// Every document, sorted by path, as it would be loaded again.
+    fn documents(&self) -> Vec<DocInput> {
+        self.paths().into_iter().filter_map(|path| self.document(path)).map(DocInput::from).collect()
+    }
+
+    /// Mount the documents of `other` under `prefix/`, e.g. product docs,
+    /// internal policies, and customer notes as `product/`, `policy/`, and
+    /// `notes/` of one instance. They are copied in and indexed with this
+    /// instance's settings, so every query sees them like any other
+    /// document. Mounting again at the same prefix replaces what was
+    /// there. Returns the number of documents mounted.
+    pub fn mount(&mut self, prefix: &str, other: &MemexFsCore) -> Result<usize, MemexError> {
+        self.mount_documents(prefix, other.documents())
+    }
+
+    /// `mount` for documents not in an instance, such as a parsed batch.
+    /// They are loaded as by `load_documents`, and nothing changes if that
+    /// fails. Fails with `mount_conflict` if `prefix` is inside or around
+    /// another mount, or documents loaded outside a mount live under it.
+    pub fn mount_documents(&mut self, prefix: &str, docs: Vec<DocInput>) -> Result<usize, MemexError> {
+        let prefix = mount_prefix(prefix)?;
+        let conflict = |reason: String| MemexError::MountConflict { prefix: prefix.clone(), reason };
+        let nested = |a: &str, b: &str| a.strip_prefix(b).is_some_and(|rest| rest.starts_with('/'));
+        if let Some(mount) = self.store.mounts().iter().find(|m| nested(m, &prefix) || nested(&prefix, m)) {
+            return Err(conflict(format!("overlaps the mount at {}/", mount)));
+        }
+        if !self.store.mounts().contains(&prefix) {
+            if let Some(path) = self.paths().into_iter().find(|p| *p == prefix || nested(p, &prefix)) {
+                return Err(conflict(format!("{} is already loaded", path)));
+            }
+        }
+
+        let docs = docs
+            .into_iter()
+            .map(|doc| {
+                let (path, content, metadata) = validate::normalize(doc)?.into_parts();
+                Ok(DocInput::Object { path: format!("{}/{}", prefix, path), content, metadata })
+            })
+            .collect::<Result<Vec<_>, MemexError>>()?;
+        let docs = sniff::screen_all(docs, self.store.binary_policy())?;
+        // The prefix is emptied first, so only repeats within the batch count
+        let docs = validate::dedupe(docs, self.store.duplicate_policy(), |_| false)?;
+        let count = docs.len();
+        self.delete_dir(&prefix);
+        self.store.add_mount(prefix);
+        self.store.load_documents(docs);
+        Ok(count)
+    }
+
+    /// Remove the corpus mounted at `prefix` with every document under it,
+    /// including ones written there since. Returns the number removed.
+    pub fn unmount(&mut self, prefix: &str) -> Result<usize, MemexError> {
+        let prefix = mount_prefix(prefix)?;
+        if !self.store.remove_mount(&prefix) {
+            let mounts = self.mounts();
+            return Err(MemexError::NotMounted { prefix, mounts });
+        }
+        Ok(self.delete_dir(&prefix))
+    }
+
+    /// Prefixes corpora are mounted at, sorted.
+    pub fn mounts(&self) -> Vec<String> {
+        self.store.mounts().iter().cloned().collect()
+    }
+
+    /// `diff_stores` between the corpora mounted at `from` and `to`, e.g.
+    /// last week's snapshot and this week's, matching documents by their
+    /// paths under the prefix. `glob` narrows both sides to the paths it
+    /// matches, relative to the mount. Fails with `not_mounted` if either
+    /// prefix isn't a mount.
+    pub fn diff_mounts(&self, from: &str, to: &str, glob: Option<&str>) -> Result<StoreDiff, MemexError> {
+        let under = |prefix: &str| -> Result<Vec<(&str, &Document)>, MemexError> {
+            let prefix = mount_prefix(prefix)?;
+            if !self.store.mounts().contains(&prefix) {
+                return Err(MemexError::NotMounted { prefix, mounts: self.mounts() });
+            }
+            let docs = self
+                .visible_paths()
+                .filter_map(|path| Some((path.strip_prefix(prefix.as_str())?.strip_prefix('/')?, path)))
+                .filter(|(relative, _)| glob.is_none_or(|g| glob_match::glob_match(g, relative)))
+                .filter_map(|(relative, path)| Some((relative, self.document(path)?)));
+            Ok(docs.collect())
+        };
+        Ok(diff::diff(under(from)?, under(to)?))
+    }
+
+    /// What this overlay changed on top of its base: the documents it wrote
+    /// and the base documents it deleted, ready to replay with
+    /// `load_documents` and `delete_document`, or to persist without the
+    /// base. Empty for an instance that isn't an overlay.
+    pub fn export_overlay(&self) -> OverlayChanges {
+        if self.base.is_none() {
+            return OverlayChanges::default();
+        }
+        let documents = self.store.paths().into_iter().filter_map(|path| self.store.get_document(path));
+        let documents = documents.map(DocInput::from).collect();
+        let deleted = self.whiteouts.iter().filter(|path| self.store.get_document(path).is_none());
+        OverlayChanges { documents, deleted: deleted.cloned().collect() }
+    }
+
+    /// Delete every document under `dir/`, returning how many there were.
+    fn delete_dir(&mut self, dir: &str) -> usize {
+        let removed = self.store.delete_dir(dir);
+        self.recent.lock().unwrap().retain(|p| !removed.contains(p));
+        removed.len()
+    }
+
+    /// Run the read tools. `write` fails with `ToolNotEnabled` here; hosts
+    /// that let the model change the store go through `call_mut`.
+    pub fn call(&self, name: &str, params_json: &str) -> Result<String, MemexError> {
+        let output = tool_params(params_json).and_then(|params| self.run_tool(name, params));
+        self.respond(name, output)
+    }
+
+    /// `call` that can also run `write`.
+    pub fn call_mut(&mut self, name: &str, params_json: &str) -> Result<String, MemexError> {
+        let output = tool_params(params_json).and_then(|params| self.run_tool_mut(name, params));
+        self.respond(name, output)
+    }
+
+    /// `call` with already-parsed parameters, for hosts that hold the tool
+    /// input as a JSON value rather than a string.
+    pub fn call_value(
+        &self,
+        name: &str,
+        params: serde_json::Value,
+    ) -> Result<String, MemexError> {
+        let output = self.run_tool(name, params);
+        self.respond(name, output)
+    }
+
+    /// `call_value` that can also run `write`.
+    pub fn call_value_mut(
+        &mut self,
+        name: &str,
+        params: serde_json::Value,
+    ) -> Result<String, MemexError> {
+        let output = self.run_tool_mut(name, params);
+        self.respond(name, output)
+    }
+
+    /// Run several tool calls, given as a JSON array of `{name, params}`,
+    /// in one go: for hosts where each call has a fixed cost, such as
+    /// crossing into WASM. Returns a JSON array with a `CallEnvelope` per
+    /// call (whatever `call_envelope` says), in order. Calls run one after
+    /// another, so a `write` is visible to the calls after it, and a
+    /// failed call doesn't stop the rest.
+    pub fn call_batch(&mut self, calls_json: &str) -> Result<String, MemexError> {
+        let calls: Vec<ToolCall> =
+            serde_json::from_str(calls_json).map_err(MemexError::invalid_params)?;
+        let envelopes = self.call_batch_value(calls)?;
+        serde_json::to_string(&envelopes).map_err(MemexError::encoding)
+    }
+
+    /// `call_batch` with already-parsed calls.
+    pub fn call_batch_value(
+        &mut self,
+        calls: Vec<ToolCall>,
+    ) -> Result<Vec<CallEnvelope>, MemexError> {
+        calls
+            .into_iter()
+            .map(|call| {
+                let output = self.run_tool_mut(&call.name, call.params);
+                self.envelope(&call.name, output)
+            })
+            .collect()
+    }
+
+    /// Render a tool's output for `call`: as is, or with
+    /// `MemexOptions::call_envelope` wrapped in a `CallEnvelope`, errors
+    /// included.
+    fn respond(
+        &self,
+        name: &str,
+        output: Result<ToolOutput, MemexError>,
+    ) -> Result<String, MemexError> {
+        if !self.settings.call_envelope {
+            return output.map(|o| o.text);
+        }
+        let envelope = self.envelope(name, output)?;
+        serde_json::to_string(&envelope).map_err(MemexError::encoding)
+    }
+
+    fn envelope(
+        &self,
+        name: &str,
+        output: Result<ToolOutput, MemexError>,
+    ) -> Result<CallEnvelope, MemexError> {
+        Ok(match output {
+            Ok(output) => CallEnvelope {
+                ok: true,
+                tool: name.to_string(),
+                data: Some(if output.json {
+                    serde_json::from_str(&output.text).map_err(MemexError::encoding)?
+                } else {
+                    serde_json::Value::String(output.text)
+                }),
+                error: None,
+                truncated: output.truncated,
+                total: output.total,
+            },
+            Err(e) => CallEnvelope {
+                ok: false,
+                tool: name.to_string(),
+                data: None,
+                error: Some(e.to_json()),
+                truncated: false,
+                total: None,
+            },
+        })
+    }
+
+    /// The built-in name of the tool called `name`, if it is enabled.
+    fn enabled_tool(&self, name: &str) -> Result<String, MemexError> {
+        // A tool renamed by `tool_text` answers to both names
+        let tool = self.settings.tool_text.canonical(name).to_string();
+        if self.settings.tools.as_ref().is_some_and(|tools| !tools.contains(&tool)) {
+            return Err(MemexError::ToolNotEnabled { name: name.to_string() });
+        }
+        Ok(tool)
+    }
+
+    /// `run_tool`, plus the tools that change the store.
+    fn run_tool_mut(&mut self, name: &str, params: serde_json::Value) -> Result<ToolOutput, MemexError> {
+        if self.enabled_tool(name)? != "write" {
+            return self.run_tool(name, params);
+        }
+        let params: WriteParams = serde_json::from_value(tools::without_nulls(params))
+            .map_err(MemexError::invalid_params)?;
+        let path = validate::normalize_path(&params.path)?;
+        let replaced = self.write_document_with_metadata(
+            &path,
+            &params.content,
+            params.metadata,
+        )?;
+        let verb = if replaced { "Updated" } else { "Created" };
+        Ok(ToolOutput::text(format!("{} {}", verb, path)))
+    }
+
+    fn run_tool(&self, name: &str, params: serde_json::Value) -> Result<ToolOutput, MemexError> {
+        let tool = self.enabled_tool(name)?;
+        let params = tools::without_nulls(params);
+        match tool.as_str() {
+            "grep" => {
+                let params: GrepParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                let options = &params.options;
+                if let GrepOutput::Lines | GrepOutput::Grouped = params.output {
+                    let page = match &params.pattern {
+                        PatternParam::One(pattern) => self.grep_page(pattern, options)?,
+                        PatternParam::Any(patterns) => self.grep_any_page(patterns, options)?,
+                    };
+                    let truncated = page.next_offset.is_some() || page.timed_out;
+                    let total = page.total_matches;
+                    let output = if let GrepOutput::Grouped = params.output {
+                        ToolOutput::json(&page.grouped())?
+                    } else {
+                        ToolOutput::json(&page)?
+                    };
+                    return Ok(output.counted(total, truncated));
+                }
+
+                let counts = match &params.pattern {
+                    PatternParam::One(pattern) => self.grep_counts(pattern, options)?,
+                    PatternParam::Any(patterns) => self.grep_any_counts(patterns, options)?,
+                };
+                if let GrepOutput::Files = params.output {
+                    let paths: Vec<&str> = counts.iter().map(|c| c.path.as_str()).collect();
+                    ToolOutput::list(&paths)
+                } else {
+                    ToolOutput::list(&counts)
+                }
+            }
+            "read" => {
+                let params: ReadParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                let text = self.read_with_options(&params.path, &params.options)?;
+                Ok(self.read_output(ToolOutput::text(text), &params))
+            }
+            "ls" => {
+                let params: LsParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                if params.detailed.unwrap_or(false) {
+                    ToolOutput::list(&self.ls_detailed(&params.path))
+                } else if params.with_metadata.unwrap_or(false) {
+                    ToolOutput::list(&self.ls_with_metadata(&params.path))
+                } else {
+                    ToolOutput::list(&self.ls(&params.path))
+                }
+            }
+            "read_lines" => {
+                let params: ReadParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                let lines = self.read_lines(&params.path, &params.options)?;
+                Ok(self.read_output(ToolOutput::json(&lines)?, &params))
+            }
+            "tree" => {
+                // Every parameter is optional, so no params at all is fine
+                let params: TreeParams = if params.is_null() {
+                    TreeParams::default()
+                } else {
+                    serde_json::from_value(params).map_err(MemexError::invalid_params)?
+                };
+                let dir = params.path.as_deref().unwrap_or("");
+                match params.format {
+                    TreeFormat::Text => Ok(ToolOutput::text(self.tree_text(dir, params.depth))),
+                    TreeFormat::Json => Ok(ToolOutput {
+                        json: true,
+                        ..ToolOutput::text(self.tree_json(dir, params.depth))
+                    }),
+                }
+            }
+            "glob" => {
+                let params: GlobParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                ToolOutput::list(&self.glob(&params.pattern))
+            }
+            "outline" => {
+                let params: PathParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                ToolOutput::list(&self.outline(&params.path)?)
+            }
+            "missing_in_glob" => {
+                let params: MissingParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                ToolOutput::list(&self.missing_in_glob(&params.term, &params.glob)?)
+            }
+            "extensions" => ToolOutput::list(&self.extensions()),
+            "query_table" => {
+                let params: TableParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                let page = self.query_table(&params.path, &params.query)?;
+                let more = page.next_offset.is_some();
+                Ok(ToolOutput::json(&page)?.counted(page.total_rows, more))
+            }
+            "read_json" => {
+                let params: JsonParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                ToolOutput::json(&self.read_json(&params.path, &params.pointer)?)
+            }
+            "search" => {
+                let params: SearchParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                ToolOutput::list(&self.search(&params.query, params.limit))
+            }
+            // Changes the store, so only `run_tool_mut` runs it
+            "write" => Err(MemexError::ToolNotEnabled { name: name.to_string() }),
+            "diff" => {
+                let params: DiffParams = serde_json::from_value(params)
+                    .map_err(MemexError::invalid_params)?;
+                let diff = self.diff_mounts(&params.from, &params.to, params.glob.as_deref())?;
+                Ok(ToolOutput::text(diff.to_string()).counted(diff.len(), false))
+            }
+            _ => Err(MemexError::UnknownTool { name: name.to_string() }),
+        }
+    }
+
+    /// `output` of a successful `read` or `read_lines`, counting the lines
+    /// of the document (or section) and whether some were left out.
+    fn read_output(&self, output: ToolOutput, params: &ReadParams) -> ToolOutput {
+        match self.document(&params.path) {
+            Some(doc) => {
+                let (shown, total) = doc.read_extent(&params.options);
+                output.counted(total, shown < total)
+            }
+            None => output,
+        }
+    }
+
+    pub fn tool_definitions(&self) -> String {
+        serde_json::to_string(&self.tool_definitions_value()).unwrap()
+    }
+
+    /// Answer one Model Context Protocol message (JSON-RPC 2.0), so the
+    /// tools can back an MCP server: `initialize`, `ping`, `tools/list`,
+    /// and `tools/call`. Returns the response to send back, or `None` for
+    /// a notification. Transport is up to the host.
+    pub fn handle_mcp_request(&mut self, message: &str) -> Option<String> {
+        mcp::handle(self, message)
+    }
+
+    /// The tool definitions in OpenAI's strict function-calling format,
+    /// ready for `tools` in a chat completions request (see
+    /// `tools::openai_tools`).
+    pub fn tool_definitions_openai(&self) -> String {
+        serde_json::to_string(&self.tool_definitions_openai_value()).unwrap()
+    }
+
+    /// `tool_definitions_openai` as JSON.
+    pub fn tool_definitions_openai_value(&self) -> serde_json::Value {
+        tools::openai_tools(&self.tool_definitions_value())
+    }
+
+    /// `tool_definitions` as JSON, limited to the tools enabled with
+    /// `MemexFsBuilder::tools` and worded by `MemexOptions::tool_text`.
+    /// `diff` is left out until two corpora are mounted.
+    pub fn tool_definitions_value(&self) -> serde_json::Value {
+        let mut definitions = tool_definitions_json();
+        if let (Some(tools), Some(all)) = (&self.settings.tools, definitions.as_array_mut()) {
+            all.retain(|def| def["name"].as_str().is_some_and(|name| tools.contains(name)));
+        }
+        // diff compares two mounts, so it is offered with their names once
+        // there are two to compare
+        let mounts = self.mounts();
+        if let Some(all) = definitions.as_array_mut() {
+            all.retain(|def| def["name"] != "diff" || mounts.len() >= 2);
+            for def in all.iter_mut().filter(|def| def["name"] == "diff") {
+                def["parameters"]["from"]["enum"] = serde_json::json!(mounts);
+                def["parameters"]["to"]["enum"] = serde_json::json!(mounts);
+            }
+        }
+        self.settings.tool_text.apply(&mut definitions);
+        definitions
+    }
+
+    /// `options` with smart case applied and the builder's defaults
+    /// (`max_results`, `glob`) filling in what it leaves unset.
+    fn resolve<'a, S: AsRef<str>>(&self, options: &'a GrepOptions, patterns: &[S]) -> Cow<'a, GrepOptions> {
+        let options = options.with_smart_case(patterns);
+        let Settings { max_results, glob, .. } = &self.settings;
+        if (options.max_results.is_some() || max_results.is_none()) && (options.glob.is_some() || glob.is_none()) {
+            return options;
+        }
+        let mut options = options.into_owned();
+        options.max_results = options.max_results.or(*max_results);
+        options.glob = options.glob.or_else(|| glob.clone());
+        Cow::Owned(options)
+    }
+
+    /// How this instance folds text compared case-insensitively.
+    fn folding(&self) -> Folding {
+        self.store.index().folding()
+    }
+
+    /// How this instance splits text into index tokens.
+    fn tokenizer(&self) -> &Tokenizer {
+        self.store.index().tokenizer()
+    }
+
+    /// Whether every occurrence of the folded `word` lies inside a single
+    /// index token, so token-level membership answers substring queries
+    /// for it exactly: `word` is alphanumeric, the tokenizer config is the
+    /// default, and it isn't CJK text cut into n-grams.
+    fn indexes_word(&self, word: &str) -> bool {
+        let tokenizer = self.tokenizer();
+        is_token(word) && tokenizer.indexes_words() && tokenizer.cjk_grams(word).is_none()
+    }
+
+    /// `s` folded like the index: case, and with `ascii_fold` diacritics.
+    fn fold(&self, s: &str) -> String {
+        self.folding().fold(s)
+    }
+
+    /// The document at `path`, looked up through the base of an overlay
+    /// unless this layer decides the path.
+    fn document(&self, path: &str) -> Option<&Document> {
+        match self.store.get_document(path) {
+            Some(doc) => Some(doc),
+            None if self.whiteouts.contains(path) => None,
+            None => self.base.as_ref()?.document(path),
+        }
+    }
+
+    /// Whether this layer wrote or deleted `path`, hiding the base's
+    /// document there.
+    fn hides(&self, path: &str) -> bool {
+        self.store.get_document(path).is_some() || self.whiteouts.contains(path)
+    }
+
+    /// Paths of every document `document` finds, in no particular order.
+    fn visible_paths(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        match &self.base {
+            None => Box::new(self.store.keys()),
+            Some(base) => Box::new(self.store.keys().chain(base.visible_paths().filter(|p| !self.hides(p)))),
+        }
+    }
+
+    /// `visible_paths`, sorted.
+    fn paths(&self) -> Vec<&str> {
+        let mut paths: Vec<&str> = self.visible_paths().collect();
+        paths.sort();
+        paths
+    }
+
+    /// This instance and, for an overlay, every base below it, top down.
+    fn layers(&self) -> impl Iterator<Item = &MemexFsCore> {
+        std::iter::successors(Some(self), |layer| layer.base.as_deref())
+    }
+
+    /// `query` run on each of `layers`, without what a lower layer returns
+    /// for paths (by `path`) that a higher one hides.
+    fn layered<'s, T>(&'s self, query: &dyn Fn(&'s MemexFsCore) -> Vec<T>, path: fn(&T) -> &str) -> Vec<T> {
+        let mut items = query(self);
+        if let Some(base) = &self.base {
+            let below = base.layered(query, path);
+            items.extend(below.into_iter().filter(|item| !self.hides(path(item))));
+        }
+        items
+    }
+
+    pub fn document_count(&self) -> usize {
+        match self.base {
+            None => self.store.document_count(),
+            Some(_) => self.visible_paths().count(),
+        }
+    }
+
+    /// Distinct indexed tokens; of an overlay, in the session layer alone.
+    pub fn token_count(&self) -> usize {
+        self.store.token_count()
+    }
+
+    /// Estimated bytes used by the documents, their folded copies, the
+    /// index, and the scan filters. Of an overlay, only the session layer
+    /// is counted, since the base is shared.
+    pub fn memory_stats(&self) -> MemoryStats {
+        self.store.memory_stats()
+    }
+}
+
+/// How `b` differs from `a`: the paths only `b` has (added), the paths
+/// only `a` has (removed), and, for paths both have, line diffs of the
+/// documents whose lines or metadata changed. Compare two snapshots, e.g.
+/// to answer what changed in the docs since last week. Mounted documents
+/// are compared at their full paths; `MemexFsCore::diff_mounts` compares
+/// two mounts of one instance.
+pub fn diff_stores(a: &MemexFsCore, b: &MemexFsCore) -> StoreDiff {
+    let old = a.visible_paths().filter_map(|path| Some((path, a.document(path)?)));
+    let new = b.visible_paths().filter_map(|path| Some((path, b.document(path)?)));
+    diff::diff(old, new)
+}
+
+/// Decode the output of `MemexFsCore::grep_binary`.
+#[cfg(feature = "binary")]
+pub fn decode_grep_results(bytes: &[u8]) -> Result<Vec<GrepResult>, MemexError> {
+    rmp_serde::from_slice(bytes).map_err(MemexError::invalid_params)
+}
+
+// ── Helpers ────────────────────────────────────────────────────────
+
+/// A tool's output as `call` returns it, plus what `CallEnvelope`
+/// reports about it.
+struct ToolOutput {
+    text: String,
+    /// Whether `text` is JSON (rather than plain text).
+    json: bool,
+    truncated: bool,
+    total: Option<usize>,
+}
+
+impl ToolOutput {
+    fn text(text: String) -> Self {
+        Self { text, json: false, truncated: false, total: None }
+    }
+
+    fn json<T: Serialize>(value: &T) -> Result<Self, MemexError> {
+        let text = serde_json::to_string(value).map_err(MemexError::encoding)?;
+        Ok(Self { json: true, ..Self::text(text) })
+    }
+
+    /// A complete list, counting its items.
+    fn list<T: Serialize>(items: &[T]) -> Result<Self, MemexError> {
+        Ok(Self::json(&items)?.counted(items.len(), false))
+    }
+
+    fn counted(self, total: usize, truncated: bool) -> Self {
+        Self { total: Some(total), truncated, ..self }
+    }
+}
+
+#[derive(Deserialize)]
+struct GrepParams {
+    pattern: PatternParam,
+    #[serde(default)]
+    output: GrepOutput,
+    #[serde(flatten)]
+    options: GrepOptions,
+}
+
+/// What the grep tool returns.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum GrepOutput {
+    /// A `GrepResponse` page of matching lines.
+    #[default]
+    Lines,
+    /// The same page as a `GroupedGrepResponse`.
+    Grouped,
+    /// Paths of the documents with at least one match.
+    Files,
+    /// Per-document match counts.
+    Count,
+}
+
+/// A single grep pattern, or several to match any of.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatternParam {
+    One(String),
+    Any(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct ReadParams {
+    path: String,
+    #[serde(flatten)]
+    options: ReadOptions,
+}
+
+#[derive(Deserialize)]
+struct LsParams {
+    path: String,
+    with_metadata: Option<bool>,
+    detailed: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+struct TreeParams {
+    path: Option<String>,
+    depth: Option<usize>,
+    #[serde(default)]
+    format: TreeFormat,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TreeFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Deserialize)]
+struct GlobParams {
+    pattern: String,
+}
+
+#[derive(Deserialize)]
+struct PathParams {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    query: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct WriteParams {
+    path: String,
+    content: String,
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct TableParams {
+    path: String,
+    #[serde(flatten)]
+    query: TableQuery,
+}
+
+#[derive(Deserialize)]
+struct JsonParams {
+    path: String,
+    #[serde(default)]
+    pointer: String,
+}
+
+#[derive(Deserialize)]
+struct MissingParams {
+    term: String,
+    glob: String,
+}
+
+#[derive(Deserialize)]
+struct DiffParams {
+    from: String,
+    to: String,
+    glob: Option<String>,
+}
+
+/// Keep at most `cap` results per path and deal them out round-robin:
+/// every file's first result (files in order of first appearance), then
+/// every file's second, and so on.
+fn interleave_files(results: Vec<GrepResult>, cap: usize) -> Vec<GrepResult> {
+    let mut files: Vec<Vec<GrepResult>> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for result in results {
+        let pos = *positions.entry(result.path.clone()).or_insert_with(|| {
+            files.push(Vec::new());
+            files.len() - 1
+        });
+        if files[pos].len() < cap {
+            files[pos].push(result);
+        }
+    }
+
+    let mut files: Vec<_> = files.into_iter().map(Vec::into_iter).collect();
+    let mut interleaved = Vec::new();
+    loop {
+        let before = interleaved.len();
+        interleaved.extend(files.iter_mut().filter_map(Iterator::next));
+        if interleaved.len() == before {
+            return interleaved;
+        }
+    }
+}
+
+/// The words of a folded pattern made only of alphanumeric words joined by
+/// single spaces, e.g. `invoice due date`, which can be matched through
+/// token positions. `None` for anything else.
+fn phrase_words(pattern_lower: &str) -> Option<Vec<&str>> {
+    let words: Vec<&str> = pattern_lower.split(' ').collect();
+    let is_phrase = words.len() >= 2
+        && words
+            .iter()
+            .all(|w| !w.is_empty() && w.chars().all(char::is_alphanumeric));
+    is_phrase.then_some(words)
+}
+
+/// Whether a regex has an uppercase letter outside escape sequences, so
+/// `\S+` does not count but `\SFoo` does.
+fn has_uppercase_literal(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Split a `near` mode pattern, `first NEAR/n second`, into its terms and
+/// distance. Both terms must be single alphanumeric words.
+fn near_query(pattern: &str) -> Option<(&str, u32, &str)> {
+    let parts: Vec<&str> = pattern.split_whitespace().collect();
+    let [first, operator, second] = parts[..] else {
+        return None;
+    };
+    let distance = match operator.strip_prefix("NEAR")? {
+        "" => DEFAULT_NEAR_DISTANCE,
+        n => n.strip_prefix('/')?.parse().ok()?,
+    };
+    let is_word = |t: &str| t.chars().all(char::is_alphanumeric);
+    (is_word(first) && is_word(second)).then_some((first, distance, second))
+}
+
+/// Distinct alphanumeric words of `pattern`, in their original case.
+fn query_words(pattern: &str) -> Vec<&str> {
+    let mut words: Vec<&str> = Vec::new();
+    for word in pattern.split(|c: char| !c.is_alphanumeric()) {
+        if !word.is_empty() && !words.contains(&word) {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// The parameters of a `call`: JSON, or nothing for a tool without any.
+fn tool_params(params_json: &str) -> Result<serde_json::Value, MemexError> {
+    if params_json.trim().is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+    serde_json::from_str(params_json).map_err(MemexError::invalid_params)
+}
+
+/// A mount prefix as a normalized directory path, with or without a
+/// trailing `/`.
+fn mount_prefix(prefix: &str) -> Result<String, MemexError> {
+    validate::normalize_path(prefix.strip_suffix('/').unwrap_or(prefix))
+}
+
+/// Fail if `options` names a section that `doc` does not have.
+fn check_section(doc: &Document, options: &ReadOptions) -> Result<(), MemexError> {
+    match &options.section {
+        Some(heading) if doc.section_range(heading).is_none() => Err(MemexError::SectionNotFound {
+            path: doc.path.clone(),
+            heading: heading.clone(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Length (in tokens) of the smallest window of `doc` containing every one
+/// of the distinct `terms`, or `None` if some term is missing.
+fn min_span(doc: &Document, terms: &[String], tokenizer: &Tokenizer) -> Option<usize> {
+    // (position, term index) for every occurrence, in document order
+    let mut hits = Vec::new();
+    let mut pos = 0;
+    for line in doc.lines() {
+        for at in tokenizer.line_terms(line) {
+            for token in at {
+                if let Ok(t) = terms.binary_search(&token) {
+                    hits.push((pos, t));
+                }
+            }
+            pos += 1;
+        }
+    }
+
+    let mut counts = vec![0usize; terms.len()];
+    let mut covered = 0;
+    let mut best: Option<usize> = None;
+    let mut left = 0;
+    for right in 0..hits.len() {
+        let t = hits[right].1;
+        counts[t] += 1;
+        if counts[t] == 1 {
+            covered += 1;
+        }
+        while covered == terms.len() {
+            let span = hits[right].0 - hits[left].0 + 1;
+            best = Some(best.map_or(span, |b| b.min(span)));
+            let lt = hits[left].1;
+            counts[lt] -= 1;
+            if counts[lt] == 0 {
+                covered -= 1;
+            }
+            left += 1;
+        }
+    }
+    best
+}
+
+/// Number of characters in `s` before byte offset `byte`.
+fn char_offset(s: &str, byte: usize) -> u32 {
+    s[..byte].chars().count() as u32
+}
+
+/// Whether `s` is a single non-empty alphanumeric run, i.e. something that
+/// can only ever match inside one index token.
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_alphanumeric())
+}
+
+fn has_regex_metacharacters(pattern: &str) -> bool {
+    pattern.contains(|c: char| {
+        matches!(
+            c,
+            '|' | '*'
+                | '+'
+                | '?'
+                | '('
+                | ')'
+                | '['
+                | ']'
+                | '{'
+                | '}'
+                | '\\'
+                | '^'
+                | '$'
+                | '.'
+        )
+    })
+}
+
+fn tool_definitions_json() -> serde_json::Value {
+    serde_json::json!([
+        {
+            "name": "grep",
+            "description": "Search for a pattern across all documents. Returns matching file paths, line numbers, and content. Use this to find relevant documents before reading them.",
+            "parameters": {
+                "pattern": { "type": ["string", "array"], "items": { "type": "string" }, "description": "Search pattern (supports regex; a word with * like 'deploy*' matches words by wildcard), or an array of literal strings to match any of, e.g. ['refund', 'chargeback', 'reimbursement']" },
+                "glob": { "type": "string", "description": "Optional file pattern filter, e.g. 'billing/**/*.md'" },
+                "body_only": { "type": "boolean", "description": "Skip YAML frontmatter lines when matching (default true); set false to also search frontmatter" },
+                "flexible_whitespace": { "type": "boolean", "description": "Treat a multi-word pattern as a phrase whose words may be separated by any amount of whitespace" },
+                "with_title": { "type": "boolean", "description": "Include each matching document's title in the results" },
+                "snippet": { "type": "string", "enum": ["chars", "sentence"], "description": "Attach an excerpt around each match: a fixed character window, or the enclosing sentence" },
+                "snippet_radius": { "type": "number", "description": "Characters to keep on each side of the match in snippets (default 60)" },
+                "context_before": { "type": "number", "description": "Number of lines to include before each match" },
+                "context_after": { "type": "number", "description": "Number of lines to include after each match" },
+                "max_context_lines": { "type": "number", "description": "Cap on the total context lines across all matches" },
+                "max_results": { "type": "number", "description": "Maximum matches to return (default 100)" },
+                "max_per_file": { "type": "number", "description": "Maximum matches per document, so one long document cannot fill the page. Results are interleaved across documents" },
+                "offset": { "type": "number", "description": "Matches to skip; pass the previous response's next_offset to get the next page" },
+                "whole_word": { "type": "boolean", "description": "Only match whole words, so 'cat' does not match 'category'" },
+                "case_sensitive": { "type": "boolean", "description": "Match letter case exactly (default false)" },
+                "literal": { "type": "boolean", "description": "Treat the pattern as a fixed string, not a regex, e.g. 'config.toml' or 'foo(bar)'" },
+                "spans": { "type": "boolean", "description": "Include the character ranges of every match within each line" },
+                "metadata": { "type": "object", "description": "Only search documents whose metadata contains these values, e.g. {\"tags\": [\"billing\"]}" },
+                "with_metadata": { "type": "boolean", "description": "Include each matching document's metadata in the results" },
+                "output": { "type": "string", "enum": ["lines", "grouped", "files", "count"], "description": "lines (default) returns matching lines; grouped returns the same page as {groups: [{path, matches}], total_matches, next_offset?}; files returns just the paths of matching documents; count returns [{path, count}] per document. files and count are uncapped and much smaller when you only need to know where matches are" },
+                "exclude": { "type": "string", "description": "Drop matching lines that also match this pattern, e.g. pattern 'token' with exclude 'deprecated'" },
+                "scope": { "type": "string", "enum": ["all", "prose", "code"], "description": "Which lines to search: all (default), prose outside fenced code blocks, or only code blocks" },
+                "ignore_markdown": { "type": "boolean", "description": "Match against lines as rendered, without markdown syntax: emphasis markers, backticks, heading and list markers, and link URLs are ignored, so * matches only literal asterisks" },
+                "stop_words": { "type": "boolean", "description": "With mode all_terms: ignore common words like 'how', 'to', 'the' in the pattern" },
+                "smart_case": { "type": "boolean", "description": "Match case-sensitively only if the pattern contains an uppercase letter, like ripgrep --smart-case" },
+                "multiline": { "type": "boolean", "description": "Let the pattern span line breaks (match them with \\n). Results start at the first matched line and report line_count" },
+                "fuzzy": { "type": "boolean", "description": "Tolerate typos in a single-word pattern: 'pasword' also finds 'password'. Use when an exact grep finds nothing" },
+                "suggest": { "type": "boolean", "description": "If nothing matches, return suggestions: indexed words close to misspelled words of the pattern, to retry with" },
+                "mode": { "type": "string", "enum": ["exact", "all_terms", "near"], "description": "exact (default) matches the pattern as written; all_terms matches lines containing every word of the pattern in any order, e.g. 'password reset' also finds 'reset your password'; near takes 'term NEAR/n term' and matches the two words within n tokens of each other (NEAR alone means 5), e.g. 'refund NEAR/5 deadline'" },
+                "within": { "type": "string", "enum": ["line", "document"], "description": "With mode all_terms: require the words on one line (default), or anywhere in the document and return every line with any of them. With mode near: keep the window on one line (default), or let it span lines" },
+                "sort": { "type": "string", "enum": ["path", "relevance"], "description": "Result order: by path and line (default), or best matches first, with headings, matching file names, and early lines ranked higher" }
+            },
+            "required": ["pattern"]
+        },
+        {
+            "name": "search",
+            "description": "Find the most relevant documents for a natural-language query. Returns documents ranked by relevance (BM25), each with a score and the best-matching line. Use this when you don't know the exact wording to grep for.",
+            "parameters": {
+                "query": { "type": "string", "description": "Words to search for, e.g. 'refund processing time'" },
+                "limit": { "type": "number", "description": "Maximum number of documents to return (default 10)" }
+            },
+            "required": ["query"]
+        },
+        {
+            "name": "read",
+            "description": "Read the contents of a document. Returns the full document or a specific line range. Use this after grep to get the full context of a matching document.",
+            "parameters": {
+                "path": { "type": "string", "description": "Document path relative to the knowledge base root" },
+                "offset": { "type": "number", "description": "Line number to start reading from (1-indexed)" },
+                "limit": { "type": "number", "description": "Number of lines to return" },
+                "tab_width": { "type": "number", "description": "Expand tabs to spaces using this tab width" },
+                "section": { "type": "string", "description": "Only return the lines under this markdown heading, up to the next heading of the same or higher level, e.g. 'Installation'" }
+            },
+            "required": ["path"]
+        },
+        {
+            "name": "read_lines",
+            "description": "Read a document as a JSON array of {number, text} line objects. Same as read, but structured for clients that process lines individually.",
+            "parameters": {
+                "path": { "type": "string", "description": "Document path relative to the knowledge base root" },
+                "offset": { "type": "number", "description": "Line number to start reading from (1-indexed)" },
+                "limit": { "type": "number", "description": "Number of lines to return" },
+                "tab_width": { "type": "number", "description": "Expand tabs to spaces using this tab width" },
+                "section": { "type": "string", "description": "Only return the lines under this markdown heading" }
+            },
+            "required": ["path"]
+        },
+        {
+            "name": "outline",
+            "description": "Get the heading structure of a document as a JSON array of {level, text, line}. Use this to navigate a long document before choosing an offset for read.",
+            "parameters": {
+                "path": { "type": "string", "description": "Document path relative to the knowledge base root" }
+            },
+            "required": ["path"]
+        },
+        {
+            "name": "ls",
+            "description": "List the contents of a directory. Returns immediate children: file names and subdirectory names (with trailing '/'). Use this to explore the document structure before grepping or reading.",
+            "parameters": {
+                "path": { "type": "string", "description": "Directory path to list, e.g. 'account' or 'billing/invoices'. Use empty string or '.' for root." },
+                "with_metadata": { "type": "boolean", "description": "Return {name, metadata} objects instead of plain names" },
+                "detailed": { "type": "boolean", "description": "Return {name, kind, lines, bytes, children, metadata} objects instead of plain names; directories report how many documents they contain" }
+            },
+            "required": ["path"]
+        },
+        {
+            "name": "tree",
+            "description": "Show the directory structure recursively in one call. Returns an indented ASCII tree by default, or nested {name, type, children} JSON. Use this to understand the layout of a large knowledge base instead of calling ls repeatedly.",
+            "parameters": {
+                "path": { "type": "string", "description": "Directory to start from (default: root)" },
+                "depth": { "type": "number", "description": "Maximum number of directory levels to expand" },
+                "format": { "type": "string", "enum": ["text", "json"], "description": "Output format (default text)" }
+            },
+            "required": []
+        },
+        {
+            "name": "glob",
+            "description": "Find documents by path pattern without searching their content. Returns a JSON array of matching paths. Use this to locate files by name across all directories.",
+            "parameters": {
+                "pattern": { "type": "string", "description": "Glob matched against full document paths, e.g. 'billing/**/*invoice*.md' or '**/README.md'" }
+            },
+            "required": ["pattern"]
+        },
+        {
+            "name": "missing_in_glob",
+            "description": "Find documents matching a glob that do NOT contain a term (case-insensitive). Use this for gap or compliance checks, e.g. which billing docs lack a required clause.",
+            "parameters": {
+                "term": { "type": "string", "description": "Text that should be present" },
+                "glob": { "type": "string", "description": "File pattern selecting the documents to check, e.g. 'billing/**/*.md'" }
+            },
+            "required": ["term", "glob"]
+        },
+        {
+            "name": "extensions",
+            "description": "List the distinct file extensions in the knowledge base with the number of documents for each, most common first. Use this to learn what kinds of content are available.",
+            "parameters": {},
+            "required": []
+        },
+        {
+            "name": "query_table",
+            "description": "Query a CSV or TSV document as a table: keep the rows whose columns match your filters and return only the columns you need, as {columns, rows: [{line, values}], total_rows, next_offset?}. Use this instead of grep for spreadsheets such as pricing tables.",
+            "parameters": {
+                "path": { "type": "string", "description": "Path of a .csv or .tsv document" },
+                "filter": { "type": "array", "items": { "type": "string" }, "description": "Conditions every row must meet, each 'column op value' with op one of = != < <= > >= ~ (contains), e.g. ['plan = Pro', 'monthly price < 50']. =, != and ~ ignore case; < and > compare numbers, allowing $ and thousands separators" },
+                "columns": { "type": "array", "items": { "type": "string" }, "description": "Columns to return, in order (default all)" },
+                "limit": { "type": "number", "description": "Maximum rows to return (default 100)" },
+                "offset": { "type": "number", "description": "Rows to skip; pass the previous response's next_offset to get the next page" }
+            },
+            "required": ["path"]
+        },
+        {
+            "name": "read_json",
+            "description": "Read one value from a .json document by its path inside the JSON, e.g. a single config setting, instead of reading the whole file. Returns the value as JSON; a missing path fails with the keys available at the deepest point reached.",
+            "parameters": {
+                "path": { "type": "string", "description": "Path of a .json document" },
+                "pointer": { "type": "string", "description": "Where the value is: a JSON Pointer such as '/server/port' or a JSONPath such as '$.server.hosts[0]' ([-1] is the last item). Empty for the whole document" }
+            },
+            "required": ["path", "pointer"]
+        },
+        {
+            "name": "diff",
+            "description": "Compare two versions of the knowledge base mounted side by side, e.g. last week's snapshot and the current docs. Lists the documents added, removed, and changed from one to the other, then a unified line diff of each changed document. Use this to answer what changed between versions.",
+            "parameters": {
+                "from": { "type": "string", "description": "Mount holding the older version, e.g. 'last-week'" },
+                "to": { "type": "string", "description": "Mount holding the newer version, e.g. 'current'" },
+                "glob": { "type": "string", "description": "Optional file pattern limiting the comparison, relative to the mounts, e.g. 'billing/**/*.md'" }
+            },
+            "required": ["from", "to"]
+        },
+        {
+            "name": "write",
+            "description": "Create or overwrite a document with the given content. The document is searchable immediately. Use this to save notes or findings back into the knowledge base.",
+            "parameters": {
+                "path": { "type": "string", "description": "Document path relative to the knowledge base root, e.g. 'notes/summary.md'" },
+                "content": { "type": "string", "description": "Full new content of the document" },
+                "metadata": { "type": "object", "description": "Optional metadata for the document, e.g. {\"tags\": [\"notes\"]}" }
+            },
+            "required": ["path", "content"]
+        }
+    ])
+}
+
+// ── Tests ──────────────────────────────────────────────────────────
+// Tests use MemexFsCore directly to avoid JsError on non-wasm targets.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fold::fold_case;
+
+    fn make_fs() -> MemexFsCore {
+        let docs = serde_json::to_string(&vec![
+            ("account/password-reset.md", "# Password Reset\n\n## How to reset your password\n\n1. Go to Settings\n2. Click Reset Password"),
+            ("billing/refund.md", "# Refunds\n\nTo request a refund, contact support.\n\nRefunds are processed within 5 business days."),
+        ]).unwrap();
+        MemexFsCore::from_json(&docs).unwrap()
+    }
+
+    #[test]
+    fn test_grep_simple() {
+        let fs = make_fs();
+        let results = fs.grep("password", None).unwrap();
+        assert!(!results.is_empty());
+        assert!(results
+            .iter()
+            .any(|r| r.path == "account/password-reset.md"));
+    }
+
+    #[test]
+    fn test_grep_with_glob() {
+        let fs = make_fs();
+        let results = fs.grep("refund", Some("billing/**/*.md")).unwrap();
+        assert!(!results.is_empty());
+        assert!(results.iter().all(|r| r.path.starts_with("billing/")));
+    }
+
+    #[test]
+    fn test_grep_regex() {
+        let fs = make_fs();
+        let results = fs.grep("reset|refund", None).unwrap();
+        assert!(results.len() >= 2);
+    }
+
+    #[test]
+    fn test_grep_case_insensitive() {
+        let fs = make_fs();
+        let results = fs.grep("PASSWORD", None).unwrap();
+        assert!(!results.is_empty());
+    }
+
+    #[test]
+    fn test_read_full() {
+        let fs = make_fs();
+        let content = fs.read("billing/refund.md", None, None).unwrap();
+        assert!(content.contains("# Refunds"));
+        assert!(content.contains("5 business days"));
+    }
+
+    #[test]
+    fn test_read_with_offset_limit() {
+        let fs = make_fs();
+        let content = fs.read("billing/refund.md", Some(3), Some(1)).unwrap();
+        assert!(content.contains("request a refund"));
+        assert!(!content.contains("# Refunds"));
+    }
+
+    #[test]
+    fn test_call_read_tab_width() {
+        let docs = serde_json::to_string(&vec![("cfg.md", "[core]\n\tbare = false")]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let result = fs
+            .call("read", r#"{"path": "cfg.md", "offset": 2, "tab_width": 4}"#)
+            .unwrap();
+        assert_eq!(result, "  2      bare = false");
+
+        let result = fs
+            .call("read_lines", r#"{"path": "cfg.md", "offset": 2, "tab_width": 4}"#)
+            .unwrap();
+        assert_eq!(result, r#"[{"number":2,"text":"    bare = false"}]"#);
+    }
+
+    #[test]
+    fn test_read_focused() {
+        let docs = serde_json::to_string(&vec![(
+            "guide.md",
+            "# Guide\nIntro text\n## Install\nRun the installer\n## Configure\nEdit the config\n### Advanced\nTweak flags\n## Uninstall\nRemove it",
+        )]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let out = fs.read_focused("guide.md", "configure").unwrap();
+        // Full outline for orientation
+        assert!(out.contains("# Guide (line 1)"));
+        assert!(out.contains("  ## Install (line 3)"));
+        assert!(out.contains("    ### Advanced (line 7)"));
+        assert!(out.contains("  ## Uninstall (line 9)"));
+        // Only the target section's body
+        assert!(out.contains("  5  ## Configure"));
+        assert!(out.contains("  6  Edit the config"));
+        assert!(out.contains("  8  Tweak flags"));
+        assert!(!out.contains("Run the installer"));
+        assert!(!out.contains("Remove it"));
+        assert!(!out.contains("Intro text"));
+
+        let err = fs.read_focused("guide.md", "nope").unwrap_err();
+        assert!(err.to_string().contains("section not found"));
+    }
+
+    #[test]
+    fn test_outline() {
+        let fs = make_fs();
+        let outline = fs.outline("account/password-reset.md").unwrap();
+        let summary: Vec<_> = outline.iter().map(|h| (h.level, h.text.as_str(), h.line)).collect();
+        assert_eq!(
+            summary,
+            vec![(1, "Password Reset", 1), (2, "How to reset your password", 3)]
+        );
+        assert!(fs.outline("missing.md").is_err());
+        assert!(fs.recently_read(10).is_empty(), "outline is not a read");
+
+        let result = fs.call("outline", r#"{"path": "billing/refund.md"}"#).unwrap();
+        assert_eq!(result, r#"[{"level":1,"text":"Refunds","line":1}]"#);
+    }
+
+    #[test]
+    fn test_read_section() {
+        let fs = make_fs();
+        let result = fs
+            .call("read", r#"{"path": "account/password-reset.md", "section": "How to reset your password"}"#)
+            .unwrap();
+        assert!(result.starts_with("  3  ## How to reset your password"));
+        assert!(result.contains("  6  2. Click Reset Password"));
+        assert!(!result.contains("# Password Reset\n"));
+
+        let err = fs
+            .call("read_lines", r#"{"path": "billing/refund.md", "section": "nope"}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("section not found: nope"));
+    }
+
+    #[test]
+    fn test_read_lines_window() {
+        let fs = make_fs();
+        let lines = fs
+            .read_lines(
+                "billing/refund.md",
+                &ReadOptions {
+                    offset: Some(3),
+                    limit: Some(2),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].number, 3);
+        assert_eq!(lines[0].text, "To request a refund, contact support.");
+        assert_eq!(lines[1].number, 4);
+
+        let result = fs
+            .call("read_lines", r#"{"path": "billing/refund.md", "offset": 5, "limit": 10}"#)
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed.as_array().unwrap().len(), 1);
+        assert_eq!(parsed[0]["number"], 5);
+        assert_eq!(parsed[0]["text"], "Refunds are processed within 5 business days.");
+    }
+
+    #[test]
+    fn test_recently_read() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "# A"),
+            ("b.md", "# B"),
+            ("c.md", "# C"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        assert!(fs.recently_read(5).is_empty());
+
+        fs.read("a.md", None, None).unwrap();
+        fs.read("b.md", None, None).unwrap();
+        fs.call("read", r#"{"path": "c.md"}"#).unwrap();
+        fs.read_lines("a.md", &ReadOptions::default()).unwrap();
+        assert!(fs.read("missing.md", None, None).is_err());
+
+        assert_eq!(fs.recently_read(10), vec!["a.md", "c.md", "b.md"]);
+        assert_eq!(fs.recently_read(2), vec!["a.md", "c.md"]);
+    }
+
+    #[test]
+    fn test_read_not_found() {
+        let fs = make_fs();
+        let result = fs.read("nonexistent.md", None, None);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("document not found"));
+    }
+
+    #[test]
+    fn test_read_not_found_suggests_paths() {
+        let fs = make_fs();
+        let err = fs.read("billing/refunds.md", None, None).unwrap_err();
+        assert_eq!(
+            err,
+            MemexError::DocumentNotFound {
+                path: "billing/refunds.md".to_string(),
+                suggestions: vec!["billing/refund.md".to_string()],
+            }
+        );
+        assert!(err.to_string().ends_with("(did you mean: billing/refund.md?)"));
+
+        // Right file name, wrong directory
+        let err = fs.outline("support/Password-Reset.md").unwrap_err();
+        assert_eq!(err.to_json()["suggestions"], serde_json::json!(["account/password-reset.md"]));
+
+        let err = fs.read("nothing/like/it.txt", None, None).unwrap_err();
+        assert_eq!(err.to_json()["suggestions"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_call_envelope() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "refund one\nrefund two\nrefund three"),
+            ("b.md", "# Title\nbody"),
+        ]).unwrap();
+        let options = MemexOptions { call_envelope: true, ..Default::default() };
+        let mut fs = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+        let call = |fs: &mut MemexFsCore, name: &str, params: &str| -> CallEnvelope {
+            serde_json::from_str(&fs.call(name, params).unwrap()).unwrap()
+        };
+
+        let capped = call(&mut fs, "grep", r#"{"pattern": "refund", "max_results": 3}"#);
+        assert!(capped.ok && capped.error.is_none());
+        assert_eq!((capped.truncated, capped.total), (false, Some(3)));
+        assert_eq!(capped.data.unwrap()["results"].as_array().unwrap().len(), 3);
+        let paged = call(&mut fs, "grep", r#"{"pattern": "refund", "max_results": 2}"#);
+        assert_eq!((paged.truncated, paged.total), (true, Some(3)));
+
+        let read = call(&mut fs, "read", r#"{"path": "a.md", "limit": 1}"#);
+        assert_eq!(read.data, Some(serde_json::json!("  1  refund one")));
+        assert_eq!((read.truncated, read.total), (true, Some(3)));
+        let files = call(&mut fs, "grep", r#"{"pattern": "body", "output": "files"}"#);
+        assert_eq!((files.data, files.total), (Some(serde_json::json!(["b.md"])), Some(1)));
+
+        // Errors come back in the envelope rather than failing the call
+        let missing = call(&mut fs, "read", r#"{"path": "c.md"}"#);
+        assert!(!missing.ok && missing.data.is_none());
+        assert_eq!(missing.tool, "read");
+        assert_eq!(missing.error.unwrap()["code"], "document_not_found");
+        let bad = call(&mut fs, "grep", "{not json");
+        assert_eq!(bad.error.unwrap()["code"], "invalid_params");
+
+        // Off by default, and kept by the builder
+        assert!(make_fs().call("read", r#"{"path": "c.md"}"#).is_err());
+        let mut built = MemexFsBuilder::new().call_envelope(true).from_json(&docs).unwrap();
+        assert!(call(&mut built, "ls", r#"{"path": ""}"#).ok);
+    }
+
+    #[test]
+    fn test_call_batch() {
+        let mut fs = make_fs();
+        let calls = r#"[
+            {"name": "write", "params": {"path": "notes/a.md", "content": "refund notes"}},
+            {"name": "grep", "params": {"pattern": "refund", "output": "files"}},
+            {"name": "read", "params": {"path": "missing.md"}},
+            {"name": "extensions"}
+        ]"#;
+        let envelopes: Vec<CallEnvelope> = serde_json::from_str(&fs.call_batch(calls).unwrap()).unwrap();
+        assert_eq!(envelopes.len(), 4);
+        assert_eq!(envelopes[0].data, Some(serde_json::json!("Created notes/a.md")));
+        // Later calls see earlier writes
+        assert_eq!(
+            envelopes[1].data,
+            Some(serde_json::json!(["billing/refund.md", "notes/a.md"]))
+        );
+        assert!(!envelopes[2].ok);
+        assert_eq!(envelopes[2].error.as_ref().unwrap()["code"], "document_not_found");
+        assert!(envelopes[3].ok);
+
+        assert_eq!(fs.call_batch("{}").unwrap_err().code(), "invalid_params");
+        assert_eq!(fs.call_batch("[]").unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_tool_definitions_openai() {
+        let fs = make_fs();
+        let tools = fs.tool_definitions_openai_value();
+        assert_eq!(tools.as_array().unwrap().len(), fs.tool_definitions_value().as_array().unwrap().len());
+
+        let grep = &tools[0];
+        assert_eq!(grep["type"], "function");
+        assert_eq!(grep["function"]["strict"], true);
+        let params = &grep["function"]["parameters"];
+        assert_eq!(params["additionalProperties"], false);
+        let properties = params["properties"].as_object().unwrap();
+        assert_eq!(params["required"].as_array().unwrap().len(), properties.len());
+        assert!(!properties.contains_key("metadata"));
+        assert_eq!(
+            properties["pattern"]["anyOf"],
+            serde_json::json!([{ "type": "string" }, { "type": "array", "items": { "type": "string" } }])
+        );
+        assert_eq!(properties["glob"]["type"], serde_json::json!(["string", "null"]));
+        assert_eq!(properties["sort"]["enum"], serde_json::json!(["path", "relevance", null]));
+
+        // What a strict model sends for the optional parameters it leaves unset
+        let strict_call = r#"{"pattern": "refund", "glob": null, "output": null, "max_results": null}"#;
+        assert_eq!(fs.call("grep", strict_call).unwrap(), fs.call("grep", r#"{"pattern": "refund"}"#).unwrap());
+    }
+
+    #[test]
+    fn test_call_dispatch() {
+        let fs = make_fs();
+        let result = fs.call("grep", r#"{"pattern": "refund"}"#).unwrap();
+        assert!(result.contains("refund"));
+
+        let result = fs
+            .call("read", r#"{"path": "billing/refund.md"}"#)
+            .unwrap();
+        assert!(result.contains("# Refunds"));
+    }
+
+    #[test]
+    fn test_read_json_tool() {
+        let docs = serde_json::to_string(&vec![
+            ("config/app.json", r#"{"server": {"port": 8080, "hosts": ["a.internal", "b.internal"]}}"#),
+            ("config/README.md", "See app.json"),
+        ])
+        .unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        assert_eq!(fs.read_json("config/app.json", "/server/port").unwrap(), 8080);
+        let result = fs.call("read_json", r#"{"path": "config/app.json", "pointer": "$.server.hosts[-1]"}"#);
+        assert_eq!(result.unwrap(), r#""b.internal""#);
+        let whole = fs.call("read_json", r#"{"path": "config/app.json"}"#).unwrap();
+        assert!(whole.starts_with(r#"{"server":"#));
+
+        let err = fs.read_json("config/app.json", "$.server.tls").unwrap_err();
+        assert_eq!(err.to_string(), "MemexError: no JSON value at $.server.tls (/server has keys: hosts, port)");
+        let err = fs.call("read_json", r#"{"path": "config/README.md", "pointer": ""}"#).unwrap_err();
+        assert_eq!(err.code(), "not_json");
+    }
+
+    #[test]
+    fn test_query_table_tool() {
+        let docs = serde_json::to_string(&vec![
+            ("pricing/plans.csv", "plan,price,seats\nFree,$0,1\nPro,$49,10\nTeam,$199,50"),
+            ("pricing/README.md", "See plans.csv"),
+        ])
+        .unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let query = TableQuery {
+            filter: vec!["price > 0".to_string()],
+            columns: vec!["plan".to_string()],
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = fs.query_table("pricing/plans.csv", &query).unwrap();
+        assert_eq!(page.rows, vec![TableRow { line: 3, values: vec!["Pro".to_string()] }]);
+        assert_eq!((page.total_rows, page.next_offset), (2, Some(1)));
+
+        let result = fs
+            .call("query_table", r#"{"path": "pricing/plans.csv", "filter": ["seats >= 10"], "columns": ["plan", "seats"], "offset": 1}"#)
+            .unwrap();
+        assert_eq!(
+            result,
+            r#"{"columns":["plan","seats"],"rows":[{"line":4,"values":["Team","50"]}],"total_rows":2}"#
+        );
+
+        let err = fs.call("query_table", r#"{"path": "pricing/README.md"}"#).unwrap_err();
+        assert_eq!(err.code(), "not_a_table");
+        let err = fs.query_table("pricing/plan.csv", &TableQuery::default()).unwrap_err();
+        assert_eq!(err.code(), "document_not_found");
+    }
+
+    #[test]
+    fn test_call_unknown_tool() {
+        let fs = make_fs();
+        let result = fs.call("delete", r#"{}"#);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("unknown tool"));
+    }
+
+    #[test]
+    fn test_tool_definitions() {
+        let fs = make_fs();
+        let defs = fs.tool_definitions();
+        let parsed: serde_json::Value = serde_json::from_str(&defs).unwrap();
+        assert!(parsed.is_array());
+        assert_eq!(parsed.as_array().unwrap().len(), 13);
+    }
+
+    #[test]
+    fn test_empty_docs() {
+        let result = MemexFsCore::from_json("[]");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("no documents"));
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        let result = MemexFsCore::from_json("not json");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_document_count() {
+        let fs = make_fs();
+        assert_eq!(fs.document_count(), 2);
+    }
+
+    #[test]
+    fn test_case_folding_symmetric() {
+        let words = ["ÅNGSTRÖM", "Sísyphus", "ΣΊΣΥΦΟΣ", "Straße", "İstanbul", "CamelCase42"];
+        let docs: Vec<(String, String)> = words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (format!("doc_{}.md", i), format!("About {} here", w)))
+            .collect();
+        let fs = MemexFsCore::from_json(&serde_json::to_string(&docs).unwrap()).unwrap();
+
+        for (i, word) in words.iter().enumerate() {
+            let path = format!("doc_{}.md", i);
+            // Index tokens and query normalization agree
+            for token in index::tokenize(word) {
+                assert!(fs.store.index().lookup(&token).is_some(), "{:?} not indexed", token);
+            }
+            // As written, folded, and via the scan path all find the same line
+            for query in [word.to_string(), fold_case(word), format!("about {}", word)] {
+                let results = fs.grep(&query, None).unwrap();
+                let paths: Vec<&str> = results.iter().map(|r| r.path.as_str()).collect();
+                assert_eq!(paths, vec![path.as_str()], "query {:?}", query);
+            }
+        }
+    }
+
+    #[test]
+    fn test_grep_whole_word_and_case_sensitive() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "The cat sat\nCategory list\nconcatenate\nCAT scan"),
+            ("b.md", "Use the API key\nrapid api calls"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let lines = |pattern: &str, options: &GrepOptions| -> Vec<(String, u32)> {
+            fs.grep_with_options(pattern, options)
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.path, r.line))
+                .collect()
+        };
+        let at = |path: &str, line: u32| (path.to_string(), line);
+
+        assert_eq!(lines("cat", &GrepOptions::default()).len(), 4);
+
+        let whole = GrepOptions {
+            whole_word: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(lines("cat", &whole), vec![at("a.md", 1), at("a.md", 4)]);
+        assert_eq!(lines("the cat", &whole), vec![at("a.md", 1)]);
+        assert_eq!(lines("ca.", &whole), vec![at("a.md", 1), at("a.md", 4)]);
+
+        let exact = GrepOptions {
+            case_sensitive: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(lines("API", &exact), vec![at("b.md", 1)]);
+        assert_eq!(lines("cat", &exact), vec![at("a.md", 1), at("a.md", 3)]);
+        assert_eq!(lines("C.T", &exact), vec![at("a.md", 4)]);
+
+        let both = GrepOptions {
+            whole_word: Some(true),
+            case_sensitive: Some(true),
+            snippet_radius: Some(3),
+            ..Default::default()
+        };
+        let results = fs.grep_with_options("CAT", &both).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].snippet.as_deref(), Some("CAT sc..."));
+    }
+
+    #[test]
+    fn test_grep_literal() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Edit config.toml first\nconfigXtoml is a typo\nCall foo(bar) here"),
+            ("b.md", "Use [brackets] and a+b"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let literal = GrepOptions {
+            literal: Some(true),
+            ..Default::default()
+        };
+
+        // Without literal, '.' is a wildcard and '(' starts a group
+        assert_eq!(fs.grep("config.toml", None).unwrap().len(), 2);
+        assert!(fs.grep("foo(bar)", None).unwrap().is_empty());
+
+        let lines = |fs: &MemexFsCore, pattern: &str| -> Vec<u32> {
+            fs.grep_with_options(pattern, &literal).unwrap().iter().map(|r| r.line).collect()
+        };
+        assert_eq!(lines(&fs, "CONFIG.toml"), vec![1]);
+        assert_eq!(lines(&fs, "foo(bar)"), vec![3]);
+        assert_eq!(lines(&fs, "[brackets]"), vec![1]);
+        assert_eq!(lines(&fs, "a+b"), vec![1]);
+
+        let result = fs
+            .call("grep", r#"{"pattern": "[unclosed", "literal": true}"#)
+            .unwrap();
+        assert!(result.contains(r#""total_matches":0"#));
+    }
+
+    #[test]
+    fn test_grep_column_and_spans() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Refund: the refund is a REFUND\nno match"),
+            ("b.md", "Ünïcode refund"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let results = fs.grep("refund", None).unwrap();
+        assert_eq!(results[0].column, 1);
+        assert!(results[0].spans.is_none());
+        assert_eq!(results[1].column, 9);
+
+        let options = GrepOptions {
+            spans: Some(true),
+            ..Default::default()
+        };
+        let results = fs.grep_with_options("refund", &options).unwrap();
+        assert_eq!(results[0].spans, Some(vec![(0, 6), (12, 18), (24, 30)]));
+        // Offsets count characters, not bytes
+        assert_eq!(results[1].spans, Some(vec![(8, 14)]));
+
+        let results = fs.grep_with_options("the r.f", &options).unwrap();
+        assert_eq!(results[0].column, 9);
+        assert_eq!(results[0].spans, Some(vec![(8, 15)]));
+    }
+
+    #[test]
+    fn test_grep_pagination() {
+        let docs: Vec<(String, String)> = (0..5)
+            .map(|i| (format!("doc_{}.md", i), "keyword one\nkeyword two".to_string()))
+            .collect();
+        let fs = MemexFsCore::from_json(&serde_json::to_string(&docs).unwrap()).unwrap();
+
+        let mut options = GrepOptions {
+            max_results: Some(4),
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        loop {
+            let page = fs.grep_page("keyword", &options).unwrap();
+            assert_eq!(page.total_matches, 10);
+            assert!(page.results.len() <= 4);
+            seen.extend(page.results.into_iter().map(|r| (r.path, r.line)));
+            match page.next_offset {
+                Some(next) => options.offset = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen.len(), 10);
+        assert_eq!(seen[4], ("doc_2.md".to_string(), 1));
+
+        // Past the end: empty page, total still reported
+        options.offset = Some(50);
+        let page = fs.grep_page("key.ord", &options).unwrap();
+        assert!(page.results.is_empty());
+        assert_eq!(page.total_matches, 10);
+        assert_eq!(page.next_offset, None);
+
+        // A zero-size page counts matches without a cursor to loop on
+        options.offset = None;
+        options.max_results = Some(0);
+        let page = fs.grep_page("keyword", &options).unwrap();
+        assert!(page.results.is_empty());
+        assert_eq!(page.total_matches, 10);
+        assert_eq!(page.next_offset, None);
+    }
+
+    #[test]
+    fn test_grep_any_pattern() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Request a Refund\nOpen a CHARGEBACK\nnothing here\nreimbursements take a week"),
+            ("b.md", "Refundable deposit"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let patterns: Vec<String> = vec!["refund".into(), "chargeback".into(), "reimbursement".into()];
+        let page = fs.grep_any_page(&patterns, &GrepOptions::default()).unwrap();
+        let hits: Vec<_> = page.results.iter().map(|r| (r.path.as_str(), r.line, r.column)).collect();
+        assert_eq!(hits, vec![("a.md", 1, 11), ("a.md", 2, 8), ("a.md", 4, 1), ("b.md", 1, 1)]);
+
+        let options = GrepOptions {
+            whole_word: Some(true),
+            case_sensitive: Some(true),
+            spans: Some(true),
+            ..Default::default()
+        };
+        let patterns: Vec<String> = vec!["Refund".into(), "CHARGEBACK".into()];
+        let page = fs.grep_any_page(&patterns, &options).unwrap();
+        assert_eq!(page.total_matches, 2);
+        assert_eq!(page.results[1].spans, Some(vec![(7, 17)]));
+
+        // Metacharacters are literal
+        let patterns: Vec<String> = vec!["a.m".into()];
+        assert_eq!(fs.grep_any_page(&patterns, &GrepOptions::default()).unwrap().total_matches, 0);
+        assert!(fs.grep_any_page(&[], &GrepOptions::default()).is_err());
+
+        let result = fs
+            .call("grep", r#"{"pattern": ["deposit", "week"]}"#)
+            .unwrap();
+        let parsed: GrepResponse = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed.total_matches, 2);
+    }
+
+    #[test]
+    fn test_grep_all_terms() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "# Passwords\nHow to reset your password\nPassword rules"),
+            ("b.md", "Reset the router\nChange the Wi-Fi password"),
+            ("c.md", "password reset link"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        // Exact mode misses the reordered phrase
+        assert_eq!(fs.grep("password reset", None).unwrap().len(), 1);
+
+        let mut options = GrepOptions {
+            mode: Some(GrepMode::AllTerms),
+            ..Default::default()
+        };
+        let page = fs.grep_page("password reset", &options).unwrap();
+        let hits: Vec<_> = page.results.iter().map(|r| (r.path.as_str(), r.line)).collect();
+        assert_eq!(hits, vec![("a.md", 2), ("c.md", 1)]);
+        assert_eq!(page.results[0].column, 8, "column of the first term found");
+
+        options.within = Some(TermsWithin::Document);
+        let page = fs.grep_page("password reset", &options).unwrap();
+        let hits: Vec<_> = page.results.iter().map(|r| (r.path.as_str(), r.line)).collect();
+        assert_eq!(
+            hits,
+            vec![("a.md", 1), ("a.md", 2), ("a.md", 3), ("b.md", 1), ("b.md", 2), ("c.md", 1)]
+        );
+
+        options.within = None;
+        options.case_sensitive = Some(true);
+        assert!(fs.grep_page("Password reset", &options).unwrap().results.is_empty());
+        assert!(fs.grep_page("  ?? ", &options).is_err());
+
+        let result = fs
+            .call("grep", r#"{"pattern": "reset password", "mode": "all_terms", "within": "document"}"#)
+            .unwrap();
+        let parsed: GrepResponse = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed.total_matches, 6);
+    }
+
+    #[test]
+    fn test_grep_near() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Refunds are issued before the deadline
+The deadline for a refund of any kind is far from here"),
+            ("b.md", "Request a refund
+within 30 days.
+Late? The deadline passed."),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let hits = |page: GrepResponse| -> Vec<(String, u32)> {
+            page.results.into_iter().map(|r| (r.path, r.line)).collect()
+        };
+        let mut options = GrepOptions {
+            mode: Some(GrepMode::Near),
+            ..Default::default()
+        };
+
+        // "Refunds" is 5 tokens from "deadline" on line 1, "refund" 3 on line 2
+        let near = hits(fs.grep_page("refund NEAR/4 deadline", &options).unwrap());
+        assert_eq!(near, vec![("a.md".to_string(), 2)]);
+        let near = hits(fs.grep_page("refund NEAR deadline", &options).unwrap());
+        assert_eq!(near, vec![("a.md".to_string(), 1), ("a.md".to_string(), 2)]);
+
+        options.whole_word = Some(true);
+        let near = hits(fs.grep_page("refund NEAR deadline", &options).unwrap());
+        assert_eq!(near, vec![("a.md".to_string(), 2)]);
+
+        // Windows across lines: refund at token 2 and deadline at 8 in b.md
+        options.whole_word = None;
+        options.within = Some(TermsWithin::Document);
+        let near = hits(fs.grep_page("refund NEAR/5 deadline", &options).unwrap());
+        assert!(near.iter().all(|(path, _)| path == "a.md"));
+        let near = hits(fs.grep_page("refund NEAR/6 deadline", &options).unwrap());
+        assert_eq!(
+            near,
+            vec![("a.md".to_string(), 1), ("a.md".to_string(), 2), ("b.md".to_string(), 1), ("b.md".to_string(), 3)]
+        );
+
+        assert!(fs.grep_page("refund deadline", &options).is_err());
+        assert!(fs.grep_page("refund NEAR/x deadline", &options).is_err());
+
+        let result = fs
+            .call("grep", r#"{"pattern": "deadline NEAR/2 passed", "mode": "near"}"#)
+            .unwrap();
+        let parsed: GrepResponse = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed.total_matches, 1);
+        assert_eq!(parsed.results[0].column, 11);
+    }
+
+    #[test]
+    fn test_grep_fuzzy() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Reset your Password\npassword rules\nPassport photos"),
+            ("b.md", "The cat sat\nA car park"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let fuzzy = GrepOptions {
+            fuzzy: Some(true),
+            ..Default::default()
+        };
+        let hits = |page: GrepResponse| -> Vec<(String, u32)> {
+            page.results.into_iter().map(|r| (r.path, r.line)).collect()
+        };
+
+        assert!(fs.grep("pasword", None).unwrap().is_empty());
+        let page = fs.grep_page("pasword", &fuzzy).unwrap();
+        assert_eq!(page.results[0].column, 12);
+        assert_eq!(hits(page), vec![("a.md".to_string(), 1), ("a.md".to_string(), 2)]);
+
+        // One edit for short words; very short ones are not fuzzy
+        assert_eq!(hits(fs.grep_page("cst", &fuzzy).unwrap()), vec![("b.md".to_string(), 1)]);
+        assert_eq!(
+            hits(fs.grep_page("ca", &fuzzy).unwrap()),
+            fs.grep("ca", None).unwrap().into_iter().map(|r| (r.path, r.line)).collect::<Vec<_>>()
+        );
+
+        let result = fs.call("grep", r#"{"pattern": "pasport", "fuzzy": true}"#).unwrap();
+        let parsed: GrepResponse = serde_json::from_str(&result).unwrap();
+        assert_eq!((parsed.total_matches, parsed.results[0].line), (1, 3));
+    }
+
+    #[test]
+    fn test_grep_suggest() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Invoicing runs nightly
+See your invoices
+Invoices are emailed"),
+            ("b.md", "Refund policy"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let suggest = GrepOptions {
+            suggest: Some(true),
+            ..Default::default()
+        };
+
+        let page = fs.grep_page("invoicng", &suggest).unwrap();
+        assert_eq!(page.total_matches, 0);
+        assert_eq!(page.suggestions, vec!["invoicing", "invoices"]);
+        // Only unknown words get suggestions, and only when asked
+        assert_eq!(fs.grep_page("refund polcy", &suggest).unwrap().suggestions, vec!["policy"]);
+        assert!(fs.grep_page("invoicng", &GrepOptions::default()).unwrap().suggestions.is_empty());
+        assert!(fs.grep_page("refund", &suggest).unwrap().suggestions.is_empty());
+
+        let result = fs.call("grep", r#"{"pattern": "refnd", "suggest": true, "output": "grouped"}"#).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed["suggestions"], serde_json::json!(["refund"]));
+        let result = fs.call("grep", r#"{"pattern": "refund", "suggest": true}"#).unwrap();
+        assert!(!result.contains("suggestions"));
+    }
+
+    #[test]
+    fn test_grep_wildcard() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Deploy the app\nDeployment guide\nredeploy after changes"),
+            ("b.md", "Rolling deploys\nThe deployer service"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let hits = |results: Vec<GrepResult>| -> Vec<(String, u32)> {
+            results.into_iter().map(|r| (r.path, r.line)).collect()
+        };
+
+        let results = fs.grep("deploy*", None).unwrap();
+        assert_eq!(results[3].column, 5);
+        assert_eq!(
+            hits(results),
+            vec![("a.md".to_string(), 1), ("a.md".to_string(), 2), ("b.md".to_string(), 1), ("b.md".to_string(), 2)]
+        );
+        assert_eq!(hits(fs.grep("*deploy", None).unwrap()).len(), 2);
+        assert_eq!(hits(fs.grep("deploy*s", None).unwrap()), vec![("b.md".to_string(), 1)]);
+
+        // Still a regex where it has to be
+        let literal = GrepOptions {
+            literal: Some(true),
+            ..Default::default()
+        };
+        assert!(fs.grep_with_options("deploy*", &literal).unwrap().is_empty());
+        assert_eq!(fs.grep("deploy(ment|er)", None).unwrap().len(), 2);
+
+        let result = fs.call("grep", r#"{"pattern": "deploy*", "output": "count"}"#).unwrap();
+        assert_eq!(result, r#"[{"path":"a.md","count":2},{"path":"b.md","count":2}]"#);
+    }
+
+    #[test]
+    fn test_grep_smart_case() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Rust is fast\nrust on metal\nTRUST me"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let smart = GrepOptions {
+            smart_case: Some(true),
+            ..Default::default()
+        };
+        let lines = |page: GrepResponse| page.results.iter().map(|r| r.line).collect::<Vec<_>>();
+
+        assert_eq!(lines(fs.grep_page("rust", &smart).unwrap()), vec![1, 2, 3]);
+        assert_eq!(lines(fs.grep_page("Rust", &smart).unwrap()), vec![1]);
+        // Regex escapes are not uppercase letters
+        assert_eq!(lines(fs.grep_page(r"rust\S*", &smart).unwrap()), vec![1, 2, 3]);
+        assert_eq!(lines(fs.grep_page(r"R\w+", &smart).unwrap()), vec![1, 3]);
+        let explicit = GrepOptions {
+            case_sensitive: Some(false),
+            ..smart.clone()
+        };
+        assert_eq!(lines(fs.grep_page("Rust", &explicit).unwrap()), vec![1, 2, 3]);
+        let any = vec!["TRUST".to_string(), "metal".to_string()];
+        assert_eq!(lines(fs.grep_any_page(&any, &smart).unwrap()), vec![2, 3]);
+
+        let result = fs.call("grep", r#"{"pattern": "Rust", "smart_case": true, "output": "count"}"#).unwrap();
+        assert_eq!(result, r#"[{"path":"a.md","count":1}]"#);
+    }
+
+    #[test]
+    fn test_grep_multiline() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "## Refunds\n\nContact billing\nfoo\nfn main() {\n}"),
+            ("b.md", "## Refunds\nSee policy"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let multiline = GrepOptions {
+            multiline: Some(true),
+            ..Default::default()
+        };
+
+        let page = fs.grep_page(r"## Refunds\n+Contact", &multiline).unwrap();
+        assert_eq!(page.total_matches, 1);
+        let r = &page.results[0];
+        assert_eq!((r.path.as_str(), r.line, r.line_count), ("a.md", 1, Some(3)));
+        assert_eq!(r.content, "## Refunds\n\nContact billing");
+
+        // A trailing line break does not pull in the next line
+        let page = fs.grep_page(r"foo\n", &multiline).unwrap();
+        assert_eq!((page.results[0].line, page.results[0].line_count), (4, Some(1)));
+
+        let literal = GrepOptions {
+            literal: Some(true),
+            ..multiline.clone()
+        };
+        let page = fs.grep_page("{\n}", &literal).unwrap();
+        assert_eq!((page.results[0].line, page.results[0].line_count), (5, Some(2)));
+
+        let result = fs.call("grep", r#"{"pattern": "Refunds\\n\\w", "multiline": true, "output": "files"}"#).unwrap();
+        assert_eq!(result, r#"["b.md"]"#);
+    }
+
+    #[test]
+    fn test_grep_multiline_empty_document() {
+        let fs = MemexFsCore::from_json(r#"[["empty.md", ""], ["a.md", "hello"]]"#).unwrap();
+        let multiline = GrepOptions {
+            multiline: Some(true),
+            ..Default::default()
+        };
+        for pattern in ["^", "x*", "$"] {
+            let page = fs.grep_page(pattern, &multiline).unwrap();
+            let paths: Vec<&str> = page.results.iter().map(|r| r.path.as_str()).collect();
+            assert_eq!(paths, ["a.md"], "{:?}", pattern);
+        }
+        let result = fs.call("grep", r#"{"pattern": "x*", "multiline": true, "output": "files"}"#).unwrap();
+        assert_eq!(result, r#"["a.md"]"#);
+    }
+
+    #[cfg(feature = "fancy-regex")]
+    #[test]
+    fn test_grep_lookaround() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Reset your password\nRenew your passport\nthe the typo"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let options = GrepOptions {
+            spans: Some(true),
+            ..Default::default()
+        };
+
+        let page = fs.grep_page(r"pass(?!word)\w+", &options).unwrap();
+        assert_eq!(page.results.iter().map(|r| r.line).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(page.results[0].spans, Some(vec![(11, 19)]));
+        let page = fs.grep_page(r"\b(\w+) \1\b", &options).unwrap();
+        assert_eq!((page.results[0].line, page.results[0].column), (3, 1));
+    }
+
+    #[test]
+    fn test_grep_exclude() {
+        let docs = serde_json::to_string(&vec![
+            ("auth.md", "Use an API token\nThe session token is deprecated\nToken (legacy) rotation\ntokens expire"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let exclude = |pattern: &str| GrepOptions {
+            exclude: Some(pattern.into()),
+            ..Default::default()
+        };
+        let lines = |page: GrepResponse| page.results.iter().map(|r| r.line).collect::<Vec<_>>();
+
+        // Index path
+        assert_eq!(lines(fs.grep_page("token", &exclude("DEPRECATED")).unwrap()), vec![1, 3, 4]);
+        // Scan path, with a literal exclusion containing metacharacters
+        let options = GrepOptions {
+            literal: Some(true),
+            ..exclude("(legacy)")
+        };
+        assert_eq!(lines(fs.grep_page("token", &options).unwrap()), vec![1, 2, 4]);
+        // Regex path, with a regex exclusion
+        let page = fs.grep_page("tokens?", &exclude("deprecated|expire")).unwrap();
+        assert_eq!(lines(page), vec![1, 3]);
+        // Multi-pattern path
+        let patterns: Vec<String> = vec!["api".into(), "session".into()];
+        assert_eq!(lines(fs.grep_any_page(&patterns, &exclude("deprecated")).unwrap()), vec![1]);
+
+        assert!(fs.grep_page("token", &exclude("(")).is_err());
+
+        let result = fs.call("grep", r#"{"pattern": "token", "exclude": "session"}"#).unwrap();
+        let parsed: GrepResponse = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed.total_matches, 3);
+    }
+
+    #[test]
+    fn test_grep_max_per_file_interleaves() {
+        let chatty: Vec<String> = (0..150).map(|i| format!("refund {i}")).collect();
+        let chatty = chatty.join("\n");
+        let docs = serde_json::to_string(&vec![
+            ("a.md", chatty.as_str()),
+            ("b.md", "refund b1\nrefund b2"),
+            ("c.md", "refund c1"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        // Without a cap the chatty document fills the default page
+        let page = fs.grep_page("refund", &GrepOptions::default()).unwrap();
+        assert!(page.results.iter().all(|r| r.path == "a.md"));
+
+        let options = GrepOptions {
+            max_per_file: Some(2),
+            max_results: Some(4),
+            ..Default::default()
+        };
+        let page = fs.grep_page("refund", &options).unwrap();
+        let order: Vec<(&str, u32)> = page.results.iter().map(|r| (r.path.as_str(), r.line)).collect();
+        assert_eq!(order, vec![("a.md", 1), ("b.md", 1), ("c.md", 1), ("a.md", 2)]);
+        assert_eq!(page.total_matches, 5);
+        assert_eq!(page.next_offset, Some(4));
+
+        let options = GrepOptions { offset: Some(4), ..options };
+        let page = fs.grep_page("ref.nd", &options).unwrap();
+        assert_eq!(page.results.len(), 1);
+        assert_eq!((page.results[0].path.as_str(), page.results[0].line), ("b.md", 2));
+
+        let grouped = fs
+            .call("grep", r#"{"pattern": ["b1", "b2", "c1"], "max_per_file": 1, "output": "grouped"}"#)
+            .unwrap();
+        let grouped: GroupedGrepResponse = serde_json::from_str(&grouped).unwrap();
+        assert_eq!(grouped.total_matches, 2);
+        let groups: Vec<(&str, usize)> =
+            grouped.groups.iter().map(|g| (g.path.as_str(), g.matches.len())).collect();
+        assert_eq!(groups, vec![("b.md", 1), ("c.md", 1)]);
+
+        // Matches past the cap are dropped as they arrive, not kept for later
+        let options = GrepOptions { max_per_file: Some(2), ..Default::default() };
+        let mut pager = Pager::new(&options, &PatternCache::default()).unwrap();
+        for (path, line) in [("a.md", 1), ("a.md", 2), ("a.md", 3), ("b.md", 1)] {
+            pager.push(path, || GrepResult::new(path.to_string(), line, String::new()));
+        }
+        let kept: Vec<(&str, u32)> = pager.results.iter().map(|r| (r.path.as_str(), r.line)).collect();
+        assert_eq!(kept, vec![("a.md", 1), ("a.md", 2), ("b.md", 1)]);
+        assert_eq!(pager.total, 3);
+    }
+
+    #[test]
+    fn test_grep_output_files_and_count() {
+        let docs = serde_json::to_string(&vec![
+            ("b.md", "refund one\nrefund two\nother"),
+            ("a.md", "refund"),
+            ("c.md", "nothing"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let counts = fs.grep_counts("refund", &GrepOptions::default()).unwrap();
+        assert_eq!(
+            counts,
+            vec![
+                FileCount { path: "a.md".into(), count: 1 },
+                FileCount { path: "b.md".into(), count: 2 },
+            ]
+        );
+        // Paging does not apply
+        let options = GrepOptions {
+            max_results: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(fs.grep_counts("ref.nd", &options).unwrap().len(), 2);
+
+        let files = fs.call("grep", r#"{"pattern": "refund", "output": "files"}"#).unwrap();
+        assert_eq!(files, r#"["a.md","b.md"]"#);
+        let count = fs
+            .call("grep", r#"{"pattern": ["two", "nothing"], "output": "count"}"#)
+            .unwrap();
+        assert_eq!(count, r#"[{"path":"b.md","count":1},{"path":"c.md","count":1}]"#);
+        let lines = fs.call("grep", r#"{"pattern": "refund", "output": "lines"}"#).unwrap();
+        assert!(lines.contains(r#""total_matches":3"#));
+    }
+
+    #[test]
+    fn test_grep_sort_relevance() {
+        let filler = "filler\n".repeat(50);
+        let docs = serde_json::to_string(&vec![
+            ("a/notes.md".to_string(), format!("# Notes\n{}see refunds below", filler)),
+            ("b/guide.md".to_string(), format!("# Guide\n{}## Refunds\nbody", filler)),
+            ("c/refunds.md".to_string(), format!("# Policy\n{}refunds again", filler)),
+        ])
+        .unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let plain = fs.grep("refunds", None).unwrap();
+        assert_eq!(plain[0].path, "a/notes.md");
+
+        let mut options = GrepOptions {
+            sort: Some(GrepSort::Relevance),
+            ..Default::default()
+        };
+        let ranked = fs.grep_page("refunds", &options).unwrap();
+        let order: Vec<_> = ranked.results.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(order, vec!["c/refunds.md", "b/guide.md", "a/notes.md"]);
+
+        // Paging applies after ranking
+        options.max_results = Some(1);
+        options.offset = Some(1);
+        let page = fs.grep_page("refunds", &options).unwrap();
+        assert_eq!(page.results[0].path, "b/guide.md");
+        assert_eq!(page.total_matches, 3);
+        assert_eq!(page.next_offset, Some(2));
+    }
+
+    #[test]
+    fn test_grep_max_results() {
+        // Build a filesystem with many matching lines
+        let mut docs = Vec::new();
+        for i in 0..200 {
+            docs.push((
+                format!("doc_{}.md", i),
+                "keyword match here\nkeyword match again".to_string(),
+            ));
+        }
+        let json = serde_json::to_string(&docs).unwrap();
+        let fs = MemexFsCore::from_json(&json).unwrap();
+        let results = fs.grep("keyword", None).unwrap();
+        assert_eq!(results.len(), 100); // capped at max
+    }
+
+    // Bug reproduction: substring matching
+    #[test]
+    fn test_grep_substring_in_token() {
+        // "arch" is a substring of "archive" but not a standalone token
+        let docs = serde_json::to_string(&vec![
+            ("test.md", "This is an archive of data"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let results = fs.grep("arch", None).unwrap();
+        assert!(!results.is_empty(), "should find 'arch' inside 'archive'");
+    }
+
+    #[test]
+    fn test_grep_numeric_substring_in_compound_token() {
+        // "559571" embedded in "SE559571232301" — mimics org number in identifier
+        let docs = serde_json::to_string(&vec![
+            ("org.md", "Company registered as SE559571232301 in Sweden"),
+            ("other.md", "Reference number 559571 standalone"),
+            ("unrelated.md", "No match here"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let results = fs.grep("559571", None).unwrap();
+        assert_eq!(results.len(), 2, "should match both embedded and standalone");
+        assert!(results.iter().any(|r| r.path == "org.md"), "should find embedded match");
+        assert!(results.iter().any(|r| r.path == "other.md"), "should find standalone match");
+    }
+
+    #[test]
+    fn test_grep_multi_word_substring_fallback() {
+        // Multi-word pattern where first token hits index but full phrase also
+        // needs substring matching across documents
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "hackathon in sekoya was great"),
+            ("b.md", "The sekoya hackathon event"),
+            ("c.md", "No match here"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let results = fs.grep("hackathon in sekoya", None).unwrap();
+        assert_eq!(results.len(), 1, "exact phrase only in a.md");
+        assert_eq!(results[0].path, "a.md");
+    }
+
+    #[test]
+    fn test_ls_root() {
+        let fs = make_fs();
+        let entries = fs.ls("");
+        assert_eq!(entries, vec!["account/", "billing/"]);
+    }
+
+    #[test]
+    fn test_ls_root_dot() {
+        let fs = make_fs();
+        let entries = fs.ls(".");
+        assert_eq!(entries, vec!["account/", "billing/"]);
+    }
+
+    #[test]
+    fn test_ls_subdirectory() {
+        let fs = make_fs();
+        let entries = fs.ls("account");
+        assert_eq!(entries, vec!["password-reset.md"]);
+    }
+
+    #[test]
+    fn test_ls_subdirectory_trailing_slash() {
+        let fs = make_fs();
+        let entries = fs.ls("account/");
+        assert_eq!(entries, vec!["password-reset.md"]);
+    }
+
+    #[test]
+    fn test_ls_empty_directory() {
+        let fs = make_fs();
+        let entries = fs.ls("nonexistent");
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn test_ls_nested() {
+        let docs = serde_json::to_string(&vec![
+            ("a/b/c.md", "content"),
+            ("a/b/d.md", "content"),
+            ("a/e.md", "content"),
+            ("f.md", "content"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let root = fs.ls("");
+        assert_eq!(root, vec!["a/", "f.md"]);
+
+        let a = fs.ls("a");
+        assert_eq!(a, vec!["b/", "e.md"]);
+
+        let ab = fs.ls("a/b");
+        assert_eq!(ab, vec!["c.md", "d.md"]);
+    }
+
+    #[test]
+    fn test_ls_detailed() {
+        let docs = serde_json::to_string(&vec![
+            ("a/b/c.md", "one\ntwo"),
+            ("a/b/d.md", "content"),
+            ("a/e.md", "content"),
+            ("f.md", "héllo\nworld\n!"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let root = fs.ls_detailed("");
+        assert_eq!(root[0].name, "a/");
+        assert_eq!(root[0].kind, NodeKind::Dir);
+        assert_eq!(root[0].children, Some(3));
+        assert_eq!(root[0].lines, None);
+        assert_eq!(root[1].name, "f.md");
+        assert_eq!(root[1].kind, NodeKind::File);
+        assert_eq!(root[1].lines, Some(3));
+        assert_eq!(root[1].bytes, Some(14));
+        assert_eq!(root[1].children, None);
+
+        let result = fs.call("ls", r#"{"path": "a", "detailed": true}"#).unwrap();
+        assert_eq!(
+            result,
+            r#"[{"name":"b/","kind":"dir","children":2},{"name":"e.md","kind":"file","lines":1,"bytes":7}]"#
+        );
+    }
+
+    #[test]
+    fn test_call_ls() {
+        let fs = make_fs();
+        let result = fs.call("ls", r#"{"path": ""}"#).unwrap();
+        let entries: Vec<String> = serde_json::from_str(&result).unwrap();
+        assert_eq!(entries, vec!["account/", "billing/"]);
+    }
+
+    #[test]
+    fn test_tool_definitions_includes_ls() {
+        let fs = make_fs();
+        let defs = fs.tool_definitions();
+        let parsed: serde_json::Value = serde_json::from_str(&defs).unwrap();
+        let arr = parsed.as_array().unwrap();
+        assert_eq!(arr.len(), 13);
+        assert!(arr.iter().any(|d| d["name"] == "ls"));
+    }
+
+    #[test]
+    fn test_grep_sentence_snippet() {
+        let docs = serde_json::to_string(&vec![
+            ("policy.md", "Orders ship in two days. Refunds are issued within 5 business days of approval. Contact support for help."),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let options = GrepOptions {
+            snippet: Some(SnippetMode::Sentence),
+            ..Default::default()
+        };
+        let results = fs.grep_with_options("refunds are", &options).unwrap();
+        assert_eq!(results.len(), 1);
+        let snippet = results[0].snippet.as_deref().unwrap();
+        assert_eq!(snippet, "Refunds are issued within 5 business days of approval.");
+        assert!(snippet.starts_with('R') && snippet.ends_with('.'));
+
+        let result = fs
+            .call("grep", r#"{"pattern": "support", "snippet_radius": 8}"#)
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed["results"][0]["snippet"], "...Contact support for hel...");
+
+        // No snippet field unless requested
+        let plain = fs.call("grep", r#"{"pattern": "support"}"#).unwrap();
+        assert!(!plain.contains("snippet"));
+    }
+
+    #[test]
+    fn test_grep_files_file_index() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "refund here\nrefund again\nand again"),
+            ("b.md", "Refunds processed"),
+            ("c.md", "nothing"),
+            ("d/e.md", "partial refund"),
+        ]).unwrap();
+        let plain = MemexFsCore::from_json(&docs).unwrap();
+        let options = MemexOptions {
+            file_index: true,
+            ..Default::default()
+        };
+        let indexed = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+
+        for pattern in ["refund", "REFUNDS", "again", "zzz", "ref", "partial refund", "refunds?"] {
+            let deduped: Vec<String> = plain
+                .grep(pattern, None)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.path)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            assert_eq!(plain.grep_files(pattern, None).unwrap(), deduped, "{:?}", pattern);
+            assert_eq!(indexed.grep_files(pattern, None).unwrap(), deduped, "{:?}", pattern);
+        }
+
+        assert_eq!(indexed.grep_files("refund", Some("d/**")).unwrap(), vec!["d/e.md"]);
+        assert_eq!(
+            indexed.missing_in_glob("refund", "**").unwrap(),
+            plain.missing_in_glob("refund", "**").unwrap()
+        );
+    }
+
+    #[cfg(feature = "unicode-folding")]
+    #[test]
+    fn test_grep_normalizes_and_case_folds() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Meet at the cafe\u{301}: noon"),
+            ("b.md", "Die STRASSE ist gesperrt"),
+            ("c.md", "Café menu"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let paths = |pattern: &str| -> Vec<String> {
+            fs.grep(pattern, None).unwrap().into_iter().map(|r| r.path).collect()
+        };
+
+        // Index path, either spelling of the accent
+        assert_eq!(paths("café"), vec!["a.md", "c.md"]);
+        assert_eq!(paths("cafe\u{301}"), vec!["a.md", "c.md"]);
+        // Scan path for punctuated literals
+        assert_eq!(paths("café:"), vec!["a.md"]);
+        // Full folding: ß matches SS
+        assert_eq!(paths("straße"), vec!["b.md"]);
+        assert_eq!(paths("traße ist"), vec!["b.md"]);
+        assert_eq!(fs.grep_files("ße is", None).unwrap(), vec!["b.md"]);
+    }
+
+    #[test]
+    fn test_ascii_fold() {
+        let docs = serde_json::to_string(&vec![
+            ("kontor/malmo.md", "# Kontoret i Malmö\nÖppet vardagar, se résumé-mallen"),
+            ("kontor/lund.md", "# Lund\nNära Malmo C"),
+            ("kontor/umea.md", "# Umeå\nStängt"),
+        ]).unwrap();
+        let options = MemexOptions {
+            ascii_fold: true,
+            ..Default::default()
+        };
+        let mut fs = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+        let paths = |fs: &MemexFsCore, pattern: &str| -> Vec<String> {
+            fs.grep(pattern, None).unwrap().into_iter().map(|r| r.path).collect()
+        };
+
+        let both = vec!["kontor/lund.md", "kontor/malmo.md"];
+        assert_eq!(paths(&fs, "malmo"), both);
+        assert_eq!(paths(&fs, "Malmö"), both);
+        assert_eq!(paths(&fs, "kontoret i malmo"), vec!["kontor/malmo.md"]);
+        assert_eq!(paths(&fs, "resume-mall"), vec!["kontor/malmo.md"]);
+        assert_eq!(paths(&fs, "oppet"), vec!["kontor/malmo.md"]);
+        assert_eq!(fs.grep_files("umea", None).unwrap(), vec!["kontor/umea.md"]);
+        assert_eq!(fs.missing_in_glob("malmö", "**").unwrap(), vec!["kontor/umea.md"]);
+        assert_eq!(fs.search("stangt umea", None)[0].path, "kontor/umea.md");
+
+        // Variants leave the index with their document
+        fs.delete_document("kontor/lund.md").unwrap();
+        assert_eq!(paths(&fs, "malmo"), vec!["kontor/malmo.md"]);
+        let restored = MemexFsCore::from_snapshot(&fs.to_snapshot()).unwrap();
+        assert_eq!(paths(&restored, "malmo"), vec!["kontor/malmo.md"]);
+
+        // Off by default
+        let plain = MemexFsCore::from_json(&docs).unwrap();
+        assert_eq!(paths(&plain, "malmo"), vec!["kontor/lund.md"]);
+    }
+
+    #[test]
+    fn test_cjk_ngrams() {
+        let docs = serde_json::to_string(&vec![
+            ("ja/tokyo.md", "# 東京\n東京都に住んでいます。iPhone手机も使えます"),
+            ("ja/kyoto.md", "# 京都\n京都の天気は晴れです"),
+            ("zh/shanghai.md", "上海的天气很好"),
+        ]).unwrap();
+        let options = MemexOptions {
+            cjk_ngram: Some(2),
+            ..Default::default()
+        };
+        let fs = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+        let paths = |fs: &MemexFsCore, pattern: &str| -> Vec<String> {
+            fs.grep(pattern, None).unwrap().into_iter().map(|r| r.path).collect()
+        };
+
+        assert!(fs.store.index().lookup("東京").is_some());
+        assert_eq!(paths(&fs, "東京都"), vec!["ja/tokyo.md"]);
+        assert_eq!(paths(&fs, "京都"), vec!["ja/kyoto.md", "ja/kyoto.md", "ja/tokyo.md"]);
+        assert_eq!(paths(&fs, "天"), vec!["ja/kyoto.md", "zh/shanghai.md"]);
+        assert_eq!(paths(&fs, "iphone手机"), vec!["ja/tokyo.md"]);
+        assert_eq!(paths(&fs, "都に住"), vec!["ja/tokyo.md"]);
+        assert!(paths(&fs, "東京の").is_empty());
+        assert_eq!(fs.search("天气", None)[0].path, "zh/shanghai.md");
+        assert_eq!(fs.grep_files("東京都", None).unwrap(), vec!["ja/tokyo.md"]);
+        assert_eq!(fs.missing_in_glob("天气", "zh/**").unwrap(), Vec::<String>::new());
+
+        // Off by default: a CJK run is one token
+        let plain = MemexFsCore::from_json(&docs).unwrap();
+        assert!(plain.store.index().lookup("天气").is_none());
+        assert!(plain.search("天气", None).is_empty());
+    }
+
+    #[test]
+    fn test_split_identifiers() {
+        let docs = serde_json::to_string(&vec![
+            ("api/users.md", "# Users\nCall `getUserById(id)` to fetch one"),
+            ("api/http.md", "The HTTPServer handles requests"),
+            ("api/db.md", "Rows are keyed by user_id"),
+        ]).unwrap();
+        let options = MemexOptions {
+            split_identifiers: true,
+            ..Default::default()
+        };
+        let fs = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+        let whole_word = GrepOptions {
+            whole_word: Some(true),
+            ..Default::default()
+        };
+        let paths = |fs: &MemexFsCore| -> Vec<String> {
+            let results = fs.grep_with_options("user", &whole_word).unwrap();
+            results.into_iter().map(|r| r.path).collect()
+        };
+
+        assert_eq!(paths(&fs), vec!["api/db.md", "api/users.md"]);
+        let found: BTreeSet<String> = fs.search("user", None).into_iter().map(|r| r.path).collect();
+        assert_eq!(found, BTreeSet::from(["api/db.md".to_string(), "api/users.md".to_string()]));
+        assert_eq!(fs.search("http server", None)[0].path, "api/http.md");
+        assert_eq!(fs.rank_by_proximity("get user"), vec!["api/users.md"]);
+        assert_eq!(fs.grep("getUserById", None).unwrap().len(), 1);
+
+        // Off by default: only the snake_case identifier has a `user` token
+        let plain = MemexFsCore::from_json(&docs).unwrap();
+        assert_eq!(paths(&plain), vec!["api/db.md"]);
+        assert!(plain.search("http server", None).is_empty());
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        let docs = serde_json::to_string(&vec![
+            (
+                "kb/refunds.html",
+                "<html><head><style>.refund { color: red }</style></head>\n\
+                 <body><h1 class=\"refund\">Refunds</h1>\n\
+                 <p>Allow <b>5&ndash;7</b> days.</p><h2>Exceptions</h2><p>None.</p></body></html>",
+            ),
+            ("kb/markup.md", "Use <span class=\"refund\"> for emphasis"),
+        ]).unwrap();
+        let options = MemexOptions {
+            html_to_text: true,
+            ..Default::default()
+        };
+        let fs = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+        let paths = |fs: &MemexFsCore, pattern: &str| -> Vec<String> {
+            fs.grep(pattern, None).unwrap().into_iter().map(|r| r.path).collect()
+        };
+
+        // Markup no longer matches in HTML documents; other files keep theirs
+        assert_eq!(paths(&fs, "class"), vec!["kb/markup.md"]);
+        assert_eq!(paths(&fs, "5–7 days"), vec!["kb/refunds.html"]);
+        assert_eq!(
+            fs.read("kb/refunds.html", None, None).unwrap(),
+            "  1  # Refunds\n  2  \n  3  Allow 5–7 days.\n  4  \n  5  ## Exceptions\n  6  \n  7  None."
+        );
+        let outline = fs.outline("kb/refunds.html").unwrap();
+        let headings: Vec<(u8, &str)> = outline.iter().map(|h| (h.level, h.text.as_str())).collect();
+        assert_eq!(headings, vec![(1, "Refunds"), (2, "Exceptions")]);
+
+        let mut restored = MemexFsCore::from_snapshot(&fs.to_snapshot()).unwrap();
+        restored.write_document("kb/new.html", "<p>Hello</p>").unwrap();
+        assert_eq!(restored.read("kb/new.html", None, None).unwrap(), "  1  Hello");
+
+        // Off by default
+        let plain = MemexFsCore::from_json(&docs).unwrap();
+        assert_eq!(paths(&plain, "class"), vec!["kb/markup.md", "kb/refunds.html"]);
+    }
+
+    #[test]
+    fn test_from_json_lenient() {
+        let docs = r#"[
+            ["billing/refunds.md", "Refunds take 5-7 days"],
+            ["", "no path"],
+            {"path": "billing/huge.md", "content": "refund refund refund refund"},
+            ["billing/refunds.md", "Refunds take 3 days"]
+        ]"#;
+        let options = MemexOptions { max_document_bytes: Some(22), ..Default::default() };
+        assert_eq!(MemexFsCore::from_json_with_options(docs, &options).unwrap_err().code(), "empty_path");
+
+        let (mut fs, report) = MemexFsCore::from_json_lenient(docs, &options).unwrap();
+        assert_eq!(report.loaded, 1);
+        let kinds: Vec<LoadIssueKind> = report.issues.iter().map(|issue| issue.kind).collect();
+        assert_eq!(kinds, [LoadIssueKind::DuplicatePath, LoadIssueKind::InvalidPath, LoadIssueKind::Oversized]);
+        assert_eq!(fs.read("billing/refunds.md", None, None).unwrap(), "  1  Refunds take 3 days");
+
+        // Later batches keep checking, and an all-bad batch still gives an instance
+        let report = fs.add_documents_lenient(r#"[["billing/empty.md", ""], 7]"#).unwrap();
+        assert_eq!((report.loaded, report.issues.len()), (1, 2));
+        assert_eq!(fs.add_documents_lenient("{}").unwrap_err().code(), "invalid_documents");
+        let (empty, report) = MemexFsCore::from_json_lenient("[[\"\", \"x\"]]", &options).unwrap();
+        assert_eq!((empty.document_count(), report.loaded), (0, 0));
+    }
+
+    #[test]
+    fn test_binary_policy() {
+        let png = String::from_utf8_lossy(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x01\0").into_owned();
+        let docs = serde_json::to_string(&vec![("guide.md", "# Guide"), ("img/logo.png", png.as_str())]).unwrap();
+        let with = |binary_policy| MemexOptions { binary_policy, ..Default::default() };
+
+        // Skipped by default, so no garbage tokens reach the index
+        let mut fs = MemexFsCore::from_json(&docs).unwrap();
+        assert_eq!(fs.ls(""), vec!["guide.md"]);
+        assert!(fs.grep("IHDR", None).unwrap().is_empty());
+        let err = fs.write_document("img/logo.png", &png).unwrap_err();
+        assert_eq!(err.to_string(), "MemexError: binary document: img/logo.png (contains NUL bytes)");
+
+        let err = MemexFsCore::from_json_with_options(&docs, &with(BinaryPolicy::Reject)).unwrap_err();
+        assert_eq!(err.code(), "binary_document");
+
+        let mut stubs = MemexFsCore::from_json_with_options(&docs, &with(BinaryPolicy::Stub)).unwrap();
+        assert_eq!(stubs.ls(""), vec!["guide.md", "img/"]);
+        assert!(stubs.grep("IHDR", None).unwrap().is_empty());
+        assert_eq!(stubs.read("img/logo.png", None, None).unwrap(), "");
+        let entries = stubs.ls_detailed("img");
+        assert_eq!(entries[0].metadata.as_ref().unwrap()["binary"], true);
+        // Snapshots keep the policy for later writes
+        let mut restored = MemexFsCore::from_snapshot(&stubs.to_snapshot()).unwrap();
+        assert!(restored.write_document("img/icon.png", &png).is_ok());
+        assert!(stubs.write_document("notes.md", "text").is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_paths() {
+        let docs = r#"[["./billing/refunds.md", "Refunds take 5-7 days"], ["/billing//refunds.md", "Refunds take 3 days"]]"#;
+        let with = |duplicate_paths| MemexOptions { duplicate_paths, ..Default::default() };
+
+        // Paths are normalized, so both entries name the same document
+        let mut fs = MemexFsCore::from_json(docs).unwrap();
+        assert_eq!(fs.ls(""), vec!["billing/"]);
+        assert_eq!(fs.read("billing/refunds.md", None, None).unwrap(), "  1  Refunds take 3 days");
+        assert!(fs.grep("5-7", None).unwrap().is_empty());
+        let err = fs.add_documents(r#"[["billing/../secrets.md", "x"]]"#).unwrap_err();
+        assert_eq!(err.to_string(), "MemexError: invalid path: billing/../secrets.md (has a `..` segment)");
+        assert_eq!(fs.call_mut("write", r#"{"path": "./notes.md", "content": "x"}"#).unwrap(), "Created notes.md");
+
+        let mut first = MemexFsCore::from_json_with_options(docs, &with(DuplicatePolicy::FirstWins)).unwrap();
+        assert_eq!(first.read("billing/refunds.md", None, None).unwrap(), "  1  Refunds take 5-7 days");
+        first.add_documents(r#"[["billing/refunds.md", "Refunds take 1 day"]]"#).unwrap();
+        assert!(first.grep("1 day", None).unwrap().is_empty());
+        // Writes replace whatever the policy
+        assert!(first.write_document("billing/refunds.md", "Refunds take 1 day").unwrap());
+
+        let err = MemexFsCore::from_json_with_options(docs, &with(DuplicatePolicy::Error)).unwrap_err();
+        assert_eq!(err, MemexError::DuplicatePath { path: "billing/refunds.md".to_string() });
+        let mut strict = MemexFsBuilder::new()
+            .duplicate_paths(DuplicatePolicy::Error)
+            .from_json(r#"[["a.md", "a"]]"#)
+            .unwrap();
+        assert_eq!(strict.add_documents(r#"[["b.md", "b"], ["a.md", "a"]]"#).unwrap_err().code(), "duplicate_path");
+        assert_eq!(strict.ls(""), vec!["a.md"]);
+        let restored = MemexFsCore::from_snapshot(&strict.to_snapshot()).unwrap();
+        assert_eq!(restored.store.duplicate_policy(), DuplicatePolicy::Error);
+    }
+
+    #[test]
+    fn test_mount() {
+        let product = MemexFsCore::from_json(r#"[["refunds.md", "---\ntags: [billing]\n---\nRefunds take 5-7 days"]]"#).unwrap();
+        let mut fs = MemexFsCore::from_json(r#"[["README.md", "Support handbook"]]"#).unwrap();
+        assert_eq!(fs.mount("product", &product).unwrap(), 1);
+        let policy = vec![DocInput::Pair("./refunds.md".into(), "Refunds over $500 need approval".into())];
+        assert_eq!(fs.mount_documents("/policy/", policy).unwrap(), 1);
+
+        assert_eq!(fs.ls(""), vec!["README.md", "policy/", "product/"]);
+        assert_eq!(fs.grep_files("refunds", None).unwrap(), vec!["policy/refunds.md", "product/refunds.md"]);
+        assert_eq!(fs.read("policy/refunds.md", None, None).unwrap(), "  1  Refunds over $500 need approval");
+        assert_eq!(fs.ls_detailed("product")[0].metadata.as_ref().unwrap()["tags"][0], "billing");
+        assert_eq!(fs.mounts(), vec!["policy", "product"]);
+
+        // Remounting replaces the corpus; overlaps are refused
+        let v2 = vec![DocInput::Pair("approvals.md".into(), "Managers approve refunds".into())];
+        fs.mount_documents("policy", v2.clone()).unwrap();
+        assert_eq!(fs.ls("policy"), vec!["approvals.md"]);
+        let err = fs.mount_documents("policy/eu", v2.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "MemexError: cannot mount at policy/eu/: overlaps the mount at policy/");
+        fs.write_document("notes/a.md", "x").unwrap();
+        assert_eq!(fs.mount_documents("notes", v2).unwrap_err().code(), "mount_conflict");
+
+        // Unmounting removes the mount with what was written into it
+        fs.write_document("policy/draft.md", "approve").unwrap();
+        let restored = MemexFsCore::from_snapshot(&fs.to_snapshot()).unwrap();
+        assert_eq!(restored.mounts(), fs.mounts());
+        assert_eq!(fs.unmount("policy").unwrap(), 2);
+        assert!(fs.grep("approve", None).unwrap().is_empty());
+        assert_eq!(fs.ls(""), vec!["README.md", "notes/", "product/"]);
+        let err = fs.unmount("policy").unwrap_err();
+        assert_eq!(err, MemexError::NotMounted { prefix: "policy".into(), mounts: vec!["product".into()] });
+
+        // Only the documents actually mounted are counted
+        let batch = vec![
+            DocInput::Pair("a.md".into(), "first".into()),
+            DocInput::Pair("a.md".into(), "second".into()),
+            DocInput::Pair("b.md".into(), "PNG\0\0".into()),
+        ];
+        assert_eq!(fs.mount_documents("batch", batch).unwrap(), 1);
+        assert_eq!(fs.ls("batch"), vec!["a.md"]);
+        assert_eq!(fs.read("batch/a.md", None, None).unwrap(), "  1  second");
+    }
+
+    #[test]
+    fn test_overlay() {
+        let docs = r##"[
+            ["README.md", "Support handbook"],
+            ["api/auth.md", "# Authentication\nTokens last one day"],
+            ["docs/guide.md", "# Guide\n\nRotate your token monthly"]
+        ]"##;
+        let base = Arc::new(MemexFsCore::from_json(docs).unwrap());
+        let mut session = MemexFsCore::overlay(Arc::clone(&base));
+        assert_eq!(session.document_count(), 3);
+
+        assert!(session.write_document("api/auth.md", "# Auth\nTokens expire after one hour").unwrap());
+        assert!(!session.write_document("notes/todo.md", "Check token expiry").unwrap());
+        session.delete_document("README.md").unwrap();
+        assert_eq!(session.delete_document("README.md").unwrap_err().code(), "document_not_found");
+
+        // The session sees its changes over the base, which is unchanged
+        assert_eq!(session.ls(""), vec!["api/", "docs/", "notes/"]);
+        assert!(base.read("README.md", None, None).is_ok());
+        let hits = session.grep("token", None).unwrap();
+        let hits: Vec<(&str, u32)> = hits.iter().map(|r| (r.path.as_str(), r.line)).collect();
+        assert_eq!(hits, vec![("api/auth.md", 2), ("docs/guide.md", 3), ("notes/todo.md", 1)]);
+        let options = GrepOptions { max_results: Some(1), offset: Some(1), ..Default::default() };
+        assert_eq!(session.grep_page("token", &options).unwrap().results[0].path, "docs/guide.md");
+        assert_eq!(session.grep_files("token", None).unwrap(), vec!["api/auth.md", "docs/guide.md", "notes/todo.md"]);
+        assert!(session.grep_files("authentication", None).unwrap().is_empty());
+        assert_eq!(base.grep_files("authentication", None).unwrap(), vec!["api/auth.md"]);
+        let mut found: Vec<String> = session.search("token", None).into_iter().map(|r| r.path).collect();
+        found.sort();
+        assert_eq!(found, vec!["docs/guide.md", "notes/todo.md"]);
+
+        // Writing a deleted path brings it back; deleting it again hides it
+        session.write_document("README.md", "Session readme").unwrap();
+        assert_eq!(session.read("README.md", None, None).unwrap(), "  1  Session readme");
+        session.delete_document("README.md").unwrap();
+        assert!(session.read("README.md", None, None).is_err());
+
+        let changes = session.export_overlay();
+        let paths: Vec<&str> = changes.documents.iter().map(|d| d.path()).collect();
+        assert_eq!(paths, vec!["api/auth.md", "notes/todo.md"]);
+        assert_eq!(changes.deleted, vec!["README.md"]);
+        let replayed = MemexFsCore::from_json(&serde_json::to_string(&changes.documents).unwrap()).unwrap();
+        assert_eq!(replayed.grep_files("expire", None).unwrap(), vec!["api/auth.md"]);
+        assert_eq!(base.export_overlay(), OverlayChanges::default());
+
+        // Search scores every visible document by the same statistics
+        let mut session = MemexFsCore::overlay(Arc::clone(&base));
+        session.write_document("notes/guide.md", "# Guide\n\nRotate your token monthly").unwrap();
+        let results = session.search("rotate token", None);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].score, results[1].score);
+
+        // Matches are only kept whole for re-sorting when the session holds
+        // a document the grep could match
+        let keeps_all = |fs: &MemexFsCore, glob: &str| {
+            let options = GrepOptions { glob: Some(glob.to_string()), ..Default::default() };
+            let mut pager = Pager::new(&options, &PatternCache::default()).unwrap();
+            fs.collect_layered(&mut pager, &|_, _| Pattern::new("token", true)).unwrap();
+            pager.keeps_all()
+        };
+        assert!(keeps_all(&session, "**"));
+        assert!(!keeps_all(&session, "docs/**"));
+        assert!(!keeps_all(&MemexFsCore::overlay(Arc::clone(&base)), "**"));
+        let options = GrepOptions { max_results: Some(1), glob: Some("**/*.md".into()), ..Default::default() };
+        let page = session.grep_page("token", &options).unwrap();
+        assert_eq!((page.results[0].path.as_str(), page.next_offset), ("api/auth.md", Some(1)));
+    }
+
+    #[test]
+    fn test_merge() {
+        let support = MemexFsCore::from_json(
+            r#"[["shared/holidays.md", "Support holidays"], ["support/refunds.md", "Refunds take 5 days"]]"#,
+        )
+        .unwrap();
+        let sales = || {
+            MemexFsCore::from_json(r#"[["shared/holidays.md", "Sales holidays"], ["sales/pricing.md", "Pro costs $49"]]"#)
+                .unwrap()
+        };
+
+        let mut kept = sales();
+        assert_eq!(kept.merge(&support, DuplicatePolicy::FirstWins).unwrap(), 1);
+        assert_eq!(kept.read("shared/holidays.md", None, None).unwrap(), "  1  Sales holidays");
+        assert_eq!(kept.grep_files("refunds", None).unwrap(), vec!["support/refunds.md"]);
+
+        let mut replaced = sales();
+        assert_eq!(replaced.merge(&support, DuplicatePolicy::LastWins).unwrap(), 2);
+        assert_eq!(replaced.document_count(), 3);
+        assert!(replaced.grep("sales", None).unwrap().is_empty());
+        assert_eq!(replaced.grep_files("holidays", None).unwrap(), vec!["shared/holidays.md"]);
+
+        let mut strict = sales();
+        let err = strict.merge(&support, DuplicatePolicy::Error).unwrap_err();
+        assert_eq!(err, MemexError::DuplicatePath { path: "shared/holidays.md".into() });
+        assert_eq!(strict.document_count(), 2);
+    }
+
+    #[test]
+    fn test_diff_stores() {
+        let last_week = MemexFsCore::from_json(
+            r##"[["billing/refunds.md", "# Refunds\nRefunds take 5 days\nContact support"], ["old.md", "x"]]"##,
+        )
+        .unwrap();
+        let current = MemexFsCore::from_json(
+            r##"[["billing/refunds.md", "# Refunds\nRefunds take 3 days\nContact support"], ["new.md", "y"]]"##,
+        )
+        .unwrap();
+        let diff = diff_stores(&last_week, &current);
+        assert_eq!((diff.added.clone(), diff.removed.clone()), (vec!["new.md".into()], vec!["old.md".into()]));
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].path, "billing/refunds.md");
+        assert_eq!(diff.changed[0].hunks[0].lines[1..3], ["-Refunds take 5 days", "+Refunds take 3 days"]);
+        assert!(diff_stores(&current, &current).is_empty());
+
+        // The tool compares two mounts, and is only offered once there are two
+        let mut fs = make_fs();
+        fs.mount("last-week", &last_week).unwrap();
+        let has_diff = |fs: &MemexFsCore| {
+            fs.tool_definitions_value().as_array().unwrap().iter().any(|d| d["name"] == "diff")
+        };
+        assert!(!has_diff(&fs));
+        fs.mount("current", &current).unwrap();
+        let defs = fs.tool_definitions_value();
+        let def = defs.as_array().unwrap().iter().find(|d| d["name"] == "diff").unwrap();
+        assert_eq!(def["parameters"]["from"]["enum"], serde_json::json!(["current", "last-week"]));
+
+        let text = fs.call("diff", r#"{"from": "last-week", "to": "current"}"#).unwrap();
+        assert!(text.starts_with("added: new.md\nremoved: old.md\nchanged: billing/refunds.md (+1 -1)\n"), "{}", text);
+        assert!(text.contains("@@ -1,3 +1,3 @@\n # Refunds\n-Refunds take 5 days\n+Refunds take 3 days\n"));
+        let billing = fs.diff_mounts("last-week", "current", Some("billing/**")).unwrap();
+        assert_eq!(billing.len(), 1);
+        assert_eq!(fs.call("diff", r#"{"from": "current", "to": "/current/"}"#).unwrap(), "No differences");
+        let err = fs.diff_mounts("last-week", "billing", None).unwrap_err();
+        assert_eq!(err, MemexError::NotMounted { prefix: "billing".into(), mounts: fs.mounts() });
+    }
+
+    #[test]
+    fn test_fold_cache_matches_lazy_folding() {
+        let docs = serde_json::to_string(&vec![
+            ("de.md", "Die STRASSE und die Straße\nÖFFNUNGSZEITEN: 9-17"),
+            ("en.md", "Reset-Password: see FAQ\nöffnungszeiten? n/a"),
+        ]).unwrap();
+        let lazy = MemexFsCore::from_json(&docs).unwrap();
+        let options = MemexOptions {
+            fold_cache: true,
+            ..Default::default()
+        };
+        let cached = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+        let restored = MemexFsCore::from_snapshot(&cached.to_snapshot()).unwrap();
+
+        let literal = GrepOptions {
+            literal: Some(true),
+            ..Default::default()
+        };
+        let lines = |fs: &MemexFsCore, pattern: &str| -> Vec<(String, u32)> {
+            fs.grep_with_options(pattern, &literal).unwrap().into_iter().map(|r| (r.path, r.line)).collect()
+        };
+        for pattern in ["öffnungszeiten:", "straße", "-password:", "n/a", "9-"] {
+            let expected = lines(&lazy, pattern);
+            assert!(!expected.is_empty(), "{:?}", pattern);
+            for fs in [&cached, &restored] {
+                assert_eq!(lines(fs, pattern), expected, "{:?}", pattern);
+                assert_eq!(fs.grep_files(pattern, None).unwrap(), lazy.grep_files(pattern, None).unwrap());
+                assert_eq!(fs.missing_in_glob(pattern, "**").unwrap(), lazy.missing_in_glob(pattern, "**").unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_grep_timeout_returns_partial_results() {
+        let docs: Vec<(String, String)> = (0..20)
+            .map(|i| (format!("doc{:02}.md", i), "reset-password here\n".repeat(300)))
+            .collect();
+        let fs = MemexFsCore::from_json(&serde_json::to_string(&docs).unwrap()).unwrap();
+
+        for pattern in ["reset-pass", r"pass\w+ here", "t-p"] {
+            let expired = GrepOptions {
+                timeout_ms: Some(0),
+                ..Default::default()
+            };
+            let page = fs.grep_page(pattern, &expired).unwrap();
+            assert!(page.timed_out, "{:?}", pattern);
+            assert!(page.total_matches < 6000, "{:?}", pattern);
+
+            let generous = GrepOptions {
+                timeout_ms: Some(60_000),
+                ..Default::default()
+            };
+            let page = fs.grep_page(pattern, &generous).unwrap();
+            assert!(!page.timed_out);
+            assert_eq!(page.total_matches, 6000, "{:?}", pattern);
+        }
+
+        // Only reported when it happened
+        let result = fs.call("grep", r#"{"pattern": "reset-pass", "timeout_ms": 0}"#).unwrap();
+        assert!(result.contains(r#""timed_out":true"#));
+        let result = fs.call("grep", r#"{"pattern": "reset-pass"}"#).unwrap();
+        assert!(!result.contains("timed_out"));
+    }
+
+    #[test]
+    fn test_resumable_grep_matches_grep_page() {
+        let docs: Vec<(String, String)> = (0..150)
+            .map(|i| (format!("doc{:03}.md", i), format!("line one\nrefund {} days\nreset-password", i % 7)))
+            .collect();
+        let fs = MemexFsCore::from_json(&serde_json::to_string(&docs).unwrap()).unwrap();
+        let hits = |results: &[GrepResult]| -> Vec<(String, u32)> {
+            results.iter().map(|r| (r.path.clone(), r.line)).collect()
+        };
+
+        for pattern in ["refund", "reset-password", r"refund [0-3] days", "one"] {
+            let options = GrepOptions {
+                max_results: Some(1000),
+                ..Default::default()
+            };
+            let expected = fs.grep_page(pattern, &options).unwrap().results;
+
+            let handle = fs.start_grep(pattern, &options).unwrap();
+            let mut found = Vec::new();
+            let mut slices = 0;
+            loop {
+                let chunk = fs.continue_grep(handle, 0).unwrap();
+                slices += 1;
+                assert_eq!(chunk.total_documents, 150);
+                found.extend(chunk.results);
+                if chunk.done {
+                    assert_eq!(chunk.searched, 150);
+                    break;
+                }
+            }
+            assert_eq!(slices, 3, "{:?}", pattern);
+            assert_eq!(hits(&found), hits(&expected), "{:?}", pattern);
+            assert!(fs.continue_grep(handle, 0).is_err());
+        }
+
+        // max_results caps the total across slices
+        let capped = GrepOptions {
+            max_results: Some(70),
+            ..Default::default()
+        };
+        let handle = fs.start_grep("refund", &capped).unwrap();
+        let first = fs.continue_grep(handle, 0).unwrap();
+        assert_eq!((first.results.len(), first.done), (64, false));
+        let second = fs.continue_grep(handle, 0).unwrap();
+        assert_eq!((second.results.len(), second.done), (6, true));
+
+        assert!(fs.start_grep("(", &GrepOptions::default()).is_err());
+        let handle = fs.start_grep("refund", &GrepOptions::default()).unwrap();
+        assert!(fs.cancel_grep(handle));
+        assert!(!fs.cancel_grep(handle));
+    }
+
+    #[test]
+    fn test_memory_stats() {
+        let content = "Reset your password from the account page.\n".repeat(50);
+        let docs = serde_json::to_string(&vec![("a.md", content.as_str()), ("b.md", "short")]).unwrap();
+        let mut fs = MemexFsCore::from_json(&docs).unwrap();
+        let stats = fs.memory_stats();
+        assert!(stats.documents >= content.len());
+        assert_eq!(stats.folded, 0);
+        assert!(stats.index > 0 && stats.filters > 0);
+        assert_eq!(stats.total, stats.documents + stats.folded + stats.index + stats.filters);
+
+        fs.write_document("c.md", &content).unwrap();
+        assert!(fs.memory_stats().documents >= stats.documents + content.len());
+        assert!(fs.memory_stats().index > stats.index);
+
+        let options = MemexOptions {
+            fold_cache: true,
+            ..Default::default()
+        };
+        let cached = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+        assert!(cached.memory_stats().folded >= content.len());
+    }
+
+    #[test]
+    fn test_missing_in_glob() {
+        let docs = serde_json::to_string(&vec![
+            ("billing/invoices.md", "Invoices follow the Refund Policy clause."),
+            ("billing/plans.md", "Plans renew monthly."),
+            ("billing/refund.md", "See the refund policy for details."),
+            ("account/profile.md", "No clause here either."),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let missing = fs.missing_in_glob("refund policy", "billing/*.md").unwrap();
+        assert_eq!(missing, vec!["billing/plans.md"]);
 
-    fn make_fs() -> MemexFsCore {
+        let result = fs
+            .call("missing_in_glob", r#"{"term": "refund policy", "glob": "**/*.md"}"#)
+            .unwrap();
+        assert_eq!(result, r#"["account/profile.md","billing/plans.md"]"#);
+
+        assert!(fs.missing_in_glob("", "**").is_err());
+    }
+
+    #[test]
+    fn test_co_occurring() {
         let docs = serde_json::to_string(&vec![
-            ("account/password-reset.md", "# Password Reset\n\n## How to reset your password\n\n1. Go to Settings\n2. Click Reset Password"),
-            ("billing/refund.md", "# Refunds\n\nTo request a refund, contact support.\n\nRefunds are processed within 5 business days."),
+            ("a.md", "Invoices are sent monthly"),
+            ("b.md", "Download your invoices here\nInvoices again"),
+            ("c.md", "Nothing relevant"),
         ]).unwrap();
-        MemexFsCore::from_json(&docs).unwrap()
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        assert_eq!(fs.co_occurring("a.md", "invoices"), vec!["b.md"]);
+        assert_eq!(fs.co_occurring("b.md", "Invoices"), vec!["a.md"]);
+        assert!(fs.co_occurring("c.md", "invoices").is_empty());
+        assert!(fs.co_occurring("a.md", "missing").is_empty());
     }
 
     #[test]
-    fn test_grep_simple() {
-        let fs = make_fs();
-        let results = fs.grep("password", None).unwrap();
-        assert!(!results.is_empty());
-        assert!(results
-            .iter()
-            .any(|r| r.path == "account/password-reset.md"));
+    fn test_grep_flexible_whitespace() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "How to reset  your   password"),
+            ("b.md", "Reset\tyour password from settings"),
+            ("c.md", "reset the password for your account"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        // Literal scan needs the exact spacing
+        assert!(fs.grep("reset your password", None).unwrap().is_empty());
+
+        let options = GrepOptions {
+            flexible_whitespace: Some(true),
+            snippet_radius: Some(0),
+            ..Default::default()
+        };
+        let results = fs.grep_with_options("reset your password", &options).unwrap();
+        let paths: Vec<&str> = results.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.md", "b.md"]);
+        assert_eq!(results[0].snippet.as_deref(), Some("...reset  your   password"));
+
+        // Words keep their order and are matched literally
+        assert!(fs.grep_with_options("password your reset", &options).unwrap().is_empty());
     }
 
     #[test]
-    fn test_grep_with_glob() {
+    fn test_grep_with_title() {
         let fs = make_fs();
-        let results = fs.grep("refund", Some("billing/**/*.md")).unwrap();
+        let options = GrepOptions {
+            with_title: Some(true),
+            ..Default::default()
+        };
+        let results = fs.grep_with_options("reset|refund", &options).unwrap();
         assert!(!results.is_empty());
-        assert!(results.iter().all(|r| r.path.starts_with("billing/")));
+        for r in &results {
+            let expected = if r.path.starts_with("account/") { "Password Reset" } else { "Refunds" };
+            assert_eq!(r.title.as_deref(), Some(expected));
+        }
+
+        // Not populated unless requested
+        let results = fs.grep("refund", None).unwrap();
+        assert!(results.iter().all(|r| r.title.is_none()));
     }
 
-    #[test]
-    fn test_grep_regex() {
-        let fs = make_fs();
-        let results = fs.grep("reset|refund", None).unwrap();
-        assert!(results.len() >= 2);
+    fn billing_docs(mode: SynonymMode) -> MemexFsCore {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Your invoice is attached"),
+            ("b.md", "Download a statement from the portal"),
+            ("c.md", "Pay your bill online"),
+            ("d.md", "Nothing related"),
+        ]).unwrap();
+        let options = MemexOptions {
+            synonyms: HashMap::from([(
+                "bill".to_string(),
+                vec!["invoice".to_string(), "statement".to_string()],
+            )]),
+            synonym_mode: mode,
+            ..Default::default()
+        };
+        MemexFsCore::from_json_with_options(&docs, &options).unwrap()
     }
 
     #[test]
-    fn test_grep_case_insensitive() {
-        let fs = make_fs();
-        let results = fs.grep("PASSWORD", None).unwrap();
-        assert!(!results.is_empty());
+    fn test_synonyms_index_time() {
+        let fs = billing_docs(SynonymMode::Index);
+
+        // Extra postings live under "bill" itself, so one lookup covers them
+        let postings = fs.store.index().lookup("bill").unwrap();
+        let paths: Vec<&str> = fs.store.resolve(postings).into_iter().map(|(p, _)| p).collect();
+        assert_eq!(paths, vec!["a.md", "b.md", "c.md"]);
+
+        let results = fs.grep("bill", None).unwrap();
+        let paths: Vec<&str> = results.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.md", "b.md", "c.md"]);
+
+        // Expansion is one-way: synonyms don't pull in the key
+        assert_eq!(fs.grep("invoice", None).unwrap().len(), 1);
     }
 
     #[test]
-    fn test_read_full() {
-        let fs = make_fs();
-        let content = fs.read("billing/refund.md", None, None).unwrap();
-        assert!(content.contains("# Refunds"));
-        assert!(content.contains("5 business days"));
+    fn test_synonyms_query_time() {
+        let fs = billing_docs(SynonymMode::Query);
+
+        // Index is untouched; expansion happens per query
+        assert_eq!(fs.store.index().lookup("bill").unwrap().len(), 1);
+
+        let results = fs.grep("bill", None).unwrap();
+        let paths: Vec<&str> = results.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.md", "b.md", "c.md"]);
     }
 
     #[test]
-    fn test_read_with_offset_limit() {
-        let fs = make_fs();
-        let content = fs.read("billing/refund.md", Some(3), Some(1)).unwrap();
-        assert!(content.contains("request a refund"));
-        assert!(!content.contains("# Refunds"));
+    fn test_search_expands_synonyms() {
+        for mode in [SynonymMode::Query, SynonymMode::Index] {
+            let fs = billing_docs(mode);
+            let results = fs.search("bill portal", None);
+            let paths: Vec<&str> = results.iter().map(|r| r.path.as_str()).collect();
+            assert_eq!(paths[0], "b.md", "{:?}: matches both words", mode);
+            assert_eq!(paths.len(), 3, "{:?}", mode);
+            assert_eq!(results[0].snippet, "Download a statement from the portal");
+            assert!(fs.search("statement", None).iter().all(|r| r.path == "b.md"));
+        }
     }
 
     #[test]
-    fn test_read_not_found() {
-        let fs = make_fs();
-        let result = fs.read("nonexistent.md", None, None);
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .message
-            .contains("document not found"));
+    fn test_stop_words() {
+        let docs = serde_json::to_string(&vec![
+            ("reset.md", "Reset the password"),
+            ("howto.md", "How to write a how-to: the how and the to of it"),
+        ]).unwrap();
+        let plain = MemexFsCore::from_json(&docs).unwrap();
+        let options = MemexOptions {
+            stop_words: Some(StopWordList::Language(StopLanguage::English)),
+            ..Default::default()
+        };
+        let fs = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+        let query = "how to reset the password";
+
+        assert_eq!(plain.search(query, None)[0].path, "howto.md");
+        let results = fs.search(query, None);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, "reset.md");
+        assert!(fs.context_for(query, 100).starts_with("[reset.md:1]"));
+        // All stop words: searched as-is
+        assert_eq!(fs.search("how to", None)[0].path, "howto.md");
+
+        // Grep keeps stop words unless asked
+        let mut grep = GrepOptions {
+            mode: Some(GrepMode::AllTerms),
+            ..Default::default()
+        };
+        assert!(fs.grep_page(query, &grep).unwrap().results.is_empty());
+        grep.stop_words = Some(true);
+        assert_eq!(fs.grep_page(query, &grep).unwrap().results[0].path, "reset.md");
+
+        let restored = MemexFsCore::from_snapshot(&fs.to_snapshot()).unwrap();
+        assert_eq!(restored.search(query, None).len(), 1);
     }
 
     #[test]
-    fn test_call_dispatch() {
+    fn test_grep_context_lines() {
         let fs = make_fs();
-        let result = fs.call("grep", r#"{"pattern": "refund"}"#).unwrap();
-        assert!(result.contains("refund"));
+        let options = GrepOptions {
+            context_before: Some(1),
+            context_after: Some(2),
+            ..Default::default()
+        };
+        let results = fs.grep_with_options("request a refund", &options).unwrap();
+        assert_eq!(results.len(), 1);
+        let r = &results[0];
+        assert_eq!(r.context_before, vec![ContextLine { line: 2, content: String::new() }]);
+        assert_eq!(r.context_after.len(), 2);
+        assert_eq!(r.context_after[1].line, 5);
 
-        let result = fs
-            .call("read", r#"{"path": "billing/refund.md"}"#)
-            .unwrap();
-        assert!(result.contains("# Refunds"));
+        // Clamped at the document start
+        let results = fs.grep_with_options("# refunds", &options).unwrap();
+        assert!(results[0].context_before.is_empty());
     }
 
     #[test]
-    fn test_call_unknown_tool() {
+    fn test_call_grep_context_lines() {
         let fs = make_fs();
-        let result = fs.call("delete", r#"{}"#);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().message.contains("unknown tool"));
+        let result = fs
+            .call("grep", r#"{"pattern": "request a refund", "context_before": 2, "context_after": 1}"#)
+            .unwrap();
+        let parsed: GrepResponse = serde_json::from_str(&result).unwrap();
+        let first = &parsed.results[0];
+        assert_eq!(first.line, 3);
+        let before: Vec<u32> = first.context_before.iter().map(|c| c.line).collect();
+        assert_eq!(before, vec![1, 2]);
+        assert_eq!(first.context_after[0].line, 4);
     }
 
     #[test]
-    fn test_tool_definitions() {
-        let fs = make_fs();
-        let defs = fs.tool_definitions();
-        let parsed: serde_json::Value = serde_json::from_str(&defs).unwrap();
-        assert!(parsed.is_array());
-        assert_eq!(parsed.as_array().unwrap().len(), 3);
+    fn test_grep_context_budget() {
+        let lines: Vec<String> = (0..40)
+            .map(|i| if i % 10 == 5 { "needle".to_string() } else { format!("filler {}", i) })
+            .collect();
+        let docs = serde_json::to_string(&vec![("a.md", lines.join("\n")), ("b.md", lines.join("\n"))]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+
+        let options = GrepOptions {
+            context_before: Some(3),
+            context_after: Some(3),
+            max_context_lines: Some(10),
+            ..Default::default()
+        };
+        let results = fs.grep_with_options("needle", &options).unwrap();
+        assert_eq!(results.len(), 8);
+
+        let context: usize = results
+            .iter()
+            .map(|r| r.context_before.len() + r.context_after.len())
+            .sum();
+        assert_eq!(context, 10, "context stays within max_context_lines");
+        // Shared fairly: no result gets more than one line per side
+        assert!(results.iter().all(|r| r.context_before.len() <= 1 && r.context_after.len() <= 1));
+        // The nearest lines are kept
+        assert_eq!(results[0].context_before[0].line, results[0].line - 1);
     }
 
     #[test]
-    fn test_empty_docs() {
-        let result = MemexFsCore::from_json("[]");
-        assert!(result.is_err());
-        assert!(result.unwrap_err().message.contains("no documents"));
+    fn test_write_document() {
+        let mut fs = make_fs();
+        assert!(fs.grep("onboarding", None).unwrap().is_empty());
+
+        // New document is searchable and listed right away
+        assert!(!fs.write_document("notes/new.md", "Onboarding checklist").unwrap());
+        assert_eq!(fs.document_count(), 3);
+        assert_eq!(fs.grep("onboarding", None).unwrap()[0].path, "notes/new.md");
+        assert_eq!(fs.ls("notes"), vec!["new.md"]);
+
+        // Replacing drops the old postings
+        assert!(fs.write_document("billing/refund.md", "# Credits\nStore credit only.").unwrap());
+        assert!(fs.grep("refunds", None).unwrap().is_empty());
+        assert_eq!(fs.grep("credit", None).unwrap().len(), 2);
+        assert!(fs.read("billing/refund.md", None, None).unwrap().contains("Store credit only."));
+
+        let result = fs
+            .call_mut("write", r#"{"path": "notes/new.md", "content": "Updated"}"#)
+            .unwrap();
+        assert_eq!(result, "Updated notes/new.md");
+        assert!(fs.grep("onboarding", None).unwrap().is_empty());
+        assert!(fs.write_document("", "x").is_err());
+
+        // Read-only dispatch turns writes away
+        let err = fs.call("write", r#"{"path": "notes/new.md", "content": "x"}"#).unwrap_err();
+        assert_eq!(err.code(), "tool_not_enabled");
+        assert!(fs.read("notes/new.md", None, None).unwrap().contains("Updated"));
     }
 
     #[test]
-    fn test_invalid_json() {
-        let result = MemexFsCore::from_json("not json");
-        assert!(result.is_err());
+    fn test_document_metadata() {
+        let docs = r#"[
+            {"path": "billing/refund.md", "content": "Refund policy", "metadata": {"tags": ["billing"], "author": "kim"}},
+            {"path": "billing/legacy.md", "content": "Old refund policy", "metadata": {"tags": ["archive"]}},
+            ["notes.md", "refund notes"]
+        ]"#;
+        let mut fs = MemexFsCore::from_json(docs).unwrap();
+        assert_eq!(fs.document_count(), 3);
+
+        let options = GrepOptions {
+            metadata: serde_json::from_str(r#"{"tags": ["billing"]}"#).unwrap(),
+            with_metadata: Some(true),
+            ..Default::default()
+        };
+        let page = fs.grep_page("refund", &options).unwrap();
+        assert_eq!(page.results.len(), 1);
+        assert_eq!(page.results[0].path, "billing/refund.md");
+        assert_eq!(page.results[0].metadata.as_ref().unwrap()["author"], "kim");
+        // Not attached unless requested
+        assert!(fs.grep("refund", None).unwrap().iter().all(|r| r.metadata.is_none()));
+
+        let entries = fs.ls_with_metadata("");
+        assert_eq!(entries[0], LsEntry { name: "billing/".into(), metadata: None });
+        assert_eq!(entries[1], LsEntry { name: "notes.md".into(), metadata: None });
+        let listed = fs.call("ls", r#"{"path": "billing", "with_metadata": true}"#).unwrap();
+        let listed: Vec<LsEntry> = serde_json::from_str(&listed).unwrap();
+        assert_eq!(listed[1].metadata.as_ref().unwrap()["tags"][0], "billing");
+
+        fs.call_mut(
+            "write",
+            r#"{"path": "notes.md", "content": "refund notes", "metadata": {"tags": ["billing"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(fs.grep_page("refund", &options).unwrap().total_matches, 2);
+
+        // Metadata survives a snapshot round trip
+        let restored = MemexFsCore::from_snapshot(&fs.to_snapshot()).unwrap();
+        assert_eq!(restored.grep_page("refund", &options).unwrap().total_matches, 2);
     }
 
     #[test]
-    fn test_document_count() {
-        let fs = make_fs();
-        assert_eq!(fs.document_count(), 2);
+    fn test_delete_document() {
+        let mut fs = make_fs();
+        fs.read("billing/refund.md", None, None).unwrap();
+
+        fs.delete_document("billing/refund.md").unwrap();
+        assert_eq!(fs.document_count(), 1);
+        assert!(fs.grep("refund", None).unwrap().is_empty());
+        assert!(fs.read("billing/refund.md", None, None).is_err());
+        assert!(fs.recently_read(10).is_empty());
+        assert_eq!(fs.ls(""), vec!["account/"]);
+        assert!(!fs.grep("password", None).unwrap().is_empty());
+
+        let err = fs.delete_document("billing/refund.md").unwrap_err();
+        assert!(err.to_string().contains("document not found"));
     }
 
     #[test]
-    fn test_grep_max_results() {
-        // Build a filesystem with many matching lines
-        let mut docs = Vec::new();
-        for i in 0..200 {
-            docs.push((
-                format!("doc_{}.md", i),
-                "keyword match here\nkeyword match again".to_string(),
-            ));
+    fn test_snapshot_round_trip() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "---\ntitle: A\n---\nYour invoice is attached"),
+            ("b/c.md", "Pay your bill online"),
+        ]).unwrap();
+        let options = MemexOptions {
+            synonyms: HashMap::from([("bill".to_string(), vec!["invoice".to_string()])]),
+            ..Default::default()
+        };
+        let fs = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+
+        let restored = MemexFsCore::from_snapshot(&fs.to_snapshot()).unwrap();
+        assert_eq!(restored.document_count(), fs.document_count());
+        assert_eq!(restored.token_count(), fs.token_count());
+        assert_eq!(restored.ls(""), fs.ls(""));
+        assert_eq!(
+            restored.read("a.md", None, None).unwrap(),
+            fs.read("a.md", None, None).unwrap()
+        );
+        // Index, folded lines, synonyms, and frontmatter all survive
+        for pattern in ["bill", "INVOICE", "pay.*online"] {
+            let expected = serde_json::to_string(&fs.grep(pattern, None).unwrap()).unwrap();
+            let actual = serde_json::to_string(&restored.grep(pattern, None).unwrap()).unwrap();
+            assert_eq!(actual, expected, "{:?}", pattern);
         }
-        let json = serde_json::to_string(&docs).unwrap();
-        let fs = MemexFsCore::from_json(&json).unwrap();
-        let results = fs.grep("keyword", None).unwrap();
-        assert_eq!(results.len(), 100); // capped at max
+        assert_eq!(restored.missing_in_glob("INVOICE", "**").unwrap(), vec!["b/c.md"]);
+
+        assert!(MemexFsCore::from_snapshot(b"nope").is_err());
+        let mut truncated = fs.to_snapshot();
+        truncated.truncate(truncated.len() / 2);
+        assert!(MemexFsCore::from_snapshot(&truncated).is_err());
     }
 
-    // Bug reproduction: substring matching
     #[test]
-    fn test_grep_substring_in_token() {
-        // "arch" is a substring of "archive" but not a standalone token
+    fn test_search_ranks_documents() {
         let docs = serde_json::to_string(&vec![
-            ("test.md", "This is an archive of data"),
+            ("a.md", "Refund policy\nRefunds are issued for refund requests within 30 days."),
+            ("b.md", "Shipping policy\nWe ship worldwide. No refund on shipping."),
+            ("c.md", "Policy overview\nSee the policy pages."),
+            ("d.md", "Unrelated content"),
         ]).unwrap();
         let fs = MemexFsCore::from_json(&docs).unwrap();
-        let results = fs.grep("arch", None).unwrap();
-        assert!(!results.is_empty(), "should find 'arch' inside 'archive'");
+
+        let results = fs.search("refund policy", None);
+        let paths: Vec<&str> = results.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.md", "b.md", "c.md"]);
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(results[0].line, 1);
+        assert_eq!(results[0].snippet, "Refund policy");
+
+        assert_eq!(fs.search("refund policy", Some(1)).len(), 1);
+        assert!(fs.search("nonexistent", None).is_empty());
+        assert!(fs.search("", None).is_empty());
+
+        let result = fs.call("search", r#"{"query": "shipping", "limit": 5}"#).unwrap();
+        let parsed: Vec<SearchResult> = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed[0].path, "b.md");
+
+        // Every occurrence on a line counts toward the term frequency
+        let docs = serde_json::to_string(&vec![("x.md", "refund other"), ("y.md", "refund refund")]).unwrap();
+        let results = MemexFsCore::from_json(&docs).unwrap().search("refund", None);
+        assert_eq!(results[0].path, "y.md");
+        assert!(results[0].score > results[1].score);
     }
 
     #[test]
-    fn test_grep_numeric_substring_in_compound_token() {
-        // "559571" embedded in "SE559571232301" — mimics org number in identifier
+    fn test_add_documents_in_batches() {
+        let mut fs = MemexFsCore::empty(&MemexOptions::default());
+        assert!(fs.finalize().is_err());
+
+        let first = serde_json::to_string(&vec![("a.md", "alpha refund"), ("b.md", "beta")]).unwrap();
+        let second = serde_json::to_string(&vec![("c.md", "gamma refund"), ("a.md", "alpha only")])
+            .unwrap();
+        assert_eq!(fs.add_documents(&first).unwrap(), 2);
+        assert_eq!(fs.add_documents(&second).unwrap(), 2);
+        assert!(fs.add_documents("not json").is_err());
+        fs.finalize().unwrap();
+
+        assert_eq!(fs.document_count(), 3);
+        // The second batch replaced a.md rather than indexing it twice
+        let results = fs.grep("refund", None).unwrap();
+        let paths: Vec<&str> = results.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, vec!["c.md"]);
+        assert_eq!(fs.grep("alpha", None).unwrap().len(), 1);
+
+        let all = serde_json::to_string(&vec![
+            ("a.md", "alpha only"),
+            ("b.md", "beta"),
+            ("c.md", "gamma refund"),
+        ]).unwrap();
+        assert_eq!(fs.token_count(), MemexFsCore::from_json(&all).unwrap().token_count());
+    }
+
+    #[test]
+    fn test_context_for() {
         let docs = serde_json::to_string(&vec![
-            ("org.md", "Company registered as SE559571232301 in Sweden"),
-            ("other.md", "Reference number 559571 standalone"),
-            ("unrelated.md", "No match here"),
+            ("faq.md", "Refunds take 5 days.\nTo request a refund policy exception, email us."),
+            ("billing/refund-policy.md", "# Refund policy\nOur refund policy covers 30 days."),
+            ("misc.md", "Nothing about money here.\nPolicy on pets: none."),
         ]).unwrap();
         let fs = MemexFsCore::from_json(&docs).unwrap();
-        let results = fs.grep("559571", None).unwrap();
-        assert_eq!(results.len(), 2, "should match both embedded and standalone");
-        assert!(results.iter().any(|r| r.path == "org.md"), "should find embedded match");
-        assert!(results.iter().any(|r| r.path == "other.md"), "should find standalone match");
+
+        let context = fs.context_for("refund policy", 30);
+        assert!(context::estimate_tokens(&context) <= 30);
+        // Both words on one line, in the document covering both, ranks first
+        assert!(context.starts_with("[billing/refund-policy.md:1] # Refund policy"));
+        assert!(!context.contains("misc.md"));
+
+        let full = fs.context_for("refund policy", 1000);
+        assert!(full.contains("[misc.md:2] Policy on pets: none."));
+        assert_eq!(fs.context_for("refund policy", 0), "");
+        assert_eq!(fs.context_for("  ", 100), "");
     }
 
     #[test]
-    fn test_grep_multi_word_substring_fallback() {
-        // Multi-word pattern where first token hits index but full phrase also
-        // needs substring matching across documents
+    fn test_rank_by_proximity() {
         let docs = serde_json::to_string(&vec![
-            ("a.md", "hackathon in sekoya was great"),
-            ("b.md", "The sekoya hackathon event"),
-            ("c.md", "No match here"),
+            ("far.md", "Reset the router first.\nThen wait a while before doing anything else.\nFinally update your password."),
+            ("near.md", "To reset your password, open settings."),
+            ("adjacent.md", "Password reset instructions"),
+            ("partial.md", "Reset only, no credentials mentioned"),
         ]).unwrap();
         let fs = MemexFsCore::from_json(&docs).unwrap();
-        let results = fs.grep("hackathon in sekoya", None).unwrap();
-        assert_eq!(results.len(), 1, "exact phrase only in a.md");
-        assert_eq!(results[0].path, "a.md");
+
+        let ranked = fs.rank_by_proximity("reset password");
+        assert_eq!(ranked, vec!["adjacent.md", "near.md", "far.md"]);
+        assert!(fs.rank_by_proximity("reset nonexistentterm").is_empty());
+        assert!(fs.rank_by_proximity("  ").is_empty());
     }
 
+    #[cfg(feature = "binary")]
     #[test]
-    fn test_ls_root() {
+    fn test_grep_binary_round_trip() {
         let fs = make_fs();
-        let entries = fs.ls("");
-        assert_eq!(entries, vec!["account/", "billing/"]);
+        let options = GrepOptions {
+            with_title: Some(true),
+            context_after: Some(1),
+            ..Default::default()
+        };
+        let bytes = fs.grep_binary("refund", &options).unwrap();
+        let decoded = decode_grep_results(&bytes).unwrap();
+        let expected = fs.grep_with_options("refund", &options).unwrap();
+
+        assert_eq!(decoded.len(), expected.len());
+        for (d, e) in decoded.iter().zip(&expected) {
+            assert_eq!((&d.path, d.line, &d.content), (&e.path, e.line, &e.content));
+            assert_eq!(d.title, e.title);
+            assert_eq!(d.context_after, e.context_after);
+            assert!(d.snippet.is_none());
+        }
+        assert!(bytes.len() < serde_json::to_vec(&expected).unwrap().len());
     }
 
+    #[cfg(feature = "stemming")]
     #[test]
-    fn test_ls_root_dot() {
-        let fs = make_fs();
-        let entries = fs.ls(".");
-        assert_eq!(entries, vec!["account/", "billing/"]);
+    fn test_stemming() {
+        let docs = serde_json::to_string(&vec![
+            ("a.md", "Keep the service running\nRun the migration"),
+            ("b.md", "Invoices are sent monthly\nthe brunch menu"),
+        ]).unwrap();
+        let plain = MemexFsCore::from_json(&docs).unwrap();
+        let options = MemexOptions {
+            stemmer: Some(StemLanguage::English),
+            ..Default::default()
+        };
+        let fs = MemexFsCore::from_json_with_options(&docs, &options).unwrap();
+        let hits = |results: Vec<GrepResult>| -> Vec<(String, u32)> {
+            results.into_iter().map(|r| (r.path, r.line)).collect()
+        };
+
+        assert_eq!(hits(plain.grep("running", None).unwrap()), vec![("a.md".to_string(), 1)]);
+        let results = fs.grep("running", None).unwrap();
+        assert_eq!(results[1].column, 1);
+        assert_eq!(hits(results), vec![("a.md".to_string(), 1), ("a.md".to_string(), 2)]);
+        // Stems add to substring matches rather than replacing them
+        assert_eq!(
+            hits(fs.grep("run", None).unwrap()),
+            vec![("a.md".to_string(), 1), ("a.md".to_string(), 2), ("b.md".to_string(), 2)]
+        );
+        assert_eq!(hits(fs.grep("invoice", None).unwrap()), vec![("b.md".to_string(), 1)]);
+
+        assert!(plain.search("invoiced", None).is_empty());
+        let results = fs.search("invoiced", None);
+        assert_eq!(results[0].path, "b.md");
+        assert!(results[0].snippet.contains("Invoices"));
     }
 
     #[test]
-    fn test_ls_subdirectory() {
+    fn test_extensions_mixed_corpus() {
+        let docs = serde_json::to_string(&vec![
+            ("guide.md", "# Guide"),
+            ("faq.md", "# FAQ"),
+            ("api/spec.json", "{}"),
+            ("api/schema.json", "{}"),
+            ("api/openapi.json", "{}"),
+            ("notes.txt", "notes"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        assert_eq!(
+            fs.extensions(),
+            vec![
+                (".json".to_string(), 3),
+                (".md".to_string(), 2),
+                (".txt".to_string(), 1),
+            ]
+        );
+
+        let result = fs.call("extensions", "{}").unwrap();
+        assert_eq!(result, r#"[[".json",3],[".md",2],[".txt",1]]"#);
+    }
+
+    #[test]
+    fn test_glob() {
         let fs = make_fs();
-        let entries = fs.ls("account");
-        assert_eq!(entries, vec!["password-reset.md"]);
+        assert_eq!(fs.glob("**/*.md"), vec!["account/password-reset.md", "billing/refund.md"]);
+        assert_eq!(fs.glob("billing/*"), vec!["billing/refund.md"]);
+        assert_eq!(fs.glob("**/*reset*"), vec!["account/password-reset.md"]);
+        assert!(fs.glob("*.md").is_empty(), "single star does not cross directories");
+
+        let result = fs.call("glob", r#"{"pattern": "**/refund.md"}"#).unwrap();
+        assert_eq!(result, r#"["billing/refund.md"]"#);
     }
 
     #[test]
-    fn test_ls_subdirectory_trailing_slash() {
+    fn test_tree_tool() {
         let fs = make_fs();
-        let entries = fs.ls("account/");
-        assert_eq!(entries, vec!["password-reset.md"]);
+        let text = fs.call("tree", "").unwrap();
+        assert_eq!(
+            text,
+            ".\n├── account/\n│   └── password-reset.md\n└── billing/\n    └── refund.md"
+        );
+        let shallow = fs.call("tree", r#"{"depth": 1}"#).unwrap();
+        assert_eq!(shallow, ".\n├── account/\n└── billing/");
+
+        let json = fs.call("tree", r#"{"path": "billing", "format": "json"}"#).unwrap();
+        let tree: TreeNode = serde_json::from_str(&json).unwrap();
+        assert_eq!(tree.name, "billing");
+        assert_eq!(tree.children.unwrap()[0].name, "refund.md");
     }
 
     #[test]
-    fn test_ls_empty_directory() {
+    fn test_tree_json() {
         let fs = make_fs();
-        let entries = fs.ls("nonexistent");
-        assert!(entries.is_empty());
+        let tree: serde_json::Value = serde_json::from_str(&fs.tree_json("", None)).unwrap();
+        assert_eq!(tree["type"], "dir");
+        assert_eq!(tree["children"][0]["name"], "account");
+        assert_eq!(tree["children"][0]["children"][0]["name"], "password-reset.md");
+        assert_eq!(tree["children"][0]["children"][0]["type"], "file");
+        assert!(tree["children"][0]["children"][0].get("children").is_none());
     }
 
     #[test]
-    fn test_ls_nested() {
+    fn test_grep_body_only_skips_frontmatter() {
         let docs = serde_json::to_string(&vec![
-            ("a/b/c.md", "content"),
-            ("a/b/d.md", "content"),
-            ("a/e.md", "content"),
-            ("f.md", "content"),
+            ("kb.md", "---\ntitle: Refund policy\n---\n# Refunds\n\nContact support."),
         ]).unwrap();
         let fs = MemexFsCore::from_json(&docs).unwrap();
 
-        let root = fs.ls("");
-        assert_eq!(root, vec!["a/", "f.md"]);
+        // Skipped by default
+        let results = fs.grep("title", None).unwrap();
+        assert!(results.is_empty(), "frontmatter-only term should be excluded");
+        let results = fs.grep("refund", None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line, 4);
 
-        let a = fs.ls("a");
-        assert_eq!(a, vec!["b/", "e.md"]);
+        let options = GrepOptions {
+            body_only: Some(false),
+            ..Default::default()
+        };
+        let results = fs.grep_with_options("title", &options).unwrap();
+        assert_eq!(results.len(), 1);
+        let results = fs.grep_with_options("refund", &options).unwrap();
+        assert_eq!(results[0].line, 2);
 
-        let ab = fs.ls("a/b");
-        assert_eq!(ab, vec!["c.md", "d.md"]);
+        let result = fs.call("grep", r#"{"pattern": "title:"}"#).unwrap();
+        assert_eq!(result, r#"{"results":[],"total_matches":0}"#);
+        let result = fs.call("grep", r#"{"pattern": "title:", "body_only": false}"#).unwrap();
+        assert!(result.contains(r#""total_matches":1"#));
+
+        // Frontmatter keys are exposed as metadata
+        assert_eq!(
+            fs.ls_with_metadata("")[0].metadata,
+            Some(serde_json::json!({ "title": "Refund policy" }))
+        );
     }
 
     #[test]
-    fn test_call_ls() {
-        let fs = make_fs();
-        let result = fs.call("ls", r#"{"path": ""}"#).unwrap();
-        let entries: Vec<String> = serde_json::from_str(&result).unwrap();
-        assert_eq!(entries, vec!["account/", "billing/"]);
+    fn test_grep_scope() {
+        let docs = serde_json::to_string(&vec![
+            ("cli.md", "# CLI\nRun memexfs index to build.\n```sh\nmemexfs index docs out.bin\n```"),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let lines = |scope: Option<GrepScope>, pattern: &str| -> Vec<u32> {
+            let options = GrepOptions { scope, ..Default::default() };
+            fs.grep_with_options(pattern, &options).unwrap().into_iter().map(|r| r.line).collect()
+        };
+
+        assert_eq!(lines(None, "index"), [2, 4]);
+        assert_eq!(lines(Some(GrepScope::Prose), "index"), [2]);
+        assert_eq!(lines(Some(GrepScope::Code), "index"), [4]);
+        assert_eq!(lines(Some(GrepScope::Code), "sh"), [3]);
+        assert_eq!(lines(Some(GrepScope::Prose), r"memexfs \w+ docs"), Vec::<u32>::new());
+
+        let result = fs.call("grep", r#"{"pattern": "memexfs", "scope": "code", "output": "count"}"#).unwrap();
+        assert_eq!(result, r#"[{"path":"cli.md","count":1}]"#);
     }
 
     #[test]
-    fn test_tool_definitions_includes_ls() {
-        let fs = make_fs();
-        let defs = fs.tool_definitions();
-        let parsed: serde_json::Value = serde_json::from_str(&defs).unwrap();
-        let arr = parsed.as_array().unwrap();
-        assert_eq!(arr.len(), 3);
-        assert!(arr.iter().any(|d| d["name"] == "ls"));
+    fn test_grep_ignore_markdown() {
+        let docs = serde_json::to_string(&vec![
+            ("math.md", "2 * 3 = 6"),
+            ("refunds.md", "- **Reset** your password\nSee [the guide](https://kb.example/refund-policy)."),
+        ]).unwrap();
+        let fs = MemexFsCore::from_json(&docs).unwrap();
+        let plain = GrepOptions {
+            ignore_markdown: Some(true),
+            ..Default::default()
+        };
+        let paths = |pattern: &str, options: &GrepOptions| -> Vec<(String, u32)> {
+            let results = fs.grep_with_options(pattern, options).unwrap();
+            results.into_iter().map(|r| (r.path, r.line)).collect()
+        };
+        let literal = GrepOptions { literal: Some(true), ..plain.clone() };
+
+        assert_eq!(paths("*", &GrepOptions { literal: Some(true), ..Default::default() }).len(), 2);
+        assert_eq!(paths("*", &literal), [("math.md".to_string(), 1)]);
+        assert_eq!(paths("refund-policy", &Default::default()).len(), 1);
+        assert!(paths("refund-policy", &plain).is_empty());
+        assert_eq!(paths("reset your", &plain), [("refunds.md".to_string(), 1)]);
+        assert_eq!(paths(r"^see the \w+\.$", &plain), [("refunds.md".to_string(), 2)]);
+
+        // Results keep the line as written
+        let results = fs.grep_with_options("the guide", &plain).unwrap();
+        assert_eq!(results[0].content, "See [the guide](https://kb.example/refund-policy).");
     }
 
     // Bug reproduction: duplicate matches per line
@@ -0,0 +1,278 @@
+use std::borrow::Cow;
+
+/// Characters that may start inline syntax.
+const INLINE: [char; 7] = ['*', '_', '~', '`', '[', '<', '\\'];
+
+/// Characters that may start a line's block marker, besides digits.
+const BLOCK: [char; 5] = ['#', '>', '-', '+', '*'];
+
+/// A line as it reads once rendered, for `GrepOptions::ignore_markdown`:
+/// heading, quote, list, and task markers are dropped, `[text](url)`
+/// links and `![alt](src)` images keep only their text, `<url>` autolinks
+/// lose the brackets, inline code loses its backticks, and paired `*`,
+/// `_`, and `~~` emphasis delimiters are removed. Backslash escapes are
+/// resolved. Anything unpaired or unclosed, like the `*` in `2 * 3` or
+/// `*nix`, is kept as written.
+pub fn plain(line: &str) -> Cow<'_, str> {
+    let block = line.trim_start().starts_with(|c: char| BLOCK.contains(&c) || c.is_ascii_digit());
+    if !block && !line.contains(INLINE) {
+        return Cow::Borrowed(line);
+    }
+    let body = strip_block_markers(line);
+    let chars: Vec<char> = body.chars().collect();
+    Cow::Owned(inline(&chars))
+}
+
+/// `line` without leading blockquote, heading, list, and task markers.
+fn strip_block_markers(line: &str) -> &str {
+    let mut rest = line.trim_start();
+    while let Some(quoted) = rest.strip_prefix('>') {
+        rest = quoted.trim_start();
+    }
+    let hashes = rest.len() - rest.trim_start_matches('#').len();
+    if (1..=6).contains(&hashes) && rest[hashes..].starts_with([' ', '\t']) {
+        return rest[hashes..].trim();
+    }
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let marker = match rest.as_bytes().first() {
+        Some(b'-' | b'*' | b'+') => 1,
+        Some(_) if (1..=9).contains(&digits) && rest[digits..].starts_with(['.', ')']) => digits + 1,
+        _ => 0,
+    };
+    if marker > 0 && rest[marker..].starts_with([' ', '\t']) {
+        rest = rest[marker..].trim_start();
+        for task in ["[ ] ", "[x] ", "[X] "] {
+            if let Some(item) = rest.strip_prefix(task) {
+                return item;
+            }
+        }
+    }
+    rest
+}
+
+/// A piece of inline text: a character, or a run of emphasis delimiters
+/// that is removed if it finds a partner.
+#[derive(Clone)]
+enum Piece {
+    Char(char),
+    Run { ch: char, len: usize, open: bool, close: bool, paired: bool },
+}
+
+fn inline(chars: &[char]) -> String {
+    let mut pieces = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if chars.get(i + 1).is_some_and(|n| n.is_ascii_punctuation()) => {
+                pieces.push(Piece::Char(chars[i + 1]));
+                i += 2;
+            }
+            '`' => {
+                let len = run_len(chars, i);
+                match closing_backticks(chars, i + len, len) {
+                    Some(end) => {
+                        let code = &chars[i + len..end];
+                        let code = match code {
+                            [' ', inner @ .., ' '] if !inner.is_empty() => inner,
+                            _ => code,
+                        };
+                        pieces.extend(code.iter().map(|&c| Piece::Char(c)));
+                        i = end + len;
+                    }
+                    None => {
+                        pieces.extend(std::iter::repeat_n(Piece::Char('`'), len));
+                        i += len;
+                    }
+                }
+            }
+            '!' | '[' => {
+                let start = if c == '!' { i + 1 } else { i };
+                match (chars.get(start) == Some(&'[')).then(|| link(chars, start)).flatten() {
+                    Some((text, end)) => {
+                        pieces.extend(inline(text).chars().map(Piece::Char));
+                        i = end;
+                    }
+                    None => {
+                        pieces.push(Piece::Char(c));
+                        i += 1;
+                    }
+                }
+            }
+            '<' => match autolink(chars, i) {
+                Some((url, end)) => {
+                    pieces.extend(url.iter().map(|&c| Piece::Char(c)));
+                    i = end;
+                }
+                None => {
+                    pieces.push(Piece::Char(c));
+                    i += 1;
+                }
+            },
+            '*' | '_' | '~' => {
+                let len = run_len(chars, i);
+                let before = i.checked_sub(1).map(|j| chars[j]);
+                let after = chars.get(i + len).copied();
+                let (mut open, mut close) = flanking(before, after);
+                if c == '_' {
+                    // snake_case and other intraword underscores stay
+                    let punct_or_none = |c: Option<char>| c.is_none_or(|c| !c.is_alphanumeric());
+                    open &= punct_or_none(before);
+                    close &= punct_or_none(after);
+                }
+                if c == '~' && len != 2 {
+                    (open, close) = (false, false);
+                }
+                pieces.push(Piece::Run { ch: c, len, open, close, paired: false });
+                i += len;
+            }
+            _ => {
+                pieces.push(Piece::Char(c));
+                i += 1;
+            }
+        }
+    }
+    pair_runs(&mut pieces);
+
+    let mut out = String::with_capacity(chars.len());
+    for piece in pieces {
+        match piece {
+            Piece::Char(c) => out.push(c),
+            Piece::Run { paired: true, .. } => {}
+            Piece::Run { ch, len, .. } => out.extend(std::iter::repeat_n(ch, len)),
+        }
+    }
+    out
+}
+
+fn run_len(chars: &[char], start: usize) -> usize {
+    chars[start..].iter().take_while(|&&c| c == chars[start]).count()
+}
+
+/// Start of the next run of exactly `len` backticks from `from`.
+fn closing_backticks(chars: &[char], from: usize, len: usize) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if chars[i] == '`' {
+            let run = run_len(chars, i);
+            if run == len {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// The text of a `[text](target)`, `[text][ref]`, or `[text][]` link
+/// starting at `start`, and the index after it.
+fn link(chars: &[char], start: usize) -> Option<(&[char], usize)> {
+    let mut depth = 0;
+    let mut close = None;
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let close = close?;
+    let (opener, closer) = match chars.get(close + 1)? {
+        '(' => ('(', ')'),
+        '[' => ('[', ']'),
+        _ => return None,
+    };
+    let mut depth = 0;
+    for (j, &c) in chars.iter().enumerate().skip(close + 1) {
+        if c == opener {
+            depth += 1;
+        } else if c == closer {
+            depth -= 1;
+            if depth == 0 {
+                return Some((&chars[start + 1..close], j + 1));
+            }
+        }
+    }
+    None
+}
+
+/// The URL of a `<scheme:...>` autolink starting at `start`, and the
+/// index after it.
+fn autolink(chars: &[char], start: usize) -> Option<(&[char], usize)> {
+    let end = start + chars[start..].iter().position(|&c| c == '>')?;
+    let url = &chars[start + 1..end];
+    let scheme = url.iter().position(|&c| c == ':')?;
+    let valid = scheme >= 2
+        && url[..scheme].iter().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+        && !url.iter().any(|c| c.is_whitespace() || *c == '<');
+    valid.then_some((url, end + 1))
+}
+
+/// Whether a delimiter run between `before` and `after` can open and
+/// close emphasis, by CommonMark's flanking rules.
+fn flanking(before: Option<char>, after: Option<char>) -> (bool, bool) {
+    let space = |c: Option<char>| c.is_none_or(char::is_whitespace);
+    let punct = |c: Option<char>| c.is_some_and(|c| c.is_ascii_punctuation());
+    let left = !space(after) && (!punct(after) || space(before) || punct(before));
+    let right = !space(before) && (!punct(before) || space(after) || punct(after));
+    (left, right)
+}
+
+/// Mark each closing run paired with the nearest open run of the same
+/// character before it. Open runs skipped over stay literal.
+fn pair_runs(pieces: &mut [Piece]) {
+    let mut openers: Vec<usize> = Vec::new();
+    for i in 0..pieces.len() {
+        let Piece::Run { ch, open, close, .. } = pieces[i] else {
+            continue;
+        };
+        let partner = close
+            .then(|| openers.iter().rposition(|&o| matches!(pieces[o], Piece::Run { ch: c, .. } if c == ch)))
+            .flatten();
+        if let Some(pos) = partner {
+            for j in [openers[pos], i] {
+                if let Piece::Run { paired, .. } = &mut pieces[j] {
+                    *paired = true;
+                }
+            }
+            openers.truncate(pos);
+        } else if open {
+            openers.push(i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain() {
+        let cases = [
+            ("## Refunds", "Refunds"),
+            ("> - [x] Ship **v2** _today_", "Ship v2 today"),
+            ("See [the *guide*](https://example.com/a_b.md) or [docs][ref].", "See the guide or docs."),
+            ("![logo](img/logo.png) <https://memex.dev>", "logo https://memex.dev"),
+            ("Run `grep -r` or ``a ` b``; 2 * 3 = 6", "Run grep -r or a ` b; 2 * 3 = 6"),
+            ("1. ~~old~~ new, ~5 min", "old new, ~5 min"),
+            ("snake_case_name on *nix, a < b", "snake_case_name on *nix, a < b"),
+            (r"\*not emphasis\*", "*not emphasis*"),
+            ("[unclosed](link", "[unclosed](link"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(plain(line), expected, "{:?}", line);
+        }
+        assert!(matches!(plain("nothing to strip"), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,154 @@
+use serde_json::{json, Value};
+
+use crate::error::MemexError;
+use crate::MemexFsCore;
+
+/// Protocol versions this handler speaks, newest first.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Answer one JSON-RPC 2.0 message from an MCP client. `None` for
+/// notifications, which get no response.
+pub fn handle(core: &mut MemexFsCore, message: &str) -> Option<String> {
+    let request: Value = match serde_json::from_str(message) {
+        Ok(request) => request,
+        Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+    };
+    let Some(method) = request["method"].as_str().filter(|_| request["jsonrpc"] == "2.0") else {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        return Some(error_response(id, INVALID_REQUEST, "not a JSON-RPC 2.0 request"));
+    };
+    // Requests carry an id; notifications don't and get no answer
+    let id = request.get("id").cloned()?;
+    let params = &request["params"];
+    let result = match method {
+        "initialize" => Ok(initialize(params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": list_tools(core) })),
+        "tools/call" => call_tool(core, params),
+        _ => Err((METHOD_NOT_FOUND, format!("method not found: {}", method))),
+    };
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string(),
+        Err((code, message)) => error_response(id, code, &message),
+    })
+}
+
+fn initialize(params: &Value) -> Value {
+    // Use the client's version if we speak it, else offer our newest
+    let requested = params["protocolVersion"].as_str();
+    let version = PROTOCOL_VERSIONS
+        .into_iter()
+        .find(|v| Some(*v) == requested)
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "memexfs", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// The tool definitions as MCP tools, with a JSON Schema `inputSchema`.
+fn list_tools(core: &MemexFsCore) -> Vec<Value> {
+    let definitions = core.tool_definitions_value();
+    definitions
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|def| {
+            json!({
+                "name": def["name"],
+                "description": def["description"],
+                "inputSchema": {
+                    "type": "object",
+                    "properties": def["parameters"],
+                    "required": def["required"],
+                },
+            })
+        })
+        .collect()
+}
+
+/// Run a tool. Failures of the tool itself are results with `isError`,
+/// so the model sees them; only a tool that can't be called is a
+/// protocol error.
+fn call_tool(core: &mut MemexFsCore, params: &Value) -> Result<Value, (i64, String)> {
+    let name = params["name"]
+        .as_str()
+        .ok_or((INVALID_PARAMS, "tools/call needs a tool name".to_string()))?;
+    let arguments = params.get("arguments").cloned().unwrap_or(Value::Null);
+    let (text, is_error) = match core.run_tool_mut(name, arguments) {
+        Ok(output) => (output.text, false),
+        Err(e @ (MemexError::UnknownTool { .. } | MemexError::ToolNotEnabled { .. })) => {
+            return Err((INVALID_PARAMS, e.to_string()));
+        }
+        Err(e) => (e.to_string(), true),
+    };
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    }))
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(core: &mut MemexFsCore, message: Value) -> Value {
+        serde_json::from_str(&handle(core, &message.to_string()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_mcp_session() {
+        let docs = serde_json::to_string(&vec![("billing/refund.md", "# Refunds\nContact support")])
+            .unwrap();
+        let mut core = MemexFsCore::from_json(&docs).unwrap();
+
+        let init = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": { "protocolVersion": "2025-03-26" },
+        });
+        let init = request(&mut core, init);
+        assert_eq!(init["id"], 1);
+        assert_eq!(init["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(init["result"]["serverInfo"]["name"], "memexfs");
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert_eq!(handle(&mut core, &notification.to_string()), None);
+
+        let list = request(&mut core, json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }));
+        let grep = &list["result"]["tools"][0];
+        assert_eq!(grep["name"], "grep");
+        assert_eq!(grep["inputSchema"]["type"], "object");
+        assert_eq!(grep["inputSchema"]["required"], json!(["pattern"]));
+
+        let call = |name: &str, arguments: Value| {
+            let params = json!({ "name": name, "arguments": arguments });
+            json!({ "jsonrpc": "2.0", "id": "c", "method": "tools/call", "params": params })
+        };
+        let read = request(&mut core, call("read", json!({ "path": "billing/refund.md" })));
+        assert_eq!(read["result"]["isError"], false);
+        assert_eq!(read["result"]["content"][0]["text"], "  1  # Refunds\n  2  Contact support");
+        let missing = request(&mut core, call("read", json!({ "path": "billing/refunds.md" })));
+        assert_eq!(missing["result"]["isError"], true);
+        assert!(missing["result"]["content"][0]["text"].as_str().unwrap().contains("did you mean"));
+        let unknown = request(&mut core, call("rm", json!({})));
+        assert_eq!(unknown["error"]["code"], INVALID_PARAMS);
+
+        let nope = request(&mut core, json!({ "jsonrpc": "2.0", "id": 3, "method": "resources/list" }));
+        assert_eq!(nope["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(request(&mut core, json!({ "id": 4 }))["error"]["code"], INVALID_REQUEST);
+        let garbled: Value = serde_json::from_str(&handle(&mut core, "{").unwrap()).unwrap();
+        assert_eq!(garbled["id"], Value::Null);
+        assert_eq!(garbled["error"]["code"], PARSE_ERROR);
+    }
+}
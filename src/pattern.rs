@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Mutex;
+#[cfg(feature = "fancy-regex")]
+use std::sync::Arc;
+
+use crate::bloom::FilterQuery;
+use crate::error::MemexError;
+
+/// A compiled grep regex. Patterns the `regex` crate accepts always use it;
+/// with the `fancy-regex` feature, ones it rejects (lookaround,
+/// backreferences) fall back to the backtracking `fancy_regex` engine.
+/// Cloning is cheap; both engines share the compiled program.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Standard(regex::Regex),
+    #[cfg(feature = "fancy-regex")]
+    Fancy(Arc<fancy_regex::Regex>),
+}
+
+impl Pattern {
+    pub fn new(source: &str, case_insensitive: bool) -> Result<Self, MemexError> {
+        let standard = regex::RegexBuilder::new(source)
+            .case_insensitive(case_insensitive)
+            .build();
+        match standard {
+            Ok(re) => Ok(Self::Standard(re)),
+            // An inline flag, since fancy_regex's own parser ignores the
+            // builder's case_insensitive for the parts it handles itself
+            #[cfg(feature = "fancy-regex")]
+            Err(_) => {
+                let flag = if case_insensitive { "(?i)" } else { "" };
+                fancy_regex::Regex::new(&format!("{}{}", flag, source))
+                    .map(|re| Self::Fancy(Arc::new(re)))
+                    .map_err(|e| MemexError::InvalidRegex { pattern: source.to_string(), reason: e.to_string() })
+            }
+            #[cfg(not(feature = "fancy-regex"))]
+            Err(e) => Err(MemexError::InvalidRegex { pattern: source.to_string(), reason: e.to_string() }),
+        }
+    }
+
+    /// Whether the pattern matches anywhere in `text`. A `Fancy` pattern
+    /// that exceeds its backtracking limit counts as no match.
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            Self::Standard(re) => re.is_match(text),
+            #[cfg(feature = "fancy-regex")]
+            Self::Fancy(re) => re.is_match(text).unwrap_or(false),
+        }
+    }
+
+    /// What a document must contain to hold a match. Patterns compiled
+    /// by `fancy_regex` rule nothing out.
+    pub fn filter_query(&self) -> FilterQuery {
+        match self {
+            Self::Standard(re) => FilterQuery::regex(re.as_str()),
+            #[cfg(feature = "fancy-regex")]
+            Self::Fancy(_) => FilterQuery::default(),
+        }
+    }
+
+    /// Byte range of the leftmost match in `text`.
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        match self {
+            Self::Standard(re) => re.find(text).map(|m| m.range()),
+            #[cfg(feature = "fancy-regex")]
+            Self::Fancy(re) => re.find(text).ok().flatten().map(|m| m.range()),
+        }
+    }
+
+    /// Byte ranges of successive non-overlapping matches in `text`.
+    pub fn find_iter<'a>(&'a self, text: &'a str) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+        match self {
+            Self::Standard(re) => Box::new(re.find_iter(text).map(|m| m.range())),
+            #[cfg(feature = "fancy-regex")]
+            Self::Fancy(re) => Box::new(re.find_iter(text).map_while(Result::ok).map(|m| m.range())),
+        }
+    }
+}
+
+/// Maximum number of compiled patterns kept by `PatternCache`.
+const CACHE_CAPACITY: usize = 64;
+
+/// Least-recently-used cache of compiled patterns, so an agent repeating
+/// the same grep across turns compiles it once. Shared through `&self`;
+/// invalid patterns are not cached.
+#[derive(Debug, Default)]
+pub struct PatternCache {
+    /// (source, case-insensitive, pattern), most recently used first.
+    entries: Mutex<VecDeque<(String, bool, Pattern)>>,
+}
+
+impl PatternCache {
+    /// The compiled `source`, from the cache or freshly compiled.
+    pub fn get(&self, source: &str, case_insensitive: bool) -> Result<Pattern, MemexError> {
+        let mut entries = self.entries.lock().unwrap();
+        let hit = entries
+            .iter()
+            .position(|(s, ci, _)| s == source && *ci == case_insensitive);
+        let entry = match hit {
+            Some(idx) => entries.remove(idx).expect("position is in bounds"),
+            None => (source.to_string(), case_insensitive, Pattern::new(source, case_insensitive)?),
+        };
+        let pattern = entry.2.clone();
+        entries.push_front(entry);
+        entries.truncate(CACHE_CAPACITY);
+        Ok(pattern)
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_patterns_use_the_standard_engine() {
+        let pattern = Pattern::new(r"pass\w+", true).unwrap();
+        assert!(matches!(pattern, Pattern::Standard(_)));
+        assert_eq!(pattern.find("My PASSWORD"), Some(3..11));
+        assert_eq!(pattern.find_iter("passes passed").collect::<Vec<_>>(), vec![0..6, 7..13]);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = PatternCache::default();
+        for i in 0..CACHE_CAPACITY {
+            cache.get(&format!("term{}", i), true).unwrap();
+        }
+        // Touch the oldest entry so the next insert evicts `term1` instead
+        cache.get("term0", true).unwrap();
+        cache.get("fresh", true).unwrap();
+        assert_eq!(cache.len(), CACHE_CAPACITY);
+
+        let entries = cache.entries.lock().unwrap();
+        let sources: Vec<&str> = entries.iter().map(|(s, _, _)| s.as_str()).collect();
+        assert_eq!(&sources[..2], &["fresh", "term0"]);
+        assert!(!sources.contains(&"term1"));
+        drop(entries);
+
+        // Case sensitivity is part of the key; errors are not cached
+        assert!(!cache.get("Term0", false).unwrap().is_match("term0"));
+        assert!(cache.get("(", true).is_err());
+        assert_eq!(cache.len(), CACHE_CAPACITY);
+    }
+
+    #[cfg(feature = "fancy-regex")]
+    #[test]
+    fn test_lookaround_falls_back_to_fancy() {
+        let pattern = Pattern::new(r"pass(?!word)\w+", true).unwrap();
+        assert!(matches!(pattern, Pattern::Fancy(_)));
+        assert!(!pattern.is_match("password"));
+        assert_eq!(pattern.find("Password, Passport"), Some(10..18));
+
+        let repeated = Pattern::new(r"\b(\w+) \1\b", false).unwrap();
+        assert_eq!(repeated.find_iter("the the cat sat sat").collect::<Vec<_>>(), vec![0..7, 12..19]);
+    }
+
+    #[cfg(not(feature = "fancy-regex"))]
+    #[test]
+    fn test_lookaround_is_rejected_without_fancy() {
+        let err = Pattern::new(r"pass(?!word)", true).unwrap_err();
+        assert_eq!(err.code(), "invalid_regex");
+        assert!(err.to_string().starts_with("MemexError: invalid regex"));
+    }
+}
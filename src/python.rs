@@ -0,0 +1,216 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+use serde::Serialize;
+
+use crate::{DirOptions, GrepOptions, MemexFsCore, MemexOptions, ReadOptions};
+
+mod exceptions {
+    pyo3::create_exception!(memexfs, MemexError, pyo3::exceptions::PyException);
+}
+
+/// Python-exported MemexFS. Thin wrapper over MemexFsCore that passes values
+/// through JSON and raises `memexfs.MemexError`.
+#[pyclass(name = "MemexFS", module = "memexfs")]
+pub struct MemexFS {
+    core: MemexFsCore,
+}
+
+#[pymethods]
+impl MemexFS {
+    /// Index `docs`: a list of `[path, content]` pairs or
+    /// `{path, content, metadata}` dicts, or their JSON string.
+    #[new]
+    #[pyo3(signature = (docs, options=None))]
+    fn new(docs: &Bound<'_, PyAny>, options: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let options = memex_options(options)?;
+        let core =
+            MemexFsCore::from_json_with_options(&json_arg(docs)?, &options).map_err(py_error)?;
+        Ok(MemexFS { core })
+    }
+
+    /// Index the text files under the directory `path` (see `DirOptions`).
+    #[staticmethod]
+    #[pyo3(signature = (path, include=None, exclude=None, options=None))]
+    fn from_dir(
+        path: std::path::PathBuf,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        options: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let options = DirOptions {
+            include: include.unwrap_or_default(),
+            exclude: exclude.unwrap_or_default(),
+            options: memex_options(options)?,
+        };
+        let core = MemexFsCore::from_dir(path, &options).map_err(py_error)?;
+        Ok(MemexFS { core })
+    }
+
+    #[staticmethod]
+    fn from_snapshot(bytes: &[u8]) -> PyResult<Self> {
+        let core = MemexFsCore::from_snapshot(bytes).map_err(py_error)?;
+        Ok(MemexFS { core })
+    }
+
+    fn to_snapshot(&self) -> Vec<u8> {
+        self.core.to_snapshot()
+    }
+
+    /// Matching lines as dicts (`path`, `line`, `content`, ...). `options`
+    /// is a `GrepOptions` dict, as in `grep_with_options`.
+    #[pyo3(signature = (pattern, glob=None, options=None))]
+    fn grep(
+        &self,
+        py: Python<'_>,
+        pattern: &str,
+        glob: Option<String>,
+        options: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        let mut options: GrepOptions = match options {
+            Some(options) => from_json_arg(options)?,
+            None => GrepOptions::default(),
+        };
+        options.glob = glob.or(options.glob);
+        let results = self.core.grep_with_options(pattern, &options).map_err(py_error)?;
+        to_py(py, &results)
+    }
+
+    #[pyo3(signature = (path, offset=None, limit=None, tab_width=None, section=None))]
+    fn read(
+        &self,
+        path: &str,
+        offset: Option<usize>,
+        limit: Option<usize>,
+        tab_width: Option<usize>,
+        section: Option<String>,
+    ) -> PyResult<String> {
+        let options = ReadOptions { offset, limit, tab_width, section };
+        self.core.read_with_options(path, &options).map_err(py_error)
+    }
+
+    #[pyo3(signature = (path=""))]
+    fn ls(&self, path: &str) -> Vec<String> {
+        self.core.ls(path)
+    }
+
+    fn glob(&self, pattern: &str) -> Vec<String> {
+        self.core.glob(pattern)
+    }
+
+    /// Run an LLM tool by name with a dict (or JSON string) of params and
+    /// return its output text, as in the JS `call`.
+    #[pyo3(signature = (name, params=None))]
+    fn call(&self, name: &str, params: Option<&Bound<'_, PyAny>>) -> PyResult<String> {
+        self.core.call(name, &params.map(json_arg).transpose()?.unwrap_or_default()).map_err(py_error)
+    }
+
+    /// `call` that can also run `write`.
+    #[pyo3(signature = (name, params=None))]
+    fn call_mut(&mut self, name: &str, params: Option<&Bound<'_, PyAny>>) -> PyResult<String> {
+        self.core.call_mut(name, &params.map(json_arg).transpose()?.unwrap_or_default()).map_err(py_error)
+    }
+
+    fn tool_definitions(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        to_py(py, &self.core.tool_definitions_value())
+    }
+
+    fn tool_definitions_openai(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        to_py(py, &self.core.tool_definitions_openai_value())
+    }
+
+    fn document_count(&self) -> usize {
+        self.core.document_count()
+    }
+
+    fn __len__(&self) -> usize {
+        self.core.document_count()
+    }
+}
+
+#[pymodule]
+fn memexfs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<MemexFS>()?;
+    m.add("MemexError", m.py().get_type::<exceptions::MemexError>())?;
+    Ok(())
+}
+
+/// `memexfs.MemexError` with the message as its argument and `code` plus
+/// the context fields (`path`, `suggestions`, ...) as attributes.
+fn py_error(error: crate::MemexError) -> PyErr {
+    Python::attach(|py| {
+        let err = exceptions::MemexError::new_err(error.to_string());
+        let value = err.value(py);
+        if let Ok(fields) = to_py(py, &error.to_json()) {
+            if let Ok(fields) = fields.bind(py).cast::<PyDict>() {
+                for (key, field) in fields.iter() {
+                    let _ = value.setattr(key.cast::<PyString>().unwrap(), field);
+                }
+            }
+        }
+        err
+    })
+}
+
+fn memex_options(options: Option<&Bound<'_, PyAny>>) -> PyResult<MemexOptions> {
+    options.map_or_else(|| Ok(MemexOptions::default()), from_json_arg)
+}
+
+/// A Python value as JSON text; strings are taken to be JSON already.
+fn json_arg(value: &Bound<'_, PyAny>) -> PyResult<String> {
+    if let Ok(text) = value.cast::<PyString>() {
+        return Ok(text.to_str()?.to_string());
+    }
+    let json = value.py().import("json")?;
+    json.call_method1("dumps", (value,))?.extract()
+}
+
+fn from_json_arg<T: serde::de::DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    serde_json::from_str(&json_arg(value)?)
+        .map_err(|e| py_error(crate::MemexError::invalid_params(e)))
+}
+
+/// A serializable value as Python objects (dicts, lists, ...), by way of
+/// `json.loads`.
+fn to_py(py: Python<'_>, value: &impl Serialize) -> PyResult<Py<PyAny>> {
+    let text = serde_json::to_string(value)
+        .map_err(|e| py_error(crate::MemexError::encoding(e)))?;
+    Ok(py.import("json")?.call_method1("loads", (text,))?.unbind())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_python_module() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = pyo3::wrap_pymodule!(memexfs)(py);
+            let modules = py.import("sys").unwrap().getattr("modules").unwrap();
+            modules.set_item("memexfs", module).unwrap();
+            let script = CString::new(
+                r##"
+import memexfs
+fs = memexfs.MemexFS([["billing/refund.md", "# Refunds\nContact support"]])
+assert len(fs) == 1
+assert fs.grep("support")[0]["line"] == 2
+assert fs.read("billing/refund.md", limit=1) == "  1  # Refunds"
+assert fs.ls() == ["billing/"]
+assert fs.call("grep", {"pattern": "refunds", "output": "files"}) == '["billing/refund.md"]'
+assert fs.tool_definitions()[0]["name"] == "grep"
+try:
+    fs.read("billing/refunds.md")
+    raise AssertionError("expected MemexError")
+except memexfs.MemexError as e:
+    assert e.code == "document_not_found"
+    assert e.suggestions == ["billing/refund.md"]
+restored = memexfs.MemexFS.from_snapshot(fs.to_snapshot())
+assert restored.glob("**/*.md") == ["billing/refund.md"]
+"##,
+            )
+            .unwrap();
+            py.run(&script, None, None).unwrap();
+        });
+    }
+}
@@ -0,0 +1,92 @@
+/// BM25 term-frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 document-length normalization.
+const B: f64 = 0.75;
+
+/// Corpus-wide statistics shared by every term score in a query.
+#[derive(Debug, Clone, Copy)]
+pub struct CorpusStats {
+    /// Number of documents.
+    pub docs: usize,
+    /// Average document length in tokens.
+    pub avg_len: f64,
+}
+
+/// Inverse document frequency of a term found in `df` documents, using the
+/// non-negative BM25+ variant so very common terms never subtract score.
+pub fn idf(stats: CorpusStats, df: usize) -> f64 {
+    let n = stats.docs as f64;
+    let df = df as f64;
+    (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+}
+
+/// BM25 contribution of one term occurring `tf` times in a document of
+/// `doc_len` tokens.
+pub fn term_score(stats: CorpusStats, idf: f64, tf: usize, doc_len: usize) -> f64 {
+    if tf == 0 {
+        return 0.0;
+    }
+    let tf = tf as f64;
+    let norm = if stats.avg_len > 0.0 {
+        1.0 - B + B * doc_len as f64 / stats.avg_len
+    } else {
+        1.0
+    };
+    idf * tf * (K1 + 1.0) / (tf + K1 * norm)
+}
+
+/// Score for a top-level (`#`) heading; each deeper level scores less.
+const HEADING_BOOST: f64 = 3.0;
+/// Score for a match in a document whose file name also matches.
+const FILE_NAME_BOOST: f64 = 2.0;
+/// Body lines after which the position bonus has halved.
+const POSITION_HALF_LIFE: f64 = 20.0;
+
+/// Relevance of a grep match on a line. Matches in headings (`heading_level`),
+/// in documents whose file name matches, and near the top of the body
+/// (`body_line`, 0-indexed after frontmatter) score higher.
+pub fn line_relevance(heading_level: Option<u8>, in_file_name: bool, body_line: usize) -> f64 {
+    let heading = heading_level.map_or(0.0, |level| HEADING_BOOST / level as f64);
+    let file_name = if in_file_name { FILE_NAME_BOOST } else { 0.0 };
+    let position = 1.0 / (1.0 + body_line as f64 / POSITION_HALF_LIFE);
+    heading + file_name + position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATS: CorpusStats = CorpusStats {
+        docs: 10,
+        avg_len: 100.0,
+    };
+
+    #[test]
+    fn test_rarer_terms_weigh_more() {
+        assert!(idf(STATS, 1) > idf(STATS, 5));
+        assert!(idf(STATS, 10) > 0.0);
+    }
+
+    #[test]
+    fn test_term_score_saturates_and_normalizes() {
+        let w = idf(STATS, 2);
+        assert_eq!(term_score(STATS, w, 0, 100), 0.0);
+        let one = term_score(STATS, w, 1, 100);
+        let two = term_score(STATS, w, 2, 100);
+        let twenty = term_score(STATS, w, 20, 100);
+        assert!(two > one && twenty > two);
+        assert!(twenty < w * (K1 + 1.0));
+        // The same count in a shorter document scores higher
+        assert!(term_score(STATS, w, 2, 50) > two);
+    }
+
+    #[test]
+    fn test_line_relevance_boosts() {
+        let deep = line_relevance(None, false, 400);
+        let early = line_relevance(None, false, 0);
+        assert!(early > deep);
+        assert!(line_relevance(Some(1), false, 400) > early);
+        assert!(line_relevance(Some(1), false, 0) > line_relevance(Some(2), false, 0));
+        assert!(line_relevance(None, true, 400) > early);
+    }
+}
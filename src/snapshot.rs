@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::MemexError;
+use crate::stopwords::StopWords;
+use crate::store::DocumentStore;
+use crate::synonyms::{SynonymMode, Synonyms};
+
+/// Leading bytes identifying a snapshot and its format version. Bump the
+/// digit whenever the layout of any serialized type changes.
+const MAGIC: &[u8; 4] = b"MX17";
+
+/// Whether this build folds text with the `unicode-folding` feature.
+pub const UNICODE_FOLDING: bool = cfg!(feature = "unicode-folding");
+
+/// Borrowed view of everything a snapshot stores, for encoding.
+#[derive(Serialize)]
+pub struct SnapshotRef<'a> {
+    /// Whether the index was built with `unicode-folding`; a build folding
+    /// text differently could not query it.
+    pub unicode_folding: bool,
+    pub store: &'a DocumentStore,
+    pub synonyms: &'a Synonyms,
+    pub synonym_mode: SynonymMode,
+    pub stop_words: &'a StopWords,
+}
+
+/// A decoded snapshot. Field order must match `SnapshotRef`.
+#[derive(Deserialize)]
+pub struct Snapshot {
+    pub unicode_folding: bool,
+    pub store: DocumentStore,
+    pub synonyms: Synonyms,
+    pub synonym_mode: SynonymMode,
+    pub stop_words: StopWords,
+}
+
+pub fn encode(snapshot: &SnapshotRef) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bincode::serialize_into(&mut bytes, snapshot).expect("in-memory serialization cannot fail");
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Snapshot, MemexError> {
+    let body = bytes
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| MemexError::InvalidSnapshot { reason: "not a memexfs snapshot".to_string() })?;
+    let mut snapshot: Snapshot = bincode::deserialize(body)
+        .map_err(|e| MemexError::InvalidSnapshot { reason: format!("corrupt snapshot: {}", e) })?;
+    if snapshot.unicode_folding != UNICODE_FOLDING {
+        return Err(MemexError::InvalidSnapshot {
+            reason: format!(
+                "snapshot was built {} the unicode-folding feature",
+                if snapshot.unicode_folding { "with" } else { "without" }
+            ),
+        });
+    }
+    snapshot.store.restore();
+    Ok(snapshot)
+}
@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::document::DocInput;
+use crate::error::MemexError;
+
+/// Characters of a document looked at when deciding whether it is text.
+const SAMPLE_CHARS: usize = 8192;
+
+/// Share of control characters (other than tabs, line breaks, and form
+/// feeds) and U+FFFD replacement characters in the sample above which a
+/// document counts as binary.
+const MAX_CONTROL_RATIO: f64 = 0.1;
+
+/// What happens to a document that isn't text: bytes that aren't UTF-8,
+/// content containing NUL, or content that is mostly control characters,
+/// like an image decoded as a string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryPolicy {
+    /// Leave it out of the corpus.
+    #[default]
+    Skip,
+    /// Fail the load (or write) with a `binary_document` error naming it.
+    Reject,
+    /// Keep its path with empty content and `{"binary": true, "bytes": n}`
+    /// metadata, so it shows in `ls` and `glob` but never matches.
+    Stub,
+}
+
+/// Why `content` isn't text, or `None` if it is.
+pub fn detect(content: &str) -> Option<&'static str> {
+    if content.contains('\0') {
+        return Some("contains NUL bytes");
+    }
+    let mut sampled = 0;
+    let mut control = 0;
+    for c in content.chars().take(SAMPLE_CHARS) {
+        sampled += 1;
+        if c == '\u{FFFD}' || (c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c')) {
+            control += 1;
+        }
+    }
+    (sampled > 0 && control as f64 / sampled as f64 > MAX_CONTROL_RATIO).then_some("mostly control characters")
+}
+
+/// A document read from a file's bytes, screened by `policy`: `None` if
+/// it is skipped.
+pub fn from_bytes(path: String, bytes: Vec<u8>, policy: BinaryPolicy) -> Result<Option<DocInput>, MemexError> {
+    match String::from_utf8(bytes) {
+        Ok(content) => screen(DocInput::Pair(path, content), policy),
+        Err(e) => {
+            let bytes = e.as_bytes().len();
+            apply(path, None, bytes, "not valid UTF-8", policy)
+        }
+    }
+}
+
+/// `doc`, screened by `policy`: `None` if it is skipped.
+pub fn screen(doc: DocInput, policy: BinaryPolicy) -> Result<Option<DocInput>, MemexError> {
+    let Some(reason) = detect(doc.content()) else {
+        return Ok(Some(doc));
+    };
+    let (path, content, metadata) = doc.into_parts();
+    apply(path, metadata, content.len(), reason, policy)
+}
+
+/// Every document in `docs` screened by `policy`, failing on the first
+/// rejected one before any is loaded.
+pub fn screen_all(docs: Vec<DocInput>, policy: BinaryPolicy) -> Result<Vec<DocInput>, MemexError> {
+    let mut screened = Vec::with_capacity(docs.len());
+    for doc in docs {
+        screened.extend(screen(doc, policy)?);
+    }
+    Ok(screened)
+}
+
+fn apply(
+    path: String,
+    metadata: Option<Value>,
+    bytes: usize,
+    reason: &str,
+    policy: BinaryPolicy,
+) -> Result<Option<DocInput>, MemexError> {
+    match policy {
+        BinaryPolicy::Skip => Ok(None),
+        BinaryPolicy::Reject => Err(MemexError::BinaryDocument { path, reason: reason.to_string() }),
+        BinaryPolicy::Stub => {
+            let metadata = Some(stub_metadata(metadata, bytes));
+            Ok(Some(DocInput::Object { path, content: String::new(), metadata }))
+        }
+    }
+}
+
+/// A stub's metadata: `metadata` (if an object) with `"binary": true` and
+/// the original size in `"bytes"`.
+pub fn stub_metadata(metadata: Option<Value>, bytes: usize) -> Value {
+    let mut metadata = match metadata {
+        Some(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    metadata.insert("binary".to_string(), Value::Bool(true));
+    metadata.insert("bytes".to_string(), bytes.into());
+    Value::Object(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("# Guide\n\tIndented\r\n\x0cNext page"), None);
+        assert_eq!(detect(""), None);
+        assert_eq!(detect("a\0b"), Some("contains NUL bytes"));
+        let png = String::from_utf8_lossy(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x01\x00\x08\x06\xff\xfe");
+        assert!(detect(&png).is_some());
+        assert_eq!(detect("\x1b[1mbold\x1b[0m and some plain words around it"), None);
+        assert_eq!(detect("\x01\x02\x03\x04 ab"), Some("mostly control characters"));
+    }
+
+    #[test]
+    fn test_policies() {
+        let png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let paths = |docs: Vec<DocInput>| docs.iter().map(|d| d.path().to_string()).collect::<Vec<_>>();
+        assert!(from_bytes("logo.png".into(), png.clone(), BinaryPolicy::Skip).unwrap().is_none());
+        let err = from_bytes("logo.png".into(), png.clone(), BinaryPolicy::Reject).unwrap_err();
+        assert_eq!(err, MemexError::BinaryDocument { path: "logo.png".into(), reason: "not valid UTF-8".into() });
+        let stub = from_bytes("logo.png".into(), png, BinaryPolicy::Stub).unwrap().unwrap();
+        let (path, content, metadata) = stub.into_parts();
+        assert_eq!((path.as_str(), content.as_str()), ("logo.png", ""));
+        assert_eq!(metadata.unwrap(), serde_json::json!({"binary": true, "bytes": 8}));
+
+        let docs = || {
+            vec![
+                DocInput::Pair("a.md".into(), "# A".into()),
+                DocInput::Object {
+                    path: "b.bin".into(),
+                    content: "\u{1}\u{2}\u{3}".into(),
+                    metadata: Some(serde_json::json!({"source": "upload"})),
+                },
+            ]
+        };
+        assert_eq!(paths(screen_all(docs(), BinaryPolicy::Skip).unwrap()), ["a.md"]);
+        assert_eq!(screen_all(docs(), BinaryPolicy::Reject).unwrap_err().code(), "binary_document");
+        let stubbed = screen_all(docs(), BinaryPolicy::Stub).unwrap();
+        let (_, _, metadata) = stubbed[1].clone().into_parts();
+        assert_eq!(metadata.unwrap(), serde_json::json!({"source": "upload", "binary": true, "bytes": 3}));
+    }
+}
@@ -0,0 +1,165 @@
+use serde::Deserialize;
+
+/// Default number of characters kept on each side of a match.
+pub const DEFAULT_RADIUS: usize = 60;
+
+/// How the window around a match is chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnippetMode {
+    /// Fixed number of characters on each side of the match.
+    #[default]
+    Chars,
+    /// Snap to the enclosing sentence, falling back to the character radius
+    /// when no boundary lies within twice the radius.
+    Sentence,
+}
+
+/// Extract a snippet around the match at byte range `start..end` of `line`.
+/// Sides cut mid-text are marked with `...`; sides that end on a sentence
+/// boundary are left unmarked.
+pub fn extract(line: &str, start: usize, end: usize, radius: usize, mode: SnippetMode) -> String {
+    let (from, from_cut) = match mode {
+        SnippetMode::Sentence => match sentence_start(line, start, radius) {
+            Some(from) => (from, false),
+            None => (back(line, start, radius), true),
+        },
+        SnippetMode::Chars => {
+            let from = back(line, start, radius);
+            (from, from > 0)
+        }
+    };
+    let (to, to_cut) = match mode {
+        SnippetMode::Sentence => match sentence_end(line, end, radius) {
+            Some(to) => (to, false),
+            None => (forward(line, end, radius), true),
+        },
+        SnippetMode::Chars => {
+            let to = forward(line, end, radius);
+            (to, to < line.len())
+        }
+    };
+
+    let mut out = String::new();
+    if from_cut {
+        out.push_str("...");
+    }
+    out.push_str(&line[from..to]);
+    if to_cut {
+        out.push_str("...");
+    }
+    out
+}
+
+/// Byte index `n` characters before `pos` (clamped to the line start).
+fn back(line: &str, pos: usize, n: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .take(n)
+        .last()
+        .map_or(pos, |(i, _)| i)
+}
+
+/// Byte index `n` characters after `pos` (clamped to the line end).
+fn forward(line: &str, pos: usize, n: usize) -> usize {
+    line[pos..]
+        .char_indices()
+        .nth(n)
+        .map_or(line.len(), |(i, _)| pos + i)
+}
+
+fn is_terminator(c: char) -> bool {
+    matches!(c, '.' | '!' | '?')
+}
+
+/// Start of the sentence containing `pos`, if a boundary (or the line
+/// start) lies within `2 * radius` characters before it.
+fn sentence_start(line: &str, pos: usize, radius: usize) -> Option<usize> {
+    let limit = back(line, pos, radius * 2);
+    let window = &line[limit..pos];
+
+    let mut next_is_space = false;
+    let mut boundary = None;
+    for (i, c) in window.char_indices().rev() {
+        if is_terminator(c) && next_is_space {
+            boundary = Some(limit + i + c.len_utf8());
+            break;
+        }
+        next_is_space = c.is_whitespace();
+    }
+
+    let start = match boundary {
+        Some(b) => b,
+        None if limit == 0 => 0,
+        None => return None,
+    };
+    // Skip the whitespace separating the previous sentence
+    let skipped = line[start..pos].len() - line[start..pos].trim_start().len();
+    Some(start + skipped)
+}
+
+/// End of the sentence containing `pos` (just past its terminator), if a
+/// boundary (or the line end) lies within `2 * radius` characters after it.
+fn sentence_end(line: &str, pos: usize, radius: usize) -> Option<usize> {
+    let limit = forward(line, pos, radius * 2);
+    let window = &line[pos..];
+
+    let mut chars = window.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if pos + i >= limit {
+            break;
+        }
+        let at_boundary = chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+        if is_terminator(c) && at_boundary {
+            return Some(pos + i + c.len_utf8());
+        }
+    }
+
+    (limit == line.len()).then_some(line.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn around(line: &str, needle: &str, radius: usize, mode: SnippetMode) -> String {
+        let start = line.find(needle).unwrap();
+        extract(line, start, start + needle.len(), radius, mode)
+    }
+
+    #[test]
+    fn test_chars_mode() {
+        let line = "aaaa bbbb MATCH cccc dddd";
+        assert_eq!(around(line, "MATCH", 5, SnippetMode::Chars), "...bbbb MATCH cccc...");
+        assert_eq!(around(line, "MATCH", 100, SnippetMode::Chars), line);
+    }
+
+    #[test]
+    fn test_sentence_mode() {
+        let line = "First sentence here. The refund is processed quickly! Last one? Yes.";
+        assert_eq!(
+            around(line, "refund", 20, SnippetMode::Sentence),
+            "The refund is processed quickly!"
+        );
+        assert_eq!(around(line, "First", 20, SnippetMode::Sentence), "First sentence here.");
+        assert_eq!(around(line, "Yes", 20, SnippetMode::Sentence), "Yes.");
+    }
+
+    #[test]
+    fn test_sentence_mode_falls_back_to_radius() {
+        let line = "no boundaries anywhere in this rather long line of words around the MATCH and then it keeps going on and on without stopping";
+        let snippet = around(line, "MATCH", 5, SnippetMode::Sentence);
+        assert_eq!(snippet, "... the MATCH and ...");
+    }
+
+    #[test]
+    fn test_multibyte_safe() {
+        let line = "Åäö läser. Här är träffen här. Slut";
+        assert_eq!(around(line, "träffen", 3, SnippetMode::Chars), "...är träffen hä...");
+        assert_eq!(
+            around(line, "träffen", 20, SnippetMode::Sentence),
+            "Här är träffen här."
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+/// Language of the Snowball stemmer used with `MemexOptions::stemmer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StemLanguage {
+    Arabic,
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Tamil,
+    Turkish,
+}
+
+impl StemLanguage {
+    /// Stem of a case-folded token. Without the `stemming` feature (e.g. a
+    /// snapshot built elsewhere) every token is its own stem.
+    pub fn stem(self, token: &str) -> String {
+        #[cfg(feature = "stemming")]
+        {
+            rust_stemmers::Stemmer::create(self.algorithm())
+                .stem(token)
+                .into_owned()
+        }
+        #[cfg(not(feature = "stemming"))]
+        {
+            token.to_string()
+        }
+    }
+
+    #[cfg(feature = "stemming")]
+    fn algorithm(self) -> rust_stemmers::Algorithm {
+        use rust_stemmers::Algorithm;
+        match self {
+            Self::Arabic => Algorithm::Arabic,
+            Self::Danish => Algorithm::Danish,
+            Self::Dutch => Algorithm::Dutch,
+            Self::English => Algorithm::English,
+            Self::Finnish => Algorithm::Finnish,
+            Self::French => Algorithm::French,
+            Self::German => Algorithm::German,
+            Self::Greek => Algorithm::Greek,
+            Self::Hungarian => Algorithm::Hungarian,
+            Self::Italian => Algorithm::Italian,
+            Self::Norwegian => Algorithm::Norwegian,
+            Self::Portuguese => Algorithm::Portuguese,
+            Self::Romanian => Algorithm::Romanian,
+            Self::Russian => Algorithm::Russian,
+            Self::Spanish => Algorithm::Spanish,
+            Self::Swedish => Algorithm::Swedish,
+            Self::Tamil => Algorithm::Tamil,
+            Self::Turkish => Algorithm::Turkish,
+        }
+    }
+}
+
+/// Groups indexed tokens by stem, so a query word can be expanded to every
+/// inflection in the corpus (`invoice` → `invoices`, `invoiced`) and looked
+/// up exactly. Indexed tokens and query words go through the same
+/// `StemLanguage::stem`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StemIndex {
+    language: StemLanguage,
+    /// Stem → indexed tokens with that stem.
+    variants: HashMap<String, BTreeSet<String>>,
+}
+
+impl StemIndex {
+    #[cfg(feature = "stemming")]
+    pub fn new(language: StemLanguage) -> Self {
+        Self {
+            language,
+            variants: HashMap::new(),
+        }
+    }
+
+    /// An empty index for the same language.
+    pub fn empty_like(&self) -> Self {
+        Self {
+            language: self.language,
+            variants: HashMap::new(),
+        }
+    }
+
+    pub fn stem(&self, token: &str) -> String {
+        self.language.stem(token)
+    }
+
+    /// Record an indexed token.
+    pub fn insert(&mut self, token: &str) {
+        self.variants
+            .entry(self.stem(token))
+            .or_default()
+            .insert(token.to_string());
+    }
+
+    /// Forget a token that left the index.
+    pub fn remove(&mut self, token: &str) {
+        let stem = self.stem(token);
+        if let Some(tokens) = self.variants.get_mut(&stem) {
+            tokens.remove(token);
+            if tokens.is_empty() {
+                self.variants.remove(&stem);
+            }
+        }
+    }
+
+    /// Estimated heap bytes, counting each map entry at its inline size.
+    pub fn heap_size(&self) -> usize {
+        self.variants
+            .iter()
+            .map(|(stem, tokens)| {
+                size_of::<(String, BTreeSet<String>)>()
+                    + stem.capacity()
+                    + tokens.iter().map(|t| size_of::<String>() + t.capacity()).sum::<usize>()
+            })
+            .sum()
+    }
+
+    /// Indexed tokens sharing the stem of `word`, which must be case-folded.
+    pub fn variants(&self, word: &str) -> impl Iterator<Item = &str> {
+        self.variants
+            .get(&self.stem(word))
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+}
+
+#[cfg(all(test, feature = "stemming"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variants_share_a_stem() {
+        let mut stems = StemIndex::new(StemLanguage::English);
+        for token in ["running", "runs", "run", "invoices", "invoice", "runner"] {
+            stems.insert(token);
+        }
+
+        assert_eq!(stems.variants("run").collect::<Vec<_>>(), vec!["run", "running", "runs"]);
+        assert_eq!(stems.variants("invoiced").collect::<Vec<_>>(), vec!["invoice", "invoices"]);
+        stems.remove("runs");
+        assert_eq!(stems.variants("ran").count(), 0);
+        assert_eq!(stems.variants("runs").collect::<Vec<_>>(), vec!["run", "running"]);
+    }
+}
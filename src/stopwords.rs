@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fold::fold_case;
+
+/// Languages with a built-in stop-word list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StopLanguage {
+    English,
+    French,
+    German,
+    Spanish,
+}
+
+/// Stop-word configuration: a built-in list, or the host's own words.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StopWordList {
+    Language(StopLanguage),
+    Words(Vec<String>),
+}
+
+const ENGLISH: &[&str] = &[
+    "a", "about", "an", "and", "are", "as", "at", "be", "but", "by", "can", "do", "does",
+    "for", "from", "has", "have", "how", "i", "if", "in", "into", "is", "it", "its", "me",
+    "my", "not", "of", "on", "or", "so", "that", "the", "their", "then", "there", "these",
+    "this", "to", "was", "we", "what", "when", "where", "which", "who", "why", "will",
+    "with", "you", "your",
+];
+
+const FRENCH: &[&str] = &[
+    "au", "aux", "avec", "ce", "ces", "comment", "dans", "de", "des", "du", "elle", "en",
+    "est", "et", "il", "je", "la", "le", "les", "leur", "mais", "mon", "ne", "nous", "ou",
+    "par", "pas", "pour", "qu", "que", "qui", "sa", "se", "ses", "son", "sur", "ta", "te",
+    "tu", "un", "une", "vos", "votre", "vous",
+];
+
+const GERMAN: &[&str] = &[
+    "aber", "als", "am", "an", "auf", "aus", "bei", "das", "dass", "dem", "den", "der",
+    "des", "die", "ein", "eine", "einem", "einen", "einer", "es", "für", "ich", "ihr",
+    "ihre", "im", "in", "ist", "mit", "nicht", "oder", "sich", "sie", "sind", "und", "von",
+    "wie", "wir", "zu", "zum", "zur",
+];
+
+const SPANISH: &[&str] = &[
+    "a", "al", "como", "con", "de", "del", "el", "en", "es", "la", "las", "lo", "los",
+    "mi", "no", "o", "para", "pero", "por", "que", "se", "su", "sus", "te", "tu", "un",
+    "una", "y", "ya",
+];
+
+/// Words dropped from ranked queries so common function words do not
+/// dominate the score. Empty (nothing dropped) by default.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StopWords {
+    words: HashSet<String>,
+}
+
+impl StopWords {
+    pub fn new(list: &StopWordList) -> Self {
+        let words = match list {
+            StopWordList::Language(language) => {
+                let words = match language {
+                    StopLanguage::English => ENGLISH,
+                    StopLanguage::French => FRENCH,
+                    StopLanguage::German => GERMAN,
+                    StopLanguage::Spanish => SPANISH,
+                };
+                words.iter().map(|w| w.to_string()).collect()
+            }
+            StopWordList::Words(words) => words.iter().map(|w| fold_case(w)).collect(),
+        };
+        Self { words }
+    }
+
+    pub fn contains(&self, term: &str) -> bool {
+        self.words.contains(&fold_case(term))
+    }
+
+    /// Drop stop words from query `terms`, unless that would leave nothing
+    /// to search for.
+    pub fn filter<S: AsRef<str>>(&self, terms: Vec<S>) -> Vec<S> {
+        if terms.iter().all(|t| self.contains(t.as_ref())) {
+            return terms;
+        }
+        terms.into_iter().filter(|t| !self.contains(t.as_ref())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_filter_keeps_content_words() {
+        let english = StopWords::new(&StopWordList::Language(StopLanguage::English));
+        let query = terms(&["how", "to", "reset", "the", "password"]);
+        assert_eq!(english.filter(query), terms(&["reset", "password"]));
+        // A query of nothing but stop words is left alone
+        assert_eq!(english.filter(terms(&["to", "be"])), terms(&["to", "be"]));
+
+        let custom = StopWords::new(&StopWordList::Words(terms(&["Acme"])));
+        assert_eq!(custom.filter(terms(&["acme", "login"])), terms(&["login"]));
+        assert_eq!(StopWords::default().filter(terms(&["the", "x"])), terms(&["the", "x"]));
+    }
+
+    #[test]
+    fn test_list_deserializes_from_language_or_words() {
+        let language: StopWordList = serde_json::from_str(r#""german""#).unwrap();
+        assert_eq!(language, StopWordList::Language(StopLanguage::German));
+        let words: StopWordList = serde_json::from_str(r#"["foo"]"#).unwrap();
+        assert_eq!(words, StopWordList::Words(terms(&["foo"])));
+    }
+}
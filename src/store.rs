@@ -1,36 +1,313 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use crate::document::Document;
-use crate::index::InvertedIndex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::bloom::{FilterQuery, TokenFilter};
+use crate::document::{DocInput, Document};
+use crate::html;
+use crate::index::{DocId, InvertedIndex, TokenizedDocument};
+use crate::sniff::BinaryPolicy;
+use crate::validate::DuplicatePolicy;
 
 /// The in-memory document store + inverted index.
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DocumentStore {
     docs: HashMap<String, Document>,
     index: InvertedIndex,
+    /// Path of each `DocId` the index refers to.
+    paths: Vec<String>,
+    /// Inverse of `paths`, rebuilt by `restore`. A path keeps its id once
+    /// assigned, so rewriting or re-adding a deleted document reuses it.
+    #[serde(skip)]
+    ids: HashMap<String, DocId>,
+    /// Per-document token filters for pruning line scans. Derived from
+    /// the documents, so left out of snapshots and rebuilt by `restore`.
+    #[serde(skip)]
+    filters: HashMap<String, TokenFilter>,
+    /// Whether documents keep a case-folded copy of their lines.
+    fold_cache: bool,
+    /// Whether `.html` documents are converted to plain text on the way in.
+    html_to_text: bool,
+    /// What loading does with documents that aren't text.
+    binary_policy: BinaryPolicy,
+    /// What loading does with a path that is already taken.
+    duplicate_policy: DuplicatePolicy,
+    /// Directories holding a mounted corpus (see `MemexFsCore::mount`).
+    mounts: BTreeSet<String>,
 }
 
 impl DocumentStore {
+    #[cfg(test)]
     pub fn new() -> Self {
+        Self::with_index(InvertedIndex::new())
+    }
+
+    /// A store that indexes into the given (empty, pre-configured) index.
+    pub fn with_index(index: InvertedIndex) -> Self {
         Self {
             docs: HashMap::new(),
-            index: InvertedIndex::new(),
+            index,
+            paths: Vec::new(),
+            ids: HashMap::new(),
+            filters: HashMap::new(),
+            fold_cache: false,
+            html_to_text: false,
+            binary_policy: BinaryPolicy::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            mounts: BTreeSet::new(),
+        }
+    }
+
+    /// An empty store that loads documents the way this one does, with no
+    /// mounts.
+    pub fn empty_like(&self) -> Self {
+        Self {
+            fold_cache: self.fold_cache,
+            html_to_text: self.html_to_text,
+            binary_policy: self.binary_policy,
+            duplicate_policy: self.duplicate_policy,
+            ..Self::with_index(self.index.empty_like())
+        }
+    }
+
+    /// Keep a case-folded copy of each document (see `Document::cache_folded`).
+    pub fn with_fold_cache(mut self) -> Self {
+        self.fold_cache = true;
+        self
+    }
+
+    /// Index `.html` and `.htm` documents as their text (see `html::to_text`).
+    pub fn with_html_to_text(mut self) -> Self {
+        self.html_to_text = true;
+        self
+    }
+
+    /// Handle documents that aren't text by `policy` (see `sniff::screen`).
+    pub fn with_binary_policy(mut self, policy: BinaryPolicy) -> Self {
+        self.binary_policy = policy;
+        self
+    }
+
+    pub fn binary_policy(&self) -> BinaryPolicy {
+        self.binary_policy
+    }
+
+    /// Handle repeated paths in loads by `policy` (see `validate::dedupe`).
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    pub fn mounts(&self) -> &BTreeSet<String> {
+        &self.mounts
+    }
+
+    /// Record `dir` as a mount point. Returns `false` if it already was.
+    pub fn add_mount(&mut self, dir: String) -> bool {
+        self.mounts.insert(dir)
+    }
+
+    /// Forget the mount point `dir`, leaving its documents alone. Returns
+    /// `false` if it wasn't one.
+    pub fn remove_mount(&mut self, dir: &str) -> bool {
+        self.mounts.remove(dir)
+    }
+
+    /// Load documents from a list of (path, content) pairs. May be called
+    /// repeatedly; a path that is already loaded is replaced.
+    /// With the `rayon` feature, documents are parsed and tokenized in
+    /// parallel and then indexed in order.
+    pub fn load_documents(&mut self, documents: impl IntoIterator<Item = impl Into<DocInput>>) {
+        let documents: Vec<DocInput> = documents.into_iter().map(Into::into).collect();
+        let prepare = |doc: DocInput| {
+            let (path, content, metadata) = doc.into_parts();
+            self.prepare(path, &content, metadata)
+        };
+        #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+        let prepared: Vec<Prepared> = {
+            use rayon::prelude::*;
+            documents.into_par_iter().map(prepare).collect()
+        };
+        #[cfg(not(all(feature = "rayon", not(target_arch = "wasm32"))))]
+        let prepared: Vec<Prepared> = documents.into_iter().map(prepare).collect();
+
+        for doc in prepared {
+            self.insert(doc);
+        }
+    }
+
+    /// Release spare capacity left over from incremental loading.
+    pub fn shrink_to_fit(&mut self) {
+        self.docs.shrink_to_fit();
+        self.index.shrink_to_fit();
+        self.filters.shrink_to_fit();
+    }
+
+    /// Insert or replace a single document, reindexing only that document.
+    /// Returns `true` if an existing document was replaced.
+    pub fn write_document(&mut self, path: &str, content: &str, metadata: Option<Value>) -> bool {
+        let prepared = self.prepare(path.to_string(), content, metadata);
+        self.insert(prepared)
+    }
+
+    /// Everything `insert` needs that can be built without mutating the
+    /// store.
+    fn prepare(&self, path: String, content: &str, metadata: Option<Value>) -> Prepared {
+        let content = if self.html_to_text && html::is_html(&path) {
+            Cow::Owned(html::to_text(content))
+        } else {
+            Cow::Borrowed(content)
+        };
+        let content = content.as_ref();
+        let mut doc = Document::new(path, content).with_metadata(metadata);
+        if self.fold_cache {
+            doc.cache_folded(self.index.folding());
+        }
+        Prepared {
+            tokens: self.index.tokenize_document(doc.lines()),
+            filter: self.filter_for(&doc),
+            doc,
+        }
+    }
+
+    /// Token filter for `doc`. Filters assume alphanumeric words are
+    /// tokens, so a custom tokenizer config goes without.
+    fn filter_for(&self, doc: &Document) -> Option<TokenFilter> {
+        let tokenizer = self.index.tokenizer();
+        tokenizer.indexes_words().then(|| TokenFilter::new(doc.lines(), tokenizer))
+    }
+
+    /// Store a prepared document, replacing any at the same path. Returns
+    /// `true` if one was replaced.
+    fn insert(&mut self, prepared: Prepared) -> bool {
+        let Prepared { doc, filter, tokens } = prepared;
+        let id = self.intern(&doc.path);
+        let replaced = match self.docs.remove(&doc.path) {
+            Some(old) => {
+                self.index.remove_document(id, old.lines());
+                true
+            }
+            None => false,
+        };
+
+        self.index.insert_tokenized(id, tokens);
+        match filter {
+            Some(filter) => self.filters.insert(doc.path.clone(), filter),
+            None => self.filters.remove(&doc.path),
+        };
+        self.docs.insert(doc.path.clone(), doc);
+        replaced
+    }
+
+    /// Remove a document and purge its postings. Returns `false` if no
+    /// document lives at `path`.
+    pub fn delete_document(&mut self, path: &str) -> bool {
+        match self.docs.remove(path) {
+            Some(doc) => {
+                self.index.remove_document(self.ids[path], doc.lines());
+                self.filters.remove(path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove every document under `dir/`, returning their paths.
+    pub fn delete_dir(&mut self, dir: &str) -> Vec<String> {
+        let prefix = format!("{}/", dir);
+        let paths: Vec<String> = self.docs.keys().filter(|p| p.starts_with(&prefix)).cloned().collect();
+        for path in &paths {
+            self.delete_document(path);
         }
+        paths
     }
 
-    /// Load documents from a serialized list of (path, content) pairs.
-    pub fn load_documents(&mut self, documents: Vec<(String, String)>) {
-        for (path, content) in documents {
-            let doc = Document::new(path.clone(), &content);
-            self.index.add_document(&path, &doc.lines);
-            self.docs.insert(path, doc);
+    /// Rebuild per-document derived data that snapshots leave out.
+    pub fn restore(&mut self) {
+        for doc in self.docs.values_mut() {
+            doc.split_lines();
+            if self.fold_cache {
+                doc.cache_folded(self.index.folding());
+            }
         }
+        self.ids = self
+            .paths
+            .iter()
+            .enumerate()
+            .map(|(id, path)| (path.clone(), id as DocId))
+            .collect();
+        self.filters = self
+            .docs
+            .iter()
+            .filter_map(|(path, doc)| Some((path.clone(), self.filter_for(doc)?)))
+            .collect();
+        self.index.restore();
     }
 
     pub fn get_document(&self, path: &str) -> Option<&Document> {
         self.docs.get(path)
     }
 
+    /// The id `path` is indexed under, if a document lives there.
+    pub fn doc_id(&self, path: &str) -> Option<DocId> {
+        self.ids.get(path).copied().filter(|_| self.docs.contains_key(path))
+    }
+
+    /// Path of the document indexed as `id`.
+    pub fn path_of(&self, id: DocId) -> &str {
+        &self.paths[id as usize]
+    }
+
+    /// Index postings as (path, line), sorted by path, then line.
+    pub fn resolve<'a>(&self, locations: impl IntoIterator<Item = &'a (DocId, u32)>) -> BTreeSet<(&str, u32)> {
+        locations
+            .into_iter()
+            .map(|&(id, line)| (self.path_of(id), line))
+            .collect()
+    }
+
+    /// The id for `path`, assigning the next one if it has none.
+    fn intern(&mut self, path: &str) -> DocId {
+        if let Some(&id) = self.ids.get(path) {
+            return id;
+        }
+        let id = self.paths.len() as DocId;
+        self.paths.push(path.to_string());
+        self.ids.insert(path.to_string(), id);
+        id
+    }
+
+    /// Whether the document at `path` may contain a match for `query`;
+    /// `false` only when its token filter rules it out.
+    pub fn may_match(&self, path: &str, query: &FilterQuery) -> bool {
+        self.filters.get(path).is_none_or(|f| f.may_match(query))
+    }
+
+    /// Estimated heap usage, by component.
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats {
+            documents: self.paths.iter().map(|p| size_of::<String>() + p.capacity()).sum(),
+            index: self.index.heap_size(),
+            filters: self.filters.values().map(TokenFilter::heap_size).sum(),
+            ..Default::default()
+        };
+        for (path, doc) in &self.docs {
+            stats.documents += size_of::<(String, Document)>() + path.capacity() + doc.heap_size();
+            stats.folded += doc.folded_size();
+        }
+        for path in self.filters.keys() {
+            stats.filters += size_of::<(String, TokenFilter)>() + path.capacity();
+        }
+        stats.total = stats.documents + stats.folded + stats.index + stats.filters;
+        stats
+    }
+
     pub fn document_count(&self) -> usize {
         self.docs.len()
     }
@@ -43,52 +320,260 @@ impl DocumentStore {
         &self.index
     }
 
+    pub fn index_mut(&mut self) -> &mut InvertedIndex {
+        &mut self.index
+    }
+
     /// Return all document paths, sorted.
     pub fn paths(&self) -> Vec<&str> {
-        let mut paths: Vec<&str> = self.docs.keys().map(|s| s.as_str()).collect();
+        let mut paths: Vec<&str> = self.keys().collect();
         paths.sort();
         paths
     }
 
-    /// List immediate children of a virtual directory path.
-    /// Returns file names and subdirectory names (with trailing `/`), sorted.
-    pub fn ls(&self, dir: &str) -> Vec<String> {
-        // Normalize: ensure prefix ends with '/' (or is empty for root)
-        let prefix = if dir.is_empty() || dir == "/" || dir == "." {
-            String::new()
-        } else if dir.ends_with('/') {
-            dir.to_string()
-        } else {
-            format!("{}/", dir)
-        };
+    /// All document paths, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.docs.keys().map(String::as_str)
+    }
+}
+
+/// Up to `n` of `paths` close to a `path` that doesn't exist, closest
+/// first, for did-you-mean hints. Paths are compared case-insensitively by
+/// edit distance, whole or by file name alone (so a right file in the wrong
+/// directory still counts); ties go to the longer shared prefix.
+pub fn similar_paths<'a>(paths: impl IntoIterator<Item = &'a str>, path: &str, n: usize) -> Vec<String> {
+    let wanted: Vec<char> = path.to_lowercase().chars().collect();
+    let wanted_name = file_name(&wanted);
+    let max = (wanted.len() / 3).max(2);
+    let mut scored: Vec<(usize, usize, &str)> = paths
+        .into_iter()
+        .filter_map(|candidate| {
+            let chars: Vec<char> = candidate.to_lowercase().chars().collect();
+            let distance = edit_distance(&wanted, &chars)
+                .min(edit_distance(wanted_name, file_name(&chars)) + 1);
+            let shared = wanted.iter().zip(&chars).take_while(|(a, b)| a == b).count();
+            (distance <= max).then_some((distance, usize::MAX - shared, candidate))
+        })
+        .collect();
+    scored.sort_unstable();
+    scored.into_iter().take(n).map(|(_, _, p)| p.to_string()).collect()
+}
+
+/// Count `paths` per file extension (e.g. `.md`), sorted by count
+/// descending, then by extension. Paths without an extension are skipped.
+pub fn extensions<'a>(paths: impl IntoIterator<Item = &'a str>) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for path in paths {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        if let Some(dot) = name.rfind('.').filter(|&i| i > 0) {
+            *counts.entry(name[dot..].to_lowercase()).or_default() += 1;
+        }
+    }
+
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// List immediate children of a virtual directory path among `paths`.
+/// Returns file names and subdirectory names (with trailing `/`), sorted.
+pub fn ls<'a>(paths: impl IntoIterator<Item = &'a str>, dir: &str) -> Vec<String> {
+    // Normalize: ensure prefix ends with '/' (or is empty for root)
+    let prefix = normalize_dir(dir);
 
-        let mut entries = std::collections::BTreeSet::new();
+    let mut entries = std::collections::BTreeSet::new();
 
-        for path in self.docs.keys() {
-            let Some(rest) = path.strip_prefix(&prefix) else {
-                // For root listing (empty prefix), rest == full path
-                if !prefix.is_empty() {
-                    continue;
-                }
-                // This shouldn't happen since strip_prefix("") always succeeds
+    for path in paths {
+        let Some(rest) = path.strip_prefix(&prefix) else {
+            // For root listing (empty prefix), rest == full path
+            if !prefix.is_empty() {
                 continue;
-            };
-
-            // rest is what comes after the prefix
-            if let Some(slash_pos) = rest.find('/') {
-                // There's a subdirectory
-                let dir_name = format!("{}/", &rest[..slash_pos]);
-                entries.insert(dir_name);
-            } else {
-                // Direct child file
-                entries.insert(rest.to_string());
             }
+            // This shouldn't happen since strip_prefix("") always succeeds
+            continue;
+        };
+
+        // rest is what comes after the prefix
+        if let Some(slash_pos) = rest.find('/') {
+            // There's a subdirectory
+            let dir_name = format!("{}/", &rest[..slash_pos]);
+            entries.insert(dir_name);
+        } else {
+            // Direct child file
+            entries.insert(rest.to_string());
+        }
+    }
+
+    entries.into_iter().collect()
+}
+
+/// Build the nested directory tree of `paths` below `dir`.
+/// `max_depth` limits how many levels are expanded; directories beyond the
+/// limit are returned without `children`.
+pub fn tree<'a>(paths: impl IntoIterator<Item = &'a str>, dir: &str, max_depth: Option<usize>) -> TreeNode {
+    let prefix = normalize_dir(dir);
+
+    let mut root = DirBuilder::default();
+    for path in paths {
+        if let Some(rest) = path.strip_prefix(&prefix) {
+            root.insert(&rest.split('/').collect::<Vec<_>>());
+        }
+    }
+
+    let name = prefix.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+    let name = if name.is_empty() { "." } else { name };
+    root.into_node(name.to_string(), 0, max_depth)
+}
+
+/// A node in the virtual directory tree, as returned by `tree`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeNode {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: NodeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<TreeNode>>,
+}
+
+impl TreeNode {
+    /// Render as an indented ASCII tree, one entry per line, with
+    /// directories marked by a trailing `/`.
+    pub fn render(&self) -> String {
+        let mut out = self.name.clone();
+        if self.kind == NodeKind::Dir && self.name != "." {
+            out.push('/');
+        }
+        self.render_children("", &mut out);
+        out
+    }
+
+    fn render_children(&self, indent: &str, out: &mut String) {
+        let Some(children) = &self.children else {
+            return;
+        };
+        for (i, child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            out.push('\n');
+            out.push_str(indent);
+            out.push_str(if last { "└── " } else { "├── " });
+            out.push_str(&child.name);
+            if child.kind == NodeKind::Dir {
+                out.push('/');
+            }
+            let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            child.render_children(&indent, out);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Dir,
+    File,
+}
+
+/// A parsed and tokenized document waiting to be stored.
+struct Prepared {
+    doc: Document,
+    filter: Option<TokenFilter>,
+    tokens: TokenizedDocument,
+}
+
+/// Estimated heap bytes held by an instance, by component, for deciding
+/// when a corpus is too large to keep in one instance. Counts allocated
+/// capacity and per-entry map sizes, not allocator overhead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryStats {
+    /// Paths, content, line spans, and metadata of the documents.
+    pub documents: usize,
+    /// Case-folded copies kept with the `fold_cache` option; 0 without it.
+    pub folded: usize,
+    /// The inverted index, including its trigram, stem, and file indexes.
+    pub index: usize,
+    /// Per-document bloom filters that let scans skip documents.
+    pub filters: usize,
+    /// Sum of the above.
+    pub total: usize,
+}
+
+/// Intermediate directory structure used while building a `TreeNode`.
+#[derive(Default)]
+struct DirBuilder {
+    dirs: BTreeMap<String, DirBuilder>,
+    files: Vec<String>,
+}
+
+impl DirBuilder {
+    fn insert(&mut self, segments: &[&str]) {
+        match segments {
+            [] => {}
+            [file] => self.files.push(file.to_string()),
+            [dir, rest @ ..] => self.dirs.entry(dir.to_string()).or_default().insert(rest),
         }
+    }
+
+    fn into_node(mut self, name: String, depth: usize, max_depth: Option<usize>) -> TreeNode {
+        let children = if max_depth.is_some_and(|max| depth >= max) {
+            None
+        } else {
+            // Directories first, then files, each sorted by name
+            self.files.sort();
+            let mut children: Vec<TreeNode> = self
+                .dirs
+                .into_iter()
+                .map(|(name, dir)| dir.into_node(name, depth + 1, max_depth))
+                .collect();
+            children.extend(self.files.into_iter().map(|name| TreeNode {
+                name,
+                kind: NodeKind::File,
+                children: None,
+            }));
+            Some(children)
+        };
+
+        TreeNode {
+            name,
+            kind: NodeKind::Dir,
+            children,
+        }
+    }
+}
+
+/// Normalize a directory path to a prefix ending in `/` (or empty for root).
+pub fn normalize_dir(dir: &str) -> String {
+    if dir.is_empty() || dir == "/" || dir == "." {
+        String::new()
+    } else if dir.ends_with('/') {
+        dir.to_string()
+    } else {
+        format!("{}/", dir)
+    }
+}
 
-        entries.into_iter().collect()
+/// The part of a path after its last `/`.
+fn file_name(path: &[char]) -> &[char] {
+    match path.iter().rposition(|&c| c == '/') {
+        Some(slash) => &path[slash + 1..],
+        None => path,
     }
 }
 
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=a.len()).collect();
+    let mut row = vec![0; a.len() + 1];
+    for (i, cb) in b.iter().enumerate() {
+        row[0] = i + 1;
+        for (j, ca) in a.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            row[j + 1] = substitution.min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev[a.len()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,6 +591,40 @@ mod tests {
         assert!(store.get_document("missing.md").is_none());
     }
 
+    #[test]
+    fn test_may_match_follows_writes_and_restore() {
+        let mut store = DocumentStore::new();
+        store.load_documents(vec![("a.md".into(), "Hello world".into())]);
+        let hello = FilterQuery::literal("hello world");
+        assert!(store.may_match("a.md", &hello));
+
+        store.write_document("a.md", "Goodbye world", None);
+        assert!(!store.may_match("a.md", &hello));
+
+        let bytes = bincode::serialize(&store).unwrap();
+        let mut restored: DocumentStore = bincode::deserialize(&bytes).unwrap();
+        restored.restore();
+        assert!(restored.may_match("a.md", &FilterQuery::literal("goodbye")));
+        assert!(!restored.may_match("a.md", &hello));
+    }
+
+    #[test]
+    fn test_delete_document() {
+        let mut store = DocumentStore::new();
+        store.load_documents(vec![
+            ("a.md".into(), "Hello world".into()),
+            ("b.md".into(), "Goodbye world".into()),
+        ]);
+
+        assert!(store.delete_document("a.md"));
+        assert!(!store.delete_document("a.md"));
+        assert_eq!(store.paths(), vec!["b.md"]);
+        assert!(store.index().lookup("hello").is_none());
+        let world = store.index().lookup("world").unwrap();
+        assert_eq!(store.resolve(world), BTreeSet::from([("b.md", 1)]));
+        assert_eq!(store.doc_id("a.md"), None);
+    }
+
     #[test]
     fn test_ls_root() {
         let mut store = DocumentStore::new();
@@ -114,7 +633,7 @@ mod tests {
             ("dir/b.md".into(), "world".into()),
             ("top.md".into(), "top".into()),
         ]);
-        let entries = store.ls("");
+        let entries = ls(store.keys(), "");
         assert_eq!(entries, vec!["dir/", "top.md"]);
     }
 
@@ -125,7 +644,7 @@ mod tests {
             ("dir/a.md".into(), "hello".into()),
             ("dir/sub/b.md".into(), "world".into()),
         ]);
-        let entries = store.ls("dir");
+        let entries = ls(store.keys(), "dir");
         assert_eq!(entries, vec!["a.md", "sub/"]);
     }
 
@@ -137,4 +656,71 @@ mod tests {
         assert!(store.index().lookup("hello").is_some());
         assert!(store.index().lookup("world").is_some());
     }
+
+    #[test]
+    fn test_extensions() {
+        let mut store = DocumentStore::new();
+        store.load_documents(vec![
+            ("a.md".into(), "".into()),
+            ("dir/b.md".into(), "".into()),
+            ("dir/c.MD".into(), "".into()),
+            ("data.json".into(), "{}".into()),
+            ("notes.txt".into(), "".into()),
+            ("v1.2/README".into(), "".into()),
+            (".env".into(), "".into()),
+        ]);
+        assert_eq!(
+            extensions(store.keys()),
+            vec![
+                (".md".to_string(), 3),
+                (".json".to_string(), 1),
+                (".txt".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tree_depth_limit() {
+        let mut store = DocumentStore::new();
+        store.load_documents(vec![
+            ("a/b/c.md".into(), "deep".into()),
+            ("a/d.md".into(), "shallow".into()),
+            ("top.md".into(), "top".into()),
+        ]);
+
+        let full = tree(store.keys(), "", None);
+        assert_eq!(full.name, ".");
+        let children = full.children.unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].name, "a");
+        assert_eq!(children[0].kind, NodeKind::Dir);
+        assert_eq!(children[1].name, "top.md");
+        assert_eq!(children[1].kind, NodeKind::File);
+
+        let shallow = tree(store.keys(), "", Some(1));
+        let a = &shallow.children.unwrap()[0];
+        assert_eq!(a.name, "a");
+        assert!(a.children.is_none(), "depth limit should stop expansion");
+
+        let sub = tree(store.keys(), "a", None);
+        assert_eq!(sub.name, "a");
+        let names: Vec<_> = sub.children.unwrap().into_iter().map(|n| n.name).collect();
+        assert_eq!(names, vec!["b", "d.md"]);
+    }
+
+    #[test]
+    fn test_tree_render() {
+        let mut store = DocumentStore::new();
+        store.load_documents(vec![
+            ("a/b/c.md".into(), "deep".into()),
+            ("a/d.md".into(), "shallow".into()),
+            ("top.md".into(), "top".into()),
+        ]);
+
+        assert_eq!(
+            tree(store.keys(), "", None).render(),
+            ".\n├── a/\n│   ├── b/\n│   │   └── c.md\n│   └── d.md\n└── top.md"
+        );
+        assert_eq!(tree(store.keys(), "a", Some(1)).render(), "a/\n├── b/\n└── d.md");
+    }
 }
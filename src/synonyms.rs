@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fold::fold_case;
+
+/// When synonym expansion happens. The modes are mutually exclusive:
+/// `Query` expands the search terms on every grep and keeps the index
+/// unchanged, `Index` adds extra postings while indexing so a lookup for a
+/// key is a single index hit, at the cost of a larger index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SynonymMode {
+    #[default]
+    Query,
+    Index,
+}
+
+/// Host-provided synonym map. Each key expands to its listed synonyms, so
+/// searching for "bill" with `bill → [invoice, statement]` also finds lines
+/// mentioning "invoice" or "statement". Keys and synonyms are single tokens
+/// and matched case-insensitively.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Synonyms {
+    map: HashMap<String, Vec<String>>,
+}
+
+impl Synonyms {
+    pub fn new(map: &HashMap<String, Vec<String>>) -> Self {
+        let map = map
+            .iter()
+            .map(|(key, syns)| {
+                let syns = syns.iter().map(|s| fold_case(s)).collect();
+                (fold_case(key), syns)
+            })
+            .collect();
+        Self { map }
+    }
+
+    /// The term itself followed by its synonyms, all lowercased.
+    pub fn expand(&self, term: &str) -> Vec<String> {
+        let term = fold_case(term);
+        let mut terms = vec![term.clone()];
+        if let Some(syns) = self.map.get(&term) {
+            terms.extend(syns.iter().filter(|s| **s != term).cloned());
+        }
+        terms
+    }
+
+    /// Index-time view: for each synonym token, the keys whose postings it
+    /// should also be recorded under.
+    pub fn reverse(&self) -> HashMap<String, Vec<String>> {
+        let mut reverse: HashMap<String, Vec<String>> = HashMap::new();
+        for (key, syns) in &self.map {
+            for syn in syns {
+                if syn != key {
+                    reverse.entry(syn.clone()).or_default().push(key.clone());
+                }
+            }
+        }
+        reverse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bill() -> Synonyms {
+        let map = HashMap::from([(
+            "Bill".to_string(),
+            vec!["Invoice".to_string(), "statement".to_string()],
+        )]);
+        Synonyms::new(&map)
+    }
+
+    #[test]
+    fn test_expand() {
+        let syns = bill();
+        assert_eq!(syns.expand("BILL"), vec!["bill", "invoice", "statement"]);
+        assert_eq!(syns.expand("invoice"), vec!["invoice"]);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let reverse = bill().reverse();
+        assert_eq!(reverse["invoice"], vec!["bill"]);
+        assert_eq!(reverse["statement"], vec!["bill"]);
+        assert!(!reverse.contains_key("bill"));
+    }
+}
@@ -0,0 +1,297 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::document::Document;
+use crate::error::MemexError;
+use crate::fold::fold_case;
+
+/// Rows returned by `query_table` when no limit is given.
+const DEFAULT_ROW_LIMIT: usize = 100;
+
+/// What `MemexFsCore::query_table` selects from a CSV or TSV document.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct TableQuery {
+    /// Conditions a row must meet, all of them, written `column op value`
+    /// with `op` one of `=`, `!=`, `<`, `<=`, `>`, `>=`, or `~` (contains),
+    /// e.g. `plan = Pro` or `price < 50`. Column names and `=`, `!=`, and
+    /// `~` ignore case. The ordering operators compare numbers when both
+    /// sides are numeric (`$1,200` and `15%` count) and text otherwise,
+    /// which orders ISO dates correctly.
+    pub filter: Vec<String>,
+    /// Columns to return, in this order; every column when empty.
+    pub columns: Vec<String>,
+    /// Maximum rows to return. Defaults to 100.
+    pub limit: Option<usize>,
+    /// Matching rows to skip before the page starts (from a previous
+    /// response's `next_offset`).
+    pub offset: Option<usize>,
+}
+
+/// A page of rows from `MemexFsCore::query_table`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableRows {
+    /// Names of the returned columns, as written in the header.
+    pub columns: Vec<String>,
+    pub rows: Vec<TableRow>,
+    /// Number of rows passing the filter, across all pages.
+    pub total_rows: usize,
+    /// Offset of the next page, present when this page returned rows and
+    /// more remain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+/// One row of `TableRows`: its values for the returned columns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableRow {
+    /// 1-indexed line the row starts on, for `read`.
+    pub line: u32,
+    pub values: Vec<String>,
+}
+
+/// The field delimiter of a table document: `,` for `.csv`, a tab for
+/// `.tsv` (in any case), or `None` for other documents.
+pub fn delimiter(path: &str) -> Option<char> {
+    let (_, ext) = path.rsplit('/').next()?.rsplit_once('.')?;
+    match ext.to_ascii_lowercase().as_str() {
+        "csv" => Some(','),
+        "tsv" => Some('\t'),
+        _ => None,
+    }
+}
+
+/// The rows of `doc`, a CSV or TSV document whose first record names the
+/// columns, selected by `query`.
+pub fn query(doc: &Document, query: &TableQuery) -> Result<TableRows, MemexError> {
+    let delimiter = delimiter(&doc.path).ok_or_else(|| MemexError::NotATable { path: doc.path.clone() })?;
+    let mut records = records(doc, delimiter).into_iter();
+    let header = records.next().map(|(_, fields)| fields).unwrap_or_default();
+    let column = |name: &str| -> Result<usize, MemexError> {
+        let wanted = fold_case(name.trim());
+        header.iter().position(|h| fold_case(h) == wanted).ok_or_else(|| MemexError::UnknownColumn {
+            path: doc.path.clone(),
+            column: name.trim().to_string(),
+            columns: header.clone(),
+        })
+    };
+
+    let conditions = query
+        .filter
+        .iter()
+        .map(|filter| {
+            let (name, op, value) = parse_condition(filter)?;
+            Ok((column(name)?, op, value))
+        })
+        .collect::<Result<Vec<_>, MemexError>>()?;
+    let selected = if query.columns.is_empty() {
+        (0..header.len()).collect()
+    } else {
+        query.columns.iter().map(|name| column(name)).collect::<Result<Vec<_>, _>>()?
+    };
+
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_ROW_LIMIT);
+    let mut rows = Vec::new();
+    let mut total_rows = 0;
+    for (line, fields) in records {
+        let field = |i: usize| fields.get(i).map_or("", String::as_str);
+        if !conditions.iter().all(|(i, op, value)| op.holds(field(*i), value)) {
+            continue;
+        }
+        if total_rows >= offset && rows.len() < limit {
+            let values = selected.iter().map(|&i| field(i).to_string()).collect();
+            rows.push(TableRow { line, values });
+        }
+        total_rows += 1;
+    }
+    let end = offset + rows.len();
+    Ok(TableRows {
+        columns: selected.iter().map(|&i| header[i].clone()).collect(),
+        rows,
+        total_rows,
+        next_offset: (end > offset && end < total_rows).then_some(end),
+    })
+}
+
+/// The records of a table with their 1-indexed starting lines, fields
+/// trimmed. CSV fields may be quoted, with `""` for a quote and line
+/// breaks kept inside quotes; TSV fields are taken as written. Blank lines
+/// are skipped.
+fn records(doc: &Document, delimiter: char) -> Vec<(u32, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, line) in doc.lines().enumerate() {
+        if quoted {
+            field.push('\n');
+        } else if line.trim().is_empty() {
+            continue;
+        } else {
+            start = i;
+        }
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted => match chars.peek() {
+                    Some('"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    _ => quoted = false,
+                },
+                '"' if delimiter == ',' && field.trim().is_empty() => {
+                    field.clear();
+                    quoted = true;
+                }
+                c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+                c => field.push(c),
+            }
+        }
+        if !quoted {
+            fields.push(std::mem::take(&mut field));
+            let fields = std::mem::take(&mut fields).into_iter().map(|f| f.trim().to_string());
+            records.push(((start + 1) as u32, fields.collect()));
+        }
+    }
+    if quoted {
+        // An unterminated quote keeps the rest of the document
+        fields.push(field);
+        records.push(((start + 1) as u32, fields.into_iter().map(|f| f.trim().to_string()).collect()));
+    }
+    records
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+impl Op {
+    fn holds(self, cell: &str, value: &str) -> bool {
+        match self {
+            Op::Eq => compare(cell, value) == Ordering::Equal,
+            Op::Ne => compare(cell, value) != Ordering::Equal,
+            Op::Lt => compare(cell, value) == Ordering::Less,
+            Op::Le => compare(cell, value) != Ordering::Greater,
+            Op::Gt => compare(cell, value) == Ordering::Greater,
+            Op::Ge => compare(cell, value) != Ordering::Less,
+            Op::Contains => fold_case(cell).contains(&fold_case(value)),
+        }
+    }
+}
+
+/// Numeric order when both sides are numbers, else case-folded text order.
+fn compare(cell: &str, value: &str) -> Ordering {
+    match (number(cell), number(value)) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => fold_case(cell).cmp(&fold_case(value)),
+    }
+}
+
+/// `s` as a number, allowing a currency sign, a trailing `%`, and `,`
+/// thousands separators: `$1,200.50` is 1200.5.
+fn number(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let s = s.strip_prefix(['$', '€', '£', '¥']).unwrap_or(s);
+    let s = s.strip_suffix('%').unwrap_or(s);
+    let digits: String = s.chars().filter(|&c| c != ',').collect();
+    if digits.is_empty() { None } else { digits.parse().ok() }
+}
+
+/// Split `column op value`, dropping quotes around the value.
+fn parse_condition(filter: &str) -> Result<(&str, Op, &str), MemexError> {
+    let at = filter.find(['=', '!', '<', '>', '~']).ok_or_else(|| {
+        MemexError::invalid_params(format!("filter `{}` needs an operator: = != < <= > >= ~", filter))
+    })?;
+    let (name, rest) = filter.split_at(at);
+    let (op, len) = match (rest.as_bytes()[0], rest.as_bytes().get(1)) {
+        (b'!', Some(b'=')) => (Op::Ne, 2),
+        (b'<', Some(b'=')) => (Op::Le, 2),
+        (b'>', Some(b'=')) => (Op::Ge, 2),
+        (b'=', Some(b'=')) => (Op::Eq, 2),
+        (b'=', _) => (Op::Eq, 1),
+        (b'<', _) => (Op::Lt, 1),
+        (b'>', _) => (Op::Gt, 1),
+        (b'~', _) => (Op::Contains, 1),
+        _ => return Err(MemexError::invalid_params(format!("filter `{}` has no valid operator", filter))),
+    };
+    let value = rest[len..].trim();
+    let value = ['"', '\'']
+        .iter()
+        .find_map(|&q| value.strip_prefix(q)?.strip_suffix(q))
+        .unwrap_or(value);
+    if name.trim().is_empty() {
+        return Err(MemexError::invalid_params(format!("filter `{}` names no column", filter)));
+    }
+    Ok((name, op, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRICING: &str = "\
+Plan,Monthly price,Seats,Notes
+Free,$0,1,\"Community support, no SLA\"
+Pro,$49,10,\"Priority \"\"email\"\" support\"
+
+Team,\"$1,200\",100,\"Multi-line
+notes\"
+Enterprise,Contact us,,Custom";
+
+    fn run(filter: &[&str], columns: &[&str]) -> Result<TableRows, MemexError> {
+        let doc = Document::new("pricing.csv".into(), PRICING);
+        let query = TableQuery {
+            filter: filter.iter().map(|s| s.to_string()).collect(),
+            columns: columns.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        super::query(&doc, &query)
+    }
+
+    fn values(rows: &TableRows) -> Vec<Vec<&str>> {
+        rows.rows.iter().map(|r| r.values.iter().map(String::as_str).collect()).collect()
+    }
+
+    #[test]
+    fn test_query_table() {
+        let all = run(&[], &[]).unwrap();
+        assert_eq!(all.columns, ["Plan", "Monthly price", "Seats", "Notes"]);
+        assert_eq!(all.total_rows, 4);
+        let lines: Vec<u32> = all.rows.iter().map(|r| r.line).collect();
+        assert_eq!(lines, [2, 3, 5, 7]);
+        assert_eq!(all.rows[1].values[3], "Priority \"email\" support");
+        assert_eq!(all.rows[2].values[3], "Multi-line\nnotes");
+
+        let cheap = run(&["monthly price < 100"], &["plan", "Monthly price"]).unwrap();
+        assert_eq!(values(&cheap), [["Free", "$0"], ["Pro", "$49"]]);
+        assert_eq!(values(&run(&["seats >= 10", "plan != team"], &["Plan"]).unwrap()), [["Pro"]]);
+        assert_eq!(values(&run(&["Notes ~ SUPPORT"], &["Plan"]).unwrap()), [["Free"], ["Pro"]]);
+        assert_eq!(values(&run(&["plan = 'enterprise'"], &["Seats"]).unwrap()), [[""]]);
+
+        let unknown = run(&["price < 100"], &[]).unwrap_err();
+        assert_eq!(unknown.code(), "unknown_column");
+        assert_eq!(run(&["plan"], &[]).unwrap_err().code(), "invalid_params");
+
+        let tsv = Document::new("a.TSV".into(), "name\tvalue\n\"a\"\t1");
+        let rows = super::query(&tsv, &TableQuery::default()).unwrap();
+        assert_eq!(values(&rows), [["\"a\"", "1"]]);
+        let md = Document::new("a.md".into(), "a,b");
+        assert_eq!(super::query(&md, &TableQuery::default()).unwrap_err().code(), "not_a_table");
+
+        let doc = Document::new("pricing.csv".into(), PRICING);
+        let count = super::query(&doc, &TableQuery { limit: Some(0), ..Default::default() }).unwrap();
+        assert!(count.rows.is_empty());
+        assert_eq!((count.total_rows, count.next_offset), (4, None));
+    }
+}
@@ -0,0 +1,352 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fold::{fold_case, strip_diacritics, Folding};
+
+/// A separator test for `TokenizerConfig::split`. Functions can't be
+/// serialized, so a snapshot only records that there was one, and restores
+/// an empty `SplitFn` for `MemexFsBuilder::from_snapshot` to fill in.
+#[derive(Clone)]
+pub struct SplitFn(Option<Arc<dyn Fn(char) -> bool + Send + Sync>>);
+
+impl SplitFn {
+    pub fn new(split: impl Fn(char) -> bool + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(split)))
+    }
+}
+
+impl fmt::Debug for SplitFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "SplitFn" } else { "SplitFn(missing)" })
+    }
+}
+
+impl Serialize for SplitFn {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit_struct("SplitFn")
+    }
+}
+
+impl<'de> Deserialize<'de> for SplitFn {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <()>::deserialize(deserializer)?;
+        Ok(Self(None))
+    }
+}
+
+/// Tokenization rules for corpora that need something other than the
+/// default "lowercased alphanumeric words", e.g. `keep_chars: "_.#"` for
+/// code so `user_id`, `node.js`, and `c#` stay whole. Set through
+/// `MemexFsBuilder::tokenizer`.
+///
+/// Grep stays exact with any config: literal patterns the index can no
+/// longer answer (every config but the default) are matched by scanning
+/// lines. `search`, `context_for`, `rank_by_proximity`, and the
+/// `all_terms` and `near` grep modes work on the configured tokens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenizerConfig {
+    /// Tokens shorter than this many characters are not indexed.
+    /// Default 1.
+    pub min_token_len: usize,
+    /// Non-alphanumeric characters that are part of tokens rather than
+    /// separating them.
+    pub keep_chars: String,
+    /// Case-fold tokens (the default). Without it the index, and so
+    /// `search` and `context_for`, are case-sensitive.
+    pub lowercase: bool,
+    /// Custom separator test replacing "not alphanumeric"; `keep_chars`
+    /// still applies. A snapshot of an instance built with one must be
+    /// restored with `MemexFsBuilder::from_snapshot` and the same function.
+    pub split: Option<SplitFn>,
+}
+
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        Self {
+            min_token_len: 1,
+            keep_chars: String::new(),
+            lowercase: true,
+            split: None,
+        }
+    }
+}
+
+impl PartialEq for TokenizerConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.min_token_len == other.min_token_len
+            && self.keep_chars == other.keep_chars
+            && self.lowercase == other.lowercase
+            && self.split.is_some() == other.split.is_some()
+    }
+}
+
+impl TokenizerConfig {
+    /// Whether a snapshot left this config without its split function.
+    pub fn missing_split(&self) -> bool {
+        matches!(self.split, Some(SplitFn(None)))
+    }
+
+    /// Give a restored config back the split function it was built with.
+    pub fn restore_split(&mut self, other: &TokenizerConfig) {
+        if let Some(split @ SplitFn(Some(_))) = &other.split {
+            if self.missing_split() {
+                self.split = Some(split.clone());
+            }
+        }
+    }
+
+    fn is_separator(&self, c: char) -> bool {
+        let split = match &self.split {
+            Some(SplitFn(Some(split))) => split(c),
+            _ => !c.is_alphanumeric(),
+        };
+        split && !self.keep_chars.contains(c)
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How an instance splits text into index tokens: its `TokenizerConfig`,
+/// with the instance's `Folding`, and optionally with runs of CJK
+/// characters cut into overlapping n-grams. Index and queries must use the
+/// same one.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tokenizer {
+    pub folding: Folding,
+    /// Length of the n-grams CJK runs are indexed as
+    /// (`MemexOptions::cjk_ngram`). Chinese and Japanese don't put spaces
+    /// between words, so without this a whole sentence is one token.
+    pub cjk_ngram: Option<usize>,
+    /// Also index the words of camelCase identifiers (`getUserById` under
+    /// `get`, `user`, `by`, and `id`), at the identifier's position
+    /// (`MemexOptions::split_identifiers`). snake_case needs no help:
+    /// `_` already separates tokens.
+    pub split_identifiers: bool,
+    pub config: TokenizerConfig,
+}
+
+impl Tokenizer {
+    /// Tokens of `line` as the index stores them, in position order.
+    pub fn tokenize(&self, line: &str) -> Vec<String> {
+        let text = if self.config.lowercase { Cow::Owned(fold_case(line)) } else { Cow::Borrowed(line) };
+        let min_len = self.config.min_token_len;
+        let words = self
+            .words(&text)
+            .filter(|w| min_len <= 1 || w.chars().count() >= min_len);
+        let Some(n) = self.cjk_ngram else {
+            return words.map(String::from).collect();
+        };
+        let mut out = Vec::new();
+        for word in words {
+            if !word.chars().any(is_cjk) {
+                out.push(word.to_string());
+                continue;
+            }
+            let chars: Vec<char> = word.chars().collect();
+            for (cjk, run) in runs(&chars) {
+                if cjk {
+                    out.extend(ngrams(run, n));
+                } else {
+                    out.push(run.iter().collect());
+                }
+            }
+        }
+        out
+    }
+
+    /// `tokenize(line)`, each token with the sub-tokens it is also indexed
+    /// under at the same position.
+    pub fn tokenize_parts(&self, line: &str) -> Vec<(String, Vec<String>)> {
+        let tokens = self.tokenize(line);
+        let mut parts: HashMap<String, Vec<String>> = HashMap::new();
+        if self.split_identifiers {
+            let lowercase = self.config.lowercase;
+            for word in self.words(line).filter(|w| w.is_ascii()) {
+                let mut words = camel_words(word, lowercase);
+                words.retain(|w| w.len() >= self.config.min_token_len);
+                if words.len() > 1 {
+                    let token = if lowercase { word.to_ascii_lowercase() } else { word.to_string() };
+                    parts.insert(token, words);
+                }
+            }
+        }
+        tokens
+            .into_iter()
+            .map(|token| {
+                let sub = parts.get(&token).cloned().unwrap_or_default();
+                (token, sub)
+            })
+            .collect()
+    }
+
+    /// Everything `line` is indexed under, in the spelling queries use:
+    /// per token position, the token followed by its sub-tokens.
+    pub fn line_terms(&self, line: &str) -> Vec<Vec<String>> {
+        self.tokenize_parts(line)
+            .into_iter()
+            .map(|(token, sub)| {
+                let token = self.folding.variant(&token).unwrap_or(token);
+                std::iter::once(token).chain(sub).collect()
+            })
+            .collect()
+    }
+
+    /// A query term in the spelling of the index's tokens.
+    pub fn normalize(&self, term: &str) -> String {
+        if self.config.lowercase { fold_case(term) } else { term.to_string() }
+    }
+
+    /// `normalize(term)`, also without diacritics under `ascii` folding,
+    /// for matching against every spelling in the index.
+    pub fn fold(&self, term: &str) -> String {
+        match (self.config.lowercase, self.folding.ascii) {
+            (true, _) => self.folding.fold(term),
+            (false, true) => strip_diacritics(term).into_owned(),
+            (false, false) => term.to_string(),
+        }
+    }
+
+    /// Whether every alphanumeric word is indexed as a token of its own,
+    /// as grep's index paths assume. Only the default config does this;
+    /// CJK n-grams break it for CJK words only (see `cjk_grams`).
+    pub fn indexes_words(&self) -> bool {
+        self.config.is_default()
+    }
+
+    fn words<'a>(&'a self, text: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        text.split(|c: char| self.config.is_separator(c)).filter(|s| !s.is_empty())
+    }
+
+    /// `tokenize(text)`, each token in the spelling queries use.
+    pub fn query_tokens(&self, text: &str) -> Vec<String> {
+        let tokens = self.tokenize(text);
+        if !self.folding.ascii {
+            return tokens;
+        }
+        tokens.into_iter().map(|t| self.folding.variant(&t).unwrap_or(t)).collect()
+    }
+
+    /// With n-grams enabled, the n-grams of the longest CJK run in the
+    /// folded literal `pattern_lower`, in order. Every line containing the
+    /// literal has them at consecutive positions, whatever text surrounds
+    /// it. A run shorter than `n` comes back whole, as a substring of the
+    /// tokens to look for. `None` if there is no CJK text.
+    pub fn cjk_grams(&self, pattern_lower: &str) -> Option<Vec<String>> {
+        let n = self.cjk_ngram?;
+        let chars: Vec<char> = pattern_lower.chars().collect();
+        let longest = runs(&chars)
+            .into_iter()
+            .filter(|(cjk, _)| *cjk)
+            .map(|(_, run)| run)
+            .max_by_key(|run| run.len())?;
+        Some(ngrams(longest, n))
+    }
+}
+
+/// Whether `c` is a Han, kana, or Hangul character.
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}'     // Hangul Jamo
+        | '\u{3040}'..='\u{30FF}'   // Hiragana, Katakana
+        | '\u{3130}'..='\u{318F}'   // Hangul Compatibility Jamo
+        | '\u{31F0}'..='\u{31FF}'   // Katakana Phonetic Extensions
+        | '\u{3400}'..='\u{4DBF}'   // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}'   // Hangul Syllables
+        | '\u{F900}'..='\u{FAFF}'   // CJK Compatibility Ideographs
+        | '\u{FF66}'..='\u{FF9F}'   // Halfwidth Katakana
+        | '\u{20000}'..='\u{2FA1F}' // CJK Extensions B-F, Compatibility Supplement
+    )
+}
+
+/// The words of an ASCII camelCase identifier, lowercased if `lowercase`:
+/// a new word starts at an uppercase letter after a lowercase letter or
+/// digit, and before the last capital of an acronym (`HTTPServer` →
+/// `http`, `server`).
+fn camel_words(word: &str, lowercase: bool) -> Vec<String> {
+    let bytes = word.as_bytes();
+    let case = |w: &str| if lowercase { w.to_ascii_lowercase() } else { w.to_string() };
+    let mut words = Vec::new();
+    let mut start = 0;
+    for i in 1..bytes.len() {
+        let (prev, cur) = (bytes[i - 1], bytes[i]);
+        let next_lower = bytes.get(i + 1).is_some_and(u8::is_ascii_lowercase);
+        let boundary = cur.is_ascii_uppercase()
+            && (prev.is_ascii_lowercase() || prev.is_ascii_digit() || (prev.is_ascii_uppercase() && next_lower));
+        if boundary {
+            words.push(case(&word[start..i]));
+            start = i;
+        }
+    }
+    words.push(case(&word[start..]));
+    words
+}
+
+/// `chars` split into maximal runs of CJK and non-CJK characters.
+fn runs(chars: &[char]) -> Vec<(bool, &[char])> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..=chars.len() {
+        if i == chars.len() || is_cjk(chars[i]) != is_cjk(chars[start]) {
+            runs.push((is_cjk(chars[start]), &chars[start..i]));
+            start = i;
+        }
+    }
+    runs
+}
+
+/// Overlapping `n`-character windows of `run`, or `run` itself if shorter.
+fn ngrams(run: &[char], n: usize) -> Vec<String> {
+    if run.len() <= n {
+        return vec![run.iter().collect()];
+    }
+    run.windows(n).map(|w| w.iter().collect()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cjk_ngrams() {
+        let bigrams = Tokenizer {
+            cjk_ngram: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(bigrams.tokenize("東京都に住む"), vec!["東京", "京都", "都に", "に住", "住む"]);
+        assert_eq!(bigrams.tokenize("iPhone手机, 猫"), vec!["iphone", "手机", "猫"]);
+        assert_eq!(bigrams.tokenize("Hello world"), vec!["hello", "world"]);
+        assert_eq!(Tokenizer::default().tokenize("東京都"), vec!["東京都"]);
+
+        assert_eq!(bigrams.cjk_grams("at 東京都, 京"), Some(vec!["東京".to_string(), "京都".to_string()]));
+        assert_eq!(bigrams.cjk_grams("猫"), Some(vec!["猫".to_string()]));
+        assert_eq!(bigrams.cjk_grams("hello"), None);
+        assert_eq!(Tokenizer::default().cjk_grams("東京都"), None);
+    }
+
+    #[test]
+    fn test_split_identifiers() {
+        assert_eq!(camel_words("getUserById", true), vec!["get", "user", "by", "id"]);
+        assert_eq!(camel_words("HTTPServer", true), vec!["http", "server"]);
+        assert_eq!(camel_words("base64Encode", false), vec!["base64", "Encode"]);
+        assert_eq!(camel_words("plain", true), vec!["plain"]);
+
+        let tokenizer = Tokenizer {
+            split_identifiers: true,
+            ..Default::default()
+        };
+        let tokens = tokenizer.tokenize_parts("call getUserById(user_id)");
+        let words: Vec<&str> = tokens.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(words, vec!["call", "getuserbyid", "user", "id"]);
+        assert_eq!(tokens[1].1, vec!["get", "user", "by", "id"]);
+        assert!(tokens[0].1.is_empty() && tokens[2].1.is_empty());
+        assert!(Tokenizer::default().tokenize_parts("getUserById")[0].1.is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::MemexError;
+
+/// Wording of the tool definitions, for tailoring the tool prompts to a
+/// deployment or language (`MemexOptions::tool_text`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolText {
+    /// What the corpus is called in descriptions, in place of
+    /// "knowledge base", e.g. `"Acme help center"`.
+    pub corpus: Option<String>,
+    /// Overrides by tool name (the built-in name, e.g. `"grep"`).
+    pub tools: BTreeMap<String, ToolOverride>,
+}
+
+/// Replacement text for one tool. Unset fields keep the built-in text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolOverride {
+    /// Name the tool is offered and called under instead. The built-in
+    /// name still works in `call`.
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Parameter descriptions by parameter name.
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl ToolText {
+    /// Fail if an override names a tool or parameter that `definitions`
+    /// (the built-in ones) don't have.
+    pub fn check(&self, definitions: &Value) -> Result<(), MemexError> {
+        for (tool, text) in &self.tools {
+            let definition = definitions
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|def| def["name"] == tool.as_str())
+                .ok_or_else(|| MemexError::UnknownTool { name: tool.clone() })?;
+            let params = &definition["parameters"];
+            if let Some(param) = text.parameters.keys().find(|p| params.get(p).is_none()) {
+                return Err(MemexError::invalid_params(format!("{} has no parameter {}", tool, param)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrite the built-in `definitions` with this text.
+    pub fn apply(&self, definitions: &mut Value) {
+        let Some(definitions) = definitions.as_array_mut() else { return };
+        for def in definitions {
+            let name = def["name"].as_str().unwrap_or_default().to_string();
+            self.rename_corpus(&mut def["description"]);
+            if let Some(params) = def["parameters"].as_object_mut() {
+                for param in params.values_mut() {
+                    self.rename_corpus(&mut param["description"]);
+                }
+            }
+            let Some(text) = self.tools.get(&name) else { continue };
+            if let Some(new_name) = &text.name {
+                def["name"] = new_name.as_str().into();
+            }
+            if let Some(description) = &text.description {
+                def["description"] = description.as_str().into();
+            }
+            for (param, description) in &text.parameters {
+                def["parameters"][param]["description"] = description.as_str().into();
+            }
+        }
+    }
+
+    /// The built-in name of the tool offered as `name`.
+    pub fn canonical<'a>(&'a self, name: &'a str) -> &'a str {
+        self.tools
+            .iter()
+            .find(|(_, text)| text.name.as_deref() == Some(name))
+            .map_or(name, |(tool, _)| tool.as_str())
+    }
+
+    fn rename_corpus(&self, description: &mut Value) {
+        if let (Some(corpus), Some(text)) = (&self.corpus, description.as_str()) {
+            *description = text.replace("knowledge base", corpus).into();
+        }
+    }
+}
+
+/// `definitions` in OpenAI's strict function-calling format:
+/// `{type: "function", function: {name, description, strict: true,
+/// parameters}}` where `parameters` is an object schema listing every
+/// property as required and allowing no others. Optional parameters are
+/// made nullable instead, and `call` treats a null as absent. Free-form
+/// object parameters (`metadata`), which strict schemas can't express,
+/// are left out.
+pub fn openai_tools(definitions: &Value) -> Value {
+    let tools = definitions.as_array().into_iter().flatten().map(|def| {
+        let required: Vec<&str> =
+            def["required"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        let mut properties = serde_json::Map::new();
+        for (name, param) in def["parameters"].as_object().into_iter().flatten() {
+            if param["type"] != "object" {
+                properties.insert(name.clone(), strict_param(param, required.contains(&name.as_str())));
+            }
+        }
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": def["name"],
+                "description": def["description"],
+                "strict": true,
+                "parameters": {
+                    "type": "object",
+                    "properties": properties.clone(),
+                    "required": properties.keys().collect::<Vec<_>>(),
+                    "additionalProperties": false,
+                },
+            },
+        })
+    });
+    Value::Array(tools.collect())
+}
+
+/// Strict schema for one parameter: a union of types becomes `anyOf`, and
+/// an optional parameter also accepts null.
+fn strict_param(param: &Value, required: bool) -> Value {
+    let mut types: Vec<Value> = match &param["type"] {
+        Value::Array(types) => types.clone(),
+        single => vec![single.clone()],
+    };
+    let mut schema = serde_json::Map::new();
+    if types.len() > 1 {
+        let mut variants: Vec<Value> = types
+            .iter()
+            .map(|t| {
+                if t == "array" {
+                    serde_json::json!({ "type": t, "items": param["items"] })
+                } else {
+                    serde_json::json!({ "type": t })
+                }
+            })
+            .collect();
+        if !required {
+            variants.push(serde_json::json!({ "type": "null" }));
+        }
+        schema.insert("anyOf".to_string(), variants.into());
+    } else {
+        if !required {
+            types.push("null".into());
+        }
+        schema.insert("type".to_string(), if required { types[0].clone() } else { types.into() });
+        for key in ["items", "enum"] {
+            if let Some(value) = param.get(key) {
+                schema.insert(key.to_string(), value.clone());
+            }
+        }
+        if let (false, Some(Value::Array(values))) = (required, schema.get_mut("enum")) {
+            values.push(Value::Null);
+        }
+    }
+    schema.insert("description".to_string(), param["description"].clone());
+    schema.into()
+}
+
+/// `params` without its null-valued fields, which strict function calling
+/// sends for optional parameters left unset.
+pub fn without_nulls(params: Value) -> Value {
+    match params {
+        Value::Object(fields) => fields.into_iter().filter(|(_, v)| !v.is_null()).collect(),
+        other => other,
+    }
+}
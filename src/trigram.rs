@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Character trigram index over index tokens, so substring lookups only
+/// visit tokens sharing every trigram of the query instead of the whole
+/// vocabulary.
+///
+/// Tokens get an id on first insert. A removed token leaves an empty slot
+/// behind so the other ids stay put; once empty slots outnumber live
+/// tokens, the ids are renumbered.
+#[derive(Debug, Default)]
+pub struct TrigramIndex {
+    /// Token → id, sharing the string with its slot in `tokens`.
+    ids: HashMap<Arc<str>, u32>,
+    /// Tokens by id, `None` once removed.
+    tokens: Vec<Option<Arc<str>>>,
+    /// Trigram → ids of the tokens containing it, ascending.
+    grams: HashMap<[char; 3], Vec<u32>>,
+}
+
+impl TrigramIndex {
+    /// Register `token`. Tokens already seen are ignored.
+    pub fn insert(&mut self, token: &str) {
+        if self.ids.contains_key(token) {
+            return;
+        }
+        let id = self.tokens.len() as u32;
+        let token: Arc<str> = Arc::from(token);
+        self.ids.insert(token.clone(), id);
+        self.tokens.push(Some(token.clone()));
+
+        for gram in distinct_trigrams(&token) {
+            self.grams.entry(gram).or_default().push(id);
+        }
+    }
+
+    /// Forget `token`, once it has left the index. Unknown tokens are
+    /// ignored.
+    pub fn remove(&mut self, token: &str) {
+        let Some(id) = self.ids.remove(token) else {
+            return;
+        };
+        self.tokens[id as usize] = None;
+        for gram in distinct_trigrams(token) {
+            if let Some(ids) = self.grams.get_mut(&gram) {
+                if let Ok(i) = ids.binary_search(&id) {
+                    ids.remove(i);
+                }
+                if ids.is_empty() {
+                    self.grams.remove(&gram);
+                }
+            }
+        }
+
+        if self.tokens.len() - self.ids.len() > self.ids.len() {
+            let live: Vec<Arc<str>> = std::mem::take(&mut self.tokens).into_iter().flatten().collect();
+            *self = Self::default();
+            for token in live {
+                self.insert(&token);
+            }
+        }
+    }
+
+    /// Estimated heap bytes, counting each map entry at its inline size.
+    /// Token strings are shared between `ids` and `tokens`, so counted once.
+    pub fn heap_size(&self) -> usize {
+        let ids: usize = self
+            .ids
+            .keys()
+            .map(|t| size_of::<(Arc<str>, u32)>() + 2 * size_of::<usize>() + t.len())
+            .sum();
+        let grams: usize = self
+            .grams
+            .values()
+            .map(|ids| size_of::<([char; 3], Vec<u32>)>() + ids.capacity() * size_of::<u32>())
+            .sum();
+        ids + self.tokens.capacity() * size_of::<Option<Arc<str>>>() + grams
+    }
+
+    /// Tokens that may contain `substring`, or `None` if it is shorter
+    /// than three characters and every token must be checked. Candidates
+    /// share all of the substring's trigrams but still need verifying.
+    pub fn candidates(&self, substring: &str) -> Option<Vec<&str>> {
+        let grams = trigrams(substring);
+        if grams.is_empty() {
+            return None;
+        }
+
+        let mut lists = Vec::with_capacity(grams.len());
+        for gram in &grams {
+            match self.grams.get(gram) {
+                Some(ids) => lists.push(ids),
+                None => return Some(Vec::new()),
+            }
+        }
+        lists.sort_by_key(|ids| ids.len());
+        let (shortest, rest) = lists.split_first().expect("at least one trigram");
+
+        let ids = shortest
+            .iter()
+            .filter(|id| rest.iter().all(|ids| ids.binary_search(id).is_ok()));
+        Some(ids.filter_map(|&id| self.tokens[id as usize].as_deref()).collect())
+    }
+}
+
+/// The trigrams of `s`, each once, in order.
+fn distinct_trigrams(s: &str) -> Vec<[char; 3]> {
+    let mut grams = trigrams(s);
+    grams.sort_unstable();
+    grams.dedup();
+    grams
+}
+
+/// Every run of three consecutive characters in `s`.
+fn trigrams(s: &str) -> Vec<[char; 3]> {
+    let chars: Vec<char> = s.chars().collect();
+    chars.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_share_every_trigram() {
+        let mut grams = TrigramIndex::default();
+        for token in ["password", "passport", "sword", "words", "été", "password"] {
+            grams.insert(token);
+        }
+
+        let mut found = grams.candidates("word").unwrap();
+        found.sort();
+        assert_eq!(found, vec!["password", "sword", "words"]);
+        assert_eq!(grams.candidates("pass").unwrap(), vec!["password", "passport"]);
+        assert_eq!(grams.candidates("été").unwrap(), vec!["été"]);
+        assert!(grams.candidates("xyz").unwrap().is_empty());
+        assert!(grams.candidates("wo").is_none());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut grams = TrigramIndex::default();
+        for token in ["password", "passport", "sword"] {
+            grams.insert(token);
+        }
+        grams.remove("password");
+        grams.remove("unknown");
+        assert_eq!(grams.candidates("pass").unwrap(), vec!["passport"]);
+        assert_eq!(grams.candidates("word").unwrap(), vec!["sword"]);
+        assert!(grams.candidates("ssw").unwrap().is_empty());
+
+        // Empty slots outnumbering live tokens renumber the rest
+        grams.remove("passport");
+        assert_eq!(grams.tokens.len(), 1);
+        assert_eq!(grams.candidates("word").unwrap(), vec!["sword"]);
+        grams.insert("password");
+        assert_eq!(grams.candidates("word").unwrap(), vec!["sword", "password"]);
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::document::DocInput;
+use crate::error::MemexError;
+use crate::sniff::{self, BinaryPolicy};
+use crate::store::DocumentStore;
+
+/// What loading does with a document whose path is already loaded, or
+/// comes up again later in the same batch. Writes always replace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// The later document replaces the earlier one (the default).
+    #[default]
+    LastWins,
+    /// The earlier document stays; later ones are left out.
+    FirstWins,
+    /// Fail the load before any of it is indexed.
+    Error,
+}
+
+/// What a lenient load (`MemexFsCore::add_documents_lenient`) did with
+/// its documents.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadReport {
+    /// Documents loaded, stubs and documents with warnings included.
+    pub loaded: usize,
+    /// Problems found, in input order.
+    pub issues: Vec<LoadIssue>,
+}
+
+/// A problem with one entry of a lenient load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadIssue {
+    /// Position of the entry in the input array.
+    pub index: usize,
+    /// The entry's path, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub kind: LoadIssueKind,
+    /// Whether the document was loaded anyway (empty content, binary
+    /// stubs) or left out.
+    pub loaded: bool,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadIssueKind {
+    /// Not a `[path, content]` pair or `{ path, content, metadata? }`
+    /// object.
+    InvalidEntry,
+    /// Empty once normalized (see `normalize_path`), ending in `/`, with a
+    /// `..` segment, or containing control characters.
+    InvalidPath,
+    /// Content that is empty or only whitespace. Loaded.
+    EmptyContent,
+    /// Another entry in the batch, or a document already loaded, has the
+    /// same path. Under `DuplicatePolicy::LastWins` the earlier entry is
+    /// reported as replaced; otherwise the later one is left out.
+    DuplicatePath,
+    /// Content over `MemexOptions::max_document_bytes`.
+    Oversized,
+    /// Content that isn't text (see `BinaryPolicy`). Loaded as a stub
+    /// under `BinaryPolicy::Stub`, left out otherwise.
+    Binary,
+}
+
+/// `path` as documents are stored under it: leading `./` and `/`
+/// stripped, and `.` segments and repeated slashes dropped, so
+/// `./docs//a.md` is `docs/a.md`.
+pub fn normalize_path(path: &str) -> Result<String, MemexError> {
+    normalized(path).map_err(|reason| match reason {
+        EMPTY_PATH => MemexError::EmptyPath,
+        reason => MemexError::InvalidPath { path: path.to_string(), reason: reason.to_string() },
+    })
+}
+
+const EMPTY_PATH: &str = "empty path";
+
+/// `normalize_path`, failing with the reason `path` can't name a document.
+fn normalized(path: &str) -> Result<String, &'static str> {
+    if path.chars().any(char::is_control) {
+        return Err("contains control characters");
+    }
+    if path.ends_with('/') && !path.trim_start_matches(['.', '/']).is_empty() {
+        return Err("ends with `/`, like a directory");
+    }
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return Err("has a `..` segment"),
+            part => parts.push(part),
+        }
+    }
+    if parts.is_empty() { Err(EMPTY_PATH) } else { Ok(parts.join("/")) }
+}
+
+/// `doc` under its normalized path.
+pub fn normalize(doc: DocInput) -> Result<DocInput, MemexError> {
+    let (path, content, metadata) = doc.into_parts();
+    let path = normalize_path(&path)?;
+    Ok(DocInput::Object { path, content, metadata })
+}
+
+/// `docs` without the ones `policy` leaves out for repeating a path of
+/// the batch or one `loaded` already has, in their original order.
+pub fn dedupe(
+    docs: Vec<DocInput>,
+    policy: DuplicatePolicy,
+    loaded: impl Fn(&str) -> bool,
+) -> Result<Vec<DocInput>, MemexError> {
+    // Path → position in `docs` of the entry kept for it
+    let mut kept: HashMap<&str, usize> = HashMap::new();
+    let mut keep = vec![true; docs.len()];
+    for (i, doc) in docs.iter().enumerate() {
+        let path = doc.path();
+        let earlier = kept.get(path).copied();
+        if policy != DuplicatePolicy::LastWins && (earlier.is_some() || loaded(path)) {
+            if policy == DuplicatePolicy::Error {
+                return Err(MemexError::DuplicatePath { path: path.to_string() });
+            }
+            keep[i] = false;
+            continue;
+        }
+        if let Some(earlier) = earlier {
+            keep[earlier] = false;
+        }
+        kept.insert(path, i);
+    }
+    Ok(docs.into_iter().zip(keep).filter_map(|(doc, keep)| keep.then_some(doc)).collect())
+}
+
+/// The documents of `entries` worth loading into `store`, and a report of
+/// the rest. Entries are checked one by one, so a bad one never stops the
+/// others. Paths are normalized; binary documents are stubbed under
+/// `BinaryPolicy::Stub` and otherwise left out, even under `Reject`; and
+/// duplicate paths, within `entries` or of a document `loaded` already
+/// has, are left out as `DuplicatePolicy` says, with `Error` keeping the
+/// first.
+pub fn check(
+    entries: Vec<Value>,
+    store: &DocumentStore,
+    max_bytes: Option<usize>,
+    loaded: impl Fn(&str) -> bool,
+) -> (Vec<DocInput>, LoadReport) {
+    let policy = store.binary_policy();
+    let last_wins = store.duplicate_policy() == DuplicatePolicy::LastWins;
+    let mut docs: Vec<Option<DocInput>> = Vec::with_capacity(entries.len());
+    let mut issues = Vec::new();
+    // Path → (position in `docs`, input index) of the entry loaded for it
+    let mut seen: HashMap<String, (usize, usize)> = HashMap::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let doc = match DocInput::deserialize(&entry) {
+            Ok(doc) => doc,
+            Err(e) => {
+                let path = entry.get("path").or_else(|| entry.get(0)).and_then(Value::as_str);
+                issues.push(LoadIssue {
+                    index,
+                    path: path.map(str::to_string),
+                    kind: LoadIssueKind::InvalidEntry,
+                    loaded: false,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let mut issue = |path: &str, kind, loaded, reason: String| {
+            issues.push(LoadIssue { index, path: Some(path.to_string()), kind, loaded, reason })
+        };
+        let path = match normalized(doc.path()) {
+            Ok(path) => path,
+            Err(reason) => {
+                issue(doc.path(), LoadIssueKind::InvalidPath, false, reason.to_string());
+                continue;
+            }
+        };
+        if !last_wins {
+            let reason = match seen.get(&path) {
+                Some(&(_, earlier)) => Some(format!("entry {} has the same path", earlier)),
+                None => loaded(&path).then(|| "already loaded".to_string()),
+            };
+            if let Some(reason) = reason {
+                issue(&path, LoadIssueKind::DuplicatePath, false, reason);
+                continue;
+            }
+        }
+        let (_, content, metadata) = doc.into_parts();
+        let doc = DocInput::Object { path: path.clone(), content, metadata };
+        let content = doc.content();
+        if let Some(max) = max_bytes.filter(|&max| content.len() > max) {
+            let reason = format!("{} bytes, over the limit of {}", content.len(), max);
+            issue(&path, LoadIssueKind::Oversized, false, reason);
+            continue;
+        }
+        let doc = match sniff::detect(content) {
+            Some(reason) => {
+                let stub = policy == BinaryPolicy::Stub;
+                issue(&path, LoadIssueKind::Binary, stub, reason.to_string());
+                if stub { sniff::screen(doc, policy).ok().flatten() } else { None }
+            }
+            None => {
+                if content.trim().is_empty() {
+                    issue(&path, LoadIssueKind::EmptyContent, true, "no content".to_string());
+                }
+                Some(doc)
+            }
+        };
+        let Some(doc) = doc else { continue };
+        if let Some((position, earlier)) = seen.insert(path.clone(), (docs.len(), index)) {
+            docs[position] = None;
+            for issue in issues.iter_mut().filter(|issue| issue.index == earlier) {
+                issue.loaded = false;
+            }
+            issues.push(LoadIssue {
+                index: earlier,
+                path: Some(path),
+                kind: LoadIssueKind::DuplicatePath,
+                loaded: false,
+                reason: format!("replaced by entry {} with the same path", index),
+            });
+        }
+        docs.push(Some(doc));
+    }
+    issues.sort_by_key(|issue| issue.index);
+    let docs: Vec<DocInput> = docs.into_iter().flatten().collect();
+    let report = LoadReport { loaded: docs.len(), issues };
+    (docs, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(report: &LoadReport) -> Vec<(usize, LoadIssueKind, bool)> {
+        report.issues.iter().map(|i| (i.index, i.kind, i.loaded)).collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let entries: Vec<Value> = serde_json::from_str(
+            r##"[
+                ["guide.md", "# Guide"],
+                {"path": "notes.md"},
+                ["../secrets.md", "x"],
+                ["empty.md", "  \n"],
+                ["big.md", "0123456789abcdef"],
+                ["logo.png", "\u0000\u0001PNG"],
+                {"path": "guide.md", "content": "# Guide v2", "metadata": {"v": 2}},
+                ["./a//b.md", "x"],
+                42
+            ]"##,
+        )
+        .unwrap();
+        let (docs, report) = check(entries.clone(), &DocumentStore::new(), Some(10), |_| false);
+        let paths: Vec<&str> = docs.iter().map(DocInput::path).collect();
+        assert_eq!(paths, ["empty.md", "guide.md", "a/b.md"]);
+        assert_eq!(report.loaded, 3);
+        use LoadIssueKind::*;
+        assert_eq!(
+            summary(&report),
+            [
+                (0, DuplicatePath, false),
+                (1, InvalidEntry, false),
+                (2, InvalidPath, false),
+                (3, EmptyContent, true),
+                (4, Oversized, false),
+                (5, Binary, false),
+                (8, InvalidEntry, false),
+            ]
+        );
+        assert_eq!(report.issues[0].reason, "replaced by entry 6 with the same path");
+        assert_eq!(report.issues[1].path.as_deref(), Some("notes.md"));
+        assert_eq!(report.issues[4].reason, "16 bytes, over the limit of 10");
+
+        // Stubs are loaded; without a limit nothing is oversized
+        let stubs = DocumentStore::new().with_binary_policy(BinaryPolicy::Stub);
+        let (docs, report) = check(entries.clone(), &stubs, None, |_| false);
+        assert_eq!(docs.len(), 5);
+        assert!(summary(&report).contains(&(5, Binary, true)));
+
+        // Under first-wins the later entry is left out, as is one already
+        // loaded
+        let mut store = DocumentStore::new().with_duplicate_policy(DuplicatePolicy::FirstWins);
+        store.load_documents(vec![("a/b.md".to_string(), "loaded".to_string())]);
+        let (docs, report) = check(entries, &store, Some(10), |path| store.get_document(path).is_some());
+        let paths: Vec<&str> = docs.iter().map(DocInput::path).collect();
+        assert_eq!(paths, ["guide.md", "empty.md"]);
+        let duplicates: Vec<(usize, &str)> = report
+            .issues
+            .iter()
+            .filter(|i| i.kind == DuplicatePath)
+            .map(|i| (i.index, i.reason.as_str()))
+            .collect();
+        assert_eq!(duplicates, [(6, "entry 0 has the same path"), (7, "already loaded")]);
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let cases = [
+            ("billing/refunds.md", "billing/refunds.md"),
+            (".github/notes.md", ".github/notes.md"),
+            ("./docs//a.md", "docs/a.md"),
+            ("/etc/passwd", "etc/passwd"),
+            ("a/./b.md", "a/b.md"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(normalize_path(path).unwrap(), expected, "{:?}", path);
+        }
+        for path in ["", "./", "/"] {
+            assert_eq!(normalize_path(path).unwrap_err(), MemexError::EmptyPath, "{:?}", path);
+        }
+        for path in ["docs/", "a/../b.md", "../secrets.md", "tab\there.md"] {
+            assert_eq!(normalize_path(path).unwrap_err().code(), "invalid_path", "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let docs = || -> Vec<DocInput> {
+            vec![("a.md", "1"), ("b.md", "2"), ("a.md", "3")]
+                .into_iter()
+                .map(|(p, c)| (p.to_string(), c.to_string()).into())
+                .collect()
+        };
+        let contents = |policy, loaded: &dyn Fn(&str) -> bool| -> Vec<String> {
+            let docs = dedupe(docs(), policy, loaded).unwrap();
+            docs.iter().map(|d| d.content().to_string()).collect()
+        };
+        assert_eq!(contents(DuplicatePolicy::LastWins, &|_| true), ["2", "3"]);
+        assert_eq!(contents(DuplicatePolicy::FirstWins, &|_| false), ["1", "2"]);
+        assert_eq!(contents(DuplicatePolicy::FirstWins, &|p| p == "b.md"), ["1"]);
+        let err = dedupe(docs(), DuplicatePolicy::Error, |_| false).unwrap_err();
+        assert_eq!(err, MemexError::DuplicatePath { path: "a.md".to_string() });
+    }
+}
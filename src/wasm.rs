@@ -0,0 +1,929 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    diff_stores, DocInput, DuplicatePolicy, GrepOptions, MemexError, MemexFsCore, MemexOptions, ReadOptions, TableQuery,
+    ToolCall,
+};
+
+/// TypeScript shapes of the values passed to and returned from `MemexFS`.
+/// Keep in sync with the serde types in `lib.rs`.
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+/** A document to load: `{ path, content, metadata? }` or a `[path, content]` pair. */
+export type Doc = { path: string; content: string; metadata?: unknown } | [string, string];
+
+export type SynonymMode = "query" | "index";
+export type SnippetMode = "chars" | "sentence";
+export type StemLanguage =
+  | "arabic" | "danish" | "dutch" | "english" | "finnish" | "french"
+  | "german" | "greek" | "hungarian" | "italian" | "norwegian"
+  | "portuguese" | "romanian" | "russian" | "spanish" | "swedish"
+  | "tamil" | "turkish";
+
+export interface MemexOptions {
+  synonyms?: Record<string, string[]>;
+  synonym_mode?: SynonymMode;
+  file_index?: boolean;
+  fold_cache?: boolean;
+  /** Ignore diacritics: `Malmo` matches `Malmö`. */
+  ascii_fold?: boolean;
+  /** Index Chinese, Japanese, and Korean text as overlapping n-grams (usually 2). */
+  cjk_ngram?: number;
+  /** Also index the words of camelCase identifiers: `user` finds `getUserById`. */
+  split_identifiers?: boolean;
+  /** Index `.html` documents as plain text, with headings as markdown `#` lines. */
+  html_to_text?: boolean;
+  /** Largest document `add_documents_lenient` loads, in bytes of content. */
+  max_document_bytes?: number;
+  /** What to do with documents that aren't text; skipped by default. */
+  binary_policy?: "skip" | "reject" | "stub";
+  /** What a load does with a path already loaded or repeated; the last one wins by default. */
+  duplicate_paths?: "last_wins" | "first_wins" | "error";
+  /** Make `call` return a `CallEnvelope` for every tool, errors included. */
+  call_envelope?: boolean;
+  tool_text?: ToolText;
+  /** A built-in list by language, or your own words. */
+  stop_words?: "english" | "french" | "german" | "spanish" | string[];
+  /** Requires a build with the `stemming` feature; ignored otherwise. */
+  stemmer?: StemLanguage;
+}
+
+/** A tool definition in OpenAI's strict function-calling format. */
+export interface OpenAITool {
+  type: "function";
+  function: {
+    name: string;
+    description: string;
+    strict: true;
+    parameters: {
+      type: "object";
+      properties: Record<string, unknown>;
+      required: string[];
+      additionalProperties: false;
+    };
+  };
+}
+
+/** Wording of the tool definitions; unset fields keep the built-in text. */
+export interface ToolText {
+  /** Replaces "knowledge base" in descriptions. */
+  corpus?: string;
+  /** By built-in tool name. */
+  tools?: Record<string, {
+    /** Offer the tool under this name; `call` accepts both. */
+    name?: string;
+    description?: string;
+    /** Parameter descriptions by parameter name. */
+    parameters?: Record<string, string>;
+  }>;
+}
+
+/** One call in `call_batch`. */
+export interface ToolCall {
+  name: string;
+  params?: ToolParams;
+}
+
+/** What `call` returns (as a JSON string) with `call_envelope`, and `call_batch` per call. */
+export interface CallEnvelope {
+  ok: boolean;
+  tool: string;
+  /** Parsed tool output, or a string for `read`, `write`, and text `tree`. */
+  data: unknown;
+  error: (MemexErrorInfo & { message: string }) | null;
+  /** More exists than `data` holds: another grep page, a timeout, a partial read. */
+  truncated: boolean;
+  /** Matches, lines, or entries in the whole answer. */
+  total: number | null;
+}
+
+export interface ContextLine {
+  line: number;
+  content: string;
+}
+
+export interface GrepResult {
+  path: string;
+  /** 1-indexed line number. */
+  line: number;
+  content: string;
+  /** Lines covered by a `multiline` match; `content` joins them with `\n`. */
+  line_count?: number;
+  /** 1-indexed character column of the first match. */
+  column: number;
+  /** `[start, end)` character offsets of every match, with `spans: true`. */
+  spans?: [number, number][];
+  title?: string;
+  metadata?: unknown;
+  snippet?: string;
+  context_before?: ContextLine[];
+  context_after?: ContextLine[];
+}
+
+/** Result of the `grep` tool via `call()`. */
+export interface GrepResponse {
+  results: GrepResult[];
+  total_matches: number;
+  next_offset?: number;
+  /** Set when `timeout_ms` cut the search short; results are partial. */
+  timed_out?: boolean;
+  /** With `suggest` and no matches: indexed words close to the pattern's. */
+  suggestions?: string[];
+}
+
+/** Result of the `grep` tool with `output: "grouped"`. */
+export interface GroupedGrepResponse {
+  groups: { path: string; matches: GrepResult[] }[];
+  total_matches: number;
+  next_offset?: number;
+  timed_out?: boolean;
+  suggestions?: string[];
+}
+
+export interface FileCount {
+  path: string;
+  count: number;
+}
+
+export interface GrepParams {
+  /** A pattern, or several literal strings to match any of. */
+  pattern: string | string[];
+  glob?: string;
+  body_only?: boolean;
+  flexible_whitespace?: boolean;
+  with_title?: boolean;
+  snippet?: SnippetMode;
+  snippet_radius?: number;
+  context_before?: number;
+  context_after?: number;
+  max_context_lines?: number;
+  max_results?: number;
+  max_per_file?: number;
+  offset?: number;
+  whole_word?: boolean;
+  case_sensitive?: boolean;
+  spans?: boolean;
+  literal?: boolean;
+  sort?: "path" | "relevance";
+  mode?: "exact" | "all_terms" | "near";
+  within?: "line" | "document";
+  exclude?: string;
+  fuzzy?: boolean;
+  /** When nothing matches, return `suggestions` for misspelled words. */
+  suggest?: boolean;
+  smart_case?: boolean;
+  multiline?: boolean;
+  /** `prose` skips fenced code blocks; `code` searches only them. */
+  scope?: "all" | "prose" | "code";
+  /** Match lines as rendered, ignoring markdown syntax and link targets. */
+  ignore_markdown?: boolean;
+  /** Give up after this many milliseconds and return partial results. */
+  timeout_ms?: number;
+  stop_words?: boolean;
+  /**
+   * Tool calls only: `grouped` returns `GroupedGrepResponse`, `files`
+   * returns `string[]`, `count` returns `FileCount[]`.
+   */
+  output?: "lines" | "grouped" | "files" | "count";
+  metadata?: Record<string, unknown>;
+  with_metadata?: boolean;
+}
+
+export interface ReadParams {
+  path: string;
+  offset?: number;
+  limit?: number;
+  tab_width?: number;
+  section?: string;
+}
+
+export interface LsParams {
+  path: string;
+  with_metadata?: boolean;
+  detailed?: boolean;
+}
+
+export interface LsEntry {
+  name: string;
+  metadata?: unknown;
+}
+
+export interface LsDetail {
+  /** File name, or directory name with a trailing `/`. */
+  name: string;
+  kind: "dir" | "file";
+  /** Line count, for files. */
+  lines?: number;
+  /** Content size in bytes, for files. */
+  bytes?: number;
+  /** Number of documents anywhere below, for directories. */
+  children?: number;
+  metadata?: unknown;
+}
+
+export interface SearchParams {
+  query: string;
+  limit?: number;
+}
+
+export interface WriteParams {
+  path: string;
+  content: string;
+  metadata?: unknown;
+}
+
+export interface GlobParams {
+  pattern: string;
+}
+
+export interface MissingParams {
+  term: string;
+  glob: string;
+}
+
+export interface DiffParams {
+  /** Mount holding the older version. */
+  from: string;
+  /** Mount holding the newer version. */
+  to: string;
+  glob?: string;
+}
+
+export type ToolParams =
+  | GrepParams
+  | ReadParams
+  | LsParams
+  | SearchParams
+  | WriteParams
+  | MissingParams
+  | GlobParams
+  | TreeParams
+  | DiffParams
+  | Record<string, never>;
+
+export interface NumberedLine {
+  number: number;
+  text: string;
+}
+
+export interface TableQuery {
+  /** Conditions like `plan = Pro` or `price < 50`; ops `= != < <= > >= ~`. */
+  filter?: string[];
+  /** Columns to return, in order; all by default. */
+  columns?: string[];
+  limit?: number;
+  offset?: number;
+}
+
+export interface TableRows {
+  columns: string[];
+  /** Each row's 1-indexed starting line and its values for `columns`. */
+  rows: { line: number; values: string[] }[];
+  total_rows: number;
+  next_offset?: number;
+}
+
+export interface LoadIssue {
+  /** Position of the entry in the input array. */
+  index: number;
+  path?: string;
+  kind: "invalid_entry" | "invalid_path" | "empty_content" | "duplicate_path" | "oversized" | "binary";
+  /** Whether the document was loaded anyway (empty content, binary stubs). */
+  loaded: boolean;
+  reason: string;
+}
+
+export interface LoadReport {
+  loaded: number;
+  issues: LoadIssue[];
+}
+
+/** How a newer corpus differs from an older one, from `diff` or `diff_mounts`. */
+export interface StoreDiff {
+  added: string[];
+  removed: string[];
+  changed: DocumentDiff[];
+}
+
+export interface DocumentDiff {
+  path: string;
+  lines_added: number;
+  lines_removed: number;
+  metadata_changed?: boolean;
+  /** Empty if only the metadata changed. */
+  hunks: DiffHunk[];
+}
+
+/** Changed lines with up to three lines of context, as in a unified diff. */
+export interface DiffHunk {
+  old_start: number;
+  old_lines: number;
+  new_start: number;
+  new_lines: number;
+  /** Each line starts with " " (unchanged), "-" (removed), or "+" (added). */
+  lines: string[];
+}
+
+export interface Heading {
+  level: number;
+  text: string;
+  /** 1-indexed line number of the heading. */
+  line: number;
+}
+
+export interface SearchResult {
+  path: string;
+  score: number;
+  line: number;
+  snippet: string;
+}
+
+export interface TreeNode {
+  name: string;
+  type: "dir" | "file";
+  /** Absent for files and for directories beyond the depth limit. */
+  children?: TreeNode[];
+}
+
+/** Options for `start_grep`: grep parameters other than the pattern. */
+export type GrepOptions = Omit<GrepParams, "pattern" | "output">;
+
+/** One slice of a resumable grep, from `continue_grep`. */
+export interface GrepChunk {
+  results: GrepResult[];
+  /** Documents searched so far, across all slices. */
+  searched: number;
+  total_documents: number;
+  /** Finished; the handle has been released. */
+  done: boolean;
+}
+
+/** Estimated heap bytes by component. */
+export interface MemoryStats {
+  documents: number;
+  /** Case-folded copies kept with `fold_cache`; 0 without it. */
+  folded: number;
+  index: number;
+  filters: number;
+  total: number;
+}
+
+export interface TreeParams {
+  path?: string;
+  depth?: number;
+  format?: "text" | "json";
+}
+
+export interface ToolParameter {
+  type: string | string[];
+  items?: { type: string };
+  description: string;
+  enum?: string[];
+}
+
+export interface ToolDefinition {
+  name: string;
+  description: string;
+  parameters: Record<string, ToolParameter>;
+  required: string[];
+}
+
+/**
+ * Errors thrown by `MemexFS` are JS `Error`s with a stable `code` and, per
+ * code, the context fields listed here.
+ */
+export type MemexErrorInfo =
+  | { code: "document_not_found"; path: string; suggestions: string[] }
+  | { code: "section_not_found"; path: string; heading: string }
+  | { code: "not_a_table"; path: string }
+  | { code: "unknown_column"; path: string; column: string; columns: string[] }
+  | { code: "not_json"; path: string; reason: string }
+  | { code: "json_value_not_found"; path: string; pointer: string; parent: string; keys: string[] }
+  | { code: "invalid_regex"; pattern: string; reason: string }
+  | { code: "invalid_params"; reason: string }
+  | { code: "invalid_documents"; reason: string }
+  | { code: "binary_document"; path: string; reason: string }
+  | { code: "invalid_path"; path: string; reason: string }
+  | { code: "duplicate_path"; path: string }
+  | { code: "mount_conflict"; prefix: string; reason: string }
+  | { code: "not_mounted"; prefix: string; mounts: string[] }
+  | { code: "unknown_tool"; name: string }
+  | { code: "tool_not_enabled"; name: string }
+  | { code: "empty_pattern" }
+  | { code: "no_search_terms" }
+  | { code: "empty_path" }
+  | { code: "no_documents" }
+  | { code: "unknown_grep_handle"; handle: number }
+  | { code: "invalid_snapshot"; reason: string }
+  | { code: "encoding"; reason: string };
+"#;
+
+/// WASM-exported MemexFS. Thin wrapper over MemexFsCore that converts errors to JsError.
+#[wasm_bindgen]
+pub struct MemexFS {
+    core: MemexFsCore,
+}
+
+/// Deserialize a JS value, or a JSON string for callers that still pass
+/// pre-stringified input.
+fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T, JsError> {
+    match value.as_string() {
+        Some(json) => serde_json::from_str(&json).map_err(|e| js_error(MemexError::invalid_params(e))),
+        None => serde_wasm_bindgen::from_value(value).map_err(|e| js_error(MemexError::invalid_params(e))),
+    }
+}
+
+/// `MemexOptions` from an optional options argument.
+fn memex_options(options: JsValue) -> Result<MemexOptions, JsError> {
+    if options.is_undefined() || options.is_null() {
+        return Ok(MemexOptions::default());
+    }
+    from_js(options)
+}
+
+/// A `call`'s tool input, null when it was left out.
+fn tool_params(params: JsValue) -> Result<serde_json::Value, JsError> {
+    if params.is_undefined() || params.is_null() {
+        return Ok(serde_json::Value::Null);
+    }
+    from_js(params)
+}
+
+/// A JS `Error` with the message of `error`, plus its `code` and context
+/// fields as properties so callers can branch on `err.code`.
+fn js_error(error: MemexError) -> JsError {
+    let js = JsError::new(&error.to_string());
+    if let Ok(fields) = to_js(&error) {
+        // JsError and JsValue are handles to the same JS object
+        let target = JsValue::from(js.clone());
+        js_sys::Object::assign(target.unchecked_ref(), fields.unchecked_ref::<js_sys::Object>());
+    }
+    js
+}
+
+/// Convert to a plain JS value (arrays and objects, never `Map`).
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Index every document in `docs` into `core`.
+fn load(core: &mut MemexFsCore, docs: JsValue) -> Result<usize, JsError> {
+    let docs: Vec<DocInput> = from_js(docs)?;
+    core.load_documents(docs).map_err(js_error)
+}
+
+#[wasm_bindgen]
+impl MemexFS {
+    /// Build from an array of `{ path, content }` objects (or `[path,
+    /// content]` pairs, or the same as a JSON string).
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        #[wasm_bindgen(unchecked_param_type = "Doc[] | string")] docs: JsValue,
+    ) -> Result<MemexFS, JsError> {
+        Self::with_options(docs, JsValue::UNDEFINED)
+    }
+
+    /// Construct with a `MemexOptions` object (or its JSON string).
+    pub fn with_options(
+        #[wasm_bindgen(unchecked_param_type = "Doc[] | string")] docs: JsValue,
+        #[wasm_bindgen(unchecked_param_type = "MemexOptions | string")] options: JsValue,
+    ) -> Result<MemexFS, JsError> {
+        let mut fs = Self::empty(options)?;
+        fs.add_documents(docs)?;
+        fs.finalize()?;
+        Ok(fs)
+    }
+
+    /// Construct from gzip-compressed documents JSON, optionally with a
+    /// `MemexOptions` object.
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    pub fn from_compressed(
+        bytes: &[u8],
+        #[wasm_bindgen(unchecked_param_type = "MemexOptions | string | undefined")] options: JsValue,
+    ) -> Result<MemexFS, JsError> {
+        let mut fs = Self::empty(options)?;
+        fs.add_documents_compressed(bytes)?;
+        fs.finalize()?;
+        Ok(fs)
+    }
+
+    /// Construct from a tar or tar.gz archive of text files, optionally
+    /// with a `MemexOptions` object. Entry paths become document paths.
+    #[cfg(feature = "tar")]
+    pub fn from_tar(
+        bytes: &[u8],
+        #[wasm_bindgen(unchecked_param_type = "MemexOptions | string | undefined")] options: JsValue,
+    ) -> Result<MemexFS, JsError> {
+        let core = crate::MemexFsBuilder::new().options(memex_options(options)?).from_tar(bytes);
+        Ok(MemexFS { core: core.map_err(js_error)? })
+    }
+
+    /// Construct from a zip archive of text files, optionally with a
+    /// `MemexOptions` object. Entry paths become document paths.
+    #[cfg(feature = "zip")]
+    pub fn from_zip(
+        bytes: &[u8],
+        #[wasm_bindgen(unchecked_param_type = "MemexOptions | string | undefined")] options: JsValue,
+    ) -> Result<MemexFS, JsError> {
+        let core = crate::MemexFsBuilder::new().options(memex_options(options)?).from_zip(bytes);
+        Ok(MemexFS { core: core.map_err(js_error)? })
+    }
+
+    /// Restore an instance from bytes produced by `to_snapshot`.
+    pub fn from_snapshot(bytes: &[u8]) -> Result<MemexFS, JsError> {
+        let core = MemexFsCore::from_snapshot(bytes).map_err(js_error)?;
+        Ok(MemexFS { core })
+    }
+
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        self.core.to_snapshot()
+    }
+
+    /// An instance with no documents, optionally configured with a
+    /// `MemexOptions` object. Load documents in batches with
+    /// `add_documents`, then call `finalize`.
+    pub fn empty(
+        #[wasm_bindgen(unchecked_param_type = "MemexOptions | string | undefined")] options: JsValue,
+    ) -> Result<MemexFS, JsError> {
+        Ok(MemexFS {
+            core: MemexFsCore::empty(&memex_options(options)?),
+        })
+    }
+
+    pub fn add_documents(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "Doc[] | string")] docs: JsValue,
+    ) -> Result<usize, JsError> {
+        load(&mut self.core, docs)
+    }
+
+    /// `add_documents` that loads what it can instead of throwing on the
+    /// first bad document, and returns a report of the rest.
+    #[wasm_bindgen(unchecked_return_type = "LoadReport")]
+    pub fn add_documents_lenient(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "unknown[] | string")] docs: JsValue,
+    ) -> Result<JsValue, JsError> {
+        let entries: Vec<serde_json::Value> = from_js(docs)?;
+        to_js(&self.core.load_documents_lenient(entries))
+    }
+
+    /// `add_documents` for the same JSON compressed with gzip (or brotli,
+    /// in a build with the `brotli` feature).
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    pub fn add_documents_compressed(&mut self, bytes: &[u8]) -> Result<usize, JsError> {
+        self.core.add_documents_compressed(bytes).map_err(js_error)
+    }
+
+    pub fn finalize(&mut self) -> Result<(), JsError> {
+        self.core.finalize().map_err(js_error)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "GrepResult[]")]
+    pub fn grep(&self, pattern: &str, glob: Option<String>) -> Result<JsValue, JsError> {
+        let results = self
+            .core
+            .grep(pattern, glob.as_deref())
+            .map_err(js_error)?;
+        to_js(&results)
+    }
+
+    /// Lines containing any of `patterns` (literal strings).
+    #[wasm_bindgen(unchecked_return_type = "GrepResult[]")]
+    pub fn grep_any(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "string[]")] patterns: JsValue,
+        glob: Option<String>,
+    ) -> Result<JsValue, JsError> {
+        let patterns: Vec<String> = from_js(patterns)?;
+        let options = GrepOptions {
+            glob,
+            ..Default::default()
+        };
+        let page = self
+            .core
+            .grep_any_page(&patterns, &options)
+            .map_err(js_error)?;
+        to_js(&page.results)
+    }
+
+    /// Begin a grep to run in slices with `continue_grep`. Returns a handle.
+    pub fn start_grep(
+        &self,
+        pattern: &str,
+        #[wasm_bindgen(unchecked_param_type = "GrepOptions | string | undefined")] options: JsValue,
+    ) -> Result<u32, JsError> {
+        let options: GrepOptions = if options.is_undefined() || options.is_null() {
+            GrepOptions::default()
+        } else {
+            from_js(options)?
+        };
+        self.core
+            .start_grep(pattern, &options)
+            .map_err(js_error)
+    }
+
+    /// Search further for up to about `budget_ms` milliseconds.
+    #[wasm_bindgen(unchecked_return_type = "GrepChunk")]
+    pub fn continue_grep(&self, handle: u32, budget_ms: u32) -> Result<JsValue, JsError> {
+        let chunk = self
+            .core
+            .continue_grep(handle, budget_ms as u64)
+            .map_err(js_error)?;
+        to_js(&chunk)
+    }
+
+    pub fn cancel_grep(&self, handle: u32) -> bool {
+        self.core.cancel_grep(handle)
+    }
+
+    pub fn read(
+        &self,
+        path: &str,
+        offset: Option<u32>,
+        limit: Option<u32>,
+        tab_width: Option<u32>,
+        section: Option<String>,
+    ) -> Result<String, JsError> {
+        let options = ReadOptions {
+            offset: offset.map(|o| o as usize),
+            limit: limit.map(|l| l as usize),
+            tab_width: tab_width.map(|t| t as usize),
+            section,
+        };
+        self.core
+            .read_with_options(path, &options)
+            .map_err(js_error)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "NumberedLine[]")]
+    pub fn read_lines(
+        &self,
+        path: &str,
+        offset: Option<u32>,
+        limit: Option<u32>,
+        tab_width: Option<u32>,
+    ) -> Result<JsValue, JsError> {
+        let options = ReadOptions {
+            offset: offset.map(|o| o as usize),
+            limit: limit.map(|l| l as usize),
+            tab_width: tab_width.map(|t| t as usize),
+            ..Default::default()
+        };
+        let lines = self
+            .core
+            .read_lines(path, &options)
+            .map_err(js_error)?;
+        to_js(&lines)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "string[]")]
+    pub fn recently_read(&self, n: usize) -> Result<JsValue, JsError> {
+        to_js(&self.core.recently_read(n))
+    }
+
+    pub fn read_focused(&self, path: &str, heading: &str) -> Result<String, JsError> {
+        self.core
+            .read_focused(path, heading)
+            .map_err(js_error)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "Heading[]")]
+    pub fn outline(&self, path: &str) -> Result<JsValue, JsError> {
+        let headings = self
+            .core
+            .outline(path)
+            .map_err(js_error)?;
+        to_js(&headings)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "TableRows")]
+    pub fn query_table(
+        &self,
+        path: &str,
+        #[wasm_bindgen(unchecked_param_type = "TableQuery | undefined")] query: JsValue,
+    ) -> Result<JsValue, JsError> {
+        let query: TableQuery = if query.is_undefined() || query.is_null() {
+            TableQuery::default()
+        } else {
+            from_js(query)?
+        };
+        to_js(&self.core.query_table(path, &query).map_err(js_error)?)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "unknown")]
+    pub fn read_json(&self, path: &str, pointer: &str) -> Result<JsValue, JsError> {
+        to_js(&self.core.read_json(path, pointer).map_err(js_error)?)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "string[]")]
+    pub fn ls(&self, path: &str) -> Result<JsValue, JsError> {
+        to_js(&self.core.ls(path))
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "string[]")]
+    pub fn glob(&self, pattern: &str) -> Result<JsValue, JsError> {
+        to_js(&self.core.glob(pattern))
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "LsEntry[]")]
+    pub fn ls_with_metadata(&self, path: &str) -> Result<JsValue, JsError> {
+        to_js(&self.core.ls_with_metadata(path))
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "LsDetail[]")]
+    pub fn ls_detailed(&self, path: &str) -> Result<JsValue, JsError> {
+        to_js(&self.core.ls_detailed(path))
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "string[]")]
+    pub fn grep_files(&self, pattern: &str, glob: Option<String>) -> Result<JsValue, JsError> {
+        let paths = self
+            .core
+            .grep_files(pattern, glob.as_deref())
+            .map_err(js_error)?;
+        to_js(&paths)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "string[]")]
+    pub fn missing_in_glob(&self, term: &str, glob: &str) -> Result<JsValue, JsError> {
+        let paths = self
+            .core
+            .missing_in_glob(term, glob)
+            .map_err(js_error)?;
+        to_js(&paths)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "string[]")]
+    pub fn co_occurring(&self, path: &str, token: &str) -> Result<JsValue, JsError> {
+        to_js(&self.core.co_occurring(path, token))
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "string[]")]
+    pub fn rank_by_proximity(&self, query: &str) -> Result<JsValue, JsError> {
+        to_js(&self.core.rank_by_proximity(query))
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "SearchResult[]")]
+    pub fn search(&self, query: &str, limit: Option<u32>) -> Result<JsValue, JsError> {
+        to_js(&self.core.search(query, limit.map(|l| l as usize)))
+    }
+
+    pub fn context_for(&self, query: &str, budget_tokens: usize) -> String {
+        self.core.context_for(query, budget_tokens)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "[string, number][]")]
+    pub fn extensions(&self) -> Result<JsValue, JsError> {
+        to_js(&self.core.extensions())
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "TreeNode")]
+    pub fn tree(&self, dir: &str, max_depth: Option<u32>) -> Result<JsValue, JsError> {
+        to_js(&self.core.tree(dir, max_depth.map(|d| d as usize)))
+    }
+
+    pub fn tree_json(&self, dir: &str, max_depth: Option<u32>) -> String {
+        self.core.tree_json(dir, max_depth.map(|d| d as usize))
+    }
+
+    pub fn tree_text(&self, dir: &str, max_depth: Option<u32>) -> String {
+        self.core.tree_text(dir, max_depth.map(|d| d as usize))
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "ToolDefinition[]")]
+    pub fn tool_definitions(&self) -> Result<JsValue, JsError> {
+        to_js(&self.core.tool_definitions_value())
+    }
+
+    /// Answer one MCP (JSON-RPC) message; `undefined` for notifications.
+    pub fn handle_mcp_request(&mut self, message: &str) -> Option<String> {
+        self.core.handle_mcp_request(message)
+    }
+
+    /// The tool definitions in OpenAI's strict function-calling format.
+    #[wasm_bindgen(unchecked_return_type = "OpenAITool[]")]
+    pub fn tool_definitions_openai(&self) -> Result<JsValue, JsError> {
+        to_js(&self.core.tool_definitions_openai_value())
+    }
+
+    pub fn write_document(&mut self, path: &str, content: &str) -> Result<bool, JsError> {
+        self.core
+            .write_document(path, content)
+            .map_err(js_error)
+    }
+
+    pub fn delete_document(&mut self, path: &str) -> Result<(), JsError> {
+        self.core
+            .delete_document(path)
+            .map_err(js_error)
+    }
+
+    /// Copy in the documents of another instance. `policy` settles paths
+    /// both have; the default, `"last_wins"`, takes `other`'s.
+    pub fn merge(
+        &mut self,
+        other: &MemexFS,
+        #[wasm_bindgen(unchecked_param_type = "\"last_wins\" | \"first_wins\" | \"error\"")] policy: Option<String>,
+    ) -> Result<usize, JsError> {
+        let policy: DuplicatePolicy = match policy {
+            Some(policy) => serde_json::from_value(serde_json::Value::String(policy))
+                .map_err(|e| js_error(MemexError::invalid_params(e)))?,
+            None => DuplicatePolicy::default(),
+        };
+        self.core.merge(&other.core, policy).map_err(js_error)
+    }
+
+    /// Mount documents under `prefix/`; mounting again replaces them.
+    pub fn mount(
+        &mut self,
+        prefix: &str,
+        #[wasm_bindgen(unchecked_param_type = "Doc[] | string")] docs: JsValue,
+    ) -> Result<usize, JsError> {
+        let docs: Vec<DocInput> = from_js(docs)?;
+        self.core.mount_documents(prefix, docs).map_err(js_error)
+    }
+
+    /// Mount the documents of another instance under `prefix/`.
+    pub fn mount_fs(&mut self, prefix: &str, other: &MemexFS) -> Result<usize, JsError> {
+        self.core.mount(prefix, &other.core).map_err(js_error)
+    }
+
+    pub fn unmount(&mut self, prefix: &str) -> Result<usize, JsError> {
+        self.core.unmount(prefix).map_err(js_error)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "string[]")]
+    pub fn mounts(&self) -> Result<JsValue, JsError> {
+        to_js(&self.core.mounts())
+    }
+
+    /// How `newer` differs from this instance: the added, removed, and
+    /// changed paths, with line diffs of the changed documents.
+    #[wasm_bindgen(unchecked_return_type = "StoreDiff")]
+    pub fn diff(&self, newer: &MemexFS) -> Result<JsValue, JsError> {
+        to_js(&diff_stores(&self.core, &newer.core))
+    }
+
+    /// `diff` between the corpora mounted at `from` and `to`.
+    #[wasm_bindgen(unchecked_return_type = "StoreDiff")]
+    pub fn diff_mounts(&self, from: &str, to: &str, glob: Option<String>) -> Result<JsValue, JsError> {
+        to_js(&self.core.diff_mounts(from, to, glob.as_deref()).map_err(js_error)?)
+    }
+
+    /// Dispatch a tool call. `params` is the tool input object (or its
+    /// JSON string); the result is always a string for the LLM. Read
+    /// tools only: `write` goes through `call_mut`.
+    pub fn call(
+        &self,
+        name: &str,
+        #[wasm_bindgen(unchecked_param_type = "ToolParams | string")] params: JsValue,
+    ) -> Result<String, JsError> {
+        self.core
+            .call_value(name, tool_params(params)?)
+            .map_err(js_error)
+    }
+
+    /// `call` that can also run `write`.
+    pub fn call_mut(
+        &mut self,
+        name: &str,
+        #[wasm_bindgen(unchecked_param_type = "ToolParams | string")] params: JsValue,
+    ) -> Result<String, JsError> {
+        self.core
+            .call_value_mut(name, tool_params(params)?)
+            .map_err(js_error)
+    }
+
+    /// Run several tool calls in one crossing into WASM, e.g. every
+    /// `tool_use` block of one model turn. `calls` is an array of
+    /// `{name, params}` (or its JSON string); returns a `CallEnvelope`
+    /// per call, in order. A failed call is reported in its envelope and
+    /// the rest still run.
+    #[wasm_bindgen(unchecked_return_type = "CallEnvelope[]")]
+    pub fn call_batch(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "ToolCall[] | string")] calls: JsValue,
+    ) -> Result<JsValue, JsError> {
+        let calls: Vec<ToolCall> = from_js(calls)?;
+        let envelopes = self.core.call_batch_value(calls).map_err(js_error)?;
+        to_js(&envelopes)
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.core.document_count()
+    }
+
+    pub fn token_count(&self) -> usize {
+        self.core.token_count()
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "MemoryStats")]
+    pub fn memory_stats(&self) -> Result<JsValue, JsError> {
+        to_js(&self.core.memory_stats())
+    }
+}
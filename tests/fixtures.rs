@@ -1,25 +1,17 @@
-use memexfs::MemexFsCore;
-use std::fs;
+use memexfs::{DirOptions, MemexFsCore};
 use std::path::Path;
 
 fn load_fixtures() -> MemexFsCore {
     let fixtures_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
-    let mut docs: Vec<(String, String)> = Vec::new();
-
-    for entry in fs::read_dir(&fixtures_dir).expect("fixtures/ directory must exist") {
-        let entry = entry.unwrap();
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) == Some("md") {
-            let name = path.file_name().unwrap().to_string_lossy().to_string();
-            let content = fs::read_to_string(&path).unwrap();
-            docs.push((name, content));
-        }
-    }
-
-    docs.sort_by(|a, b| a.0.cmp(&b.0));
+    let options = DirOptions { include: vec!["*.md".to_string()], ..Default::default() };
+    MemexFsCore::from_dir(fixtures_dir, &options).expect("fixtures/ directory must exist")
+}
 
-    let json = serde_json::to_string(&docs).unwrap();
-    MemexFsCore::from_json(&json).unwrap()
+/// Load fixtures/nested recursively, keeping paths relative to that directory.
+fn load_nested_fixtures() -> MemexFsCore {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/nested");
+    let options = DirOptions { include: vec!["**/*.md".to_string()], ..Default::default() };
+    MemexFsCore::from_dir(root, &options).unwrap()
 }
 
 #[test]
@@ -95,7 +87,7 @@ fn test_read_missing_file() {
     let fs = load_fixtures();
     let result = fs.read("nonexistent.md", None, None);
     assert!(result.is_err());
-    assert!(result.unwrap_err().message.contains("document not found"));
+    assert!(result.unwrap_err().to_string().contains("document not found"));
 }
 
 #[test]
@@ -105,8 +97,9 @@ fn test_call_dispatch_grep() {
         .call("grep", r#"{"pattern": "server"}"#)
         .unwrap();
     let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
-    assert!(parsed.is_array());
-    assert!(!parsed.as_array().unwrap().is_empty());
+    assert!(parsed["results"].is_array());
+    assert!(!parsed["results"].as_array().unwrap().is_empty());
+    assert!(parsed["total_matches"].as_u64().unwrap() > 0);
 }
 
 #[test]
@@ -124,5 +117,64 @@ fn test_tool_definitions() {
     let defs = fs.tool_definitions();
     let parsed: serde_json::Value = serde_json::from_str(&defs).unwrap();
     assert!(parsed.is_array());
-    assert_eq!(parsed.as_array().unwrap().len(), 3);
+    assert_eq!(parsed.as_array().unwrap().len(), 13);
+}
+
+#[test]
+fn test_extensions() {
+    let fs = load_fixtures();
+    assert_eq!(fs.extensions(), vec![(".md".to_string(), 100)]);
+}
+
+#[test]
+fn test_tree_json_nested() {
+    let fs = load_nested_fixtures();
+    let tree: serde_json::Value = serde_json::from_str(&fs.tree_json("", None)).unwrap();
+
+    assert_eq!(tree["type"], "dir");
+    let children = tree["children"].as_array().unwrap();
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0]["name"], "subdir");
+    assert_eq!(children[0]["type"], "dir");
+    assert_eq!(children[0]["children"][0]["name"], "deep.md");
+    assert_eq!(children[0]["children"][0]["type"], "file");
+    assert_eq!(children[1]["name"], "top.md");
+    assert_eq!(children[1]["type"], "file");
+
+    let shallow: serde_json::Value = serde_json::from_str(&fs.tree_json("", Some(1))).unwrap();
+    assert!(shallow["children"][0].get("children").is_none());
+}
+
+#[test]
+fn test_grep_phrase_matches_line_scan() {
+    let fs = load_fixtures();
+    let options = memexfs::GrepOptions {
+        max_results: Some(usize::MAX),
+        literal: Some(true),
+        ..Default::default()
+    };
+    let fixtures_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+
+    // Phrases, then punctuated literals verified against index candidates
+    let punctuated = ["{{path/to", "more information:", "e.g. ", "specified file", "t (", "-"];
+    for phrase in ["the file", "in the current directory", "ile to", "a b"].into_iter().chain(punctuated) {
+        let found: Vec<(String, u32)> = fs
+            .grep_with_options(phrase, &options)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.path, r.line))
+            .collect();
+
+        let mut expected = Vec::new();
+        for path in fs.glob("*.md") {
+            let content = std::fs::read_to_string(fixtures_dir.join(&path)).unwrap();
+            for (i, line) in content.lines().enumerate() {
+                if line.to_lowercase().contains(phrase) {
+                    expected.push((path.clone(), (i + 1) as u32));
+                }
+            }
+        }
+        assert!(!expected.is_empty() || phrase == "a b", "{:?} should occur", phrase);
+        assert_eq!(found, expected, "phrase {:?}", phrase);
+    }
 }